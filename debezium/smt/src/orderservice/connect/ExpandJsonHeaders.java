@@ -0,0 +1,75 @@
+package orderservice.connect;
+
+import java.util.Iterator;
+import java.util.Map;
+
+import com.fasterxml.jackson.databind.JsonNode;
+import com.fasterxml.jackson.databind.ObjectMapper;
+
+import org.apache.kafka.common.config.AbstractConfig;
+import org.apache.kafka.common.config.ConfigDef;
+import org.apache.kafka.connect.connector.ConnectRecord;
+import org.apache.kafka.connect.errors.DataException;
+import org.apache.kafka.connect.header.Header;
+import org.apache.kafka.connect.header.Headers;
+import org.apache.kafka.connect.transforms.Transformation;
+
+/**
+ * Replaces the Kafka header holding the outbox row's {@code headers} JSON
+ * object (placed by the EventRouter's {@code additional.placement}) with one
+ * string header per entry, as the service's own outbox relay publishes them.
+ * Entries whose value is not a string are dropped, as the relay drops them.
+ */
+public class ExpandJsonHeaders<R extends ConnectRecord<R>> implements Transformation<R> {
+
+    public static final String HEADER_CONFIG = "header";
+
+    private static final ConfigDef CONFIG_DEF = new ConfigDef()
+            .define(HEADER_CONFIG, ConfigDef.Type.STRING, "headers", ConfigDef.Importance.MEDIUM,
+                    "Header holding the JSON object to expand");
+
+    private static final ObjectMapper MAPPER = new ObjectMapper();
+
+    private String header;
+
+    @Override
+    public void configure(Map<String, ?> props) {
+        header = new AbstractConfig(CONFIG_DEF, props).getString(HEADER_CONFIG);
+    }
+
+    @Override
+    public R apply(R record) {
+        Header json = record.headers().lastWithName(header);
+        if (json == null) {
+            return record;
+        }
+        Headers headers = record.headers().duplicate();
+        headers.remove(header);
+        if (json.value() != null) {
+            JsonNode entries;
+            try {
+                entries = MAPPER.readTree(json.value().toString());
+            } catch (Exception e) {
+                throw new DataException("header " + header + " is not JSON", e);
+            }
+            Iterator<Map.Entry<String, JsonNode>> fields = entries.fields();
+            while (fields.hasNext()) {
+                Map.Entry<String, JsonNode> entry = fields.next();
+                if (entry.getValue().isTextual()) {
+                    headers.addString(entry.getKey(), entry.getValue().textValue());
+                }
+            }
+        }
+        return record.newRecord(record.topic(), record.kafkaPartition(), record.keySchema(),
+                record.key(), record.valueSchema(), record.value(), record.timestamp(), headers);
+    }
+
+    @Override
+    public ConfigDef config() {
+        return CONFIG_DEF;
+    }
+
+    @Override
+    public void close() {
+    }
+}
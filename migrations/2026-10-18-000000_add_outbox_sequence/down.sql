@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS commerce_order_outbox_aggregate_id_sequence_idx;
+ALTER TABLE commerce_order_outbox DROP COLUMN sequence;
+DROP TABLE outbox_aggregate_sequences;
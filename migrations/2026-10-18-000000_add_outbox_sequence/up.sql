@@ -0,0 +1,37 @@
+-- Per-aggregate event sequence numbers, so consumers can detect missed,
+-- duplicated and reordered events.
+--
+-- `outbox_aggregate_sequences` holds the last number handed out per
+-- aggregate. Incrementing it with an upsert row-locks the counter until the
+-- writing transaction commits, so concurrent writers of the same aggregate
+-- are serialised and never share a number.
+CREATE TABLE outbox_aggregate_sequences (
+    aggregate_type  VARCHAR(255) NOT NULL,
+    aggregate_id    VARCHAR(255) NOT NULL,
+    last_sequence   BIGINT NOT NULL,
+    PRIMARY KEY (aggregate_type, aggregate_id)
+);
+
+ALTER TABLE commerce_order_outbox ADD COLUMN sequence BIGINT;
+
+-- Number existing rows in creation order.
+UPDATE commerce_order_outbox o
+SET sequence = numbered.sequence
+FROM (
+    SELECT id,
+           ROW_NUMBER() OVER (
+               PARTITION BY aggregate_type, aggregate_id
+               ORDER BY created_at, id
+           ) AS sequence
+    FROM commerce_order_outbox
+) numbered
+WHERE o.id = numbered.id;
+
+ALTER TABLE commerce_order_outbox ALTER COLUMN sequence SET NOT NULL;
+
+INSERT INTO outbox_aggregate_sequences (aggregate_type, aggregate_id, last_sequence)
+SELECT aggregate_type, aggregate_id, MAX(sequence)
+FROM commerce_order_outbox
+GROUP BY aggregate_type, aggregate_id;
+
+CREATE INDEX ON commerce_order_outbox (aggregate_id, sequence);
@@ -0,0 +1,8 @@
+-- Inbox of messages consumed from other services. A message is recorded in
+-- the same transaction as its side effects, so a redelivered message hits the
+-- primary key and is skipped instead of being applied twice.
+CREATE TABLE processed_messages (
+    message_id    VARCHAR(255) PRIMARY KEY,
+    message_type  VARCHAR(255) NOT NULL,
+    processed_at  TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,2 @@
+DROP TABLE order_saga_steps;
+DROP TABLE order_sagas;
@@ -0,0 +1,30 @@
+-- Placement saga of each order (reserve inventory, authorize payment,
+-- confirm). `version` is bumped by every transition so that concurrent
+-- replies and timeouts are applied one at a time; `step_deadline` is NULL
+-- once the saga has ended.
+CREATE TABLE order_sagas (
+    order_id        UUID PRIMARY KEY REFERENCES orders(id) ON DELETE CASCADE,
+    customer_id     UUID NOT NULL,
+    amount          NUMERIC NOT NULL,
+    state           VARCHAR(50) NOT NULL,
+    version         BIGINT NOT NULL DEFAULT 0,
+    step_deadline   TIMESTAMPTZ,
+    failure_reason  TEXT,
+    created_at      TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at      TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX order_sagas_step_deadline_idx ON order_sagas (step_deadline)
+    WHERE step_deadline IS NOT NULL;
+
+-- Append-only journal of the steps each saga went through, for debugging.
+CREATE TABLE order_saga_steps (
+    id           BIGSERIAL PRIMARY KEY,
+    order_id     UUID NOT NULL REFERENCES order_sagas(order_id) ON DELETE CASCADE,
+    step         VARCHAR(50) NOT NULL,
+    outcome      VARCHAR(50) NOT NULL,
+    detail       TEXT,
+    recorded_at  TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX order_saga_steps_order_id_idx ON order_saga_steps (order_id, id);
@@ -0,0 +1,38 @@
+-- Partners notified of order events over HTTP. An empty event_types array
+-- subscribes to every event type. Signing secrets are derived from the id
+-- and WEBHOOK_SECRET_KEY, so they are not stored.
+CREATE TABLE webhook_subscriptions (
+    id           UUID PRIMARY KEY,
+    url          TEXT NOT NULL,
+    event_types  TEXT[] NOT NULL DEFAULT '{}',
+    created_at   TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at   TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Outbox rows already fanned out to webhook deliveries. Cleaned up together
+-- with the outbox row.
+CREATE TABLE webhook_dispatched_events (
+    event_id       UUID PRIMARY KEY REFERENCES commerce_order_outbox (id) ON DELETE CASCADE,
+    dispatched_at  TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- One event to deliver to one subscription. Deliveries that keep failing end
+-- up DEAD (the dead-letter list) instead of being retried forever.
+CREATE TABLE webhook_deliveries (
+    id               BIGSERIAL PRIMARY KEY,
+    subscription_id  UUID NOT NULL REFERENCES webhook_subscriptions (id) ON DELETE CASCADE,
+    event_id         UUID NOT NULL,
+    event_type       VARCHAR(255) NOT NULL,
+    body             JSONB NOT NULL,
+    status           VARCHAR(20) NOT NULL DEFAULT 'PENDING',
+    attempts         INT NOT NULL DEFAULT 0,
+    next_attempt_at  TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_error       TEXT,
+    created_at       TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at       TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (subscription_id, event_id)
+);
+
+CREATE INDEX idx_webhook_deliveries_due ON webhook_deliveries (next_attempt_at)
+    WHERE status = 'PENDING';
+CREATE INDEX idx_webhook_deliveries_subscription ON webhook_deliveries (subscription_id, status, id);
@@ -0,0 +1,2 @@
+DROP TRIGGER commerce_order_outbox_notify ON commerce_order_outbox;
+DROP FUNCTION notify_order_event();
@@ -0,0 +1,15 @@
+-- Wake up SSE streams as soon as an order event is committed: every Order
+-- outbox row sends its id on the `order_events` channel. Postgres delivers
+-- notifications on commit only, and not at all for rolled back transactions.
+CREATE FUNCTION notify_order_event() RETURNS trigger AS $$
+BEGIN
+    PERFORM pg_notify('order_events', NEW.id::text);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER commerce_order_outbox_notify
+    AFTER INSERT ON commerce_order_outbox
+    FOR EACH ROW
+    WHEN (NEW.aggregate_type = 'Order')
+    EXECUTE FUNCTION notify_order_event();
@@ -0,0 +1,15 @@
+DROP TRIGGER commerce_order_outbox_notify ON commerce_order_outbox;
+DROP FUNCTION notify_outbox_insert();
+
+CREATE FUNCTION notify_order_event() RETURNS trigger AS $$
+BEGIN
+    PERFORM pg_notify('order_events', NEW.id::text);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER commerce_order_outbox_notify
+    AFTER INSERT ON commerce_order_outbox
+    FOR EACH ROW
+    WHEN (NEW.aggregate_type = 'Order')
+    EXECUTE FUNCTION notify_order_event();
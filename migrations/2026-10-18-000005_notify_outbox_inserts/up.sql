@@ -0,0 +1,18 @@
+-- Announce every committed outbox row, not only order events, so that any
+-- in-process consumer can react to inserts instead of polling: the id of each
+-- row is sent on the `commerce_order_outbox` channel. Postgres delivers
+-- notifications on commit only, and not at all for rolled back transactions.
+DROP TRIGGER commerce_order_outbox_notify ON commerce_order_outbox;
+DROP FUNCTION notify_order_event();
+
+CREATE FUNCTION notify_outbox_insert() RETURNS trigger AS $$
+BEGIN
+    PERFORM pg_notify('commerce_order_outbox', NEW.id::text);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER commerce_order_outbox_notify
+    AFTER INSERT ON commerce_order_outbox
+    FOR EACH ROW
+    EXECUTE FUNCTION notify_outbox_insert();
@@ -0,0 +1 @@
+DROP TABLE commerce_order_outbox_publications;
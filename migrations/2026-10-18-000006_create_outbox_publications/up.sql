@@ -0,0 +1,19 @@
+-- Publishing state of outbox rows taken up by the in-process relay; rows
+-- without an entry have not been seen by it yet. Events that cannot be
+-- published end up DEAD (the dead-letter list) instead of blocking the relay,
+-- until an operator requeues or discards them. Cleaned up together with the
+-- outbox row.
+CREATE TABLE commerce_order_outbox_publications (
+    event_id         UUID PRIMARY KEY REFERENCES commerce_order_outbox (id) ON DELETE CASCADE,
+    status           VARCHAR(20) NOT NULL DEFAULT 'PENDING',
+    attempts         INT NOT NULL DEFAULT 0,
+    next_attempt_at  TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_error       TEXT,
+    created_at       TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at       TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_outbox_publications_due ON commerce_order_outbox_publications (next_attempt_at)
+    WHERE status = 'PENDING';
+CREATE INDEX idx_outbox_publications_dead ON commerce_order_outbox_publications (updated_at)
+    WHERE status = 'DEAD';
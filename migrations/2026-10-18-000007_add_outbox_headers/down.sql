@@ -0,0 +1 @@
+ALTER TABLE commerce_order_outbox DROP COLUMN headers;
@@ -0,0 +1,4 @@
+-- Message headers of each outbox event as a JSON object of strings, emitted
+-- by Debezium as the `headers` Kafka header and by the in-process relay as
+-- one transport header per entry.
+ALTER TABLE commerce_order_outbox ADD COLUMN headers JSONB NOT NULL DEFAULT '{}';
@@ -0,0 +1,7 @@
+-- Single row the replication monitor touches on every check when heartbeats
+-- are enabled. Capturing it gives Debezium a change to confirm on databases
+-- whose outbox is idle, so its replication slot keeps advancing.
+CREATE TABLE replication_heartbeat (
+    id       SMALLINT PRIMARY KEY DEFAULT 1 CHECK (id = 1),
+    beat_at  TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,2 @@
+DROP TABLE outbox_snapshot_requests;
+DROP TABLE debezium_signal;
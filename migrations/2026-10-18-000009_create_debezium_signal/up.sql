@@ -0,0 +1,28 @@
+-- Debezium's signaling table (`signal.data.collection`). The connector reads
+-- the signals inserted here from the WAL, and writes the watermarks of its
+-- incremental snapshots back into it.
+CREATE TABLE debezium_signal (
+    id    VARCHAR(42) PRIMARY KEY,
+    type  VARCHAR(32) NOT NULL,
+    data  VARCHAR(2048)
+);
+
+-- Snapshot signals sent by `order-admin outbox snapshot`, one per signal.
+-- `signal_lsn` is the WAL position right after the signal was committed (NULL
+-- until then): once the connector's slot has confirmed up to it, the connector
+-- has read the signal and the request moves from PENDING to ACCEPTED. It is
+-- COMPLETED once the snapshot windows the connector writes back into
+-- debezium_signal have all closed.
+CREATE TABLE outbox_snapshot_requests (
+    signal_id        VARCHAR(42) PRIMARY KEY REFERENCES debezium_signal (id) ON DELETE CASCADE,
+    data_collection  VARCHAR(255) NOT NULL,
+    condition        TEXT,
+    matching_events  BIGINT NOT NULL,
+    signal_lsn       BIGINT,
+    status           VARCHAR(20) NOT NULL DEFAULT 'PENDING',
+    requested_at     TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at       TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_outbox_snapshot_requests_pending ON outbox_snapshot_requests (signal_lsn)
+    WHERE status = 'PENDING';
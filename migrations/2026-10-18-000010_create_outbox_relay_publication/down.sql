@@ -0,0 +1 @@
+DROP PUBLICATION commerce_order_outbox_relay;
@@ -0,0 +1,4 @@
+-- What the logical replication relay streams: inserts into the outbox, and
+-- nothing else. Its replication slot is created by the relay itself.
+CREATE PUBLICATION commerce_order_outbox_relay FOR TABLE commerce_order_outbox
+    WITH (publish = 'insert');
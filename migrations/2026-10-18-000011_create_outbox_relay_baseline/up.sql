@@ -0,0 +1,8 @@
+-- Single row written the first time the in-process relay takes up the outbox.
+-- Rows already in the outbox then were published by whatever ran before it
+-- (Debezium), so the relay records them as SKIPPED instead of publishing the
+-- whole history again.
+CREATE TABLE outbox_relay_baseline (
+    id          SMALLINT PRIMARY KEY DEFAULT 1 CHECK (id = 1),
+    started_at  TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,803 @@
+//! Operator tasks behind the `order-admin` binary: migrations, outbox
+//! inspection and replay, Debezium snapshot signals, the replication relay's
+//! slot, and order dumps. The Debezium connector is managed through
+//! [`crate::debezium`].
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use diesel::migration::MigrationSource;
+use diesel::pg::{Pg, PgConnection};
+use diesel::prelude::*;
+use serde::Serialize;
+use serde_json::Value;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::config::ConfigError;
+use crate::debezium::{self, ConnectError};
+use crate::domain::order::ORDER_AGGREGATE;
+use crate::infrastructure::models::{
+    NewDebeziumSignalRow, NewOutboxEventRow, NewSnapshotRequestRow, OrderLineRow, OrderRow,
+    OutboxEventRow, SnapshotRequestRow,
+};
+use crate::infrastructure::outbox::REPLAY_OF_HEADER;
+use crate::schema::{
+    commerce_order_outbox, debezium_signal, order_lines, orders, outbox_snapshot_requests,
+};
+use crate::MIGRATIONS;
+
+#[derive(Debug, Error)]
+pub enum AdminError {
+    #[error("Migration error: {0}")]
+    Migration(String),
+
+    #[error("Database error: {0}")]
+    Database(#[from] diesel::result::Error),
+
+    #[error("{0}")]
+    NotFound(String),
+
+    #[error("Replication slot {0} is in use")]
+    SlotInUse(String),
+
+    #[error("Configuration error: {0}")]
+    Config(#[from] ConfigError),
+
+    #[error("Kafka Connect error: {0}")]
+    Connect(#[from] ConnectError),
+}
+
+type MigrationError = Box<dyn std::error::Error + Send + Sync>;
+
+fn migration_error(e: MigrationError) -> AdminError {
+    AdminError::Migration(e.to_string())
+}
+
+// ── Migrations ───────────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MigrationStatus {
+    pub version: String,
+    pub name: String,
+    pub applied: bool,
+}
+
+/// Every migration embedded in [`MIGRATIONS`], oldest first.
+pub fn migration_status(conn: &mut PgConnection) -> Result<Vec<MigrationStatus>, AdminError> {
+    use diesel_migrations::MigrationHarness;
+
+    let applied = conn.applied_migrations().map_err(migration_error)?;
+    let mut migrations = MigrationSource::<Pg>::migrations(&MIGRATIONS).map_err(migration_error)?;
+    migrations.sort_by(|a, b| a.name().version().cmp(&b.name().version()));
+
+    Ok(migrations
+        .iter()
+        .map(|m| MigrationStatus {
+            version: m.name().version().to_string(),
+            name: m.name().to_string(),
+            applied: applied.contains(&m.name().version()),
+        })
+        .collect())
+}
+
+/// Apply pending migrations; returns the versions that were run.
+pub fn run_migrations(conn: &mut PgConnection) -> Result<Vec<String>, AdminError> {
+    use diesel_migrations::MigrationHarness;
+
+    Ok(conn
+        .run_pending_migrations(MIGRATIONS)
+        .map_err(migration_error)?
+        .iter()
+        .map(ToString::to_string)
+        .collect())
+}
+
+/// Revert the `steps` most recent migrations; returns the reverted versions.
+pub fn revert_migrations(conn: &mut PgConnection, steps: usize) -> Result<Vec<String>, AdminError> {
+    use diesel_migrations::MigrationHarness;
+
+    let mut reverted = Vec::with_capacity(steps);
+    for _ in 0..steps {
+        if conn
+            .applied_migrations()
+            .map_err(migration_error)?
+            .is_empty()
+        {
+            break;
+        }
+        let version = conn
+            .revert_last_migration(MIGRATIONS)
+            .map_err(migration_error)?;
+        reverted.push(version.to_string());
+    }
+    Ok(reverted)
+}
+
+// ── Outbox ───────────────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Default)]
+pub struct OutboxFilter {
+    pub aggregate_type: Option<String>,
+    pub aggregate_id: Option<String>,
+    pub event_type: Option<String>,
+    /// Only rows created at or after this instant.
+    pub since: Option<DateTime<Utc>>,
+    /// Only rows created strictly before this instant.
+    pub before: Option<DateTime<Utc>>,
+    pub limit: i64,
+}
+
+/// Outbox rows matching `filter`, oldest first.
+pub fn list_outbox(
+    conn: &mut PgConnection,
+    filter: &OutboxFilter,
+) -> Result<Vec<OutboxEventRow>, AdminError> {
+    Ok(filtered_outbox(filter)
+        .select(OutboxEventRow::as_select())
+        .order((
+            commerce_order_outbox::created_at.asc(),
+            commerce_order_outbox::id.asc(),
+        ))
+        .limit(filter.limit)
+        .load(conn)?)
+}
+
+/// Outbox rows matching `filter`, ignoring its limit.
+fn filtered_outbox(filter: &OutboxFilter) -> commerce_order_outbox::BoxedQuery<'_, Pg> {
+    let mut query = commerce_order_outbox::table.into_boxed();
+    if let Some(aggregate_type) = &filter.aggregate_type {
+        query = query.filter(commerce_order_outbox::aggregate_type.eq(aggregate_type));
+    }
+    if let Some(aggregate_id) = &filter.aggregate_id {
+        query = query.filter(commerce_order_outbox::aggregate_id.eq(aggregate_id));
+    }
+    if let Some(event_type) = &filter.event_type {
+        query = query.filter(commerce_order_outbox::event_type.eq(event_type));
+    }
+    if let Some(since) = filter.since {
+        query = query.filter(commerce_order_outbox::created_at.ge(since));
+    }
+    if let Some(before) = filter.before {
+        query = query.filter(commerce_order_outbox::created_at.lt(before));
+    }
+    query
+}
+
+/// Re-insert the outbox events of `order_id` (optionally only those of
+/// `event_type`) under fresh ids, so that Debezium publishes them again.
+/// Saga commands share the order's `aggregate_id` but are not replayed:
+/// other services would act on them a second time. Neither are earlier
+/// replays.
+///
+/// Returns the inserted rows. Replayed events keep their original payload and
+/// `sequence`, and carry the original id in a [`REPLAY_OF_HEADER`] header, so
+/// consumers that already processed them can recognise them as duplicates.
+/// The order's event history and webhook fan-out skip them.
+pub fn replay_order_events(
+    conn: &mut PgConnection,
+    order_id: Uuid,
+    event_type: Option<&str>,
+) -> Result<Vec<OutboxEventRow>, AdminError> {
+    conn.transaction(|conn| {
+        let originals = list_outbox(
+            conn,
+            &OutboxFilter {
+                aggregate_type: Some(ORDER_AGGREGATE.to_string()),
+                aggregate_id: Some(order_id.to_string()),
+                event_type: event_type.map(str::to_string),
+                limit: i64::MAX,
+                ..Default::default()
+            },
+        )?;
+        if originals.is_empty() {
+            return Err(AdminError::NotFound(format!(
+                "no outbox events to replay for order {}",
+                order_id
+            )));
+        }
+
+        let copies: Vec<NewOutboxEventRow> = originals
+            .into_iter()
+            .filter(|e| e.headers.get(REPLAY_OF_HEADER).is_none())
+            .map(|e| {
+                let mut headers = e.headers;
+                if let Value::Object(headers) = &mut headers {
+                    headers.insert(REPLAY_OF_HEADER.to_string(), e.id.to_string().into());
+                }
+                NewOutboxEventRow {
+                    id: Uuid::new_v4(),
+                    aggregate_type: e.aggregate_type,
+                    aggregate_id: e.aggregate_id,
+                    event_type: e.event_type,
+                    payload: e.payload,
+                    sequence: e.sequence,
+                    headers,
+                }
+            })
+            .collect();
+        Ok(diesel::insert_into(commerce_order_outbox::table)
+            .values(&copies)
+            .returning(OutboxEventRow::as_returning())
+            .get_results(conn)?)
+    })
+}
+
+// ── Snapshots ────────────────────────────────────────────────────────────────
+
+/// `filter` as the SQL condition Debezium adds to the queries of an
+/// incremental snapshot; `None` if it matches every row. The limit does not
+/// apply.
+pub fn snapshot_condition(filter: &OutboxFilter) -> Option<String> {
+    let literal = |value: &str| format!("'{}'", value.replace('\'', "''"));
+    let instant = |at: DateTime<Utc>| literal(&at.to_rfc3339());
+    let mut conditions = Vec::new();
+    if let Some(aggregate_type) = &filter.aggregate_type {
+        conditions.push(format!("aggregate_type = {}", literal(aggregate_type)));
+    }
+    if let Some(aggregate_id) = &filter.aggregate_id {
+        conditions.push(format!("aggregate_id = {}", literal(aggregate_id)));
+    }
+    if let Some(event_type) = &filter.event_type {
+        conditions.push(format!("event_type = {}", literal(event_type)));
+    }
+    if let Some(since) = filter.since {
+        conditions.push(format!("created_at >= {}", instant(since)));
+    }
+    if let Some(before) = filter.before {
+        conditions.push(format!("created_at < {}", instant(before)));
+    }
+    (!conditions.is_empty()).then(|| conditions.join(" AND "))
+}
+
+/// How long no snapshot window may have opened after the last one closed
+/// before the snapshot counts as complete. Chunks follow each other within
+/// milliseconds.
+pub const SNAPSHOT_QUIET_PERIOD: Duration = Duration::from_secs(10);
+
+/// Ask the connector to re-emit the outbox rows matching `filter` through an
+/// incremental snapshot of `data_collection`, the outbox table as Debezium
+/// names it. Returns the `PENDING` request that tracks the signal.
+///
+/// Unlike [`replay_order_events`] this writes no outbox rows: the connector
+/// reads the existing ones again and publishes them with their original ids.
+pub fn request_snapshot(
+    conn: &mut PgConnection,
+    data_collection: &str,
+    filter: &OutboxFilter,
+) -> Result<SnapshotRequestRow, AdminError> {
+    let request: SnapshotRequestRow = conn.transaction(|conn| {
+        let matching_events: i64 = filtered_outbox(filter).count().get_result(conn)?;
+        if matching_events == 0 {
+            return Err(AdminError::NotFound(
+                "no outbox events match the snapshot filter".to_string(),
+            ));
+        }
+
+        let signal_id = Uuid::new_v4().to_string();
+        let condition = snapshot_condition(filter);
+        let data = debezium::execute_snapshot(data_collection, condition.as_deref()).to_string();
+        diesel::insert_into(debezium_signal::table)
+            .values(NewDebeziumSignalRow {
+                id: &signal_id,
+                signal_type: "execute-snapshot",
+                data: Some(&data),
+            })
+            .execute(conn)?;
+        Ok(diesel::insert_into(outbox_snapshot_requests::table)
+            .values(NewSnapshotRequestRow {
+                signal_id: &signal_id,
+                data_collection,
+                condition: condition.as_deref(),
+                matching_events,
+            })
+            .returning(SnapshotRequestRow::as_returning())
+            .get_result(conn)?)
+    })?;
+
+    // The connector only sees the signal once it is committed, so the slot
+    // must confirm the commit, which is before this position.
+    let signal_lsn: i64 = diesel::select(diesel::dsl::sql::<diesel::sql_types::BigInt>(
+        "pg_wal_lsn_diff(pg_current_wal_insert_lsn(), '0/0')::bigint",
+    ))
+    .get_result(conn)?;
+    Ok(
+        diesel::update(outbox_snapshot_requests::table.find(&request.signal_id))
+            .set(outbox_snapshot_requests::signal_lsn.eq(signal_lsn))
+            .returning(SnapshotRequestRow::as_returning())
+            .get_result(conn)?,
+    )
+}
+
+/// Move snapshot requests forward. `PENDING` requests whose signal the
+/// connector has read, that is whose commit the slot `slot_name` has
+/// confirmed, become `ACCEPTED`. `ACCEPTED` requests become `COMPLETED` once
+/// a snapshot window closed after they were requested and none has opened
+/// for [`SNAPSHOT_QUIET_PERIOD`]: the connector writes an open and a close
+/// row into `debezium_signal` around every chunk it reads. Returns how many
+/// requests were updated.
+pub fn refresh_snapshot_requests(
+    conn: &mut PgConnection,
+    slot_name: &str,
+) -> Result<usize, AdminError> {
+    conn.transaction(|conn| {
+        let accepted = diesel::sql_query(
+            "UPDATE outbox_snapshot_requests SET status = 'ACCEPTED', updated_at = NOW() \
+             WHERE status = 'PENDING' AND signal_lsn <= ( \
+                 SELECT pg_wal_lsn_diff(confirmed_flush_lsn, '0/0')::bigint \
+                 FROM pg_replication_slots WHERE slot_name = $1)",
+        )
+        .bind::<diesel::sql_types::Text, _>(slot_name)
+        .execute(conn)?;
+        let completed = diesel::sql_query(
+            "WITH windows AS ( \
+                 SELECT type, COALESCE( \
+                     data::jsonb ->> 'openWindowTimestamp', \
+                     data::jsonb ->> 'closeWindowTimestamp')::timestamptz AS at \
+                 FROM debezium_signal \
+                 WHERE type IN ('snapshot-window-open', 'snapshot-window-close')), \
+             latest AS ( \
+                 SELECT type, at FROM windows WHERE at IS NOT NULL \
+                 ORDER BY at DESC, type = 'snapshot-window-close' DESC LIMIT 1) \
+             UPDATE outbox_snapshot_requests r SET status = 'COMPLETED', updated_at = NOW() \
+             FROM latest \
+             WHERE r.status = 'ACCEPTED' AND latest.type = 'snapshot-window-close' \
+               AND latest.at > r.requested_at \
+               AND latest.at < NOW() - make_interval(secs => $1)",
+        )
+        .bind::<diesel::sql_types::Double, _>(SNAPSHOT_QUIET_PERIOD.as_secs_f64())
+        .execute(conn)?;
+        Ok(accepted + completed)
+    })
+}
+
+/// The most recent snapshot requests, newest first.
+pub fn list_snapshot_requests(
+    conn: &mut PgConnection,
+    limit: i64,
+) -> Result<Vec<SnapshotRequestRow>, AdminError> {
+    Ok(outbox_snapshot_requests::table
+        .select(SnapshotRequestRow::as_select())
+        .order(outbox_snapshot_requests::requested_at.desc())
+        .limit(limit)
+        .load(conn)?)
+}
+
+// ── Replication relay ────────────────────────────────────────────────────────
+
+#[derive(QueryableByName)]
+struct SlotActivity {
+    #[diesel(sql_type = diesel::sql_types::Bool)]
+    active: bool,
+}
+
+/// Drop the logical replication slot of the
+/// [replication relay](crate::replication_relay), which keeps WAL around for
+/// as long as it exists, once the relay polls again. Returns whether there
+/// was such a slot; a slot still in use is not dropped.
+pub fn drop_replication_slot(conn: &mut PgConnection, slot_name: &str) -> Result<bool, AdminError> {
+    let slot = diesel::sql_query(
+        "SELECT active FROM pg_replication_slots WHERE slot_name = $1 AND slot_type = 'logical'",
+    )
+    .bind::<diesel::sql_types::Text, _>(slot_name)
+    .get_result::<SlotActivity>(conn)
+    .optional()?;
+    match slot {
+        None => Ok(false),
+        Some(SlotActivity { active: true }) => Err(AdminError::SlotInUse(slot_name.to_string())),
+        Some(SlotActivity { active: false }) => {
+            diesel::sql_query("SELECT pg_drop_replication_slot($1)")
+                .bind::<diesel::sql_types::Text, _>(slot_name)
+                .execute(conn)?;
+            Ok(true)
+        }
+    }
+}
+
+// ── Orders ───────────────────────────────────────────────────────────────────
+
+/// Everything stored about an order, for `order-admin order dump`.
+#[derive(Debug, Serialize)]
+pub struct OrderDump {
+    pub order: OrderRow,
+    pub lines: Vec<OrderLineRow>,
+    pub outbox_events: Vec<OutboxEventRow>,
+}
+
+pub fn dump_order(conn: &mut PgConnection, order_id: Uuid) -> Result<OrderDump, AdminError> {
+    let order = orders::table
+        .find(order_id)
+        .select(OrderRow::as_select())
+        .first(conn)
+        .optional()?
+        .ok_or_else(|| AdminError::NotFound(format!("order {} not found", order_id)))?;
+    let lines = OrderLineRow::belonging_to(&order)
+        .select(OrderLineRow::as_select())
+        .order(order_lines::created_at.asc())
+        .load(conn)?;
+    let outbox_events = list_outbox(
+        conn,
+        &OutboxFilter {
+            aggregate_id: Some(order_id.to_string()),
+            limit: i64::MAX,
+            ..Default::default()
+        },
+    )?;
+    Ok(OrderDump {
+        order,
+        lines,
+        outbox_events,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bigdecimal::BigDecimal;
+    use serde_json::json;
+
+    use super::*;
+    use crate::application::order_service::OrderService;
+    use crate::domain::caller::Caller;
+    use crate::domain::order::OrderLineInput;
+    use crate::domain::ports::OrderRepository;
+    use crate::domain::saga::SagaTimeouts;
+    use crate::infrastructure::order_repo::{self, DieselOrderRepository};
+    use crate::infrastructure::unit_of_work::DieselUnitOfWork;
+    use crate::infrastructure::webhook_repo;
+    use crate::test_support::setup_db;
+
+    fn place_order(pool: &crate::DbPool) -> Uuid {
+        order_repo::tests::place_order(
+            pool,
+            Uuid::new_v4(),
+            vec![OrderLineInput {
+                product_id: Uuid::new_v4(),
+                quantity: 2,
+                unit_price: BigDecimal::from_str("4.50").expect("decimal"),
+            }],
+        )
+        .expect("create order")
+    }
+
+    #[tokio::test]
+    async fn status_lists_all_embedded_migrations_as_applied() {
+        let (_container, pool) = setup_db().await;
+        let mut conn = pool.get().expect("connection");
+
+        let status = migration_status(&mut conn).expect("status");
+        assert!(!status.is_empty());
+        assert!(status.iter().all(|m| m.applied));
+        assert!(status.windows(2).all(|w| w[0].version < w[1].version));
+    }
+
+    #[tokio::test]
+    async fn revert_then_run_round_trips_the_latest_migration() {
+        let (_container, pool) = setup_db().await;
+        let mut conn = pool.get().expect("connection");
+        let latest = migration_status(&mut conn)
+            .expect("status")
+            .pop()
+            .expect("at least one migration");
+
+        let reverted = revert_migrations(&mut conn, 1).expect("revert");
+        assert_eq!(reverted, vec![latest.version.clone()]);
+        let status = migration_status(&mut conn).expect("status");
+        assert!(!status.last().expect("latest").applied);
+
+        assert_eq!(
+            run_migrations(&mut conn).expect("run"),
+            vec![latest.version]
+        );
+        assert!(run_migrations(&mut conn).expect("run again").is_empty());
+    }
+
+    #[tokio::test]
+    async fn list_outbox_filters_by_aggregate_and_event_type() {
+        let (_container, pool) = setup_db().await;
+        let first = place_order(&pool);
+        place_order(&pool);
+        let mut conn = pool.get().expect("connection");
+
+        let all = list_outbox(
+            &mut conn,
+            &OutboxFilter {
+                limit: 100,
+                ..Default::default()
+            },
+        )
+        .expect("list");
+        assert_eq!(all.len(), 2);
+
+        let one = list_outbox(
+            &mut conn,
+            &OutboxFilter {
+                aggregate_id: Some(first.to_string()),
+                event_type: Some("OrderCreated".to_string()),
+                limit: 100,
+                ..Default::default()
+            },
+        )
+        .expect("list");
+        assert_eq!(one.len(), 1);
+        assert_eq!(one[0].aggregate_id, first.to_string());
+
+        let none = list_outbox(
+            &mut conn,
+            &OutboxFilter {
+                event_type: Some("OrderShipped".to_string()),
+                limit: 100,
+                ..Default::default()
+            },
+        )
+        .expect("list");
+        assert!(none.is_empty());
+
+        let old = list_outbox(
+            &mut conn,
+            &OutboxFilter {
+                before: Some(all[0].created_at),
+                limit: 100,
+                ..Default::default()
+            },
+        )
+        .expect("list");
+        assert!(old.is_empty());
+    }
+
+    #[tokio::test]
+    async fn replay_reinserts_events_with_new_ids() {
+        let (_container, pool) = setup_db().await;
+        let order_id = place_order(&pool);
+        let mut conn = pool.get().expect("connection");
+
+        let replayed = replay_order_events(&mut conn, order_id, None).expect("replay");
+        assert_eq!(replayed.len(), 1);
+
+        let events = dump_order(&mut conn, order_id).expect("dump").outbox_events;
+        assert_eq!(events.len(), 2);
+        assert_ne!(events[0].id, events[1].id);
+        assert_eq!(events[0].payload, events[1].payload);
+        assert_eq!(events[1].event_type, "OrderCreated");
+        assert_eq!(events[0].sequence, events[1].sequence);
+        assert_eq!(
+            events[1].headers[REPLAY_OF_HEADER],
+            json!(events[0].id.to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn replayed_events_stay_out_of_the_history_and_the_webhooks() {
+        let (_container, pool) = setup_db().await;
+        let order_id = place_order(&pool);
+        let mut conn = pool.get().expect("connection");
+        assert_eq!(webhook_repo::fan_out(&mut conn, 10).expect("fan out"), 1);
+
+        replay_order_events(&mut conn, order_id, None).expect("replay");
+        let again = replay_order_events(&mut conn, order_id, None).expect("replay");
+        assert_eq!(again.len(), 1, "earlier replays are not replayed");
+
+        let history = DieselOrderRepository::new(pool.clone())
+            .events(order_id)
+            .expect("events");
+        assert_eq!(history.len(), 1);
+        assert_eq!(webhook_repo::fan_out(&mut conn, 10).expect("fan out"), 0);
+    }
+
+    #[tokio::test]
+    async fn replay_leaves_the_saga_commands_alone() {
+        let (_container, pool) = setup_db().await;
+        let order_id = OrderService::new(
+            DieselOrderRepository::new(pool.clone()),
+            DieselUnitOfWork::new(pool.clone()),
+        )
+        .with_saga(SagaTimeouts::default())
+        .create_order(
+            &Caller::Staff,
+            Uuid::new_v4(),
+            vec![OrderLineInput {
+                product_id: Uuid::new_v4(),
+                quantity: 1,
+                unit_price: BigDecimal::from_str("4.50").expect("decimal"),
+            }],
+        )
+        .expect("create order");
+        let mut conn = pool.get().expect("connection");
+        let before = dump_order(&mut conn, order_id).expect("dump").outbox_events;
+        assert!(before.iter().any(|e| e.aggregate_type != ORDER_AGGREGATE));
+
+        let replayed = replay_order_events(&mut conn, order_id, None).expect("replay");
+        assert_eq!(
+            replayed
+                .iter()
+                .map(|e| (e.aggregate_type.as_str(), e.event_type.as_str()))
+                .collect::<Vec<_>>(),
+            [(ORDER_AGGREGATE, "OrderCreated")]
+        );
+        let after = dump_order(&mut conn, order_id).expect("dump").outbox_events;
+        assert_eq!(after.len(), before.len() + 1);
+    }
+
+    #[tokio::test]
+    async fn replay_of_unknown_order_is_an_error() {
+        let (_container, pool) = setup_db().await;
+        let mut conn = pool.get().expect("connection");
+
+        let result = replay_order_events(&mut conn, Uuid::new_v4(), None);
+        assert!(matches!(result, Err(AdminError::NotFound(_))));
+    }
+
+    #[test]
+    fn snapshot_conditions_quote_their_values() {
+        assert_eq!(snapshot_condition(&OutboxFilter::default()), None);
+        let filter = OutboxFilter {
+            aggregate_id: Some("o'brien".to_string()),
+            since: Some("2026-01-01T00:00:00Z".parse().expect("timestamp")),
+            before: Some("2026-02-01T00:00:00Z".parse().expect("timestamp")),
+            ..Default::default()
+        };
+        assert_eq!(
+            snapshot_condition(&filter).as_deref(),
+            Some(
+                "aggregate_id = 'o''brien' \
+                 AND created_at >= '2026-01-01T00:00:00+00:00' \
+                 AND created_at < '2026-02-01T00:00:00+00:00'"
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn snapshot_request_is_accepted_then_completed_by_the_connector() {
+        let (_container, pool) = setup_db().await;
+        let order_id = place_order(&pool);
+        place_order(&pool);
+        let mut conn = pool.get().expect("connection");
+        let slot = format!("snapshot_{}", Uuid::new_v4().simple());
+        diesel::sql_query("SELECT pg_create_logical_replication_slot($1, 'pgoutput')")
+            .bind::<diesel::sql_types::Text, _>(&slot)
+            .execute(&mut conn)
+            .expect("create slot");
+
+        let filter = OutboxFilter {
+            aggregate_id: Some(order_id.to_string()),
+            ..Default::default()
+        };
+        let request =
+            request_snapshot(&mut conn, "public.commerce_order_outbox", &filter).expect("request");
+        assert_eq!(request.status, "PENDING");
+        assert_eq!(request.matching_events, 1);
+        assert!(request.signal_lsn.is_some(), "recorded after the commit");
+
+        let (signal_type, data): (String, Option<String>) = debezium_signal::table
+            .find(&request.signal_id)
+            .select((debezium_signal::signal_type, debezium_signal::data))
+            .first(&mut conn)
+            .expect("signal");
+        assert_eq!(signal_type, "execute-snapshot");
+        let data: serde_json::Value =
+            serde_json::from_str(&data.expect("data")).expect("signal data");
+        assert_eq!(
+            data,
+            debezium::execute_snapshot(
+                "public.commerce_order_outbox",
+                snapshot_condition(&filter).as_deref()
+            )
+        );
+
+        assert_eq!(
+            refresh_snapshot_requests(&mut conn, &slot).expect("refresh"),
+            0
+        );
+        diesel::sql_query("SELECT pg_replication_slot_advance($1, pg_current_wal_lsn())")
+            .bind::<diesel::sql_types::Text, _>(&slot)
+            .execute(&mut conn)
+            .expect("advance slot");
+        assert_eq!(
+            refresh_snapshot_requests(&mut conn, &slot).expect("refresh"),
+            1
+        );
+        let requests = list_snapshot_requests(&mut conn, 10).expect("list");
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].status, "ACCEPTED");
+
+        // The connector writes a window around every chunk it reads.
+        diesel::sql_query(
+            "UPDATE outbox_snapshot_requests SET requested_at = NOW() - INTERVAL '5 minutes'",
+        )
+        .execute(&mut conn)
+        .expect("backdate request");
+        let window = |conn: &mut PgConnection, chunk: &str, kind: &str, secs_ago: i64| {
+            let at = (Utc::now() - chrono::Duration::seconds(secs_ago)).to_rfc3339();
+            diesel::insert_into(debezium_signal::table)
+                .values(NewDebeziumSignalRow {
+                    id: &format!("{}-{}", chunk, kind),
+                    signal_type: &format!("snapshot-window-{}", kind),
+                    data: Some(&json!({ format!("{}WindowTimestamp", kind): at }).to_string()),
+                })
+                .execute(conn)
+                .expect("window");
+        };
+        window(&mut conn, "chunk-1", "open", 120);
+        window(&mut conn, "chunk-1", "close", 119);
+        window(&mut conn, "chunk-2", "open", 119);
+        assert_eq!(
+            refresh_snapshot_requests(&mut conn, &slot).expect("refresh"),
+            0,
+            "a window is still open"
+        );
+        window(&mut conn, "chunk-2", "close", 1);
+        assert_eq!(
+            refresh_snapshot_requests(&mut conn, &slot).expect("refresh"),
+            0,
+            "another chunk may follow"
+        );
+        diesel::sql_query("DELETE FROM debezium_signal WHERE id = 'chunk-2-close'")
+            .execute(&mut conn)
+            .expect("delete window");
+        window(&mut conn, "chunk-2", "close", 60);
+        assert_eq!(
+            refresh_snapshot_requests(&mut conn, &slot).expect("refresh"),
+            1
+        );
+        let requests = list_snapshot_requests(&mut conn, 10).expect("list");
+        assert_eq!(requests[0].status, "COMPLETED");
+
+        diesel::sql_query("SELECT pg_drop_replication_slot($1)")
+            .bind::<diesel::sql_types::Text, _>(&slot)
+            .execute(&mut conn)
+            .expect("drop slot");
+    }
+
+    #[tokio::test]
+    async fn snapshot_of_nothing_is_an_error() {
+        let (_container, pool) = setup_db().await;
+        let mut conn = pool.get().expect("connection");
+
+        let result = request_snapshot(
+            &mut conn,
+            "public.commerce_order_outbox",
+            &OutboxFilter {
+                aggregate_id: Some(Uuid::new_v4().to_string()),
+                ..Default::default()
+            },
+        );
+        assert!(matches!(result, Err(AdminError::NotFound(_))));
+        assert!(list_snapshot_requests(&mut conn, 10)
+            .expect("list")
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn replication_slot_is_dropped_once() {
+        let (_container, pool) = setup_db().await;
+        let mut conn = pool.get().expect("connection");
+        // Slot names are shared by every database of the server.
+        let slot = format!("relay_{}", Uuid::new_v4().simple());
+        diesel::sql_query("SELECT pg_create_logical_replication_slot($1, 'pgoutput')")
+            .bind::<diesel::sql_types::Text, _>(&slot)
+            .execute(&mut conn)
+            .expect("create slot");
+
+        assert!(drop_replication_slot(&mut conn, &slot).expect("drop"));
+        assert!(!drop_replication_slot(&mut conn, &slot).expect("drop again"));
+    }
+
+    #[tokio::test]
+    async fn dump_contains_order_lines_and_events() {
+        let (_container, pool) = setup_db().await;
+        let order_id = place_order(&pool);
+        let mut conn = pool.get().expect("connection");
+
+        let dump = dump_order(&mut conn, order_id).expect("dump");
+        let json = serde_json::to_value(&dump).expect("serializable");
+        assert_eq!(json["order"]["id"], order_id.to_string());
+        assert_eq!(json["lines"][0]["quantity"], 2);
+        assert_eq!(json["outbox_events"][0]["event_type"], "OrderCreated");
+
+        assert!(matches!(
+            dump_order(&mut conn, Uuid::new_v4()),
+            Err(AdminError::NotFound(_))
+        ));
+    }
+}
@@ -1 +1,4 @@
 pub mod order_service;
+pub mod outbox_service;
+pub mod saga_service;
+pub mod webhook_service;
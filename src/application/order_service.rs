@@ -1,31 +1,744 @@
+use chrono::Utc;
 use uuid::Uuid;
 
+use crate::domain::caller::Caller;
 use crate::domain::errors::DomainError;
-use crate::domain::order::{ListResult, OrderLineInput, OrderView};
-use crate::domain::ports::OrderRepository;
+use crate::domain::events::{
+    self, OrderCreatedLine, OrderCreatedPayload, OrderStatusChangedPayload,
+};
+use crate::domain::order::{
+    ListResult, OrderHistory, OrderLineInput, OrderStatus, OrderView, PaymentOutcome,
+    PaymentTransition, SourceMessage, StatusChange, ORDER_CREATED,
+};
+use crate::domain::ports::{OrderRepository, Transaction, UnitOfWork};
+use crate::domain::saga::{OrderSaga, SagaState, SagaTimeouts};
 
-pub struct OrderService<R> {
+/// How often a status change is re-decided after losing a race with another one.
+pub(crate) const STALE_STATUS_RETRIES: usize = 3;
+
+/// Decides what a [`Caller`] may see and do with orders.
+///
+/// Customers are confined to their own orders; staff are unrestricted. A
+/// customer looking up someone else's order gets the same answer as for an
+/// unknown id so the existence of the order is not leaked.
+pub struct OrderAccessPolicy;
+
+impl OrderAccessPolicy {
+    pub fn can_view(caller: &Caller, order: &OrderView) -> bool {
+        match caller {
+            Caller::Customer(customer_id) => order.customer_id == *customer_id,
+            Caller::Staff => true,
+        }
+    }
+
+    pub fn can_view_saga(caller: &Caller, saga: &OrderSaga) -> bool {
+        match caller {
+            Caller::Customer(customer_id) => saga.customer_id == *customer_id,
+            Caller::Staff => true,
+        }
+    }
+
+    pub fn can_place_for(caller: &Caller, customer_id: Uuid) -> bool {
+        match caller {
+            Caller::Customer(own_id) => *own_id == customer_id,
+            Caller::Staff => true,
+        }
+    }
+
+    /// Customer filter to apply when listing orders (`None` = all customers).
+    pub fn list_filter(caller: &Caller) -> Option<Uuid> {
+        match caller {
+            Caller::Customer(customer_id) => Some(*customer_id),
+            Caller::Staff => None,
+        }
+    }
+}
+
+/// What [`OrderService::apply_payment`] did with a payment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PaymentApplication {
+    /// The order moved to this status.
+    Applied(OrderStatus),
+    /// The order already had the resulting status, or the message was seen before.
+    AlreadyApplied,
+    /// The payment contradicts the order's current status and was ignored.
+    Rejected { status: String },
+    /// The order's placement saga settles it, so the payment was ignored.
+    InSaga { state: SagaState },
+}
+
+/// Why a unit of work was rolled back.
+pub(crate) enum Rollback {
+    /// Another writer changed the order (or saga) since it was read.
+    Stale,
+    Failed(DomainError),
+}
+
+impl From<DomainError> for Rollback {
+    fn from(e: DomainError) -> Self {
+        Rollback::Failed(e)
+    }
+}
+
+/// Order use cases. Queries go to the repository; each change runs in one
+/// unit of work with everything it entails.
+pub struct OrderService<R, U> {
     repo: R,
+    uow: U,
+    /// Start a placement saga with every new order.
+    saga: Option<SagaTimeouts>,
 }
 
-impl<R: OrderRepository> OrderService<R> {
-    pub fn new(repo: R) -> Self {
-        Self { repo }
+impl<R: OrderRepository, U: UnitOfWork> OrderService<R, U> {
+    pub fn new(repo: R, uow: U) -> Self {
+        Self {
+            repo,
+            uow,
+            saga: None,
+        }
+    }
+
+    /// Start the placement saga of each created order in the order's own
+    /// unit of work, so that its first command is published with it.
+    pub fn with_saga(mut self, timeouts: SagaTimeouts) -> Self {
+        self.saga = Some(timeouts);
+        self
     }
 
+    /// Store the order, record its `OrderCreated` event and start its saga,
+    /// all or nothing.
     pub fn create_order(
         &self,
+        caller: &Caller,
         customer_id: Uuid,
         lines: Vec<OrderLineInput>,
     ) -> Result<Uuid, DomainError> {
-        self.repo.create(customer_id, lines)
+        if !OrderAccessPolicy::can_place_for(caller, customer_id) {
+            return Err(DomainError::Forbidden(
+                "customers may only place their own orders".to_string(),
+            ));
+        }
+        let order_id = Uuid::new_v4();
+        self.uow.run(|tx| {
+            tx.orders().insert(order_id, customer_id, &lines)?;
+            // Debezium's EventRouter SMT derives the Kafka topic from `aggregate_type`.
+            tx.outbox().append(&OrderCreatedPayload {
+                order_id,
+                customer_id,
+                status: OrderStatus::Pending.as_str().to_string(),
+                lines: lines
+                    .iter()
+                    .map(|l| OrderCreatedLine {
+                        product_id: l.product_id,
+                        quantity: l.quantity,
+                        unit_price: l.unit_price.to_string(),
+                    })
+                    .collect(),
+            })?;
+            if let Some(timeouts) = &self.saga {
+                let start = OrderSaga::start(order_id, customer_id, &lines, timeouts, Utc::now());
+                tx.sagas().start(&start)?;
+            }
+            Ok(order_id)
+        })
     }
 
-    pub fn get_order(&self, id: Uuid) -> Result<Option<OrderView>, DomainError> {
-        self.repo.find_by_id(id)
+    pub fn get_order(&self, caller: &Caller, id: Uuid) -> Result<Option<OrderView>, DomainError> {
+        Ok(self
+            .repo
+            .find_by_id(id)?
+            .filter(|order| OrderAccessPolicy::can_view(caller, order)))
     }
 
-    pub fn list_orders(&self, page: i64, limit: i64) -> Result<ListResult, DomainError> {
-        self.repo.list(page, limit)
+    pub fn list_orders(
+        &self,
+        caller: &Caller,
+        page: i64,
+        limit: i64,
+    ) -> Result<ListResult, DomainError> {
+        self.repo
+            .list(page, limit, OrderAccessPolicy::list_filter(caller))
+    }
+
+    /// Events of an order the caller may view, or `None` like [`get_order`].
+    ///
+    /// An order whose early events were cleaned up still yields the events
+    /// that remain, flagged as incomplete.
+    ///
+    /// [`get_order`]: Self::get_order
+    pub fn order_history(
+        &self,
+        caller: &Caller,
+        id: Uuid,
+    ) -> Result<Option<OrderHistory>, DomainError> {
+        if self.get_order(caller, id)?.is_none() {
+            return Ok(None);
+        }
+        let events = self.repo.events(id)?;
+        let complete = events
+            .first()
+            .is_some_and(|e| e.event_type == ORDER_CREATED);
+        Ok(Some(OrderHistory { events, complete }))
+    }
+
+    /// Move an order along after the payments service reported `outcome`.
+    ///
+    /// `source` is the inbound message carrying the outcome; it is recorded
+    /// with the status change so that a redelivery is a no-op.
+    pub fn apply_payment(
+        &self,
+        order_id: Uuid,
+        outcome: &PaymentOutcome,
+        source: &SourceMessage,
+    ) -> Result<PaymentApplication, DomainError> {
+        for _ in 0..STALE_STATUS_RETRIES {
+            match self
+                .uow
+                .run(|tx| Self::apply_payment_in(tx, order_id, outcome, source))
+            {
+                Ok(application) => return Ok(application),
+                // Someone else changed the order in the meantime: decide again.
+                Err(Rollback::Stale) => continue,
+                Err(Rollback::Failed(e)) => return Err(e),
+            }
+        }
+        Err(DomainError::Internal(format!(
+            "order {} kept changing while applying a payment",
+            order_id
+        )))
+    }
+
+    fn apply_payment_in(
+        tx: &mut dyn Transaction,
+        order_id: Uuid,
+        outcome: &PaymentOutcome,
+        source: &SourceMessage,
+    ) -> Result<PaymentApplication, Rollback> {
+        let order = tx
+            .orders()
+            .find_by_id(order_id)?
+            .ok_or(DomainError::NotFound)?;
+        // A running saga settles the order itself once the payment is
+        // authorized; paying it here would leave the saga behind.
+        if let Some(saga) = tx.sagas().find(order_id)? {
+            if !saga.state.is_terminal() {
+                return Ok(PaymentApplication::InSaga { state: saga.state });
+            }
+        }
+        let Some(current) = OrderStatus::parse(&order.status) else {
+            return Ok(PaymentApplication::Rejected {
+                status: order.status,
+            });
+        };
+        let target = match current.after_payment(outcome) {
+            PaymentTransition::Move(target) => target,
+            PaymentTransition::Unchanged => return Ok(PaymentApplication::AlreadyApplied),
+            PaymentTransition::Invalid => {
+                return Ok(PaymentApplication::Rejected {
+                    status: order.status,
+                })
+            }
+        };
+
+        if !tx.inbox().record(source)? {
+            return Ok(PaymentApplication::AlreadyApplied);
+        }
+        if !tx.orders().update_status(order_id, current, target)? {
+            // Also forget the source message so that it can be retried.
+            return Err(Rollback::Stale);
+        }
+        let reason = match outcome {
+            PaymentOutcome::Failed { reason } => reason.clone(),
+            PaymentOutcome::Captured => None,
+        };
+        tx.outbox().append(&StatusChange {
+            order_id,
+            from: current,
+            to: target,
+            event_type: outcome.event_type(),
+            payload: events::to_json(&OrderStatusChangedPayload {
+                order_id,
+                customer_id: order.customer_id,
+                status: target.as_str().to_string(),
+                previous_status: current.as_str().to_string(),
+                reason,
+            }),
+        })?;
+        Ok(PaymentApplication::Applied(target))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::order::{OrderEvent, ORDER_AGGREGATE, ORDER_PAID};
+    use crate::domain::ports::{
+        TransactionalInbox, TransactionalOrderRepository, TransactionalOutbox,
+        TransactionalSagaRepository,
+    };
+    use crate::domain::saga::{RESERVE_INVENTORY, SAGA_AGGREGATE};
+    use crate::infrastructure::in_memory::InMemoryUnitOfWork;
+    use chrono::Utc;
+    use std::sync::Mutex;
+
+    struct StubRepo {
+        order: OrderView,
+        list_filter: Mutex<Option<Option<Uuid>>>,
+        events: Vec<OrderEvent>,
+    }
+
+    impl StubRepo {
+        fn owned_by(customer_id: Uuid) -> Self {
+            Self {
+                order: OrderView {
+                    id: Uuid::new_v4(),
+                    customer_id,
+                    status: "PENDING".to_string(),
+                    created_at: Utc::now(),
+                    lines: vec![],
+                },
+                list_filter: Mutex::new(None),
+                events: vec![],
+            }
+        }
+
+        fn with_events(mut self, event_types: &[&str]) -> Self {
+            let order_id = self.order.id;
+            self.events = event_types
+                .iter()
+                .zip(1..)
+                .map(|(event_type, sequence)| OrderEvent {
+                    id: Uuid::new_v4(),
+                    order_id,
+                    event_type: event_type.to_string(),
+                    sequence,
+                    occurred_at: Utc::now(),
+                    payload: serde_json::json!({}),
+                })
+                .collect();
+            self
+        }
+    }
+
+    impl OrderRepository for StubRepo {
+        fn find_by_id(&self, id: Uuid) -> Result<Option<OrderView>, DomainError> {
+            Ok((id == self.order.id).then(|| self.order.clone()))
+        }
+
+        fn list(
+            &self,
+            _page: i64,
+            _limit: i64,
+            customer_id: Option<Uuid>,
+        ) -> Result<ListResult, DomainError> {
+            *self.list_filter.lock().expect("lock") = Some(customer_id);
+            Ok(ListResult {
+                items: vec![],
+                total: 0,
+            })
+        }
+
+        fn events(&self, order_id: Uuid) -> Result<Vec<OrderEvent>, DomainError> {
+            Ok(if order_id == self.order.id {
+                self.events.clone()
+            } else {
+                vec![]
+            })
+        }
+    }
+
+    /// Reads an order with the status it had before a concurrent change, in
+    /// the next unit of work only.
+    struct StaleReads {
+        inner: InMemoryUnitOfWork,
+        stale: Mutex<Option<OrderStatus>>,
+    }
+
+    struct StaleTransaction<'t> {
+        tx: &'t mut dyn Transaction,
+        stale: Option<OrderStatus>,
+    }
+
+    impl UnitOfWork for StaleReads {
+        fn run<T, E>(&self, work: impl FnOnce(&mut dyn Transaction) -> Result<T, E>) -> Result<T, E>
+        where
+            E: From<DomainError>,
+        {
+            let stale = self.stale.lock().expect("lock").take();
+            self.inner
+                .run(|tx| work(&mut StaleTransaction { tx, stale }))
+        }
+    }
+
+    impl Transaction for StaleTransaction<'_> {
+        fn orders(&mut self) -> &mut dyn TransactionalOrderRepository {
+            self
+        }
+
+        fn sagas(&mut self) -> &mut dyn TransactionalSagaRepository {
+            self.tx.sagas()
+        }
+
+        fn outbox(&mut self) -> &mut dyn TransactionalOutbox {
+            self.tx.outbox()
+        }
+
+        fn inbox(&mut self) -> &mut dyn TransactionalInbox {
+            self.tx.inbox()
+        }
+    }
+
+    impl TransactionalOrderRepository for StaleTransaction<'_> {
+        fn find_by_id(&mut self, id: Uuid) -> Result<Option<OrderView>, DomainError> {
+            let order = self.tx.orders().find_by_id(id)?;
+            Ok(order.map(|order| match self.stale {
+                Some(stale) => OrderView {
+                    status: stale.as_str().to_string(),
+                    ..order
+                },
+                None => order,
+            }))
+        }
+
+        fn insert(
+            &mut self,
+            order_id: Uuid,
+            customer_id: Uuid,
+            lines: &[OrderLineInput],
+        ) -> Result<(), DomainError> {
+            self.tx.orders().insert(order_id, customer_id, lines)
+        }
+
+        fn update_status(
+            &mut self,
+            order_id: Uuid,
+            from: OrderStatus,
+            to: OrderStatus,
+        ) -> Result<bool, DomainError> {
+            self.tx.orders().update_status(order_id, from, to)
+        }
+    }
+
+    fn stub_service(repo: StubRepo) -> OrderService<StubRepo, InMemoryUnitOfWork> {
+        OrderService::new(repo, InMemoryUnitOfWork::new())
+    }
+
+    /// An order in `status`, stored through `uow`.
+    fn stored_order(uow: &impl UnitOfWork, status: OrderStatus) -> Uuid {
+        let order_id = Uuid::new_v4();
+        uow.run(|tx| {
+            tx.orders().insert(order_id, Uuid::new_v4(), &[])?;
+            if status != OrderStatus::Pending {
+                tx.orders()
+                    .update_status(order_id, OrderStatus::Pending, status)?;
+            }
+            Ok::<_, DomainError>(())
+        })
+        .expect("store order");
+        order_id
+    }
+
+    fn payment_message() -> SourceMessage {
+        SourceMessage {
+            id: "payment-1".to_string(),
+            message_type: "PaymentCaptured".to_string(),
+        }
+    }
+
+    #[test]
+    fn customer_sees_own_order() {
+        let owner = Uuid::new_v4();
+        let repo = StubRepo::owned_by(owner);
+        let order_id = repo.order.id;
+        let service = stub_service(repo);
+
+        let found = service
+            .get_order(&Caller::Customer(owner), order_id)
+            .expect("lookup succeeds");
+        assert_eq!(found.map(|o| o.id), Some(order_id));
+    }
+
+    #[test]
+    fn foreign_order_looks_like_a_missing_one() {
+        let repo = StubRepo::owned_by(Uuid::new_v4());
+        let order_id = repo.order.id;
+        let service = stub_service(repo);
+
+        let found = service
+            .get_order(&Caller::Customer(Uuid::new_v4()), order_id)
+            .expect("lookup succeeds");
+        assert!(found.is_none());
+    }
+    #[test]
+    fn staff_sees_any_order() {
+        let repo = StubRepo::owned_by(Uuid::new_v4());
+        let order_id = repo.order.id;
+        let service = stub_service(repo);
+
+        let found = service
+            .get_order(&Caller::Staff, order_id)
+            .expect("lookup succeeds");
+        assert!(found.is_some());
+    }
+
+    #[test]
+    fn customer_listing_is_filtered_to_own_orders() {
+        let customer = Uuid::new_v4();
+        let service = stub_service(StubRepo::owned_by(customer));
+
+        service
+            .list_orders(&Caller::Customer(customer), 1, 20)
+            .expect("list succeeds");
+        assert_eq!(
+            *service.repo.list_filter.lock().expect("lock"),
+            Some(Some(customer))
+        );
+    }
+
+    #[test]
+    fn staff_listing_is_unfiltered() {
+        let service = stub_service(StubRepo::owned_by(Uuid::new_v4()));
+
+        service
+            .list_orders(&Caller::Staff, 1, 20)
+            .expect("list succeeds");
+        assert_eq!(*service.repo.list_filter.lock().expect("lock"), Some(None));
+    }
+
+    #[test]
+    fn customer_cannot_place_order_for_someone_else() {
+        let service = stub_service(StubRepo::owned_by(Uuid::new_v4()));
+
+        let result =
+            service.create_order(&Caller::Customer(Uuid::new_v4()), Uuid::new_v4(), vec![]);
+        assert!(matches!(result, Err(DomainError::Forbidden(_))));
+    }
+
+    #[test]
+    fn customer_can_place_own_order() {
+        let customer = Uuid::new_v4();
+        let service = stub_service(StubRepo::owned_by(customer));
+
+        assert!(service
+            .create_order(&Caller::Customer(customer), customer, vec![])
+            .is_ok());
+    }
+
+    #[test]
+    fn history_starting_with_order_created_is_complete() {
+        let repo = StubRepo::owned_by(Uuid::new_v4()).with_events(&["OrderCreated", "OrderPaid"]);
+        let order_id = repo.order.id;
+        let service = stub_service(repo);
+
+        let history = service
+            .order_history(&Caller::Staff, order_id)
+            .expect("history")
+            .expect("order exists");
+        assert!(history.complete);
+        assert_eq!(history.events.len(), 2);
+    }
+
+    #[test]
+    fn history_missing_its_oldest_events_is_incomplete() {
+        let repo = StubRepo::owned_by(Uuid::new_v4()).with_events(&["OrderPaid"]);
+        let order_id = repo.order.id;
+        let service = stub_service(repo);
+
+        let history = service
+            .order_history(&Caller::Staff, order_id)
+            .expect("history")
+            .expect("order exists");
+        assert!(!history.complete);
+
+        let repo = StubRepo::owned_by(Uuid::new_v4());
+        let order_id = repo.order.id;
+        let service = stub_service(repo);
+        let history = service
+            .order_history(&Caller::Staff, order_id)
+            .expect("history")
+            .expect("order exists even without events");
+        assert!(history.events.is_empty());
+        assert!(!history.complete);
+    }
+
+    #[test]
+    fn history_of_a_foreign_order_is_hidden() {
+        let repo = StubRepo::owned_by(Uuid::new_v4()).with_events(&["OrderCreated"]);
+        let order_id = repo.order.id;
+        let service = stub_service(repo);
+
+        let history = service
+            .order_history(&Caller::Customer(Uuid::new_v4()), order_id)
+            .expect("history");
+        assert!(history.is_none());
+    }
+
+    #[test]
+    fn order_its_event_and_its_saga_are_created_together() {
+        let customer = Uuid::new_v4();
+        let uow = InMemoryUnitOfWork::new();
+        let service = OrderService::new(StubRepo::owned_by(customer), uow.clone())
+            .with_saga(SagaTimeouts::default());
+
+        let order_id = service
+            .create_order(&Caller::Customer(customer), customer, vec![])
+            .expect("create");
+
+        let order = uow.order(order_id).expect("stored order");
+        assert_eq!(order.customer_id, customer);
+        assert_eq!(order.status, "PENDING");
+        assert!(uow.saga(order_id).is_some());
+        let outbox: Vec<(String, String)> = uow
+            .outbox()
+            .into_iter()
+            .map(|e| (e.aggregate_type, e.event_type))
+            .collect();
+        assert_eq!(
+            outbox,
+            [
+                (ORDER_AGGREGATE.to_string(), ORDER_CREATED.to_string()),
+                (SAGA_AGGREGATE.to_string(), RESERVE_INVENTORY.to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn forbidden_order_writes_nothing() {
+        let uow = InMemoryUnitOfWork::new();
+        let service = OrderService::new(StubRepo::owned_by(Uuid::new_v4()), uow.clone());
+
+        let result =
+            service.create_order(&Caller::Customer(Uuid::new_v4()), Uuid::new_v4(), vec![]);
+        assert!(result.is_err());
+        assert!(uow.outbox().is_empty());
+    }
+
+    #[test]
+    fn captured_payment_marks_a_pending_order_paid() {
+        let uow = InMemoryUnitOfWork::new();
+        let order_id = stored_order(&uow, OrderStatus::Pending);
+        let service = OrderService::new(StubRepo::owned_by(Uuid::new_v4()), uow.clone());
+
+        let result = service
+            .apply_payment(order_id, &PaymentOutcome::Captured, &payment_message())
+            .expect("apply");
+        assert_eq!(result, PaymentApplication::Applied(OrderStatus::Paid));
+
+        assert_eq!(uow.order(order_id).expect("order").status, "PAID");
+        let outbox = uow.outbox();
+        assert_eq!(outbox.len(), 1);
+        assert_eq!(outbox[0].event_type, ORDER_PAID);
+        assert_eq!(outbox[0].payload["status"], "PAID");
+        assert_eq!(outbox[0].payload["previous_status"], "PENDING");
+    }
+
+    #[test]
+    fn payment_leaves_an_order_with_a_running_saga_alone() {
+        let customer = Uuid::new_v4();
+        let uow = InMemoryUnitOfWork::new();
+        let service = OrderService::new(StubRepo::owned_by(customer), uow.clone())
+            .with_saga(SagaTimeouts::default());
+        let order_id = service
+            .create_order(&Caller::Customer(customer), customer, vec![])
+            .expect("create");
+
+        let result = service
+            .apply_payment(order_id, &PaymentOutcome::Captured, &payment_message())
+            .expect("apply");
+        assert_eq!(
+            result,
+            PaymentApplication::InSaga {
+                state: SagaState::ReservingInventory
+            }
+        );
+        assert_eq!(uow.order(order_id).expect("order").status, "PENDING");
+        assert_eq!(uow.outbox().len(), 2);
+    }
+
+    #[test]
+    fn redelivered_payment_is_already_applied() {
+        let uow = InMemoryUnitOfWork::new();
+        let order_id = stored_order(&uow, OrderStatus::PaymentFailed);
+        let service = OrderService::new(StubRepo::owned_by(Uuid::new_v4()), uow.clone());
+
+        service
+            .apply_payment(order_id, &PaymentOutcome::Captured, &payment_message())
+            .expect("apply");
+        // Moved on since, so only the inbox recognises the message.
+        uow.run(|tx| {
+            tx.orders()
+                .update_status(order_id, OrderStatus::Paid, OrderStatus::PaymentFailed)
+        })
+        .expect("move on");
+        let result = service
+            .apply_payment(order_id, &PaymentOutcome::Captured, &payment_message())
+            .expect("apply again");
+        assert_eq!(result, PaymentApplication::AlreadyApplied);
+        assert_eq!(uow.outbox().len(), 1);
+    }
+
+    #[test]
+    fn repeated_payment_outcome_changes_nothing() {
+        let uow = InMemoryUnitOfWork::new();
+        let order_id = stored_order(&uow, OrderStatus::Paid);
+        let service = OrderService::new(StubRepo::owned_by(Uuid::new_v4()), uow.clone());
+
+        let result = service
+            .apply_payment(order_id, &PaymentOutcome::Captured, &payment_message())
+            .expect("apply");
+        assert_eq!(result, PaymentApplication::AlreadyApplied);
+        assert!(uow.outbox().is_empty());
+    }
+
+    #[test]
+    fn payment_failure_after_payment_is_rejected() {
+        let uow = InMemoryUnitOfWork::new();
+        let order_id = stored_order(&uow, OrderStatus::Paid);
+        let service = OrderService::new(StubRepo::owned_by(Uuid::new_v4()), uow);
+
+        let result = service
+            .apply_payment(
+                order_id,
+                &PaymentOutcome::Failed { reason: None },
+                &payment_message(),
+            )
+            .expect("apply");
+        assert_eq!(
+            result,
+            PaymentApplication::Rejected {
+                status: "PAID".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn payment_is_decided_again_after_a_concurrent_status_change() {
+        let inner = InMemoryUnitOfWork::new();
+        let order_id = stored_order(&inner, OrderStatus::PaymentFailed);
+        let uow = StaleReads {
+            inner: inner.clone(),
+            stale: Mutex::new(Some(OrderStatus::Pending)),
+        };
+        let service = OrderService::new(StubRepo::owned_by(Uuid::new_v4()), uow);
+
+        let result = service
+            .apply_payment(order_id, &PaymentOutcome::Captured, &payment_message())
+            .expect("apply");
+        assert_eq!(result, PaymentApplication::Applied(OrderStatus::Paid));
+        let outbox = inner.outbox();
+        assert_eq!(outbox.len(), 1);
+        assert_eq!(outbox[0].payload["previous_status"], "PAYMENT_FAILED");
+    }
+
+    #[test]
+    fn payment_for_unknown_order_is_not_found() {
+        let service = stub_service(StubRepo::owned_by(Uuid::new_v4()));
+
+        let result = service.apply_payment(
+            Uuid::new_v4(),
+            &PaymentOutcome::Captured,
+            &payment_message(),
+        );
+        assert!(matches!(result, Err(DomainError::NotFound)));
     }
 }
@@ -0,0 +1,47 @@
+use uuid::Uuid;
+
+use crate::domain::errors::DomainError;
+use crate::domain::outbox::OutboxDeadLetter;
+use crate::domain::ports::OutboxDeadLetterRepository;
+
+/// Most dead letters returned at once.
+const DEAD_LETTER_LIMIT: i64 = 100;
+
+/// Operator access to the outbox events the relay gave up on.
+pub struct OutboxDeadLetterService<R> {
+    repo: R,
+}
+
+impl<R: OutboxDeadLetterRepository> OutboxDeadLetterService<R> {
+    pub fn new(repo: R) -> Self {
+        Self { repo }
+    }
+
+    /// Most recently dead-lettered first.
+    pub fn dead_letters(&self) -> Result<Vec<OutboxDeadLetter>, DomainError> {
+        self.repo.list(DEAD_LETTER_LIMIT)
+    }
+
+    pub fn dead_letter(&self, event_id: Uuid) -> Result<OutboxDeadLetter, DomainError> {
+        self.repo.find(event_id)?.ok_or(DomainError::NotFound)
+    }
+
+    /// Publish a dead-lettered event again, with a fresh attempt budget.
+    pub fn requeue(&self, event_id: Uuid) -> Result<(), DomainError> {
+        if self.repo.requeue(event_id)? {
+            Ok(())
+        } else {
+            Err(DomainError::NotFound)
+        }
+    }
+
+    /// Never publish a dead-lettered event. It stays in the outbox, marked as
+    /// discarded.
+    pub fn discard(&self, event_id: Uuid) -> Result<(), DomainError> {
+        if self.repo.discard(event_id)? {
+            Ok(())
+        } else {
+            Err(DomainError::NotFound)
+        }
+    }
+}
@@ -0,0 +1,514 @@
+use chrono::Utc;
+use uuid::Uuid;
+
+use super::order_service::{OrderAccessPolicy, Rollback, STALE_STATUS_RETRIES};
+use crate::domain::caller::Caller;
+use crate::domain::errors::DomainError;
+use crate::domain::order::SourceMessage;
+use crate::domain::ports::{SagaRepository, Transaction, UnitOfWork};
+use crate::domain::saga::{
+    SagaInput, SagaReply, SagaState, SagaTimeouts, SagaTransition, SagaView,
+};
+
+/// How many overdue sagas one [`SagaService::expire_overdue`] call handles.
+const OVERDUE_BATCH: i64 = 100;
+
+/// What [`SagaService`] did with a reply or timeout.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SagaApplication {
+    /// The saga moved to this state.
+    Advanced(SagaState),
+    /// The message was seen before.
+    AlreadyApplied,
+    /// The saga is not waiting for this (a late or repeated reply, or a
+    /// deadline that was pushed back) and stays in `state`.
+    Ignored { state: SagaState },
+}
+
+/// Drives order placement sagas from replies and step timeouts. Queries go
+/// to the repository; each transition runs in one unit of work with the
+/// commands it sends and the order change it makes.
+pub struct SagaService<S, U> {
+    repo: S,
+    uow: U,
+    timeouts: SagaTimeouts,
+}
+
+impl<S: SagaRepository, U: UnitOfWork> SagaService<S, U> {
+    pub fn new(repo: S, uow: U, timeouts: SagaTimeouts) -> Self {
+        Self {
+            repo,
+            uow,
+            timeouts,
+        }
+    }
+
+    /// Saga and journal of an order the caller may view; `None` for unknown
+    /// and foreign orders alike, and for orders placed without a saga.
+    pub fn saga(&self, caller: &Caller, order_id: Uuid) -> Result<Option<SagaView>, DomainError> {
+        let Some(saga) = self
+            .repo
+            .find(order_id)?
+            .filter(|saga| OrderAccessPolicy::can_view_saga(caller, saga))
+        else {
+            return Ok(None);
+        };
+        let steps = self.repo.steps(order_id)?;
+        Ok(Some(SagaView { saga, steps }))
+    }
+
+    /// Advance the saga of `order_id` with a reply from another service.
+    ///
+    /// `source` is recorded with the transition so that a redelivery is a
+    /// no-op.
+    pub fn handle_reply(
+        &self,
+        order_id: Uuid,
+        reply: &SagaReply,
+        source: &SourceMessage,
+    ) -> Result<SagaApplication, DomainError> {
+        self.advance(order_id, SagaInput::Reply(reply), Some(source))
+    }
+
+    /// Time out the steps whose deadline has passed; returns how many sagas
+    /// moved on.
+    pub fn expire_overdue(&self) -> Result<usize, DomainError> {
+        let mut advanced = 0;
+        for order_id in self.repo.overdue(Utc::now(), OVERDUE_BATCH)? {
+            if let SagaApplication::Advanced(_) =
+                self.advance(order_id, SagaInput::Timeout, None)?
+            {
+                advanced += 1;
+            }
+        }
+        Ok(advanced)
+    }
+
+    fn advance(
+        &self,
+        order_id: Uuid,
+        input: SagaInput<'_>,
+        source: Option<&SourceMessage>,
+    ) -> Result<SagaApplication, DomainError> {
+        for _ in 0..STALE_STATUS_RETRIES {
+            let saga = self.repo.find(order_id)?.ok_or(DomainError::NotFound)?;
+            let Some(transition) = saga.decide(input, &self.timeouts, Utc::now()) else {
+                return Ok(SagaApplication::Ignored { state: saga.state });
+            };
+            match self.uow.run(|tx| Self::advance_in(tx, &transition, source)) {
+                Ok(application) => return Ok(application),
+                // A reply and a timeout raced: decide again.
+                Err(Rollback::Stale) => continue,
+                Err(Rollback::Failed(e)) => return Err(e),
+            }
+        }
+        Err(DomainError::Internal(format!(
+            "saga of order {} kept changing while advancing it",
+            order_id
+        )))
+    }
+
+    fn advance_in(
+        tx: &mut dyn Transaction,
+        transition: &SagaTransition,
+        source: Option<&SourceMessage>,
+    ) -> Result<SagaApplication, Rollback> {
+        if let Some(source) = source {
+            if !tx.inbox().record(source)? {
+                return Ok(SagaApplication::AlreadyApplied);
+            }
+        }
+        if !tx.sagas().advance(transition)? {
+            // Also forget the source message so that it can be retried.
+            return Err(Rollback::Stale);
+        }
+        for command in &transition.commands {
+            tx.outbox()
+                .append(&command.for_order(transition.order_id))?;
+        }
+        if let Some(change) = &transition.order_change {
+            // Payments leave orders with a running saga alone, so only a
+            // change made by hand gets here; the saga must not end without
+            // the order.
+            if !tx
+                .orders()
+                .update_status(change.order_id, change.from, change.to)?
+            {
+                return Err(Rollback::Failed(DomainError::Internal(format!(
+                    "order {} is no longer {}; its saga cannot move to {}",
+                    change.order_id,
+                    change.from.as_str(),
+                    transition.to.as_str()
+                ))));
+            }
+            tx.outbox().append(change)?;
+        }
+        Ok(SagaApplication::Advanced(transition.to))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    use bigdecimal::BigDecimal;
+    use chrono::{DateTime, Utc};
+
+    use super::*;
+    use crate::domain::order::{OrderLineInput, OrderStatus, ORDER_CANCELLED};
+    use crate::domain::outbox::OutboxMessage;
+    use crate::domain::ports::{
+        TransactionalInbox, TransactionalOrderRepository, TransactionalOutbox,
+        TransactionalSagaRepository,
+    };
+    use crate::domain::saga::{
+        OrderSaga, SagaStepEntry, AUTHORIZE_PAYMENT, RELEASE_INVENTORY, RESERVE_INVENTORY,
+    };
+    use crate::infrastructure::in_memory::InMemoryUnitOfWork;
+
+    /// Reads the saga of one order from an [`InMemoryUnitOfWork`].
+    struct StubSagas {
+        uow: InMemoryUnitOfWork,
+        order_id: Uuid,
+        /// Returned by the next `find` instead, as if another writer moved
+        /// the saga on right after it was read.
+        stale: Mutex<Option<OrderSaga>>,
+    }
+
+    impl SagaRepository for StubSagas {
+        fn find(&self, order_id: Uuid) -> Result<Option<OrderSaga>, DomainError> {
+            if let Some(stale) = self.stale.lock().expect("lock").take() {
+                return Ok(Some(stale));
+            }
+            Ok(self.uow.saga(order_id))
+        }
+
+        fn steps(&self, _order_id: Uuid) -> Result<Vec<SagaStepEntry>, DomainError> {
+            Ok(vec![])
+        }
+
+        fn overdue(&self, now: DateTime<Utc>, _limit: i64) -> Result<Vec<Uuid>, DomainError> {
+            Ok(self
+                .uow
+                .saga(self.order_id)
+                .filter(|saga| saga.is_overdue(now))
+                .map(|saga| saga.order_id)
+                .into_iter()
+                .collect())
+        }
+    }
+
+    /// Fails to append `failing` events, as a lost connection would.
+    struct FailingOutbox {
+        inner: InMemoryUnitOfWork,
+        failing: &'static str,
+    }
+
+    struct FailingTransaction<'t> {
+        tx: &'t mut dyn Transaction,
+        failing: &'static str,
+    }
+
+    impl UnitOfWork for FailingOutbox {
+        fn run<T, E>(&self, work: impl FnOnce(&mut dyn Transaction) -> Result<T, E>) -> Result<T, E>
+        where
+            E: From<DomainError>,
+        {
+            let failing = self.failing;
+            self.inner
+                .run(|tx| work(&mut FailingTransaction { tx, failing }))
+        }
+    }
+
+    impl Transaction for FailingTransaction<'_> {
+        fn orders(&mut self) -> &mut dyn TransactionalOrderRepository {
+            self.tx.orders()
+        }
+
+        fn sagas(&mut self) -> &mut dyn TransactionalSagaRepository {
+            self.tx.sagas()
+        }
+
+        fn outbox(&mut self) -> &mut dyn TransactionalOutbox {
+            self
+        }
+
+        fn inbox(&mut self) -> &mut dyn TransactionalInbox {
+            self.tx.inbox()
+        }
+    }
+
+    impl TransactionalOutbox for FailingTransaction<'_> {
+        fn append(&mut self, message: &dyn OutboxMessage) -> Result<(), DomainError> {
+            if message.event_type() == self.failing {
+                return Err(DomainError::Internal("connection lost".to_string()));
+            }
+            self.tx.outbox().append(message)
+        }
+    }
+
+    /// A placed order of `customer_id` with its saga, stored through `uow`.
+    fn placed_order(
+        uow: &InMemoryUnitOfWork,
+        customer_id: Uuid,
+        timeouts: &SagaTimeouts,
+    ) -> StubSagas {
+        let order_id = Uuid::new_v4();
+        let lines = [OrderLineInput {
+            product_id: Uuid::new_v4(),
+            quantity: 1,
+            unit_price: BigDecimal::from(10),
+        }];
+        let start = OrderSaga::start(order_id, customer_id, &lines, timeouts, Utc::now());
+        uow.run(|tx| {
+            tx.orders().insert(order_id, customer_id, &lines)?;
+            tx.sagas().start(&start)
+        })
+        .expect("place order");
+        StubSagas {
+            uow: uow.clone(),
+            order_id,
+            stale: Mutex::new(None),
+        }
+    }
+
+    fn service(timeouts: SagaTimeouts) -> SagaService<StubSagas, InMemoryUnitOfWork> {
+        let uow = InMemoryUnitOfWork::new();
+        let repo = placed_order(&uow, Uuid::new_v4(), &timeouts);
+        SagaService::new(repo, uow, timeouts)
+    }
+
+    fn message(id: &str) -> SourceMessage {
+        SourceMessage {
+            id: id.to_string(),
+            message_type: "InventoryReserved".to_string(),
+        }
+    }
+
+    fn state<U: UnitOfWork>(service: &SagaService<StubSagas, U>) -> SagaState {
+        let repo = &service.repo;
+        repo.uow.saga(repo.order_id).expect("saga").state
+    }
+
+    fn outbox_types(uow: &InMemoryUnitOfWork) -> Vec<String> {
+        uow.outbox().into_iter().map(|e| e.event_type).collect()
+    }
+
+    #[test]
+    fn saga_of_a_foreign_order_is_hidden() {
+        let owner = Uuid::new_v4();
+        let uow = InMemoryUnitOfWork::new();
+        let repo = placed_order(&uow, owner, &SagaTimeouts::default());
+        let order_id = repo.order_id;
+        let service = SagaService::new(repo, uow, SagaTimeouts::default());
+
+        let own = service
+            .saga(&Caller::Customer(owner), order_id)
+            .expect("saga");
+        assert!(own.is_some());
+        let foreign = service
+            .saga(&Caller::Customer(Uuid::new_v4()), order_id)
+            .expect("saga");
+        assert!(foreign.is_none());
+        assert!(service
+            .saga(&Caller::Staff, order_id)
+            .expect("saga")
+            .is_some());
+    }
+
+    #[test]
+    fn reply_advances_the_saga_once() {
+        let service = service(SagaTimeouts::default());
+        let order_id = service.repo.order_id;
+
+        let applied = service
+            .handle_reply(order_id, &SagaReply::InventoryReserved, &message("m-1"))
+            .expect("applies");
+        assert_eq!(
+            applied,
+            SagaApplication::Advanced(SagaState::AuthorizingPayment)
+        );
+        assert_eq!(
+            outbox_types(&service.uow),
+            [RESERVE_INVENTORY, AUTHORIZE_PAYMENT]
+        );
+
+        let repeated = service
+            .handle_reply(order_id, &SagaReply::InventoryReserved, &message("m-2"))
+            .expect("applies");
+        assert_eq!(
+            repeated,
+            SagaApplication::Ignored {
+                state: SagaState::AuthorizingPayment
+            }
+        );
+    }
+
+    #[test]
+    fn reply_ending_the_saga_settles_the_order() {
+        let service = service(SagaTimeouts::default());
+        let order_id = service.repo.order_id;
+
+        let reply = SagaReply::InventoryReservationFailed { reason: None };
+        let applied = service
+            .handle_reply(order_id, &reply, &message("m-1"))
+            .expect("applies");
+        assert_eq!(applied, SagaApplication::Advanced(SagaState::Failed));
+        assert_eq!(
+            service.uow.order(order_id).map(|o| o.status),
+            Some("CANCELLED".to_string())
+        );
+        assert_eq!(
+            outbox_types(&service.uow),
+            [RESERVE_INVENTORY, ORDER_CANCELLED]
+        );
+    }
+
+    #[test]
+    fn redelivered_reply_is_already_applied() {
+        let service = service(SagaTimeouts::default());
+        let order_id = service.repo.order_id;
+        service
+            .uow
+            .run(|tx| tx.inbox().record(&message("m-1")))
+            .expect("record");
+
+        let result = service
+            .handle_reply(order_id, &SagaReply::InventoryReserved, &message("m-1"))
+            .expect("applies");
+        assert_eq!(result, SagaApplication::AlreadyApplied);
+        assert_eq!(state(&service), SagaState::ReservingInventory);
+    }
+
+    #[test]
+    fn reply_losing_a_race_is_decided_again() {
+        let service = service(SagaTimeouts::default());
+        let order_id = service.repo.order_id;
+        let read = service.uow.saga(order_id).expect("saga");
+        // Another writer moves the saga on after it was read.
+        service
+            .uow
+            .run(|tx| {
+                tx.sagas().advance(&SagaTransition {
+                    order_id,
+                    version: read.version,
+                    to: read.state,
+                    deadline: read.deadline,
+                    failure_reason: None,
+                    steps: vec![],
+                    commands: vec![],
+                    order_change: None,
+                })
+            })
+            .expect("advance");
+        *service.repo.stale.lock().expect("lock") = Some(read);
+
+        let result = service
+            .handle_reply(order_id, &SagaReply::InventoryReserved, &message("m-1"))
+            .expect("applies");
+        assert_eq!(
+            result,
+            SagaApplication::Advanced(SagaState::AuthorizingPayment)
+        );
+        assert_eq!(service.uow.saga(order_id).expect("saga").version, 2);
+        assert_eq!(
+            outbox_types(&service.uow),
+            [RESERVE_INVENTORY, AUTHORIZE_PAYMENT]
+        );
+    }
+
+    #[test]
+    fn failing_step_rolls_back_the_saga_with_its_commands() {
+        let timeouts = SagaTimeouts {
+            authorize_payment: Duration::ZERO,
+            ..SagaTimeouts::default()
+        };
+        let uow = InMemoryUnitOfWork::new();
+        let repo = placed_order(&uow, Uuid::new_v4(), &timeouts);
+        let order_id = repo.order_id;
+        let service = SagaService::new(
+            repo,
+            FailingOutbox {
+                inner: uow.clone(),
+                failing: RELEASE_INVENTORY,
+            },
+            timeouts,
+        );
+        service
+            .handle_reply(order_id, &SagaReply::InventoryReserved, &message("m-1"))
+            .expect("applies");
+
+        // The timeout voids the payment, then fails to release the inventory.
+        let result = service.expire_overdue();
+        assert!(matches!(result, Err(DomainError::Internal(_))));
+        let saga = uow.saga(order_id).expect("saga");
+        assert_eq!(saga.state, SagaState::AuthorizingPayment);
+        assert_eq!(saga.version, 1);
+        assert_eq!(outbox_types(&uow), [RESERVE_INVENTORY, AUTHORIZE_PAYMENT]);
+    }
+
+    #[test]
+    fn saga_does_not_end_without_its_order() {
+        let service = service(SagaTimeouts::default());
+        let order_id = service.repo.order_id;
+        service
+            .handle_reply(order_id, &SagaReply::InventoryReserved, &message("m-1"))
+            .expect("applies");
+        // The order was paid behind the saga's back.
+        service
+            .uow
+            .run(|tx| {
+                tx.orders()
+                    .update_status(order_id, OrderStatus::Pending, OrderStatus::Paid)
+            })
+            .expect("pay");
+
+        let result = service.handle_reply(order_id, &SagaReply::PaymentAuthorized, &message("m-2"));
+        assert!(matches!(result, Err(DomainError::Internal(_))));
+        assert_eq!(state(&service), SagaState::AuthorizingPayment);
+        assert_eq!(
+            outbox_types(&service.uow),
+            [RESERVE_INVENTORY, AUTHORIZE_PAYMENT]
+        );
+        // The reply can be retried once the order is sorted out.
+        let retried = service
+            .uow
+            .run(|tx| tx.inbox().record(&message("m-2")))
+            .expect("record");
+        assert!(retried);
+    }
+
+    #[test]
+    fn expire_overdue_compensates_a_timed_out_step() {
+        let service = service(SagaTimeouts {
+            reserve_inventory: Duration::ZERO,
+            ..SagaTimeouts::default()
+        });
+
+        assert_eq!(service.expire_overdue().expect("expires"), 1);
+        assert_eq!(state(&service), SagaState::Compensating);
+        assert_eq!(
+            outbox_types(&service.uow),
+            [RESERVE_INVENTORY, RELEASE_INVENTORY]
+        );
+    }
+
+    #[test]
+    fn expire_overdue_only_touches_sagas_past_their_deadline() {
+        let service = service(SagaTimeouts::default());
+        assert_eq!(service.expire_overdue().expect("expires"), 0);
+        assert_eq!(state(&service), SagaState::ReservingInventory);
+    }
+
+    #[test]
+    fn reply_for_an_unknown_saga_is_not_found() {
+        let service = service(SagaTimeouts::default());
+        let result = service.handle_reply(
+            Uuid::new_v4(),
+            &SagaReply::InventoryReserved,
+            &message("m-1"),
+        );
+        assert!(matches!(result, Err(DomainError::NotFound)));
+    }
+}
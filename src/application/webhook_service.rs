@@ -0,0 +1,311 @@
+use std::sync::Arc;
+
+use uuid::Uuid;
+
+use crate::domain::errors::DomainError;
+use crate::domain::ports::{HostResolver, WebhookRepository};
+use crate::domain::webhook::{
+    destination, is_public_address, WebhookDelivery, WebhookSecretKey, WebhookSubscription,
+    WebhookSubscriptionInput,
+};
+
+/// Most dead letters returned for one subscription.
+const DEAD_LETTER_LIMIT: i64 = 100;
+
+/// Manages webhook subscriptions. Access is governed by the `webhooks:manage`
+/// scope alone: subscribers receive the events of every order.
+///
+/// URLs whose host resolves to an internal address (loopback, private,
+/// link-local, ...) are refused unless private destinations are allowed.
+pub struct WebhookService<W> {
+    repo: W,
+    resolver: Arc<dyn HostResolver>,
+    /// `None` refuses new subscriptions: their secret cannot be derived.
+    secret_key: Option<WebhookSecretKey>,
+    allow_private_destinations: bool,
+}
+
+impl<W: WebhookRepository> WebhookService<W> {
+    pub fn new(
+        repo: W,
+        resolver: Arc<dyn HostResolver>,
+        secret_key: Option<WebhookSecretKey>,
+    ) -> Self {
+        Self {
+            repo,
+            resolver,
+            secret_key,
+            allow_private_destinations: false,
+        }
+    }
+
+    /// Accept URLs pointing to internal addresses, for local setups.
+    pub fn with_private_destinations(mut self, allow: bool) -> Self {
+        self.allow_private_destinations = allow;
+        self
+    }
+
+    /// Register a subscription; returns it together with its signing secret,
+    /// which is not retrievable afterwards.
+    pub fn subscribe(
+        &self,
+        input: &WebhookSubscriptionInput,
+    ) -> Result<(WebhookSubscription, String), DomainError> {
+        let input = input.normalized()?;
+        self.check_destination(&input)?;
+        let secret_key = self.secret_key.as_ref().ok_or_else(|| {
+            DomainError::Internal("WEBHOOK_SECRET_KEY is not configured".to_string())
+        })?;
+        let subscription = self.repo.create(&input)?;
+        let secret = secret_key.secret_for(subscription.id);
+        Ok((subscription, secret))
+    }
+
+    pub fn subscriptions(&self) -> Result<Vec<WebhookSubscription>, DomainError> {
+        self.repo.list()
+    }
+
+    pub fn subscription(&self, id: Uuid) -> Result<WebhookSubscription, DomainError> {
+        self.repo.find(id)?.ok_or(DomainError::NotFound)
+    }
+
+    pub fn update(
+        &self,
+        id: Uuid,
+        input: &WebhookSubscriptionInput,
+    ) -> Result<WebhookSubscription, DomainError> {
+        let input = input.normalized()?;
+        self.check_destination(&input)?;
+        self.repo.update(id, &input)?.ok_or(DomainError::NotFound)
+    }
+
+    pub fn unsubscribe(&self, id: Uuid) -> Result<(), DomainError> {
+        if self.repo.delete(id)? {
+            Ok(())
+        } else {
+            Err(DomainError::NotFound)
+        }
+    }
+
+    /// Deliveries of a subscription that ran out of attempts, newest first.
+    pub fn dead_letters(&self, id: Uuid) -> Result<Vec<WebhookDelivery>, DomainError> {
+        self.subscription(id)?;
+        self.repo.dead_letters(id, DEAD_LETTER_LIMIT)
+    }
+
+    /// Try a dead-lettered delivery again, with a fresh attempt budget.
+    pub fn redeliver(&self, id: Uuid, delivery_id: i64) -> Result<(), DomainError> {
+        if self.repo.requeue(id, delivery_id)? {
+            Ok(())
+        } else {
+            Err(DomainError::NotFound)
+        }
+    }
+
+    fn check_destination(&self, input: &WebhookSubscriptionInput) -> Result<(), DomainError> {
+        if self.allow_private_destinations {
+            return Ok(());
+        }
+        let (host, port) = destination(&input.url)?;
+        let addrs = self.resolver.resolve(&host, port)?;
+        if addrs.is_empty() {
+            return Err(DomainError::InvalidInput(format!(
+                "url host {} does not resolve",
+                host
+            )));
+        }
+        match addrs.into_iter().find(|ip| !is_public_address(*ip)) {
+            Some(ip) => Err(DomainError::InvalidInput(format!(
+                "url must not point to an internal address ({} resolves to {})",
+                host, ip
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use chrono::Utc;
+
+    use super::*;
+    use crate::infrastructure::host_resolver::StaticHostResolver;
+
+    #[derive(Default)]
+    struct StubRepo {
+        created: Mutex<Vec<WebhookSubscriptionInput>>,
+    }
+
+    impl WebhookRepository for StubRepo {
+        fn create(
+            &self,
+            input: &WebhookSubscriptionInput,
+        ) -> Result<WebhookSubscription, DomainError> {
+            self.created.lock().expect("lock").push(input.clone());
+            Ok(WebhookSubscription {
+                id: Uuid::new_v4(),
+                url: input.url.clone(),
+                event_types: input.event_types.clone(),
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+            })
+        }
+
+        fn list(&self) -> Result<Vec<WebhookSubscription>, DomainError> {
+            Ok(vec![])
+        }
+
+        fn find(&self, _id: Uuid) -> Result<Option<WebhookSubscription>, DomainError> {
+            Ok(None)
+        }
+
+        fn update(
+            &self,
+            _id: Uuid,
+            _input: &WebhookSubscriptionInput,
+        ) -> Result<Option<WebhookSubscription>, DomainError> {
+            Ok(None)
+        }
+
+        fn delete(&self, _id: Uuid) -> Result<bool, DomainError> {
+            Ok(false)
+        }
+
+        fn dead_letters(
+            &self,
+            _subscription_id: Uuid,
+            _limit: i64,
+        ) -> Result<Vec<WebhookDelivery>, DomainError> {
+            Ok(vec![])
+        }
+
+        fn requeue(&self, _subscription_id: Uuid, _delivery_id: i64) -> Result<bool, DomainError> {
+            Ok(false)
+        }
+    }
+
+    fn input(url: &str) -> WebhookSubscriptionInput {
+        WebhookSubscriptionInput {
+            url: url.to_string(),
+            event_types: vec![" OrderPaid ".to_string()],
+        }
+    }
+
+    fn key() -> WebhookSecretKey {
+        WebhookSecretKey::new(*b"0123456789abcdef0123456789abcdef")
+    }
+
+    fn service() -> WebhookService<StubRepo> {
+        let resolver = StaticHostResolver::new()
+            .with_host(
+                "partner.example",
+                vec!["93.184.216.34".parse().expect("ip")],
+            )
+            .with_host(
+                "metadata.internal",
+                vec![
+                    "93.184.216.34".parse().expect("ip"),
+                    "169.254.169.254".parse().expect("ip"),
+                ],
+            );
+        WebhookService::new(StubRepo::default(), Arc::new(resolver), Some(key()))
+    }
+
+    #[test]
+    fn subscribing_stores_normalized_input_and_hands_out_the_derived_secret() {
+        let service = service();
+
+        let (subscription, secret) = service
+            .subscribe(&input("https://partner.example/hook"))
+            .expect("subscribe");
+        let (other, other_secret) = service
+            .subscribe(&input("https://partner.example/hook"))
+            .expect("subscribe");
+
+        assert_eq!(subscription.event_types, ["OrderPaid"]);
+        assert_eq!(secret, key().secret_for(subscription.id));
+        assert_eq!(other_secret, key().secret_for(other.id));
+        assert_ne!(secret, other_secret);
+        assert_eq!(service.repo.created.lock().expect("lock").len(), 2);
+    }
+
+    #[test]
+    fn internal_destinations_are_rejected() {
+        let service = service();
+
+        for url in [
+            "http://127.0.0.1:8081/hook",
+            "http://[::1]/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "https://metadata.internal/hook",
+            "https://unknown.example/hook",
+        ] {
+            let result = service.subscribe(&input(url));
+            assert!(
+                matches!(result, Err(DomainError::InvalidInput(_))),
+                "{url} was accepted"
+            );
+        }
+        assert!(matches!(
+            service.update(Uuid::new_v4(), &input("http://10.0.0.5/hook")),
+            Err(DomainError::InvalidInput(_))
+        ));
+        assert!(service.repo.created.lock().expect("lock").is_empty());
+    }
+
+    #[test]
+    fn private_destinations_can_be_allowed() {
+        let service = service().with_private_destinations(true);
+
+        assert!(service
+            .subscribe(&input("http://127.0.0.1:8081/hook"))
+            .is_ok());
+    }
+
+    #[test]
+    fn subscribing_without_a_secret_key_fails_before_storing() {
+        let service = WebhookService::new(
+            StubRepo::default(),
+            Arc::new(StaticHostResolver::new()),
+            None,
+        );
+
+        let result = service.subscribe(&input("https://93.184.216.34/hook"));
+        assert!(matches!(result, Err(DomainError::Internal(_))));
+        assert!(service.repo.created.lock().expect("lock").is_empty());
+    }
+
+    #[test]
+    fn invalid_input_is_rejected_before_storing() {
+        let service = service();
+
+        let result = service.subscribe(&input("mailto:ops@partner.example"));
+        assert!(matches!(result, Err(DomainError::InvalidInput(_))));
+        assert!(service.repo.created.lock().expect("lock").is_empty());
+    }
+
+    #[test]
+    fn unknown_subscriptions_are_not_found() {
+        let service = service();
+        let id = Uuid::new_v4();
+
+        assert!(matches!(
+            service.subscription(id),
+            Err(DomainError::NotFound)
+        ));
+        assert!(matches!(
+            service.update(id, &input("https://partner.example")),
+            Err(DomainError::NotFound)
+        ));
+        assert!(matches!(
+            service.unsubscribe(id),
+            Err(DomainError::NotFound)
+        ));
+        assert!(matches!(
+            service.dead_letters(id),
+            Err(DomainError::NotFound)
+        ));
+    }
+}
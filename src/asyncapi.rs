@@ -0,0 +1,409 @@
+//! [AsyncAPI 3.0](https://www.asyncapi.com/docs/reference/specification/v3.0.0)
+//! document of the messages published from the outbox, served at
+//! `/api-docs/asyncapi.json`.
+//!
+//! It describes what the Debezium EventRouter emits: one channel per routed
+//! destination (order events and saga commands apart), keyed by
+//! `aggregate_id`, with the event id in the `id` header and the
+//! [envelope](crate::publishers::envelope) around the payload. The payload
+//! schemas are generated from the [typed payloads](crate::domain::events).
+
+use serde_json::{json, Map, Value};
+use utoipa::openapi::schema::Schema;
+use utoipa::openapi::RefOr;
+use utoipa::ToSchema;
+
+use crate::domain::events::{
+    AuthorizePaymentPayload, OrderCompensationPayload, OrderCreatedPayload,
+    OrderStatusChangedPayload, ReserveInventoryPayload,
+};
+use crate::domain::order::{
+    ORDER_AGGREGATE, ORDER_CANCELLED, ORDER_CONFIRMED, ORDER_CREATED, ORDER_PAID,
+    ORDER_PAYMENT_FAILED,
+};
+use crate::domain::saga::{
+    AUTHORIZE_PAYMENT, RELEASE_INVENTORY, RESERVE_INVENTORY, SAGA_AGGREGATE,
+    VOID_PAYMENT_AUTHORIZATION,
+};
+use crate::infrastructure::outbox::REPLAY_OF_HEADER;
+use crate::publishers::{EventRoute, ID_HEADER};
+
+pub const ASYNCAPI_VERSION: &str = "3.0.0";
+
+/// Version of the Kafka bindings used in the document.
+const KAFKA_BINDING_VERSION: &str = "0.5.0";
+
+/// Schema of the headers every message carries.
+const HEADERS_SCHEMA: &str = "OutboxEventHeaders";
+
+/// One kind of message written to the outbox.
+struct MessageDefinition {
+    aggregate_type: &'static str,
+    event_type: &'static str,
+    summary: &'static str,
+    /// Name of the payload schema.
+    payload: String,
+}
+
+/// The messages and the schemas they refer to.
+#[derive(Default)]
+struct Catalog {
+    messages: Vec<MessageDefinition>,
+    schemas: Vec<(String, RefOr<Schema>)>,
+}
+
+impl Catalog {
+    fn add<T: ToSchema>(
+        &mut self,
+        aggregate_type: &'static str,
+        event_type: &'static str,
+        summary: &'static str,
+    ) {
+        let payload = T::name().into_owned();
+        if !self.schemas.iter().any(|(name, _)| *name == payload) {
+            self.schemas.push((payload.clone(), T::schema()));
+            T::schemas(&mut self.schemas);
+        }
+        self.messages.push(MessageDefinition {
+            aggregate_type,
+            event_type,
+            summary,
+            payload,
+        });
+    }
+}
+
+/// Every message the service writes to the outbox.
+fn catalog() -> Catalog {
+    let mut catalog = Catalog::default();
+    catalog.add::<OrderCreatedPayload>(ORDER_AGGREGATE, ORDER_CREATED, "An order was placed");
+    catalog.add::<OrderStatusChangedPayload>(
+        ORDER_AGGREGATE,
+        ORDER_PAID,
+        "A captured payment moved the order to PAID",
+    );
+    catalog.add::<OrderStatusChangedPayload>(
+        ORDER_AGGREGATE,
+        ORDER_PAYMENT_FAILED,
+        "A failed payment moved the order to PAYMENT_FAILED",
+    );
+    catalog.add::<OrderStatusChangedPayload>(
+        ORDER_AGGREGATE,
+        ORDER_CONFIRMED,
+        "The placement saga confirmed the order",
+    );
+    catalog.add::<OrderStatusChangedPayload>(
+        ORDER_AGGREGATE,
+        ORDER_CANCELLED,
+        "The placement saga cancelled the order",
+    );
+    catalog.add::<ReserveInventoryPayload>(
+        SAGA_AGGREGATE,
+        RESERVE_INVENTORY,
+        "Saga command: reserve the order's items",
+    );
+    catalog.add::<AuthorizePaymentPayload>(
+        SAGA_AGGREGATE,
+        AUTHORIZE_PAYMENT,
+        "Saga command: authorize the order's total",
+    );
+    catalog.add::<OrderCompensationPayload>(
+        SAGA_AGGREGATE,
+        RELEASE_INVENTORY,
+        "Saga command: release the order's reservation",
+    );
+    catalog.add::<OrderCompensationPayload>(
+        SAGA_AGGREGATE,
+        VOID_PAYMENT_AUTHORIZATION,
+        "Saga command: void the order's payment authorization",
+    );
+    catalog
+}
+
+/// The AsyncAPI document for events routed by `route`.
+pub fn document(route: &EventRoute) -> Value {
+    let catalog = catalog();
+
+    let mut channels = Map::new();
+    let mut operations = Map::new();
+    let mut messages = Map::new();
+    for definition in &catalog.messages {
+        let address = route.destination(definition.aggregate_type);
+        let channel = channels.entry(address.clone()).or_insert_with(|| {
+            json!({
+                "address": address,
+                "description": "Outbox messages, routed by `aggregate_type` like the EventRouter's topic.",
+                "messages": {},
+                "bindings": { "kafka": { "bindingVersion": KAFKA_BINDING_VERSION } },
+            })
+        });
+        channel["messages"][definition.event_type] =
+            json!({ "$ref": format!("#/components/messages/{}", definition.event_type) });
+
+        let operation = operations
+            .entry(format!("{}.send", address))
+            .or_insert_with(|| {
+                json!({
+                    "action": "send",
+                    "channel": { "$ref": format!("#/channels/{}", pointer_escape(&address)) },
+                    "messages": [],
+                })
+            });
+        if let Some(refs) = operation["messages"].as_array_mut() {
+            refs.push(json!({
+                "$ref": format!(
+                    "#/channels/{}/messages/{}",
+                    pointer_escape(&address),
+                    definition.event_type
+                )
+            }));
+        }
+
+        messages.insert(definition.event_type.to_string(), message(definition));
+    }
+
+    let mut schemas: Map<String, Value> = catalog
+        .schemas
+        .into_iter()
+        .map(|(name, schema)| {
+            let schema = serde_json::to_value(schema).unwrap_or(Value::Null);
+            (name, schema)
+        })
+        .collect();
+    schemas.insert(
+        HEADERS_SCHEMA.to_string(),
+        json!({
+            "type": "object",
+            "required": [ID_HEADER],
+            "properties": {
+                ID_HEADER: {
+                    "type": "string",
+                    "format": "uuid",
+                    "description": "Event id, also the envelope's `event_id`",
+                },
+                REPLAY_OF_HEADER: {
+                    "type": "string",
+                    "format": "uuid",
+                    "description": "On an event replayed by `order-admin outbox replay`, \
+                        the id of the original event",
+                },
+            },
+            "additionalProperties": {
+                "type": "string",
+                "description": "One header per entry of the outbox row's `headers` object",
+            },
+        }),
+    );
+
+    json!({
+        "asyncapi": ASYNCAPI_VERSION,
+        "info": {
+            "title": "Order Service events",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "Events and saga commands published from the transactional outbox",
+        },
+        "channels": channels,
+        "operations": operations,
+        "components": {
+            "messages": messages,
+            "schemas": schemas,
+        },
+    })
+}
+
+fn message(definition: &MessageDefinition) -> Value {
+    json!({
+        "name": definition.event_type,
+        "title": definition.event_type,
+        "summary": definition.summary,
+        "headers": { "$ref": format!("#/components/schemas/{}", HEADERS_SCHEMA) },
+        "payload": {
+            "type": "object",
+            "required": ["event_id", "event_type", "event_date", "sequence", "payload"],
+            "properties": {
+                "event_id": { "type": "string", "format": "uuid" },
+                "event_type": { "type": "string", "const": definition.event_type },
+                "event_date": { "type": "string", "format": "date-time" },
+                "sequence": {
+                    "type": "integer",
+                    "format": "int64",
+                    "minimum": 1,
+                    "description": "Position of the event within its aggregate",
+                },
+                "payload": { "$ref": format!("#/components/schemas/{}", definition.payload) },
+            },
+        },
+        "bindings": {
+            "kafka": {
+                "key": {
+                    "type": "string",
+                    "description": format!(
+                        "`aggregate_id` of the {}: its events share a partition",
+                        definition.aggregate_type
+                    ),
+                },
+                "bindingVersion": KAFKA_BINDING_VERSION,
+            },
+        },
+    })
+}
+
+/// `segment` as a JSON pointer reference token.
+fn pointer_escape(segment: &str) -> String {
+    segment.replace('~', "~0").replace('/', "~1")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::debezium::EXPAND_HEADERS_TRANSFORM;
+    use crate::domain::events::{self, OrderCreatedLine};
+    use crate::publishers::{DEFAULT_ROUTE, SAGA_COMMAND_ROUTE};
+
+    /// Header the connector places the outbox row's own headers in, as a
+    /// JSON object, before its `ExpandJsonHeaders` transform replaces it with
+    /// one header per entry.
+    const OUTBOX_HEADERS_HEADER: &str = "headers";
+
+    /// Every `$ref` in `value`.
+    fn refs(value: &Value, found: &mut Vec<String>) {
+        match value {
+            Value::Object(object) => {
+                for (key, value) in object {
+                    match (key.as_str(), value) {
+                        ("$ref", Value::String(reference)) => found.push(reference.clone()),
+                        _ => refs(value, found),
+                    }
+                }
+            }
+            Value::Array(values) => values.iter().for_each(|value| refs(value, found)),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn documents_every_outbox_message_on_the_routed_channel() {
+        let document = document(&EventRoute::default());
+        assert_eq!(document["asyncapi"], "3.0.0");
+
+        let channels = document["channels"].as_object().expect("channels");
+        assert_eq!(channels.len(), 2);
+        let message_types = |address: &str| {
+            let channel = &channels[address];
+            assert_eq!(channel["address"], address);
+            let mut types: Vec<String> = channel["messages"]
+                .as_object()
+                .expect("messages")
+                .keys()
+                .cloned()
+                .collect();
+            types.sort();
+            types
+        };
+        assert_eq!(
+            message_types(DEFAULT_ROUTE),
+            [
+                ORDER_CANCELLED,
+                ORDER_CONFIRMED,
+                ORDER_CREATED,
+                ORDER_PAID,
+                ORDER_PAYMENT_FAILED,
+            ]
+        );
+        // Saga commands stay off the order event topic.
+        assert_eq!(
+            message_types(SAGA_COMMAND_ROUTE),
+            [
+                AUTHORIZE_PAYMENT,
+                RELEASE_INVENTORY,
+                RESERVE_INVENTORY,
+                VOID_PAYMENT_AUTHORIZATION,
+            ]
+        );
+
+        let created = &document["components"]["messages"][ORDER_CREATED];
+        assert_eq!(
+            created["payload"]["properties"]["payload"]["$ref"],
+            "#/components/schemas/OrderCreatedPayload"
+        );
+        assert_eq!(created["bindings"]["kafka"]["key"]["type"], "string");
+    }
+
+    #[test]
+    fn headers_match_the_debezium_connector() {
+        let connector: Value =
+            serde_json::from_str(include_str!("../debezium/register-connector.json"))
+                .expect("connector config");
+        let config = &connector["config"];
+        let placement = config["transforms.outbox.table.fields.additional.placement"]
+            .as_str()
+            .expect("placement");
+        assert!(placement
+            .split(',')
+            .any(|field| field == format!("headers:header:{}", OUTBOX_HEADERS_HEADER)));
+        assert_eq!(config["transforms.headers.type"], EXPAND_HEADERS_TRANSFORM);
+        assert_eq!(config["transforms.headers.header"], OUTBOX_HEADERS_HEADER);
+
+        // The connector expands the JSON header, so messages carry string
+        // headers of their own instead.
+        let document = document(&EventRoute::default());
+        let headers = &document["components"]["schemas"][HEADERS_SCHEMA];
+        assert!(headers["properties"].get(OUTBOX_HEADERS_HEADER).is_none());
+        assert_eq!(headers["additionalProperties"]["type"], "string");
+    }
+
+    #[test]
+    fn routes_aggregates_to_separate_channels() {
+        let route = EventRoute::new("outbox.event.${routedByValue}")
+            .with_aggregate(SAGA_AGGREGATE, "outbox.command.OrderSaga");
+        let document = document(&route);
+        let channels = document["channels"].as_object().expect("channels");
+        assert!(channels.contains_key("outbox.event.Order"));
+        assert!(channels.contains_key("outbox.command.OrderSaga"));
+        assert!(
+            document["operations"]["outbox.command.OrderSaga.send"]["messages"]
+                .as_array()
+                .is_some_and(|messages| messages.len() == 4)
+        );
+    }
+
+    #[test]
+    fn every_reference_resolves() {
+        let document = document(&EventRoute::default());
+        let mut found = Vec::new();
+        refs(&document, &mut found);
+        assert!(found.contains(&"#/components/schemas/OrderCreatedLine".to_string()));
+        for reference in found {
+            let pointer = reference.strip_prefix('#').expect("local reference");
+            assert!(
+                document.pointer(pointer).is_some(),
+                "{reference} does not resolve"
+            );
+        }
+    }
+
+    #[test]
+    fn payload_schema_matches_the_serialized_payload() {
+        let document = document(&EventRoute::default());
+        let schema = &document["components"]["schemas"]["OrderCreatedPayload"];
+        let payload = events::to_json(&OrderCreatedPayload {
+            order_id: uuid::Uuid::new_v4(),
+            customer_id: uuid::Uuid::new_v4(),
+            status: "PENDING".to_string(),
+            lines: vec![OrderCreatedLine {
+                product_id: uuid::Uuid::new_v4(),
+                quantity: 1,
+                unit_price: "9.99".to_string(),
+            }],
+        });
+        let mut documented: Vec<&String> = schema["properties"]
+            .as_object()
+            .expect("properties")
+            .keys()
+            .collect();
+        documented.sort();
+        let mut serialized: Vec<&String> = payload.as_object().expect("object").keys().collect();
+        serialized.sort();
+        assert_eq!(documented, serialized);
+    }
+}
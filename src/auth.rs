@@ -0,0 +1,927 @@
+//! JWT bearer-token authentication and scope-based authorization.
+//!
+//! [`Authentication`] validates the `Authorization: Bearer …` header of every
+//! request it wraps and stores the resulting [`Principal`] in the request
+//! extensions. [`RequireScope`] is then attached to individual routes to
+//! enforce the scope each handler needs.
+
+use std::collections::HashSet;
+use std::future::{ready, Future, Ready};
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::AUTHORIZATION;
+use actix_web::{Error, FromRequest, HttpMessage, HttpRequest};
+use jsonwebtoken::jwk::{AlgorithmParameters, EllipticCurve, Jwk, JwkSet};
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::config::ConfigError;
+use crate::domain::caller::Caller;
+use crate::errors::AppError;
+
+/// Scopes understood by the order API.
+pub mod scopes {
+    pub const ORDERS_READ: &str = "orders:read";
+    pub const ORDERS_WRITE: &str = "orders:write";
+    /// Back-office access to every customer's orders.
+    pub const ORDERS_STAFF: &str = "orders:staff";
+    /// Register and manage webhook subscriptions for all order events.
+    pub const WEBHOOKS_MANAGE: &str = "webhooks:manage";
+    /// Inspect, requeue and discard outbox events the relay gave up on.
+    pub const OUTBOX_ADMIN: &str = "outbox:admin";
+}
+
+// ── Configuration ────────────────────────────────────────────────────────────
+
+/// Where the keys used to verify token signatures come from.
+#[derive(Debug, Clone)]
+pub enum JwtKeySource {
+    /// Shared secret for HS256-signed tokens.
+    Hs256Secret(String),
+    /// PEM-encoded RSA public key for RS256-signed tokens.
+    Rs256PublicKeyFile(PathBuf),
+    /// JSON Web Key Set; the key is selected by the token's `kid` header.
+    JwksFile(PathBuf),
+}
+
+#[derive(Debug, Clone)]
+pub struct AuthConfig {
+    pub key: JwtKeySource,
+    /// Expected `iss` claim, if any.
+    pub issuer: Option<String>,
+    /// Expected `aud` claim, if any.
+    pub audience: Option<String>,
+}
+
+// ── Principal ────────────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone)]
+enum Scopes {
+    /// Authentication is disabled: every scope is granted.
+    All,
+    Granted(HashSet<String>),
+}
+
+/// The authenticated caller of a request.
+#[derive(Debug, Clone)]
+pub struct Principal {
+    pub subject: String,
+    /// Customer the token was issued to (`customer_id` claim), if any.
+    pub customer_id: Option<Uuid>,
+    scopes: Scopes,
+}
+
+impl Principal {
+    pub fn new(subject: impl Into<String>, scopes: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            subject: subject.into(),
+            customer_id: None,
+            scopes: Scopes::Granted(scopes.into_iter().collect()),
+        }
+    }
+
+    pub fn with_customer(mut self, customer_id: Uuid) -> Self {
+        self.customer_id = Some(customer_id);
+        self
+    }
+
+    /// Principal attached to every request when authentication is disabled.
+    pub fn anonymous() -> Self {
+        Self {
+            subject: "anonymous".to_string(),
+            customer_id: None,
+            scopes: Scopes::All,
+        }
+    }
+
+    /// `false` for the [`anonymous`](Self::anonymous) principal used when
+    /// authentication is disabled.
+    pub fn is_authenticated(&self) -> bool {
+        matches!(self.scopes, Scopes::Granted(_))
+    }
+
+    pub fn has_scope(&self, scope: &str) -> bool {
+        match &self.scopes {
+            Scopes::All => true,
+            Scopes::Granted(granted) => granted.contains(scope),
+        }
+    }
+
+    /// The identity use cases run under.
+    ///
+    /// The `orders:staff` scope makes the caller back-office staff; otherwise
+    /// the token must name a customer.
+    pub fn caller(&self) -> Result<Caller, AuthError> {
+        if self.has_scope(scopes::ORDERS_STAFF) {
+            return Ok(Caller::Staff);
+        }
+        self.customer_id
+            .map(Caller::Customer)
+            .ok_or(AuthError::NoCallerIdentity)
+    }
+}
+
+impl FromRequest for Caller {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut actix_web::dev::Payload) -> Self::Future {
+        let caller = match req.extensions().get::<Principal>() {
+            Some(principal) => principal.caller(),
+            None => Err(AuthError::MissingToken),
+        };
+        ready(caller.map_err(|e| AppError::from(e).into()))
+    }
+}
+
+impl FromRequest for Principal {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut actix_web::dev::Payload) -> Self::Future {
+        ready(
+            req.extensions()
+                .get::<Principal>()
+                .cloned()
+                .ok_or_else(|| AppError::from(AuthError::MissingToken).into()),
+        )
+    }
+}
+
+// ── Token verification ───────────────────────────────────────────────────────
+
+#[derive(Debug, Error)]
+pub enum AuthError {
+    #[error("missing bearer token")]
+    MissingToken,
+
+    #[error("invalid token: {0}")]
+    InvalidToken(String),
+
+    #[error("missing scope '{0}'")]
+    InsufficientScope(&'static str),
+
+    #[error("token identifies neither a customer nor staff")]
+    NoCallerIdentity,
+
+    #[error("authentication is required but no token key is configured")]
+    NotConfigured,
+}
+
+impl From<AuthError> for AppError {
+    fn from(e: AuthError) -> Self {
+        match e {
+            AuthError::MissingToken | AuthError::InvalidToken(_) | AuthError::NotConfigured => {
+                AppError::Unauthorized(e.to_string())
+            }
+            AuthError::InsufficientScope(_) | AuthError::NoCallerIdentity => {
+                AppError::Forbidden(e.to_string())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Claims {
+    sub: String,
+    /// OAuth 2.0 style space-delimited scopes.
+    #[serde(default)]
+    scope: Option<String>,
+    /// Array form used by some identity providers.
+    #[serde(default)]
+    scp: Vec<String>,
+    #[serde(default)]
+    customer_id: Option<Uuid>,
+}
+
+impl Claims {
+    fn into_principal(self) -> Principal {
+        let scopes = self
+            .scope
+            .iter()
+            .flat_map(|s| s.split_whitespace())
+            .map(str::to_string)
+            .chain(self.scp);
+        let principal = Principal::new(self.sub, scopes);
+        match self.customer_id {
+            Some(customer_id) => principal.with_customer(customer_id),
+            None => principal,
+        }
+    }
+}
+
+struct VerificationKey {
+    kid: Option<String>,
+    algorithm: Algorithm,
+    key: DecodingKey,
+}
+
+/// Verifies bearer tokens against the configured keys and claims.
+pub struct JwtVerifier {
+    keys: Vec<VerificationKey>,
+    issuer: Option<String>,
+    audience: Option<String>,
+}
+
+impl JwtVerifier {
+    pub fn from_config(config: &AuthConfig) -> Result<Self, ConfigError> {
+        let keys = match &config.key {
+            JwtKeySource::Hs256Secret(secret) => vec![VerificationKey {
+                kid: None,
+                algorithm: Algorithm::HS256,
+                key: DecodingKey::from_secret(secret.as_bytes()),
+            }],
+            JwtKeySource::Rs256PublicKeyFile(path) => {
+                let pem = read_key_file("AUTH_JWT_RS256_PUBLIC_KEY_FILE", path)?;
+                let key = DecodingKey::from_rsa_pem(pem.as_bytes()).map_err(|e| {
+                    ConfigError::Invalid {
+                        name: "AUTH_JWT_RS256_PUBLIC_KEY_FILE",
+                        reason: e.to_string(),
+                    }
+                })?;
+                vec![VerificationKey {
+                    kid: None,
+                    algorithm: Algorithm::RS256,
+                    key,
+                }]
+            }
+            JwtKeySource::JwksFile(path) => {
+                let json = read_key_file("AUTH_JWKS_FILE", path)?;
+                jwks_keys(&json).map_err(|reason| ConfigError::Invalid {
+                    name: "AUTH_JWKS_FILE",
+                    reason,
+                })?
+            }
+        };
+
+        Ok(Self {
+            keys,
+            issuer: config.issuer.clone(),
+            audience: config.audience.clone(),
+        })
+    }
+
+    /// Validate `token` and return the principal it identifies.
+    pub fn verify(&self, token: &str) -> Result<Principal, AuthError> {
+        let header = decode_header(token).map_err(|e| AuthError::InvalidToken(e.to_string()))?;
+        let key = self
+            .keys
+            .iter()
+            .find(|k| {
+                k.algorithm == header.alg
+                    && (k.kid.is_none() || header.kid.is_none() || k.kid == header.kid)
+            })
+            .ok_or_else(|| AuthError::InvalidToken("no matching verification key".to_string()))?;
+
+        let mut validation = Validation::new(key.algorithm);
+        if let Some(issuer) = &self.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+        match &self.audience {
+            Some(audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+
+        let data = decode::<Claims>(token, &key.key, &validation)
+            .map_err(|e| AuthError::InvalidToken(e.to_string()))?;
+        Ok(data.claims.into_principal())
+    }
+}
+
+fn read_key_file(name: &'static str, path: &PathBuf) -> Result<String, ConfigError> {
+    std::fs::read_to_string(path).map_err(|e| ConfigError::Invalid {
+        name,
+        reason: format!("{}: {}", path.display(), e),
+    })
+}
+
+/// Signing keys of a JWKS document, each for the algorithm of its `alg`;
+/// keys of other types or algorithms are skipped.
+fn jwks_keys(json: &str) -> Result<Vec<VerificationKey>, String> {
+    let set: JwkSet = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    let mut keys = Vec::new();
+    for jwk in &set.keys {
+        let Some(algorithm) = jwk_algorithm(jwk) else {
+            log::warn!(
+                "Skipping JWKS key {} with unsupported algorithm {:?}",
+                jwk.common.key_id.as_deref().unwrap_or("without kid"),
+                jwk.common.key_algorithm
+            );
+            continue;
+        };
+        keys.push(VerificationKey {
+            kid: jwk.common.key_id.clone(),
+            algorithm,
+            key: DecodingKey::from_jwk(jwk).map_err(|e| e.to_string())?,
+        });
+    }
+    if keys.is_empty() {
+        return Err("JWKS contains no supported signing keys".to_string());
+    }
+    Ok(keys)
+}
+
+/// The algorithm `jwk` verifies: its `alg`, or without one the usual
+/// algorithm of its key type (RS256 for RSA keys). `None` for symmetric keys,
+/// unsupported curves and an `alg` that does not fit the key type.
+fn jwk_algorithm(jwk: &Jwk) -> Option<Algorithm> {
+    let usual = match &jwk.algorithm {
+        AlgorithmParameters::RSA(_) => Algorithm::RS256,
+        AlgorithmParameters::EllipticCurve(params) => match params.curve {
+            EllipticCurve::P256 => Algorithm::ES256,
+            EllipticCurve::P384 => Algorithm::ES384,
+            _ => return None,
+        },
+        AlgorithmParameters::OctetKeyPair(params) if params.curve == EllipticCurve::Ed25519 => {
+            Algorithm::EdDSA
+        }
+        _ => return None,
+    };
+    let Some(alg) = jwk.common.key_algorithm else {
+        return Some(usual);
+    };
+    let algorithm = Algorithm::from_str(&alg.to_string()).ok()?;
+    let fits = match &jwk.algorithm {
+        AlgorithmParameters::RSA(_) => matches!(
+            algorithm,
+            Algorithm::RS256
+                | Algorithm::RS384
+                | Algorithm::RS512
+                | Algorithm::PS256
+                | Algorithm::PS384
+                | Algorithm::PS512
+        ),
+        _ => algorithm == usual,
+    };
+    fits.then_some(algorithm)
+}
+
+fn bearer_token(req: &ServiceRequest) -> Result<&str, AuthError> {
+    let header = req
+        .headers()
+        .get(AUTHORIZATION)
+        .ok_or(AuthError::MissingToken)?
+        .to_str()
+        .map_err(|_| AuthError::InvalidToken("malformed Authorization header".to_string()))?;
+    match header.split_once(' ') {
+        Some((scheme, token)) if scheme.eq_ignore_ascii_case("bearer") && !token.is_empty() => {
+            Ok(token.trim())
+        }
+        _ => Err(AuthError::MissingToken),
+    }
+}
+
+// ── Middleware ───────────────────────────────────────────────────────────────
+
+type LocalBoxFuture<T> = Pin<Box<dyn Future<Output = T>>>;
+
+/// Authenticates every request of the wrapped service.
+///
+/// With no verifier (authentication disabled) each request is attached
+/// [`Principal::anonymous`], which holds every scope, unless authentication
+/// is [required](Authentication::required).
+#[derive(Clone)]
+pub struct Authentication {
+    verifier: Option<Arc<JwtVerifier>>,
+    required: bool,
+}
+
+impl Authentication {
+    pub fn new(verifier: Option<Arc<JwtVerifier>>) -> Self {
+        Self {
+            verifier,
+            required: false,
+        }
+    }
+
+    /// Like [`Authentication::new`], but rejects every request with 401
+    /// Unauthorized when there is no verifier.
+    pub fn required(verifier: Option<Arc<JwtVerifier>>) -> Self {
+        Self {
+            verifier,
+            required: true,
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for Authentication
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = AuthenticationMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AuthenticationMiddleware {
+            service: Rc::new(service),
+            verifier: self.verifier.clone(),
+            required: self.required,
+        }))
+    }
+}
+
+pub struct AuthenticationMiddleware<S> {
+    service: Rc<S>,
+    verifier: Option<Arc<JwtVerifier>>,
+    required: bool,
+}
+
+impl<S, B> Service<ServiceRequest> for AuthenticationMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let principal = match &self.verifier {
+            None if self.required => Err(AuthError::NotConfigured),
+            None => Ok(Principal::anonymous()),
+            Some(verifier) => bearer_token(&req).and_then(|token| verifier.verify(token)),
+        };
+        match principal {
+            Ok(principal) => {
+                req.extensions_mut().insert(principal);
+                Box::pin(self.service.call(req))
+            }
+            Err(e) => {
+                log::debug!("Rejected request to {}: {}", req.path(), e);
+                Box::pin(ready(Err(AppError::from(e).into())))
+            }
+        }
+    }
+}
+
+/// Rejects requests whose [`Principal`] lacks `scope` with 403 Forbidden.
+///
+/// Must run inside [`Authentication`].
+#[derive(Clone, Copy)]
+pub struct RequireScope {
+    scope: &'static str,
+}
+
+impl RequireScope {
+    pub fn new(scope: &'static str) -> Self {
+        Self { scope }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RequireScope
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequireScopeMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequireScopeMiddleware {
+            service,
+            scope: self.scope,
+        }))
+    }
+}
+
+pub struct RequireScopeMiddleware<S> {
+    service: S,
+    scope: &'static str,
+}
+
+impl<S, B> Service<ServiceRequest> for RequireScopeMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let allowed = match req.extensions().get::<Principal>() {
+            None => Err(AuthError::MissingToken),
+            Some(p) if p.has_scope(self.scope) => Ok(()),
+            Some(_) => Err(AuthError::InsufficientScope(self.scope)),
+        };
+        match allowed {
+            Ok(()) => Box::pin(self.service.call(req)),
+            Err(e) => Box::pin(ready(Err(AppError::from(e).into()))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+    use actix_web::{test as actix_test, web, App, HttpResponse};
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use serde_json::json;
+
+    const SECRET: &str = "test-secret";
+    const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/jwt");
+
+    fn fixture(name: &str) -> PathBuf {
+        PathBuf::from(FIXTURES).join(name)
+    }
+
+    fn exp_in(secs: i64) -> i64 {
+        chrono::Utc::now().timestamp() + secs
+    }
+
+    fn hs256_token(claims: serde_json::Value) -> String {
+        encode(
+            &Header::new(Algorithm::HS256),
+            &claims,
+            &EncodingKey::from_secret(SECRET.as_bytes()),
+        )
+        .expect("encode HS256 token")
+    }
+
+    fn rs256_token(claims: serde_json::Value, kid: Option<&str>) -> String {
+        let pem = std::fs::read(fixture("rs256_private.pem")).expect("read private key");
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = kid.map(str::to_string);
+        encode(
+            &header,
+            &claims,
+            &EncodingKey::from_rsa_pem(&pem).expect("parse private key"),
+        )
+        .expect("encode RS256 token")
+    }
+
+    fn hs256_verifier() -> JwtVerifier {
+        JwtVerifier::from_config(&AuthConfig {
+            key: JwtKeySource::Hs256Secret(SECRET.to_string()),
+            issuer: Some("https://issuer.test".to_string()),
+            audience: None,
+        })
+        .expect("HS256 verifier")
+    }
+
+    // ── JwtVerifier ───────────────────────────────────────────────────────────
+
+    #[test]
+    fn hs256_token_with_space_delimited_scopes_is_accepted() {
+        let token = hs256_token(json!({
+            "sub": "user-1",
+            "iss": "https://issuer.test",
+            "exp": exp_in(60),
+            "scope": "orders:read orders:write",
+        }));
+        let principal = hs256_verifier().verify(&token).expect("valid token");
+        assert_eq!(principal.subject, "user-1");
+        assert!(principal.has_scope(scopes::ORDERS_READ));
+        assert!(principal.has_scope(scopes::ORDERS_WRITE));
+        assert!(!principal.has_scope("admin"));
+    }
+
+    #[test]
+    fn scp_array_claim_is_accepted() {
+        let token = hs256_token(json!({
+            "sub": "user-1",
+            "iss": "https://issuer.test",
+            "exp": exp_in(60),
+            "scp": ["orders:read"],
+        }));
+        let principal = hs256_verifier().verify(&token).expect("valid token");
+        assert!(principal.has_scope(scopes::ORDERS_READ));
+        assert!(!principal.has_scope(scopes::ORDERS_WRITE));
+    }
+
+    #[test]
+    fn customer_id_claim_makes_a_customer_caller() {
+        let customer = Uuid::new_v4();
+        let token = hs256_token(json!({
+            "sub": "user-1",
+            "iss": "https://issuer.test",
+            "exp": exp_in(60),
+            "scope": "orders:read",
+            "customer_id": customer,
+        }));
+        let principal = hs256_verifier().verify(&token).expect("valid token");
+        assert_eq!(
+            principal.caller().expect("caller"),
+            Caller::Customer(customer)
+        );
+    }
+
+    #[test]
+    fn staff_scope_makes_a_staff_caller() {
+        let principal = Principal::new(
+            "agent",
+            ["orders:read".to_string(), "orders:staff".to_string()],
+        )
+        .with_customer(Uuid::new_v4());
+        assert_eq!(principal.caller().expect("caller"), Caller::Staff);
+    }
+
+    #[test]
+    fn principal_without_customer_or_staff_scope_has_no_caller() {
+        let principal = Principal::new("svc", ["orders:read".to_string()]);
+        assert!(matches!(
+            principal.caller(),
+            Err(AuthError::NoCallerIdentity)
+        ));
+    }
+
+    #[test]
+    fn anonymous_principal_is_staff() {
+        assert_eq!(
+            Principal::anonymous().caller().expect("caller"),
+            Caller::Staff
+        );
+    }
+
+    #[test]
+    fn expired_token_is_rejected() {
+        let token = hs256_token(json!({
+            "sub": "user-1",
+            "iss": "https://issuer.test",
+            "exp": exp_in(-3600),
+        }));
+        assert!(matches!(
+            hs256_verifier().verify(&token),
+            Err(AuthError::InvalidToken(_))
+        ));
+    }
+
+    #[test]
+    fn wrong_issuer_is_rejected() {
+        let token = hs256_token(json!({
+            "sub": "user-1",
+            "iss": "https://someone-else",
+            "exp": exp_in(60),
+        }));
+        assert!(matches!(
+            hs256_verifier().verify(&token),
+            Err(AuthError::InvalidToken(_))
+        ));
+    }
+
+    #[test]
+    fn token_signed_with_another_secret_is_rejected() {
+        let token = encode(
+            &Header::new(Algorithm::HS256),
+            &json!({"sub": "user-1", "iss": "https://issuer.test", "exp": exp_in(60)}),
+            &EncodingKey::from_secret(b"other-secret"),
+        )
+        .expect("encode token");
+        assert!(hs256_verifier().verify(&token).is_err());
+    }
+
+    #[test]
+    fn audience_is_enforced_when_configured() {
+        let verifier = JwtVerifier::from_config(&AuthConfig {
+            key: JwtKeySource::Hs256Secret(SECRET.to_string()),
+            issuer: None,
+            audience: Some("order-service".to_string()),
+        })
+        .expect("verifier");
+        let good = hs256_token(json!({"sub": "u", "aud": "order-service", "exp": exp_in(60)}));
+        let bad = hs256_token(json!({"sub": "u", "aud": "billing", "exp": exp_in(60)}));
+        assert!(verifier.verify(&good).is_ok());
+        assert!(verifier.verify(&bad).is_err());
+    }
+
+    #[test]
+    fn rs256_public_key_file_verifies_tokens() {
+        let verifier = JwtVerifier::from_config(&AuthConfig {
+            key: JwtKeySource::Rs256PublicKeyFile(fixture("rs256_public.pem")),
+            issuer: None,
+            audience: None,
+        })
+        .expect("RS256 verifier");
+        let token = rs256_token(json!({"sub": "svc", "exp": exp_in(60)}), None);
+        assert_eq!(verifier.verify(&token).expect("valid token").subject, "svc");
+
+        // An HS256 token must not be accepted by an RS256-only verifier.
+        let hs = hs256_token(json!({"sub": "svc", "exp": exp_in(60)}));
+        assert!(verifier.verify(&hs).is_err());
+    }
+
+    #[test]
+    fn jwks_file_selects_key_by_kid() {
+        let verifier = JwtVerifier::from_config(&AuthConfig {
+            key: JwtKeySource::JwksFile(fixture("jwks.json")),
+            issuer: None,
+            audience: None,
+        })
+        .expect("JWKS verifier");
+        let token = rs256_token(json!({"sub": "svc", "exp": exp_in(60)}), Some("test-key-1"));
+        assert!(verifier.verify(&token).is_ok());
+
+        let unknown_kid = rs256_token(json!({"sub": "svc", "exp": exp_in(60)}), Some("rotated"));
+        assert!(verifier.verify(&unknown_kid).is_err());
+    }
+
+    fn fixture_jwks() -> serde_json::Value {
+        let json = std::fs::read_to_string(fixture("jwks.json")).expect("read JWKS");
+        serde_json::from_str(&json).expect("parse JWKS")
+    }
+
+    #[test]
+    fn jwks_keys_verify_the_algorithm_of_their_alg() {
+        let with_alg = |alg: Option<&str>| {
+            let mut key = fixture_jwks()["keys"][0].clone();
+            match alg {
+                Some(alg) => key["alg"] = json!(alg),
+                None => {
+                    key.as_object_mut().expect("object").remove("alg");
+                }
+            }
+            key
+        };
+        let jwks = |keys: Vec<serde_json::Value>| json!({ "keys": keys }).to_string();
+
+        let keys = jwks_keys(&jwks(vec![with_alg(Some("PS256"))])).expect("PS256 key");
+        assert_eq!(keys[0].algorithm, Algorithm::PS256);
+        let keys = jwks_keys(&jwks(vec![with_alg(None)])).expect("RSA key without alg");
+        assert_eq!(keys[0].algorithm, Algorithm::RS256);
+
+        // An RSA key cannot verify HMAC or ECDSA signatures, and symmetric
+        // keys are not taken from a JWKS.
+        let secret = json!({ "kty": "oct", "alg": "HS256", "kid": "hmac", "k": "c2VjcmV0" });
+        let keys = jwks_keys(&jwks(vec![
+            with_alg(Some("HS256")),
+            with_alg(Some("ES256")),
+            secret,
+            with_alg(Some("RS384")),
+        ]))
+        .expect("RS384 key");
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0].algorithm, Algorithm::RS384);
+        assert!(jwks_keys(&jwks(vec![with_alg(Some("HS256"))])).is_err());
+    }
+
+    #[test]
+    fn jwks_verifier_rejects_tokens_of_another_algorithm_than_the_keys() {
+        let mut jwks = fixture_jwks();
+        jwks["keys"][0]["alg"] = json!("RS512");
+        let verifier = JwtVerifier {
+            keys: jwks_keys(&jwks.to_string()).expect("keys"),
+            issuer: None,
+            audience: None,
+        };
+        let token = rs256_token(json!({"sub": "svc", "exp": exp_in(60)}), Some("test-key-1"));
+        assert!(verifier.verify(&token).is_err());
+    }
+
+    #[test]
+    fn missing_key_file_is_a_config_error() {
+        let result = JwtVerifier::from_config(&AuthConfig {
+            key: JwtKeySource::JwksFile(fixture("does-not-exist.json")),
+            issuer: None,
+            audience: None,
+        });
+        assert!(matches!(
+            result,
+            Err(ConfigError::Invalid {
+                name: "AUTH_JWKS_FILE",
+                ..
+            })
+        ));
+    }
+
+    // ── Middleware ────────────────────────────────────────────────────────────
+
+    async fn whoami(principal: Principal) -> HttpResponse {
+        HttpResponse::Ok().body(principal.subject)
+    }
+
+    macro_rules! test_app {
+        ($authentication:expr) => {
+            actix_test::init_service(
+                App::new().service(
+                    web::scope("")
+                        .wrap($authentication)
+                        .route(
+                            "/read",
+                            web::get()
+                                .to(whoami)
+                                .wrap(RequireScope::new(scopes::ORDERS_READ)),
+                        )
+                        .route(
+                            "/write",
+                            web::post()
+                                .to(whoami)
+                                .wrap(RequireScope::new(scopes::ORDERS_WRITE)),
+                        ),
+                ),
+            )
+            .await
+        };
+    }
+
+    fn read_only_token() -> String {
+        hs256_token(json!({
+            "sub": "reader",
+            "iss": "https://issuer.test",
+            "exp": exp_in(60),
+            "scope": "orders:read",
+        }))
+    }
+
+    #[actix_web::test]
+    async fn missing_token_yields_401_with_challenge() {
+        let app = test_app!(Authentication::new(Some(Arc::new(hs256_verifier()))));
+        let req = actix_test::TestRequest::get().uri("/read").to_request();
+        let resp = actix_test::try_call_service(&app, req)
+            .await
+            .expect_err("request must be rejected");
+        let resp = resp.error_response();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            resp.headers()
+                .get(actix_web::http::header::WWW_AUTHENTICATE)
+                .and_then(|v| v.to_str().ok()),
+            Some("Bearer")
+        );
+    }
+
+    #[actix_web::test]
+    async fn invalid_token_yields_401() {
+        let app = test_app!(Authentication::new(Some(Arc::new(hs256_verifier()))));
+        let req = actix_test::TestRequest::get()
+            .uri("/read")
+            .insert_header((AUTHORIZATION, "Bearer not-a-jwt"))
+            .to_request();
+        let err = actix_test::try_call_service(&app, req)
+            .await
+            .expect_err("request must be rejected");
+        assert_eq!(err.error_response().status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn token_with_required_scope_reaches_handler() {
+        let app = test_app!(Authentication::new(Some(Arc::new(hs256_verifier()))));
+        let req = actix_test::TestRequest::get()
+            .uri("/read")
+            .insert_header((AUTHORIZATION, format!("Bearer {}", read_only_token())))
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = actix_test::read_body(resp).await;
+        assert_eq!(body, "reader");
+    }
+
+    #[actix_web::test]
+    async fn token_without_required_scope_yields_403() {
+        let app = test_app!(Authentication::new(Some(Arc::new(hs256_verifier()))));
+        let req = actix_test::TestRequest::post()
+            .uri("/write")
+            .insert_header((AUTHORIZATION, format!("Bearer {}", read_only_token())))
+            .to_request();
+        let err = actix_test::try_call_service(&app, req)
+            .await
+            .expect_err("request must be rejected");
+        assert_eq!(err.error_response().status(), StatusCode::FORBIDDEN);
+    }
+
+    #[actix_web::test]
+    async fn disabled_authentication_grants_every_scope() {
+        let app = test_app!(Authentication::new(None));
+        let req = actix_test::TestRequest::post().uri("/write").to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = actix_test::read_body(resp).await;
+        assert_eq!(body, "anonymous");
+    }
+
+    #[actix_web::test]
+    async fn required_authentication_rejects_requests_when_disabled() {
+        let app = test_app!(Authentication::required(None));
+        let req = actix_test::TestRequest::get().uri("/read").to_request();
+        let err = actix_test::try_call_service(&app, req)
+            .await
+            .expect_err("request must be rejected");
+        assert_eq!(err.error_response().status(), StatusCode::UNAUTHORIZED);
+
+        let app = test_app!(Authentication::required(Some(Arc::new(hs256_verifier()))));
+        let req = actix_test::TestRequest::get()
+            .uri("/read")
+            .insert_header((AUTHORIZATION, format!("Bearer {}", read_only_token())))
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,469 @@
+//! Operator CLI: migrations, outbox inspection and replay, order dumps.
+//!
+//!   order-admin migrate status
+//!   order-admin outbox list --event-type OrderCreated --before 2026-01-01T00:00:00Z
+//!   order-admin outbox replay <ORDER_ID>
+//!   order-admin outbox snapshot --aggregate-id <ORDER_ID>
+//!   order-admin outbox snapshots
+//!   order-admin outbox drop-slot
+//!   order-admin order dump <ORDER_ID>
+//!   order-admin connector reconcile
+
+use std::process::ExitCode;
+
+use chrono::{DateTime, Utc};
+use clap::{Parser, Subcommand};
+use diesel::{Connection, PgConnection};
+use dotenvy::dotenv;
+use order_service::admin::{self, AdminError, OutboxFilter};
+use order_service::config::ConfigError;
+use order_service::debezium::{self, ConnectClient, Reconciliation};
+use order_service::infrastructure::models::{OutboxEventRow, SnapshotRequestRow};
+use order_service::replication_relay;
+use order_service::Config;
+use uuid::Uuid;
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "order-admin",
+    about = "Administration tasks for the order service"
+)]
+struct Cli {
+    /// PostgreSQL connection string.
+    #[arg(long, env = "DATABASE_URL", hide_env_values = true)]
+    database_url: String,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Inspect, apply or revert the embedded Diesel migrations.
+    #[command(subcommand)]
+    Migrate(MigrateCommand),
+    /// Inspect, replay and re-emit `commerce_order_outbox` rows.
+    #[command(subcommand)]
+    Outbox(OutboxCommand),
+    /// Inspect orders.
+    #[command(subcommand)]
+    Order(OrderCommand),
+    /// Manage the Debezium outbox connector through Kafka Connect
+    /// (`DEBEZIUM_*` variables).
+    #[command(subcommand)]
+    Connector(ConnectorCommand),
+}
+
+#[derive(Debug, Subcommand)]
+enum MigrateCommand {
+    /// Show every migration and whether it has been applied.
+    Status,
+    /// Apply all pending migrations.
+    Run,
+    /// Revert the most recent migrations.
+    Revert {
+        /// Number of migrations to revert.
+        #[arg(long, default_value_t = 1)]
+        steps: usize,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum OutboxCommand {
+    /// List outbox rows, oldest first.
+    List {
+        #[arg(long)]
+        aggregate_type: Option<String>,
+        #[arg(long)]
+        aggregate_id: Option<String>,
+        #[arg(long)]
+        event_type: Option<String>,
+        /// Only rows created at or after this RFC 3339 timestamp.
+        #[arg(long)]
+        since: Option<DateTime<Utc>>,
+        /// Only rows created before this RFC 3339 timestamp.
+        #[arg(long)]
+        before: Option<DateTime<Utc>>,
+        #[arg(long, default_value_t = 50)]
+        limit: i64,
+        /// Print one JSON object per line instead of a table.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Re-insert the events of an order so Debezium publishes them again.
+    Replay {
+        order_id: Uuid,
+        /// Only replay events of this type.
+        #[arg(long)]
+        event_type: Option<String>,
+    },
+    /// Signal Debezium to re-emit the matching rows, unchanged, through an
+    /// incremental snapshot.
+    Snapshot {
+        #[arg(long)]
+        aggregate_type: Option<String>,
+        #[arg(long)]
+        aggregate_id: Option<String>,
+        #[arg(long)]
+        event_type: Option<String>,
+        /// Only rows created at or after this RFC 3339 timestamp.
+        #[arg(long)]
+        since: Option<DateTime<Utc>>,
+        /// Only rows created before this RFC 3339 timestamp.
+        #[arg(long)]
+        before: Option<DateTime<Utc>>,
+    },
+    /// List snapshot requests, newest first, with their status.
+    Snapshots {
+        #[arg(long, default_value_t = 20)]
+        limit: i64,
+        /// Print one JSON object per line instead of a table.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Drop the replication slot of the outbox relay after switching back to
+    /// polling; it retains WAL until dropped.
+    DropSlot {
+        #[arg(long, env = "OUTBOX_REPLICATION_SLOT", default_value = replication_relay::DEFAULT_SLOT_NAME)]
+        slot: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum OrderCommand {
+    /// Print an order with its lines and outbox events as JSON.
+    Dump { order_id: Uuid },
+}
+
+#[derive(Debug, Subcommand)]
+enum ConnectorCommand {
+    /// Print the connector built from the configuration, as registered.
+    Config,
+    /// Create the connector, update its configuration if it differs, or
+    /// restart it if it failed.
+    Reconcile,
+    /// Print the state of the connector and its tasks as JSON.
+    Status,
+    /// Restart the connector and its failed tasks.
+    Restart,
+    /// Remove the connector (its replication slot is kept).
+    Delete,
+}
+
+fn main() -> ExitCode {
+    dotenv().ok();
+    let cli = Cli::parse();
+
+    let result = match cli.command {
+        Command::Connector(command) => run_connector(command, cli.database_url),
+        command => match PgConnection::establish(&cli.database_url) {
+            Ok(mut conn) => run(command, &mut conn, &cli.database_url),
+            Err(e) => {
+                eprintln!("Cannot connect to the database: {}", e);
+                return ExitCode::FAILURE;
+            }
+        },
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(command: Command, conn: &mut PgConnection, database_url: &str) -> Result<(), AdminError> {
+    match command {
+        Command::Migrate(MigrateCommand::Status) => {
+            for m in admin::migration_status(conn)? {
+                let mark = if m.applied { "applied" } else { "pending" };
+                println!("{:<8} {}", mark, m.name);
+            }
+        }
+        Command::Migrate(MigrateCommand::Run) => {
+            let applied = admin::run_migrations(conn)?;
+            if applied.is_empty() {
+                println!("No pending migrations");
+            }
+            for version in applied {
+                println!("Applied {}", version);
+            }
+        }
+        Command::Migrate(MigrateCommand::Revert { steps }) => {
+            for version in admin::revert_migrations(conn, steps)? {
+                println!("Reverted {}", version);
+            }
+        }
+        Command::Outbox(OutboxCommand::List {
+            aggregate_type,
+            aggregate_id,
+            event_type,
+            since,
+            before,
+            limit,
+            json,
+        }) => {
+            let rows = admin::list_outbox(
+                conn,
+                &OutboxFilter {
+                    aggregate_type,
+                    aggregate_id,
+                    event_type,
+                    since,
+                    before,
+                    limit,
+                },
+            )?;
+            print_outbox(&rows, json);
+        }
+        Command::Outbox(OutboxCommand::Replay {
+            order_id,
+            event_type,
+        }) => {
+            let rows = admin::replay_order_events(conn, order_id, event_type.as_deref())?;
+            println!("Replayed {} event(s) for order {}", rows.len(), order_id);
+            print_outbox(&rows, false);
+        }
+        Command::Outbox(OutboxCommand::Snapshot {
+            aggregate_type,
+            aggregate_id,
+            event_type,
+            since,
+            before,
+        }) => {
+            let config = config(database_url)?;
+            let request = admin::request_snapshot(
+                conn,
+                &config.debezium.table,
+                &OutboxFilter {
+                    aggregate_type,
+                    aggregate_id,
+                    event_type,
+                    since,
+                    before,
+                    ..Default::default()
+                },
+            )?;
+            println!(
+                "Sent snapshot signal {} for {} event(s)",
+                request.signal_id, request.matching_events
+            );
+        }
+        Command::Outbox(OutboxCommand::Snapshots { limit, json }) => {
+            let config = config(database_url)?;
+            admin::refresh_snapshot_requests(conn, config.debezium.slot())?;
+            print_snapshot_requests(&admin::list_snapshot_requests(conn, limit)?, json);
+        }
+        Command::Outbox(OutboxCommand::DropSlot { slot }) => {
+            if admin::drop_replication_slot(conn, &slot)? {
+                println!("Dropped replication slot {}", slot);
+            } else {
+                println!("No replication slot {}", slot);
+            }
+        }
+        Command::Order(OrderCommand::Dump { order_id }) => {
+            let dump = admin::dump_order(conn, order_id)?;
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&dump).expect("order dump is serializable")
+            );
+        }
+        Command::Connector(command) => unreachable!("{:?} needs no database", command),
+    }
+    Ok(())
+}
+
+/// The service configuration, with `--database-url` taking the place of the
+/// `DATABASE_URL` variable.
+fn config(database_url: &str) -> Result<Config, ConfigError> {
+    Config::from_lookup(|name| match name {
+        "DATABASE_URL" => Some(database_url.to_string()),
+        _ => std::env::var(name).ok(),
+    })
+}
+
+/// Connector commands talk to Kafka Connect only; `DATABASE_URL` supplies the
+/// connector's database settings.
+fn run_connector(command: ConnectorCommand, database_url: String) -> Result<(), AdminError> {
+    let config = config(&database_url)?;
+    let desired = debezium::connector(&config)?;
+    if let ConnectorCommand::Config = command {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&desired).expect("connector is serializable")
+        );
+        return Ok(());
+    }
+
+    let client = ConnectClient::new(&config.debezium.connect_url);
+    let runtime = tokio::runtime::Runtime::new().expect("tokio runtime");
+    runtime.block_on(async {
+        let name = &desired.name;
+        match command {
+            ConnectorCommand::Config => {}
+            ConnectorCommand::Reconcile => match debezium::reconcile(&client, &desired).await? {
+                Reconciliation::Created => println!("Created connector {}", name),
+                Reconciliation::Updated => println!("Updated the configuration of {}", name),
+                Reconciliation::Unchanged { restarted: true } => {
+                    println!("Restarted failed connector {}", name)
+                }
+                Reconciliation::Unchanged { restarted: false } => {
+                    println!("Connector {} is up to date", name)
+                }
+            },
+            ConnectorCommand::Status => {
+                let status = client.status(name).await?;
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&status).expect("status is serializable")
+                );
+            }
+            ConnectorCommand::Restart => {
+                client.restart(name).await?;
+                println!("Restarting connector {}", name);
+            }
+            ConnectorCommand::Delete => {
+                if client.delete(name).await? {
+                    println!("Deleted connector {}", name);
+                } else {
+                    println!("No connector {}", name);
+                }
+            }
+        }
+        Ok(())
+    })
+}
+
+fn print_outbox(rows: &[OutboxEventRow], json: bool) {
+    if json {
+        for row in rows {
+            println!(
+                "{}",
+                serde_json::to_string(row).expect("outbox row is serializable")
+            );
+        }
+        return;
+    }
+    println!(
+        "{:<32} {:<36} {:<14} {:<36} {:>8} EVENT_TYPE",
+        "CREATED_AT", "ID", "AGGREGATE_TYPE", "AGGREGATE_ID", "SEQUENCE"
+    );
+    for row in rows {
+        println!(
+            "{:<32} {:<36} {:<14} {:<36} {:>8} {}",
+            row.created_at.to_rfc3339(),
+            row.id,
+            row.aggregate_type,
+            row.aggregate_id,
+            row.sequence,
+            row.event_type
+        );
+    }
+}
+
+fn print_snapshot_requests(requests: &[SnapshotRequestRow], json: bool) {
+    if json {
+        for request in requests {
+            println!(
+                "{}",
+                serde_json::to_string(request).expect("snapshot request is serializable")
+            );
+        }
+        return;
+    }
+    println!(
+        "{:<32} {:<36} {:<9} {:>8} CONDITION",
+        "REQUESTED_AT", "SIGNAL_ID", "STATUS", "EVENTS"
+    );
+    for request in requests {
+        println!(
+            "{:<32} {:<36} {:<9} {:>8} {}",
+            request.requested_at.to_rfc3339(),
+            request.signal_id,
+            request.status,
+            request.matching_events,
+            request.condition.as_deref().unwrap_or("-")
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn cli_definition_is_valid() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn outbox_list_parses_filters() {
+        let cli = Cli::try_parse_from([
+            "order-admin",
+            "--database-url",
+            "postgres://db",
+            "outbox",
+            "list",
+            "--event-type",
+            "OrderCreated",
+            "--before",
+            "2026-01-01T00:00:00Z",
+        ])
+        .expect("valid arguments");
+        match cli.command {
+            Command::Outbox(OutboxCommand::List {
+                event_type, before, ..
+            }) => {
+                assert_eq!(event_type.as_deref(), Some("OrderCreated"));
+                assert!(before.is_some());
+            }
+            other => panic!("unexpected command {:?}", other),
+        }
+    }
+
+    #[test]
+    fn outbox_snapshot_parses_a_date_range() {
+        let cli = Cli::try_parse_from([
+            "order-admin",
+            "--database-url",
+            "postgres://db",
+            "outbox",
+            "snapshot",
+            "--since",
+            "2026-01-01T00:00:00Z",
+            "--before",
+            "2026-02-01T00:00:00Z",
+        ])
+        .expect("valid arguments");
+        match cli.command {
+            Command::Outbox(OutboxCommand::Snapshot {
+                aggregate_id,
+                since,
+                before,
+                ..
+            }) => {
+                assert_eq!(aggregate_id, None);
+                assert!(since.is_some() && before.is_some());
+            }
+            other => panic!("unexpected command {:?}", other),
+        }
+    }
+
+    #[test]
+    fn connector_commands_parse() {
+        let cli = Cli::try_parse_from([
+            "order-admin",
+            "--database-url",
+            "postgres://db",
+            "connector",
+            "reconcile",
+        ])
+        .expect("valid arguments");
+        assert!(matches!(
+            cli.command,
+            Command::Connector(ConnectorCommand::Reconcile)
+        ));
+    }
+}
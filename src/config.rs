@@ -0,0 +1,1121 @@
+use std::env;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use thiserror::Error;
+
+use crate::auth::{AuthConfig, JwtKeySource};
+use crate::debezium::DebeziumConfig;
+use crate::domain::retry::RetryPolicy;
+use crate::domain::saga::SAGA_AGGREGATE;
+use crate::domain::webhook::WebhookSecretKey;
+use crate::outbox_listener::OutboxListenerConfig;
+use crate::outbox_relay::OutboxRelayConfig;
+use crate::payments::PaymentsConsumerConfig;
+use crate::publishers::cloudevents::CloudEventsConfig;
+use crate::publishers::{EventFormat, EventRoute, PublisherBackend, PublisherConfig};
+use crate::rate_limit::{Budget, RateLimitConfig};
+use crate::replication_monitor::ReplicationMonitorConfig;
+use crate::replication_relay::ReplicationRelayConfig;
+use crate::saga::SagaConfig;
+use crate::shutdown::ShutdownConfig;
+use crate::webhooks::{WebhookConfig, WebhookSecurity};
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("{0} must be set")]
+    Missing(&'static str),
+
+    #[error("{name} is invalid: {reason}")]
+    Invalid { name: &'static str, reason: String },
+}
+
+/// Service configuration, read from the environment (and `.env`).
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub database_url: String,
+    pub host: String,
+    pub port: u16,
+    /// `None` leaves the API unauthenticated.
+    pub auth: Option<AuthConfig>,
+    /// `None` disables rate limiting.
+    pub rate_limit: Option<RateLimitConfig>,
+    pub shutdown: ShutdownConfig,
+    /// `None` leaves order status independent of payment events.
+    pub payments: Option<PaymentsConsumerConfig>,
+    /// `None` places orders without a saga.
+    pub saga: Option<SagaConfig>,
+    /// `None` disables webhook delivery (subscriptions can still be managed).
+    pub webhooks: Option<WebhookConfig>,
+    pub webhook_security: WebhookSecurity,
+    pub outbox_listener: OutboxListenerConfig,
+    pub outbox_relay: OutboxRelayConfig,
+    /// `None` leaves publishing to Debezium (the outbox relay does not run).
+    pub outbox_publisher: Option<PublisherConfig>,
+    /// `Some` makes the outbox relay stream the outbox through logical
+    /// replication instead of polling it.
+    pub outbox_replication: Option<ReplicationRelayConfig>,
+    /// The Debezium connector, as `order-admin connector` manages it.
+    pub debezium: DebeziumConfig,
+    /// `None` leaves the connector's (or the relay's) replication slot
+    /// unwatched.
+    pub replication_monitor: Option<ReplicationMonitorConfig>,
+}
+
+impl Config {
+    /// Configuration with defaults for everything but the database URL.
+    pub fn new(database_url: impl Into<String>) -> Self {
+        Self {
+            database_url: database_url.into(),
+            host: "0.0.0.0".to_string(),
+            port: 8080,
+            auth: None,
+            rate_limit: Some(RateLimitConfig::default()),
+            shutdown: ShutdownConfig::default(),
+            payments: None,
+            saga: None,
+            webhooks: Some(WebhookConfig::default()),
+            webhook_security: WebhookSecurity::default(),
+            outbox_listener: OutboxListenerConfig::default(),
+            outbox_relay: OutboxRelayConfig::default(),
+            outbox_publisher: None,
+            outbox_replication: None,
+            debezium: DebeziumConfig::default(),
+            replication_monitor: None,
+        }
+    }
+
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Self::from_lookup(|name| env::var(name).ok())
+    }
+
+    /// Build the configuration from an arbitrary variable lookup (used by tests).
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        let database_url = lookup("DATABASE_URL").ok_or(ConfigError::Missing("DATABASE_URL"))?;
+        let mut config = Self::new(database_url);
+
+        if let Some(host) = lookup("HOST") {
+            config.host = host;
+        }
+        if let Some(port) = lookup("PORT") {
+            config.port = parse("PORT", &port)?;
+        }
+        config.auth = auth_from_lookup(&lookup)?;
+        config.rate_limit = rate_limit_from_lookup(&lookup)?;
+        if let Some(secs) = lookup("SHUTDOWN_READINESS_DELAY_SECS") {
+            config.shutdown.readiness_delay =
+                Duration::from_secs(parse("SHUTDOWN_READINESS_DELAY_SECS", &secs)?);
+        }
+        if let Some(secs) = lookup("SHUTDOWN_DRAIN_TIMEOUT_SECS") {
+            config.shutdown.drain_timeout =
+                Duration::from_secs(parse("SHUTDOWN_DRAIN_TIMEOUT_SECS", &secs)?);
+        }
+        config.payments = payments_from_lookup(&lookup);
+        config.saga = saga_from_lookup(&lookup)?;
+        config.webhooks = webhooks_from_lookup(&lookup)?;
+        config.webhook_security = webhook_security_from_lookup(&lookup)?;
+        if let Some(millis) = lookup("OUTBOX_POLL_INTERVAL_MS") {
+            config.outbox_listener.poll_interval =
+                Duration::from_millis(parse("OUTBOX_POLL_INTERVAL_MS", &millis)?);
+        }
+        config.outbox_relay = outbox_relay_from_lookup(&lookup)?;
+        config.outbox_publisher = outbox_publisher_from_lookup(&lookup)?;
+        config.outbox_replication = outbox_replication_from_lookup(
+            &lookup,
+            &config.outbox_relay,
+            config.outbox_publisher.as_ref(),
+        )?;
+        config.debezium = debezium_from_lookup(&lookup);
+        config.replication_monitor = replication_monitor_from_lookup(
+            &lookup,
+            &config.debezium,
+            config.outbox_replication.as_ref(),
+        )?;
+
+        Ok(config)
+    }
+}
+
+fn parse<T>(name: &'static str, value: &str) -> Result<T, ConfigError>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    value
+        .trim()
+        .parse()
+        .map_err(|e: T::Err| ConfigError::Invalid {
+            name,
+            reason: e.to_string(),
+        })
+}
+
+/// The consumer is enabled by `PAYMENTS_KAFKA_BOOTSTRAP_SERVERS`.
+fn payments_from_lookup(
+    lookup: &impl Fn(&str) -> Option<String>,
+) -> Option<PaymentsConsumerConfig> {
+    let mut payments = PaymentsConsumerConfig::new(lookup("PAYMENTS_KAFKA_BOOTSTRAP_SERVERS")?);
+    if let Some(topic) = lookup("PAYMENTS_TOPIC") {
+        payments.topic = topic;
+    }
+    if let Some(group_id) = lookup("PAYMENTS_CONSUMER_GROUP") {
+        payments.group_id = group_id;
+    }
+    Some(payments)
+}
+
+/// The saga is enabled by `SAGA_KAFKA_BOOTSTRAP_SERVERS`.
+fn saga_from_lookup(
+    lookup: &impl Fn(&str) -> Option<String>,
+) -> Result<Option<SagaConfig>, ConfigError> {
+    let Some(bootstrap_servers) = lookup("SAGA_KAFKA_BOOTSTRAP_SERVERS") else {
+        return Ok(None);
+    };
+    let mut saga = SagaConfig::new(bootstrap_servers);
+    if let Some(topic) = lookup("SAGA_REPLY_TOPIC") {
+        saga.reply_topic = topic;
+    }
+    if let Some(group_id) = lookup("SAGA_CONSUMER_GROUP") {
+        saga.group_id = group_id;
+    }
+    let secs = |name: &'static str| -> Result<Option<Duration>, ConfigError> {
+        lookup(name)
+            .map(|value| parse(name, &value).map(Duration::from_secs))
+            .transpose()
+    };
+    if let Some(timeout) = secs("SAGA_RESERVE_INVENTORY_TIMEOUT_SECS")? {
+        saga.timeouts.reserve_inventory = timeout;
+    }
+    if let Some(timeout) = secs("SAGA_AUTHORIZE_PAYMENT_TIMEOUT_SECS")? {
+        saga.timeouts.authorize_payment = timeout;
+    }
+    if let Some(timeout) = secs("SAGA_RELEASE_INVENTORY_TIMEOUT_SECS")? {
+        saga.timeouts.release_inventory = timeout;
+    }
+    if let Some(interval) = secs("SAGA_TIMEOUT_CHECK_INTERVAL_SECS")? {
+        saga.timeout_check_interval = interval;
+    }
+    Ok(Some(saga))
+}
+
+/// Webhook delivery is on by default; `WEBHOOKS_ENABLED=false` turns it off.
+fn webhooks_from_lookup(
+    lookup: &impl Fn(&str) -> Option<String>,
+) -> Result<Option<WebhookConfig>, ConfigError> {
+    if let Some(enabled) = lookup("WEBHOOKS_ENABLED") {
+        if !parse::<bool>("WEBHOOKS_ENABLED", &enabled)? {
+            return Ok(None);
+        }
+    }
+
+    let mut webhooks = WebhookConfig::default();
+    let secs = |name: &'static str| -> Result<Option<Duration>, ConfigError> {
+        lookup(name)
+            .map(|value| parse(name, &value).map(Duration::from_secs))
+            .transpose()
+    };
+    webhooks.retry = retry_from_lookup(
+        lookup,
+        webhooks.retry,
+        [
+            "WEBHOOK_MAX_ATTEMPTS",
+            "WEBHOOK_INITIAL_BACKOFF_SECS",
+            "WEBHOOK_MAX_BACKOFF_SECS",
+        ],
+    )?;
+    if let Some(timeout) = secs("WEBHOOK_REQUEST_TIMEOUT_SECS")? {
+        webhooks.request_timeout = timeout;
+    }
+    if let Some(millis) = lookup("WEBHOOK_POLL_INTERVAL_MS") {
+        webhooks.poll_interval = Duration::from_millis(parse("WEBHOOK_POLL_INTERVAL_MS", &millis)?);
+    }
+    Ok(Some(webhooks))
+}
+
+/// Shortest accepted `WEBHOOK_SECRET_KEY`, in bytes.
+const MIN_WEBHOOK_SECRET_KEY_LENGTH: usize = 32;
+
+fn webhook_security_from_lookup(
+    lookup: &impl Fn(&str) -> Option<String>,
+) -> Result<WebhookSecurity, ConfigError> {
+    let mut security = WebhookSecurity::default();
+    if let Some(key) = lookup("WEBHOOK_SECRET_KEY") {
+        if key.len() < MIN_WEBHOOK_SECRET_KEY_LENGTH {
+            return Err(ConfigError::Invalid {
+                name: "WEBHOOK_SECRET_KEY",
+                reason: format!(
+                    "must be at least {} bytes long",
+                    MIN_WEBHOOK_SECRET_KEY_LENGTH
+                ),
+            });
+        }
+        security.secret_key = Some(WebhookSecretKey::new(key));
+    }
+    if let Some(allow) = lookup("WEBHOOK_ALLOW_PRIVATE_DESTINATIONS") {
+        security.allow_private_destinations = parse("WEBHOOK_ALLOW_PRIVATE_DESTINATIONS", &allow)?;
+    }
+    Ok(security)
+}
+
+/// The outbox relay only runs with a publisher backend; these settings apply
+/// whichever it is.
+fn outbox_relay_from_lookup(
+    lookup: &impl Fn(&str) -> Option<String>,
+) -> Result<OutboxRelayConfig, ConfigError> {
+    let mut relay = OutboxRelayConfig::default();
+    relay.retry = retry_from_lookup(
+        lookup,
+        relay.retry,
+        [
+            "OUTBOX_RELAY_MAX_ATTEMPTS",
+            "OUTBOX_RELAY_INITIAL_BACKOFF_SECS",
+            "OUTBOX_RELAY_MAX_BACKOFF_SECS",
+        ],
+    )?;
+    if let Some(secs) = lookup("OUTBOX_RELAY_PUBLISH_TIMEOUT_SECS") {
+        relay.publish_timeout =
+            Duration::from_secs(parse("OUTBOX_RELAY_PUBLISH_TIMEOUT_SECS", &secs)?);
+    }
+    if let Some(millis) = lookup("OUTBOX_RELAY_POLL_INTERVAL_MS") {
+        relay.poll_interval =
+            Duration::from_millis(parse("OUTBOX_RELAY_POLL_INTERVAL_MS", &millis)?);
+    }
+    Ok(relay)
+}
+
+/// The relay is enabled by `OUTBOX_PUBLISHER`; broker backends need
+/// `OUTBOX_PUBLISHER_URL`. Debezium must not capture the outbox next to the
+/// relay, or every event is published twice; the service only warns when the
+/// replication monitor watches Debezium's slot.
+fn outbox_publisher_from_lookup(
+    lookup: &impl Fn(&str) -> Option<String>,
+) -> Result<Option<PublisherConfig>, ConfigError> {
+    let Some(name) = lookup("OUTBOX_PUBLISHER") else {
+        return Ok(None);
+    };
+    let url = || lookup("OUTBOX_PUBLISHER_URL").ok_or(ConfigError::Missing("OUTBOX_PUBLISHER_URL"));
+    let backend = match name.trim() {
+        "file" => PublisherBackend::File {
+            path: lookup("OUTBOX_PUBLISHER_PATH")
+                .unwrap_or_else(|| "outbox-events.ndjson".to_string())
+                .into(),
+        },
+        "nats" => PublisherBackend::Nats { url: url()? },
+        "amqp" => PublisherBackend::Amqp { url: url()? },
+        "redis" => PublisherBackend::RedisStreams { url: url()? },
+        other => {
+            return Err(ConfigError::Invalid {
+                name: "OUTBOX_PUBLISHER",
+                reason: format!("unknown backend {:?} (file, nats, amqp or redis)", other),
+            })
+        }
+    };
+    let route = event_route_from_lookup(lookup);
+    let format = event_format_from_lookup(lookup)?;
+    Ok(Some(PublisherConfig {
+        backend,
+        route,
+        format,
+    }))
+}
+
+/// `OUTBOX_RELAY_MODE` is `poll` (the default) or `replication`; the
+/// replication relay shares the publish timeout of the polling one.
+/// Replication needs a publisher: without one the relay would not run and
+/// the mode would be silently ignored.
+fn outbox_replication_from_lookup(
+    lookup: &impl Fn(&str) -> Option<String>,
+    relay: &OutboxRelayConfig,
+    publisher: Option<&PublisherConfig>,
+) -> Result<Option<ReplicationRelayConfig>, ConfigError> {
+    match lookup("OUTBOX_RELAY_MODE").as_deref().map(str::trim) {
+        None | Some("poll") => return Ok(None),
+        Some("replication") => {}
+        Some(other) => {
+            return Err(ConfigError::Invalid {
+                name: "OUTBOX_RELAY_MODE",
+                reason: format!("unknown mode {:?} (poll or replication)", other),
+            })
+        }
+    }
+    if publisher.is_none() {
+        return Err(ConfigError::Invalid {
+            name: "OUTBOX_RELAY_MODE",
+            reason: "replication needs an OUTBOX_PUBLISHER".to_string(),
+        });
+    }
+
+    let mut replication = ReplicationRelayConfig {
+        publish_timeout: relay.publish_timeout,
+        ..ReplicationRelayConfig::default()
+    };
+    if let Some(slot) = lookup("OUTBOX_REPLICATION_SLOT") {
+        // Slot names are interpolated into replication commands.
+        if slot.is_empty()
+            || !slot
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        {
+            return Err(ConfigError::Invalid {
+                name: "OUTBOX_REPLICATION_SLOT",
+                reason: "only lower case letters, digits and underscores are allowed".to_string(),
+            });
+        }
+        replication.slot_name = slot;
+    }
+    if let Some(secs) = lookup("OUTBOX_REPLICATION_STATUS_INTERVAL_SECS") {
+        replication.status_interval =
+            Duration::from_secs(parse("OUTBOX_REPLICATION_STATUS_INTERVAL_SECS", &secs)?);
+    }
+    Ok(Some(replication))
+}
+
+/// `OUTBOX_ROUTE_REPLACEMENT` routes events, `SAGA_COMMAND_TOPIC` the saga
+/// commands; the relay and the connector share both.
+fn event_route_from_lookup(lookup: &impl Fn(&str) -> Option<String>) -> EventRoute {
+    let route = lookup("OUTBOX_ROUTE_REPLACEMENT")
+        .map(EventRoute::new)
+        .unwrap_or_default();
+    match lookup("SAGA_COMMAND_TOPIC") {
+        Some(topic) => route.with_aggregate(SAGA_AGGREGATE, topic),
+        None => route,
+    }
+}
+
+/// The connector routes like the relay, through [`event_route_from_lookup`].
+fn debezium_from_lookup(lookup: &impl Fn(&str) -> Option<String>) -> DebeziumConfig {
+    let mut debezium = DebeziumConfig::default();
+    if let Some(url) = lookup("DEBEZIUM_CONNECT_URL") {
+        debezium.connect_url = url;
+    }
+    if let Some(name) = lookup("DEBEZIUM_CONNECTOR_NAME") {
+        debezium.connector_name = name;
+    }
+    debezium.database_hostname = lookup("DEBEZIUM_DATABASE_HOSTNAME");
+    if let Some(prefix) = lookup("DEBEZIUM_TOPIC_PREFIX") {
+        debezium.topic_prefix = prefix;
+    }
+    if let Some(table) = lookup("DEBEZIUM_OUTBOX_TABLE") {
+        debezium.table = table;
+    }
+    debezium.slot_name = lookup("DEBEZIUM_SLOT_NAME");
+    debezium.publication_name = lookup("DEBEZIUM_PUBLICATION_NAME");
+    if let Some(url) = lookup("DEBEZIUM_SCHEMA_REGISTRY_URL") {
+        debezium.schema_registry_url = url;
+    }
+    debezium.route = event_route_from_lookup(lookup);
+    debezium
+}
+
+/// The monitor is enabled by `REPLICATION_MONITOR_ENABLED=true` and watches
+/// the replication relay's slot with `OUTBOX_RELAY_MODE=replication`, the
+/// connector's otherwise.
+fn replication_monitor_from_lookup(
+    lookup: &impl Fn(&str) -> Option<String>,
+    debezium: &DebeziumConfig,
+    replication: Option<&ReplicationRelayConfig>,
+) -> Result<Option<ReplicationMonitorConfig>, ConfigError> {
+    let enabled = lookup("REPLICATION_MONITOR_ENABLED")
+        .map(|value| parse::<bool>("REPLICATION_MONITOR_ENABLED", &value))
+        .transpose()?
+        .unwrap_or(false);
+    if !enabled {
+        return Ok(None);
+    }
+
+    let mut monitor = ReplicationMonitorConfig::default();
+    if let Some(replication) = replication {
+        monitor.slot_name = replication.slot_name.clone();
+    } else if let Some(slot) = &debezium.slot_name {
+        monitor.slot_name = slot.clone();
+    }
+    if let Some(secs) = lookup("REPLICATION_MONITOR_INTERVAL_SECS") {
+        monitor.interval = Duration::from_secs(parse("REPLICATION_MONITOR_INTERVAL_SECS", &secs)?);
+    }
+    if let Some(bytes) = lookup("REPLICATION_MAX_RETAINED_WAL_BYTES") {
+        monitor.max_retained_wal_bytes = parse("REPLICATION_MAX_RETAINED_WAL_BYTES", &bytes)?;
+    }
+    if let Some(bytes) = lookup("REPLICATION_MAX_FLUSH_LAG_BYTES") {
+        monitor.max_flush_lag_bytes = parse("REPLICATION_MAX_FLUSH_LAG_BYTES", &bytes)?;
+    }
+    if let Some(secs) = lookup("REPLICATION_MAX_INACTIVE_SECS") {
+        monitor.max_inactive = Duration::from_secs(parse("REPLICATION_MAX_INACTIVE_SECS", &secs)?);
+    }
+    if let Some(heartbeat) = lookup("REPLICATION_HEARTBEAT_ENABLED") {
+        monitor.heartbeat = parse("REPLICATION_HEARTBEAT_ENABLED", &heartbeat)?;
+    }
+    if let Some(required) = lookup("REPLICATION_READINESS_REQUIRED") {
+        monitor.required_for_readiness = parse("REPLICATION_READINESS_REQUIRED", &required)?;
+    }
+    Ok(Some(monitor))
+}
+
+/// `OUTBOX_EVENT_FORMAT` picks the representation; the CloudEvents ones take
+/// their producer attributes from `OUTBOX_CLOUDEVENTS_*`.
+fn event_format_from_lookup(
+    lookup: &impl Fn(&str) -> Option<String>,
+) -> Result<EventFormat, ConfigError> {
+    let cloudevents = || {
+        let mut config = CloudEventsConfig::default();
+        if let Some(source) = lookup("OUTBOX_CLOUDEVENTS_SOURCE") {
+            config.source = source;
+        }
+        config.dataschema = lookup("OUTBOX_CLOUDEVENTS_DATASCHEMA");
+        config
+    };
+    match lookup("OUTBOX_EVENT_FORMAT").as_deref().map(str::trim) {
+        None | Some("event-router") => Ok(EventFormat::EventRouter),
+        Some("cloudevents-structured") => Ok(EventFormat::CloudEventsStructured(cloudevents())),
+        Some("cloudevents-binary") => Ok(EventFormat::CloudEventsBinary(cloudevents())),
+        Some(other) => Err(ConfigError::Invalid {
+            name: "OUTBOX_EVENT_FORMAT",
+            reason: format!(
+                "unknown format {:?} (event-router, cloudevents-structured or cloudevents-binary)",
+                other
+            ),
+        }),
+    }
+}
+
+/// Override `policy` from the variables naming the maximum attempts, the
+/// initial backoff and the maximum backoff (in seconds).
+fn retry_from_lookup(
+    lookup: &impl Fn(&str) -> Option<String>,
+    mut policy: RetryPolicy,
+    [max_attempts, initial_backoff, max_backoff]: [&'static str; 3],
+) -> Result<RetryPolicy, ConfigError> {
+    if let Some(value) = lookup(max_attempts) {
+        policy.max_attempts = parse(max_attempts, &value)?;
+        if policy.max_attempts == 0 {
+            return Err(ConfigError::Invalid {
+                name: max_attempts,
+                reason: "must be at least 1".to_string(),
+            });
+        }
+    }
+    if let Some(value) = lookup(initial_backoff) {
+        policy.initial_backoff = Duration::from_secs(parse(initial_backoff, &value)?);
+    }
+    if let Some(value) = lookup(max_backoff) {
+        policy.max_backoff = Duration::from_secs(parse(max_backoff, &value)?);
+    }
+    Ok(policy)
+}
+
+/// Exactly one key source may be configured; none at all disables authentication.
+fn auth_from_lookup(
+    lookup: &impl Fn(&str) -> Option<String>,
+) -> Result<Option<AuthConfig>, ConfigError> {
+    let mut sources = Vec::new();
+    if let Some(secret) = lookup("AUTH_JWT_HS256_SECRET") {
+        sources.push(JwtKeySource::Hs256Secret(secret));
+    }
+    if let Some(path) = lookup("AUTH_JWT_RS256_PUBLIC_KEY_FILE") {
+        sources.push(JwtKeySource::Rs256PublicKeyFile(PathBuf::from(path)));
+    }
+    if let Some(path) = lookup("AUTH_JWKS_FILE") {
+        sources.push(JwtKeySource::JwksFile(PathBuf::from(path)));
+    }
+
+    let key = match sources.len() {
+        0 => return Ok(None),
+        1 => sources.remove(0),
+        _ => {
+            return Err(ConfigError::Invalid {
+                name: "AUTH_JWT_*",
+                reason: "configure only one of AUTH_JWT_HS256_SECRET, \
+                         AUTH_JWT_RS256_PUBLIC_KEY_FILE and AUTH_JWKS_FILE"
+                    .to_string(),
+            })
+        }
+    };
+
+    Ok(Some(AuthConfig {
+        key,
+        issuer: lookup("AUTH_JWT_ISSUER"),
+        audience: lookup("AUTH_JWT_AUDIENCE"),
+    }))
+}
+
+/// Rate limiting is on by default; `RATE_LIMIT_ENABLED=false` turns it off.
+fn rate_limit_from_lookup(
+    lookup: &impl Fn(&str) -> Option<String>,
+) -> Result<Option<RateLimitConfig>, ConfigError> {
+    if let Some(enabled) = lookup("RATE_LIMIT_ENABLED") {
+        if !parse::<bool>("RATE_LIMIT_ENABLED", &enabled)? {
+            return Ok(None);
+        }
+    }
+
+    let budget = |default: Budget,
+                  capacity_var: &'static str,
+                  refill_var: &'static str|
+     -> Result<Budget, ConfigError> {
+        let mut budget = default;
+        if let Some(capacity) = lookup(capacity_var) {
+            budget.capacity = parse(capacity_var, &capacity)?;
+        }
+        if let Some(refill) = lookup(refill_var) {
+            budget.refill_per_second = parse(refill_var, &refill)?;
+        }
+        if budget.capacity == 0
+            || !budget.refill_per_second.is_finite()
+            || budget.refill_per_second <= 0.0
+        {
+            return Err(ConfigError::Invalid {
+                name: capacity_var,
+                reason: format!("{capacity_var} and {refill_var} must be positive"),
+            });
+        }
+        Ok(budget)
+    };
+
+    let defaults = RateLimitConfig::default();
+    Ok(Some(RateLimitConfig {
+        read: budget(
+            defaults.read,
+            "RATE_LIMIT_READ_BURST",
+            "RATE_LIMIT_READ_PER_SECOND",
+        )?,
+        write: budget(
+            defaults.write,
+            "RATE_LIMIT_WRITE_BURST",
+            "RATE_LIMIT_WRITE_PER_SECOND",
+        )?,
+        api_key_header: lookup("RATE_LIMIT_API_KEY_HEADER").unwrap_or(defaults.api_key_header),
+        api_keys: lookup("RATE_LIMIT_API_KEYS")
+            .map(|keys| {
+                keys.split(',')
+                    .map(str::trim)
+                    .filter(|key| !key.is_empty())
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn lookup(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    #[test]
+    fn database_url_is_required() {
+        let err = Config::from_lookup(lookup(&[])).expect_err("DATABASE_URL missing");
+        assert!(matches!(err, ConfigError::Missing("DATABASE_URL")));
+    }
+
+    #[test]
+    fn defaults_apply_when_only_database_url_is_set() {
+        let config = Config::from_lookup(lookup(&[("DATABASE_URL", "postgres://db")]))
+            .expect("valid config");
+        assert_eq!(config.database_url, "postgres://db");
+        assert_eq!(config.host, "0.0.0.0");
+        assert_eq!(config.port, 8080);
+        assert!(config.auth.is_none());
+        assert!(config.rate_limit.is_some());
+        assert!(config.payments.is_none());
+        assert!(config.saga.is_none());
+        assert!(config.webhooks.is_some());
+        assert_eq!(config.outbox_listener, OutboxListenerConfig::default());
+        assert_eq!(config.outbox_relay, OutboxRelayConfig::default());
+        assert!(config.outbox_publisher.is_none());
+        assert!(config.outbox_replication.is_none());
+    }
+
+    #[test]
+    fn outbox_poll_interval_is_configurable() {
+        let config = Config::from_lookup(lookup(&[
+            ("DATABASE_URL", "x"),
+            ("OUTBOX_POLL_INTERVAL_MS", "750"),
+        ]))
+        .expect("valid config");
+        assert_eq!(
+            config.outbox_listener.poll_interval,
+            Duration::from_millis(750)
+        );
+    }
+
+    #[test]
+    fn outbox_relay_retries_are_configurable() {
+        let config = Config::from_lookup(lookup(&[
+            ("DATABASE_URL", "x"),
+            ("OUTBOX_RELAY_MAX_ATTEMPTS", "4"),
+            ("OUTBOX_RELAY_MAX_BACKOFF_SECS", "60"),
+            ("OUTBOX_RELAY_PUBLISH_TIMEOUT_SECS", "3"),
+        ]))
+        .expect("valid config");
+        let relay = config.outbox_relay;
+        assert_eq!(relay.retry.max_attempts, 4);
+        assert_eq!(relay.retry.initial_backoff, Duration::from_secs(10));
+        assert_eq!(relay.retry.max_backoff, Duration::from_secs(60));
+        assert_eq!(relay.publish_timeout, Duration::from_secs(3));
+
+        let err = Config::from_lookup(lookup(&[
+            ("DATABASE_URL", "x"),
+            ("OUTBOX_RELAY_MAX_ATTEMPTS", "0"),
+        ]))
+        .expect_err("zero attempts");
+        assert!(matches!(
+            err,
+            ConfigError::Invalid {
+                name: "OUTBOX_RELAY_MAX_ATTEMPTS",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn outbox_relay_can_stream_through_logical_replication() {
+        let config = Config::from_lookup(lookup(&[
+            ("DATABASE_URL", "x"),
+            ("OUTBOX_PUBLISHER", "file"),
+            ("OUTBOX_RELAY_MODE", "replication"),
+            ("OUTBOX_REPLICATION_SLOT", "orders_relay"),
+            ("OUTBOX_RELAY_PUBLISH_TIMEOUT_SECS", "3"),
+        ]))
+        .expect("valid config");
+        let replication = config.outbox_replication.expect("replication relay");
+        assert_eq!(replication.slot_name, "orders_relay");
+        assert_eq!(replication.publish_timeout, Duration::from_secs(3));
+        assert_eq!(replication.status_interval, Duration::from_secs(10));
+
+        for (name, value) in [
+            ("OUTBOX_RELAY_MODE", "stream"),
+            ("OUTBOX_REPLICATION_SLOT", "orders\"; DROP"),
+        ] {
+            let err = Config::from_lookup(lookup(&[
+                ("DATABASE_URL", "x"),
+                ("OUTBOX_PUBLISHER", "file"),
+                ("OUTBOX_RELAY_MODE", "replication"),
+                (name, value),
+            ]))
+            .expect_err("invalid setting");
+            assert!(
+                matches!(err, ConfigError::Invalid { name: invalid, .. } if invalid == name),
+                "{:?}",
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn replication_monitor_watches_the_replication_relay_slot() {
+        let config = Config::from_lookup(lookup(&[
+            ("DATABASE_URL", "x"),
+            ("OUTBOX_PUBLISHER", "file"),
+            ("OUTBOX_RELAY_MODE", "replication"),
+            ("DEBEZIUM_SLOT_NAME", "orders_slot"),
+            ("REPLICATION_MONITOR_ENABLED", "true"),
+        ]))
+        .expect("valid config");
+        let monitor = config.replication_monitor.expect("monitor configured");
+        assert_eq!(
+            monitor.slot_name,
+            crate::replication_relay::DEFAULT_SLOT_NAME
+        );
+
+        let config = Config::from_lookup(lookup(&[
+            ("DATABASE_URL", "x"),
+            ("OUTBOX_PUBLISHER", "file"),
+            ("OUTBOX_RELAY_MODE", "replication"),
+            ("OUTBOX_REPLICATION_SLOT", "relay_slot"),
+            ("REPLICATION_MONITOR_ENABLED", "true"),
+        ]))
+        .expect("valid config");
+        let monitor = config.replication_monitor.expect("monitor configured");
+        assert_eq!(monitor.slot_name, "relay_slot");
+
+        // The polling relay has no slot: the monitor keeps watching Debezium's.
+        let config = Config::from_lookup(lookup(&[
+            ("DATABASE_URL", "x"),
+            ("OUTBOX_PUBLISHER", "file"),
+            ("REPLICATION_MONITOR_ENABLED", "true"),
+        ]))
+        .expect("valid config");
+        let monitor = config.replication_monitor.expect("monitor configured");
+        assert_eq!(monitor.slot_name, config.debezium.slot());
+    }
+
+    #[test]
+    fn replication_mode_needs_a_publisher() {
+        let err = Config::from_lookup(lookup(&[
+            ("DATABASE_URL", "x"),
+            ("OUTBOX_RELAY_MODE", "replication"),
+        ]))
+        .expect_err("no publisher");
+        assert!(
+            matches!(
+                err,
+                ConfigError::Invalid {
+                    name: "OUTBOX_RELAY_MODE",
+                    ..
+                }
+            ),
+            "{:?}",
+            err
+        );
+    }
+
+    #[test]
+    fn outbox_publisher_is_selected_by_name() {
+        let config = Config::from_lookup(lookup(&[
+            ("DATABASE_URL", "x"),
+            ("OUTBOX_PUBLISHER", "file"),
+        ]))
+        .expect("valid config");
+        let publisher = config.outbox_publisher.expect("publisher configured");
+        assert_eq!(
+            publisher.backend,
+            PublisherBackend::File {
+                path: PathBuf::from("outbox-events.ndjson")
+            }
+        );
+        assert_eq!(publisher.route, EventRoute::default());
+        assert_eq!(publisher.format, EventFormat::EventRouter);
+
+        let config = Config::from_lookup(lookup(&[
+            ("DATABASE_URL", "x"),
+            ("OUTBOX_PUBLISHER", "nats"),
+            ("OUTBOX_PUBLISHER_URL", "nats://nats:4222"),
+            ("OUTBOX_ROUTE_REPLACEMENT", "outbox.${routedByValue}"),
+        ]))
+        .expect("valid config");
+        let publisher = config.outbox_publisher.expect("publisher configured");
+        assert_eq!(
+            publisher.backend,
+            PublisherBackend::Nats {
+                url: "nats://nats:4222".to_string()
+            }
+        );
+        assert_eq!(publisher.route.destination("Order"), "outbox.Order");
+    }
+
+    #[test]
+    fn debezium_connector_shares_the_outbox_route() {
+        let config = Config::from_lookup(lookup(&[
+            ("DATABASE_URL", "x"),
+            ("DEBEZIUM_CONNECT_URL", "http://connect:8083"),
+            ("DEBEZIUM_DATABASE_HOSTNAME", "postgres"),
+            ("DEBEZIUM_SLOT_NAME", "orders_slot"),
+            ("OUTBOX_ROUTE_REPLACEMENT", "outbox.${routedByValue}"),
+            ("SAGA_COMMAND_TOPIC", "order.saga.commands"),
+        ]))
+        .expect("valid config");
+        assert_eq!(config.debezium.connect_url, "http://connect:8083");
+        assert_eq!(
+            config.debezium.database_hostname.as_deref(),
+            Some("postgres")
+        );
+        assert_eq!(config.debezium.slot_name.as_deref(), Some("orders_slot"));
+        assert_eq!(config.debezium.publication_name, None);
+        assert_eq!(config.debezium.table, "public.commerce_order_outbox");
+        assert_eq!(config.debezium.route.destination("Order"), "outbox.Order");
+        assert_eq!(
+            config.debezium.route.destination(SAGA_AGGREGATE),
+            "order.saga.commands"
+        );
+    }
+
+    #[test]
+    fn replication_monitor_watches_the_connector_slot() {
+        let config = Config::from_lookup(lookup(&[("DATABASE_URL", "x")])).expect("valid config");
+        assert_eq!(config.replication_monitor, None);
+
+        let config = Config::from_lookup(lookup(&[
+            ("DATABASE_URL", "x"),
+            ("REPLICATION_MONITOR_ENABLED", "true"),
+            ("DEBEZIUM_SLOT_NAME", "orders_slot"),
+            ("REPLICATION_MAX_RETAINED_WAL_BYTES", "5368709120"),
+            ("REPLICATION_MAX_INACTIVE_SECS", "60"),
+            ("REPLICATION_HEARTBEAT_ENABLED", "true"),
+        ]))
+        .expect("valid config");
+        let monitor = config.replication_monitor.expect("monitor configured");
+        assert_eq!(monitor.slot_name, "orders_slot");
+        assert_eq!(monitor.max_retained_wal_bytes, 5 << 30);
+        assert_eq!(monitor.max_inactive, Duration::from_secs(60));
+        assert!(monitor.heartbeat);
+        assert!(!monitor.required_for_readiness);
+
+        let err = Config::from_lookup(lookup(&[
+            ("DATABASE_URL", "x"),
+            ("REPLICATION_MONITOR_ENABLED", "true"),
+            ("REPLICATION_MAX_FLUSH_LAG_BYTES", "lots"),
+        ]))
+        .expect_err("invalid threshold");
+        assert!(matches!(
+            err,
+            ConfigError::Invalid {
+                name: "REPLICATION_MAX_FLUSH_LAG_BYTES",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn cloudevents_format_is_configurable() {
+        let config = Config::from_lookup(lookup(&[
+            ("DATABASE_URL", "x"),
+            ("OUTBOX_PUBLISHER", "file"),
+            ("OUTBOX_EVENT_FORMAT", "cloudevents-binary"),
+            ("OUTBOX_CLOUDEVENTS_SOURCE", "urn:example:orders"),
+        ]))
+        .expect("valid config");
+        let publisher = config.outbox_publisher.expect("publisher configured");
+        assert_eq!(
+            publisher.format,
+            EventFormat::CloudEventsBinary(CloudEventsConfig {
+                source: "urn:example:orders".to_string(),
+                dataschema: None,
+            })
+        );
+
+        let err = Config::from_lookup(lookup(&[
+            ("DATABASE_URL", "x"),
+            ("OUTBOX_PUBLISHER", "file"),
+            ("OUTBOX_EVENT_FORMAT", "avro"),
+        ]))
+        .expect_err("unknown format");
+        assert!(matches!(
+            err,
+            ConfigError::Invalid {
+                name: "OUTBOX_EVENT_FORMAT",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn broker_publishers_need_a_url() {
+        let err = Config::from_lookup(lookup(&[
+            ("DATABASE_URL", "x"),
+            ("OUTBOX_PUBLISHER", "redis"),
+        ]))
+        .expect_err("no URL");
+        assert!(matches!(err, ConfigError::Missing("OUTBOX_PUBLISHER_URL")));
+
+        let err = Config::from_lookup(lookup(&[
+            ("DATABASE_URL", "x"),
+            ("OUTBOX_PUBLISHER", "kafka"),
+        ]))
+        .expect_err("unknown backend");
+        assert!(matches!(
+            err,
+            ConfigError::Invalid {
+                name: "OUTBOX_PUBLISHER",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn payments_consumer_is_enabled_by_bootstrap_servers() {
+        let config = Config::from_lookup(lookup(&[
+            ("DATABASE_URL", "x"),
+            ("PAYMENTS_KAFKA_BOOTSTRAP_SERVERS", "kafka:9092"),
+            ("PAYMENTS_TOPIC", "payments"),
+        ]))
+        .expect("valid config");
+        let payments = config.payments.expect("payments consumer enabled");
+        assert_eq!(payments.bootstrap_servers, "kafka:9092");
+        assert_eq!(payments.topic, "payments");
+        assert_eq!(payments.group_id, "order-service");
+    }
+
+    #[test]
+    fn saga_is_enabled_by_bootstrap_servers() {
+        let config = Config::from_lookup(lookup(&[
+            ("DATABASE_URL", "x"),
+            ("SAGA_KAFKA_BOOTSTRAP_SERVERS", "kafka:9092"),
+            ("SAGA_AUTHORIZE_PAYMENT_TIMEOUT_SECS", "120"),
+        ]))
+        .expect("valid config");
+        let saga = config.saga.expect("saga enabled");
+        assert_eq!(saga.bootstrap_servers, "kafka:9092");
+        assert_eq!(saga.reply_topic, "public.commerce.order-saga-reply.c2.v1");
+        assert_eq!(saga.timeouts.reserve_inventory, Duration::from_secs(30));
+        assert_eq!(saga.timeouts.authorize_payment, Duration::from_secs(120));
+
+        let err = Config::from_lookup(lookup(&[
+            ("DATABASE_URL", "x"),
+            ("SAGA_KAFKA_BOOTSTRAP_SERVERS", "kafka:9092"),
+            ("SAGA_TIMEOUT_CHECK_INTERVAL_SECS", "soon"),
+        ]))
+        .expect_err("invalid interval");
+        assert!(matches!(
+            err,
+            ConfigError::Invalid {
+                name: "SAGA_TIMEOUT_CHECK_INTERVAL_SECS",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn invalid_port_is_rejected() {
+        let err = Config::from_lookup(lookup(&[("DATABASE_URL", "x"), ("PORT", "eighty")]))
+            .expect_err("invalid port");
+        assert!(matches!(err, ConfigError::Invalid { name: "PORT", .. }));
+    }
+
+    #[test]
+    fn hs256_secret_enables_auth() {
+        let config = Config::from_lookup(lookup(&[
+            ("DATABASE_URL", "x"),
+            ("AUTH_JWT_HS256_SECRET", "s3cret"),
+            ("AUTH_JWT_ISSUER", "https://issuer"),
+        ]))
+        .expect("valid config");
+        let auth = config.auth.expect("auth configured");
+        assert!(matches!(auth.key, JwtKeySource::Hs256Secret(ref s) if s == "s3cret"));
+        assert_eq!(auth.issuer.as_deref(), Some("https://issuer"));
+        assert_eq!(auth.audience, None);
+    }
+
+    #[test]
+    fn multiple_key_sources_are_rejected() {
+        let err = Config::from_lookup(lookup(&[
+            ("DATABASE_URL", "x"),
+            ("AUTH_JWT_HS256_SECRET", "s3cret"),
+            ("AUTH_JWKS_FILE", "/etc/jwks.json"),
+        ]))
+        .expect_err("ambiguous key source");
+        assert!(matches!(err, ConfigError::Invalid { .. }));
+    }
+
+    #[test]
+    fn rate_limit_budgets_are_configurable() {
+        let config = Config::from_lookup(lookup(&[
+            ("DATABASE_URL", "x"),
+            ("RATE_LIMIT_WRITE_BURST", "3"),
+            ("RATE_LIMIT_WRITE_PER_SECOND", "0.5"),
+            ("RATE_LIMIT_API_KEY_HEADER", "X-Partner-Key"),
+            ("RATE_LIMIT_API_KEYS", "partner-1, partner-2"),
+        ]))
+        .expect("valid config");
+        let rate_limit = config.rate_limit.expect("rate limiting enabled");
+        assert_eq!(rate_limit.write.capacity, 3);
+        assert_eq!(rate_limit.write.refill_per_second, 0.5);
+        assert_eq!(rate_limit.read, RateLimitConfig::default().read);
+        assert_eq!(rate_limit.api_key_header, "X-Partner-Key");
+        assert!(rate_limit.api_keys.contains("partner-2"));
+    }
+
+    #[test]
+    fn webhook_secret_key_and_destinations_are_configurable() {
+        let config = Config::from_lookup(lookup(&[("DATABASE_URL", "x")])).expect("valid config");
+        assert_eq!(config.webhook_security, WebhookSecurity::default());
+
+        let key = "0123456789abcdef0123456789abcdef";
+        let config = Config::from_lookup(lookup(&[
+            ("DATABASE_URL", "x"),
+            ("WEBHOOK_SECRET_KEY", key),
+            ("WEBHOOK_ALLOW_PRIVATE_DESTINATIONS", "true"),
+        ]))
+        .expect("valid config");
+        assert_eq!(
+            config.webhook_security,
+            WebhookSecurity {
+                secret_key: Some(WebhookSecretKey::new(key)),
+                allow_private_destinations: true,
+            }
+        );
+
+        let err = Config::from_lookup(lookup(&[
+            ("DATABASE_URL", "x"),
+            ("WEBHOOK_SECRET_KEY", "short"),
+        ]))
+        .expect_err("short key");
+        assert!(matches!(
+            err,
+            ConfigError::Invalid {
+                name: "WEBHOOK_SECRET_KEY",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn webhook_retries_are_configurable() {
+        let config = Config::from_lookup(lookup(&[
+            ("DATABASE_URL", "x"),
+            ("WEBHOOK_MAX_ATTEMPTS", "3"),
+            ("WEBHOOK_INITIAL_BACKOFF_SECS", "5"),
+            ("WEBHOOK_POLL_INTERVAL_MS", "250"),
+        ]))
+        .expect("valid config");
+        let webhooks = config.webhooks.expect("webhooks enabled");
+        assert_eq!(webhooks.retry.max_attempts, 3);
+        assert_eq!(webhooks.retry.initial_backoff, Duration::from_secs(5));
+        assert_eq!(webhooks.retry.max_backoff, Duration::from_secs(3600));
+        assert_eq!(webhooks.poll_interval, Duration::from_millis(250));
+
+        let config = Config::from_lookup(lookup(&[
+            ("DATABASE_URL", "x"),
+            ("WEBHOOKS_ENABLED", "false"),
+        ]))
+        .expect("valid config");
+        assert!(config.webhooks.is_none());
+
+        let err = Config::from_lookup(lookup(&[
+            ("DATABASE_URL", "x"),
+            ("WEBHOOK_MAX_ATTEMPTS", "0"),
+        ]))
+        .expect_err("zero attempts");
+        assert!(matches!(
+            err,
+            ConfigError::Invalid {
+                name: "WEBHOOK_MAX_ATTEMPTS",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn rate_limit_can_be_disabled() {
+        let config = Config::from_lookup(lookup(&[
+            ("DATABASE_URL", "x"),
+            ("RATE_LIMIT_ENABLED", "false"),
+        ]))
+        .expect("valid config");
+        assert!(config.rate_limit.is_none());
+    }
+
+    #[test]
+    fn zero_rate_limit_budget_is_rejected() {
+        let err = Config::from_lookup(lookup(&[
+            ("DATABASE_URL", "x"),
+            ("RATE_LIMIT_READ_BURST", "0"),
+        ]))
+        .expect_err("empty bucket");
+        assert!(matches!(
+            err,
+            ConfigError::Invalid {
+                name: "RATE_LIMIT_READ_BURST",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn shutdown_timeouts_are_configurable() {
+        let config = Config::from_lookup(lookup(&[
+            ("DATABASE_URL", "x"),
+            ("SHUTDOWN_READINESS_DELAY_SECS", "5"),
+            ("SHUTDOWN_DRAIN_TIMEOUT_SECS", "10"),
+        ]))
+        .expect("valid config");
+        assert_eq!(config.shutdown.readiness_delay, Duration::from_secs(5));
+        assert_eq!(config.shutdown.drain_timeout, Duration::from_secs(10));
+    }
+}
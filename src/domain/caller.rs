@@ -0,0 +1,10 @@
+use uuid::Uuid;
+
+/// Identity on whose behalf a use case runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Caller {
+    /// A customer; may only see and place their own orders.
+    Customer(Uuid),
+    /// Back-office staff; unrestricted.
+    Staff,
+}
@@ -6,6 +6,8 @@ pub enum DomainError {
     NotFound,
     #[error("Invalid input: {0}")]
     InvalidInput(String),
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
     #[error("Internal error: {0}")]
     Internal(String),
 }
@@ -0,0 +1,96 @@
+//! Payloads of the events and commands written to the outbox.
+//!
+//! These are the `payload` of outbox rows, hence of the messages Debezium
+//! publishes; the [AsyncAPI document](crate::asyncapi) is generated from them.
+//! Decimal amounts are strings to avoid floating-point issues.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use super::order::{ORDER_AGGREGATE, ORDER_CREATED};
+use super::outbox::OutboxMessage;
+
+/// `OrderCreated`: an order was placed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct OrderCreatedPayload {
+    pub order_id: Uuid,
+    pub customer_id: Uuid,
+    /// Always `PENDING`.
+    pub status: String,
+    pub lines: Vec<OrderCreatedLine>,
+}
+
+impl OutboxMessage for OrderCreatedPayload {
+    fn aggregate_type(&self) -> &str {
+        ORDER_AGGREGATE
+    }
+
+    fn aggregate_id(&self) -> String {
+        self.order_id.to_string()
+    }
+
+    fn event_type(&self) -> &str {
+        ORDER_CREATED
+    }
+
+    fn payload(&self) -> Value {
+        to_json(self)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct OrderCreatedLine {
+    pub product_id: Uuid,
+    pub quantity: i32,
+    /// Decimal, e.g. "9.99".
+    pub unit_price: String,
+}
+
+/// `OrderPaid`, `OrderPaymentFailed`, `OrderConfirmed` and `OrderCancelled`:
+/// the order moved from `previous_status` to `status`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct OrderStatusChangedPayload {
+    pub order_id: Uuid,
+    pub customer_id: Uuid,
+    pub status: String,
+    pub previous_status: String,
+    /// Why a payment failed or the order was cancelled, when known.
+    pub reason: Option<String>,
+}
+
+/// `ReserveInventory`: saga command to reserve the order's items.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ReserveInventoryPayload {
+    pub order_id: Uuid,
+    pub lines: Vec<ReservedItem>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ReservedItem {
+    pub product_id: Uuid,
+    pub quantity: i32,
+}
+
+/// `AuthorizePayment`: saga command to authorize the order's total.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct AuthorizePaymentPayload {
+    pub order_id: Uuid,
+    pub customer_id: Uuid,
+    /// Decimal, e.g. "19.98".
+    pub amount: String,
+}
+
+/// `ReleaseInventory` and `VoidPaymentAuthorization`: saga commands undoing
+/// a step for the order.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct OrderCompensationPayload {
+    pub order_id: Uuid,
+}
+
+/// `payload` as the JSON stored in the outbox.
+pub fn to_json<T: Serialize>(payload: &T) -> Value {
+    // Plain structs with string keys: serialization cannot fail.
+    serde_json::to_value(payload).expect("event payloads serialize to JSON")
+}
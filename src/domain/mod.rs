@@ -1,3 +1,9 @@
+pub mod caller;
 pub mod errors;
+pub mod events;
 pub mod order;
+pub mod outbox;
 pub mod ports;
+pub mod retry;
+pub mod saga;
+pub mod webhook;
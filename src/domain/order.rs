@@ -2,6 +2,152 @@ use bigdecimal::BigDecimal;
 use chrono::{DateTime, Utc};
 use uuid::Uuid;
 
+use super::outbox::OutboxMessage;
+
+/// Outbox `aggregate_type` of order events.
+pub const ORDER_AGGREGATE: &str = "Order";
+/// Event type of the outbox row written when an order is placed; always the
+/// first event of an order's history.
+pub const ORDER_CREATED: &str = "OrderCreated";
+/// Event type recorded when a captured payment moves an order to `PAID`.
+pub const ORDER_PAID: &str = "OrderPaid";
+/// Event type recorded when a failed payment moves an order to `PAYMENT_FAILED`.
+pub const ORDER_PAYMENT_FAILED: &str = "OrderPaymentFailed";
+/// Event type recorded when the placement saga moves an order to `CONFIRMED`.
+pub const ORDER_CONFIRMED: &str = "OrderConfirmed";
+/// Event type recorded when the placement saga moves an order to `CANCELLED`.
+pub const ORDER_CANCELLED: &str = "OrderCancelled";
+
+/// Lifecycle status of an order, stored as its `as_str` form.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderStatus {
+    Pending,
+    /// Inventory is reserved and payment authorized by the placement saga.
+    Confirmed,
+    /// The placement saga failed and was compensated.
+    Cancelled,
+    Paid,
+    PaymentFailed,
+}
+
+impl OrderStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            OrderStatus::Pending => "PENDING",
+            OrderStatus::Confirmed => "CONFIRMED",
+            OrderStatus::Cancelled => "CANCELLED",
+            OrderStatus::Paid => "PAID",
+            OrderStatus::PaymentFailed => "PAYMENT_FAILED",
+        }
+    }
+
+    pub fn parse(status: &str) -> Option<Self> {
+        match status {
+            "PENDING" => Some(OrderStatus::Pending),
+            "CONFIRMED" => Some(OrderStatus::Confirmed),
+            "CANCELLED" => Some(OrderStatus::Cancelled),
+            "PAID" => Some(OrderStatus::Paid),
+            "PAYMENT_FAILED" => Some(OrderStatus::PaymentFailed),
+            _ => None,
+        }
+    }
+
+    /// Status an order in this status moves to when `outcome` is reported.
+    ///
+    /// A pending or confirmed order follows the payment; an order whose
+    /// payment failed can still be paid by a later attempt. Anything else is
+    /// either a repeat of the current status or not allowed.
+    pub fn after_payment(self, outcome: &PaymentOutcome) -> PaymentTransition {
+        let target = outcome.target_status();
+        match (self, outcome) {
+            _ if self == target => PaymentTransition::Unchanged,
+            (OrderStatus::Pending | OrderStatus::Confirmed, _)
+            | (OrderStatus::PaymentFailed, PaymentOutcome::Captured) => {
+                PaymentTransition::Move(target)
+            }
+            _ => PaymentTransition::Invalid,
+        }
+    }
+}
+
+/// Result of a payment reported by the payments service.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PaymentOutcome {
+    Captured,
+    Failed { reason: Option<String> },
+}
+
+impl PaymentOutcome {
+    pub fn target_status(&self) -> OrderStatus {
+        match self {
+            PaymentOutcome::Captured => OrderStatus::Paid,
+            PaymentOutcome::Failed { .. } => OrderStatus::PaymentFailed,
+        }
+    }
+
+    /// Outbox event type recorded when the outcome changes an order's status.
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            PaymentOutcome::Captured => ORDER_PAID,
+            PaymentOutcome::Failed { .. } => ORDER_PAYMENT_FAILED,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaymentTransition {
+    Move(OrderStatus),
+    /// The order already has the status the payment leads to.
+    Unchanged,
+    /// The payment contradicts the order's status (e.g. a failure after it was paid).
+    Invalid,
+}
+
+/// A compare-and-set of an order's status, recorded in the outbox as `event_type`.
+#[derive(Debug, Clone)]
+pub struct StatusChange {
+    pub order_id: Uuid,
+    pub from: OrderStatus,
+    pub to: OrderStatus,
+    pub event_type: &'static str,
+    pub payload: serde_json::Value,
+}
+
+impl OutboxMessage for StatusChange {
+    fn aggregate_type(&self) -> &str {
+        ORDER_AGGREGATE
+    }
+
+    fn aggregate_id(&self) -> String {
+        self.order_id.to_string()
+    }
+
+    fn event_type(&self) -> &str {
+        self.event_type
+    }
+
+    fn payload(&self) -> serde_json::Value {
+        self.payload.clone()
+    }
+}
+
+/// The inbound message that triggered a change, recorded in the inbox with it.
+#[derive(Debug, Clone)]
+pub struct SourceMessage {
+    pub id: String,
+    pub message_type: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusChangeResult {
+    Applied,
+    /// The source message was processed before; nothing changed.
+    Duplicate,
+    /// The order (or saga) was no longer in `from` (or does not exist);
+    /// nothing changed.
+    Stale,
+}
+
 #[derive(Debug, Clone)]
 pub struct OrderLineInput {
     pub product_id: Uuid,
@@ -31,3 +177,113 @@ pub struct ListResult {
     pub items: Vec<OrderView>,
     pub total: i64,
 }
+
+/// A domain event recorded for an order in the outbox.
+#[derive(Debug, Clone)]
+pub struct OrderEvent {
+    pub id: Uuid,
+    pub order_id: Uuid,
+    pub event_type: String,
+    /// Position within the order's events, starting at 1.
+    pub sequence: i64,
+    pub occurred_at: DateTime<Utc>,
+    pub payload: serde_json::Value,
+}
+
+/// Selects the order events a stream subscriber receives.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OrderEventFilter {
+    /// Only the events of this order.
+    pub order_id: Option<Uuid>,
+    /// Only events of these types; empty means all of them.
+    pub event_types: Vec<String>,
+}
+
+impl OrderEventFilter {
+    pub fn matches(&self, event: &OrderEvent) -> bool {
+        self.order_id.is_none_or(|id| id == event.order_id)
+            && (self.event_types.is_empty() || self.event_types.contains(&event.event_type))
+    }
+}
+
+/// Chronological events of one order.
+#[derive(Debug, Clone)]
+pub struct OrderHistory {
+    pub events: Vec<OrderEvent>,
+    /// `false` when the oldest events are gone from the outbox (e.g. removed
+    /// by retention cleanup), detected by a missing `OrderCreated` event.
+    pub complete: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failed() -> PaymentOutcome {
+        PaymentOutcome::Failed {
+            reason: Some("card declined".to_string()),
+        }
+    }
+
+    #[test]
+    fn status_round_trips_through_its_string_form() {
+        for status in [
+            OrderStatus::Pending,
+            OrderStatus::Confirmed,
+            OrderStatus::Cancelled,
+            OrderStatus::Paid,
+            OrderStatus::PaymentFailed,
+        ] {
+            assert_eq!(OrderStatus::parse(status.as_str()), Some(status));
+        }
+        assert_eq!(OrderStatus::parse("SHIPPED"), None);
+    }
+
+    #[test]
+    fn pending_orders_follow_the_payment() {
+        assert_eq!(
+            OrderStatus::Pending.after_payment(&PaymentOutcome::Captured),
+            PaymentTransition::Move(OrderStatus::Paid)
+        );
+        assert_eq!(
+            OrderStatus::Pending.after_payment(&failed()),
+            PaymentTransition::Move(OrderStatus::PaymentFailed)
+        );
+    }
+
+    #[test]
+    fn failed_payments_can_be_retried_but_paid_orders_cannot_fail() {
+        assert_eq!(
+            OrderStatus::PaymentFailed.after_payment(&PaymentOutcome::Captured),
+            PaymentTransition::Move(OrderStatus::Paid)
+        );
+        assert_eq!(
+            OrderStatus::Paid.after_payment(&failed()),
+            PaymentTransition::Invalid
+        );
+    }
+
+    #[test]
+    fn confirmed_orders_follow_the_payment_but_cancelled_ones_do_not() {
+        assert_eq!(
+            OrderStatus::Confirmed.after_payment(&PaymentOutcome::Captured),
+            PaymentTransition::Move(OrderStatus::Paid)
+        );
+        assert_eq!(
+            OrderStatus::Cancelled.after_payment(&PaymentOutcome::Captured),
+            PaymentTransition::Invalid
+        );
+    }
+
+    #[test]
+    fn repeated_outcomes_leave_the_status_unchanged() {
+        assert_eq!(
+            OrderStatus::Paid.after_payment(&PaymentOutcome::Captured),
+            PaymentTransition::Unchanged
+        );
+        assert_eq!(
+            OrderStatus::PaymentFailed.after_payment(&failed()),
+            PaymentTransition::Unchanged
+        );
+    }
+}
@@ -0,0 +1,123 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use uuid::Uuid;
+
+/// Something to record in an outbox table: what happened to which aggregate.
+///
+/// The outbox writer gives it the aggregate's next sequence number and an
+/// event id, and stores it in the transaction that changed the aggregate.
+pub trait OutboxMessage {
+    /// Routes the event, e.g. the EventRouter's topic.
+    fn aggregate_type(&self) -> &str;
+    /// Orders the aggregate's events and keys its messages.
+    fn aggregate_id(&self) -> String;
+    fn event_type(&self) -> &str;
+    fn payload(&self) -> Value;
+    /// Extra message headers, e.g. trace context or a schema version.
+    fn headers(&self) -> BTreeMap<String, String> {
+        BTreeMap::new()
+    }
+}
+
+/// An [`OutboxMessage`] assembled from its parts, for events without a
+/// dedicated type.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutboxEvent {
+    pub aggregate_type: String,
+    pub aggregate_id: String,
+    pub event_type: String,
+    pub payload: Value,
+    pub headers: BTreeMap<String, String>,
+}
+
+impl OutboxEvent {
+    pub fn new(
+        aggregate_type: impl Into<String>,
+        aggregate_id: impl Into<String>,
+        event_type: impl Into<String>,
+        payload: Value,
+    ) -> Self {
+        Self {
+            aggregate_type: aggregate_type.into(),
+            aggregate_id: aggregate_id.into(),
+            event_type: event_type.into(),
+            payload,
+            headers: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.insert(name.into(), value.into());
+        self
+    }
+}
+
+impl OutboxMessage for OutboxEvent {
+    fn aggregate_type(&self) -> &str {
+        &self.aggregate_type
+    }
+
+    fn aggregate_id(&self) -> String {
+        self.aggregate_id.clone()
+    }
+
+    fn event_type(&self) -> &str {
+        &self.event_type
+    }
+
+    fn payload(&self) -> Value {
+        self.payload.clone()
+    }
+
+    fn headers(&self) -> BTreeMap<String, String> {
+        self.headers.clone()
+    }
+}
+
+/// Where an outbox event stands with the in-process relay, stored as its
+/// `as_str` form. Events the relay has not picked up yet have no status.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PublicationStatus {
+    /// Not published yet; attempted again at `next_attempt_at`.
+    Pending,
+    Published,
+    /// Gave up after a rejection or too many failed attempts (the dead-letter
+    /// list).
+    Dead,
+    /// Dead letter an operator decided not to publish.
+    Discarded,
+    /// Already in the outbox when the relay first ran; left to whatever
+    /// published the outbox before.
+    Skipped,
+}
+
+impl PublicationStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PublicationStatus::Pending => "PENDING",
+            PublicationStatus::Published => "PUBLISHED",
+            PublicationStatus::Dead => "DEAD",
+            PublicationStatus::Discarded => "DISCARDED",
+            PublicationStatus::Skipped => "SKIPPED",
+        }
+    }
+}
+
+/// An outbox event the relay gave up on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutboxDeadLetter {
+    pub event_id: Uuid,
+    pub aggregate_type: String,
+    pub aggregate_id: String,
+    pub event_type: String,
+    pub sequence: i64,
+    pub payload: Value,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    /// When the event was written to the outbox.
+    pub occurred_at: DateTime<Utc>,
+    /// When the last attempt failed.
+    pub dead_lettered_at: DateTime<Utc>,
+}
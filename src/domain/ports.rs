@@ -1,10 +1,134 @@
+use std::net::IpAddr;
+
+use chrono::{DateTime, Utc};
 use uuid::Uuid;
 
 use super::errors::DomainError;
-use super::order::{ListResult, OrderLineInput, OrderView};
+use super::order::{ListResult, OrderEvent, OrderLineInput, OrderStatus, OrderView, SourceMessage};
+use super::outbox::{OutboxDeadLetter, OutboxMessage};
+use super::saga::{OrderSaga, SagaStart, SagaStepEntry, SagaTransition};
+use super::webhook::{WebhookDelivery, WebhookSubscription, WebhookSubscriptionInput};
 
+/// Order queries; changes go through a [`UnitOfWork`].
 pub trait OrderRepository: Send + Sync + 'static {
-    fn create(&self, customer_id: Uuid, lines: Vec<OrderLineInput>) -> Result<Uuid, DomainError>;
     fn find_by_id(&self, id: Uuid) -> Result<Option<OrderView>, DomainError>;
-    fn list(&self, page: i64, limit: i64) -> Result<ListResult, DomainError>;
+    /// Page through orders, newest first, optionally restricted to one customer.
+    fn list(
+        &self,
+        page: i64,
+        limit: i64,
+        customer_id: Option<Uuid>,
+    ) -> Result<ListResult, DomainError>;
+    /// Outbox events recorded for an order, oldest first.
+    fn events(&self, order_id: Uuid) -> Result<Vec<OrderEvent>, DomainError>;
+}
+
+/// Runs the steps of a use case atomically: what is done through the
+/// [`Transaction`] handed to `work` commits if it returns `Ok` and is rolled
+/// back otherwise.
+pub trait UnitOfWork: Send + Sync + 'static {
+    fn run<T, E>(&self, work: impl FnOnce(&mut dyn Transaction) -> Result<T, E>) -> Result<T, E>
+    where
+        E: From<DomainError>;
+}
+
+/// The repositories of one unit of work.
+pub trait Transaction {
+    fn orders(&mut self) -> &mut dyn TransactionalOrderRepository;
+    fn sagas(&mut self) -> &mut dyn TransactionalSagaRepository;
+    fn outbox(&mut self) -> &mut dyn TransactionalOutbox;
+    fn inbox(&mut self) -> &mut dyn TransactionalInbox;
+}
+
+pub trait TransactionalOrderRepository {
+    /// The order, which concurrent units of work cannot change until this one
+    /// ends.
+    fn find_by_id(&mut self, id: Uuid) -> Result<Option<OrderView>, DomainError>;
+    /// Store a new `PENDING` order.
+    fn insert(
+        &mut self,
+        order_id: Uuid,
+        customer_id: Uuid,
+        lines: &[OrderLineInput],
+    ) -> Result<(), DomainError>;
+    /// Compare-and-set the order's status; `false` if it is not in `from`.
+    fn update_status(
+        &mut self,
+        order_id: Uuid,
+        from: OrderStatus,
+        to: OrderStatus,
+    ) -> Result<bool, DomainError>;
+}
+
+pub trait TransactionalSagaRepository {
+    /// Store a new saga with its first step and command.
+    fn start(&mut self, start: &SagaStart) -> Result<(), DomainError>;
+    /// The saga of `order_id`, if the order was placed with one.
+    fn find(&mut self, order_id: Uuid) -> Result<Option<OrderSaga>, DomainError>;
+    /// Compare-and-set the saga from `transition.version` and journal its
+    /// steps; `false` if the saga moved on. Sending its commands and settling
+    /// the order are up to the caller.
+    fn advance(&mut self, transition: &SagaTransition) -> Result<bool, DomainError>;
+}
+
+pub trait TransactionalOutbox {
+    /// Append `message` with its aggregate's next sequence number.
+    fn append(&mut self, message: &dyn OutboxMessage) -> Result<(), DomainError>;
+}
+
+pub trait TransactionalInbox {
+    /// Record `message` as processed; `false` if it already was.
+    fn record(&mut self, message: &SourceMessage) -> Result<bool, DomainError>;
+}
+
+/// Saga queries; transitions go through a [`UnitOfWork`].
+pub trait SagaRepository: Send + Sync + 'static {
+    fn find(&self, order_id: Uuid) -> Result<Option<OrderSaga>, DomainError>;
+    /// Journal of a saga, oldest first.
+    fn steps(&self, order_id: Uuid) -> Result<Vec<SagaStepEntry>, DomainError>;
+    /// Orders whose saga step deadline is at or before `now`, earliest first.
+    fn overdue(&self, now: DateTime<Utc>, limit: i64) -> Result<Vec<Uuid>, DomainError>;
+}
+
+pub trait WebhookRepository: Send + Sync + 'static {
+    /// Store a subscription; `input` is normalized.
+    fn create(&self, input: &WebhookSubscriptionInput) -> Result<WebhookSubscription, DomainError>;
+    /// All subscriptions, oldest first.
+    fn list(&self) -> Result<Vec<WebhookSubscription>, DomainError>;
+    fn find(&self, id: Uuid) -> Result<Option<WebhookSubscription>, DomainError>;
+    /// Replace URL and filters; `None` for an unknown subscription.
+    fn update(
+        &self,
+        id: Uuid,
+        input: &WebhookSubscriptionInput,
+    ) -> Result<Option<WebhookSubscription>, DomainError>;
+    /// Remove a subscription and its deliveries; `false` if it did not exist.
+    fn delete(&self, id: Uuid) -> Result<bool, DomainError>;
+    /// Dead-lettered deliveries of a subscription, newest first.
+    fn dead_letters(
+        &self,
+        subscription_id: Uuid,
+        limit: i64,
+    ) -> Result<Vec<WebhookDelivery>, DomainError>;
+    /// Put a dead-lettered delivery back in the queue with a fresh attempt
+    /// budget; `false` if there is no such dead letter.
+    fn requeue(&self, subscription_id: Uuid, delivery_id: i64) -> Result<bool, DomainError>;
+}
+
+/// Name resolution for webhook destinations.
+pub trait HostResolver: Send + Sync + 'static {
+    /// Addresses `host` (a domain name or an IP address) resolves to.
+    fn resolve(&self, host: &str, port: u16) -> Result<Vec<IpAddr>, DomainError>;
+}
+
+pub trait OutboxDeadLetterRepository: Send + Sync + 'static {
+    /// Outbox events the relay gave up on, most recently dead-lettered first.
+    fn list(&self, limit: i64) -> Result<Vec<OutboxDeadLetter>, DomainError>;
+    fn find(&self, event_id: Uuid) -> Result<Option<OutboxDeadLetter>, DomainError>;
+    /// Queue a dead-lettered event again with a fresh attempt budget; `false`
+    /// if there is no such dead letter.
+    fn requeue(&self, event_id: Uuid) -> Result<bool, DomainError>;
+    /// Give up on a dead-lettered event for good; `false` if there is no such
+    /// dead letter.
+    fn discard(&self, event_id: Uuid) -> Result<bool, DomainError>;
 }
@@ -0,0 +1,87 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+
+/// How failed attempts (webhook deliveries, outbox publications) are retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Attempts before giving up and dead-lettering.
+    pub max_attempts: u32,
+    /// Wait after the first failure; doubled after each further one.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 8,
+            initial_backoff: Duration::from_secs(10),
+            max_backoff: Duration::from_secs(3600),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Wait before the next attempt after `attempts` failed ones, or `None`
+    /// once it is time to dead-letter.
+    pub fn backoff(&self, attempts: u32) -> Option<Duration> {
+        if attempts >= self.max_attempts {
+            return None;
+        }
+        let doublings = attempts.saturating_sub(1).min(31);
+        Some(
+            self.initial_backoff
+                .saturating_mul(1 << doublings)
+                .min(self.max_backoff),
+        )
+    }
+}
+
+/// `delay` after `now`, or the latest representable time when that is out
+/// of range: an absurd delay postpones for good instead of panicking.
+pub fn after(now: DateTime<Utc>, delay: Duration) -> DateTime<Utc> {
+    chrono::Duration::from_std(delay)
+        .ok()
+        .and_then(|delay| now.checked_add_signed(delay))
+        .unwrap_or(DateTime::<Utc>::MAX_UTC)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn after_saturates_instead_of_overflowing() {
+        let now = Utc::now();
+        assert_eq!(
+            after(now, Duration::from_secs(10)),
+            now + chrono::Duration::seconds(10)
+        );
+        assert_eq!(after(now, Duration::MAX), DateTime::<Utc>::MAX_UTC);
+        assert_eq!(
+            after(now, Duration::from_secs(i64::MAX as u64 / 1000)),
+            DateTime::<Utc>::MAX_UTC
+        );
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap_then_gives_up() {
+        let policy = RetryPolicy {
+            max_attempts: 5,
+            initial_backoff: Duration::from_secs(10),
+            max_backoff: Duration::from_secs(30),
+        };
+        let delays: Vec<_> = (1..=5).map(|attempts| policy.backoff(attempts)).collect();
+        assert_eq!(
+            delays,
+            [
+                Some(Duration::from_secs(10)),
+                Some(Duration::from_secs(20)),
+                Some(Duration::from_secs(30)),
+                Some(Duration::from_secs(30)),
+                None,
+            ]
+        );
+    }
+}
@@ -0,0 +1,771 @@
+//! Orchestration of order placement.
+//!
+//! A new order runs through three steps: reserve its inventory, authorize its
+//! payment, then confirm it. The first two are commands to other services
+//! (sent through the outbox) whose replies drive the saga forward; each waits
+//! at most a configured timeout. When a step fails or times out, the steps
+//! already done are compensated and the order ends up `CANCELLED`.
+//!
+//! [`OrderSaga::decide`] is the whole state machine; persisting its
+//! [`SagaTransition`]s is up to the
+//! [`TransactionalSagaRepository`](super::ports::TransactionalSagaRepository).
+
+use std::time::Duration;
+
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use super::events::{
+    self, AuthorizePaymentPayload, OrderCompensationPayload, OrderStatusChangedPayload,
+    ReserveInventoryPayload, ReservedItem,
+};
+use super::order::{OrderLineInput, OrderStatus, StatusChange, ORDER_CANCELLED, ORDER_CONFIRMED};
+use super::outbox::OutboxEvent;
+use super::retry::after;
+
+/// `aggregate_type` of the saga's commands in the outbox.
+pub const SAGA_AGGREGATE: &str = "OrderSaga";
+
+pub const RESERVE_INVENTORY: &str = "ReserveInventory";
+pub const RELEASE_INVENTORY: &str = "ReleaseInventory";
+pub const AUTHORIZE_PAYMENT: &str = "AuthorizePayment";
+pub const VOID_PAYMENT_AUTHORIZATION: &str = "VoidPaymentAuthorization";
+
+pub const INVENTORY_RESERVED: &str = "InventoryReserved";
+pub const INVENTORY_RESERVATION_FAILED: &str = "InventoryReservationFailed";
+pub const INVENTORY_RELEASED: &str = "InventoryReleased";
+pub const PAYMENT_AUTHORIZED: &str = "PaymentAuthorized";
+pub const PAYMENT_AUTHORIZATION_FAILED: &str = "PaymentAuthorizationFailed";
+
+/// Where a saga stands, stored as its `as_str` form.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SagaState {
+    /// `ReserveInventory` was sent; waiting for the inventory service.
+    ReservingInventory,
+    /// `AuthorizePayment` was sent; waiting for the payments service.
+    AuthorizingPayment,
+    /// A step failed; `ReleaseInventory` was sent and is awaited.
+    Compensating,
+    /// The order was confirmed.
+    Completed,
+    /// The order was cancelled, after compensation where needed.
+    Failed,
+}
+
+impl SagaState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SagaState::ReservingInventory => "RESERVING_INVENTORY",
+            SagaState::AuthorizingPayment => "AUTHORIZING_PAYMENT",
+            SagaState::Compensating => "COMPENSATING",
+            SagaState::Completed => "COMPLETED",
+            SagaState::Failed => "FAILED",
+        }
+    }
+
+    pub fn parse(state: &str) -> Option<Self> {
+        match state {
+            "RESERVING_INVENTORY" => Some(SagaState::ReservingInventory),
+            "AUTHORIZING_PAYMENT" => Some(SagaState::AuthorizingPayment),
+            "COMPENSATING" => Some(SagaState::Compensating),
+            "COMPLETED" => Some(SagaState::Completed),
+            "FAILED" => Some(SagaState::Failed),
+            _ => None,
+        }
+    }
+
+    pub fn is_terminal(self) -> bool {
+        matches!(self, SagaState::Completed | SagaState::Failed)
+    }
+}
+
+/// A step of the saga, as recorded in its journal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SagaStep {
+    ReserveInventory,
+    AuthorizePayment,
+    Confirm,
+    /// Compensation of `ReserveInventory`.
+    ReleaseInventory,
+    /// Compensation of `AuthorizePayment`; sent without awaiting a reply.
+    VoidPayment,
+}
+
+impl SagaStep {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SagaStep::ReserveInventory => "RESERVE_INVENTORY",
+            SagaStep::AuthorizePayment => "AUTHORIZE_PAYMENT",
+            SagaStep::Confirm => "CONFIRM",
+            SagaStep::ReleaseInventory => "RELEASE_INVENTORY",
+            SagaStep::VoidPayment => "VOID_PAYMENT",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepOutcome {
+    Started,
+    Succeeded,
+    Failed,
+    TimedOut,
+}
+
+impl StepOutcome {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            StepOutcome::Started => "STARTED",
+            StepOutcome::Succeeded => "SUCCEEDED",
+            StepOutcome::Failed => "FAILED",
+            StepOutcome::TimedOut => "TIMED_OUT",
+        }
+    }
+}
+
+/// A journal entry to append for a transition.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SagaStepRecord {
+    pub step: SagaStep,
+    pub outcome: StepOutcome,
+    pub detail: Option<String>,
+}
+
+impl SagaStepRecord {
+    fn new(step: SagaStep, outcome: StepOutcome) -> Self {
+        Self {
+            step,
+            outcome,
+            detail: None,
+        }
+    }
+
+    fn with_detail(mut self, detail: Option<String>) -> Self {
+        self.detail = detail;
+        self
+    }
+}
+
+/// A journal entry as stored.
+#[derive(Debug, Clone)]
+pub struct SagaStepEntry {
+    pub step: String,
+    pub outcome: String,
+    pub detail: Option<String>,
+    pub recorded_at: DateTime<Utc>,
+}
+
+/// A reply from the inventory or payments service.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SagaReply {
+    InventoryReserved,
+    InventoryReservationFailed { reason: Option<String> },
+    InventoryReleased,
+    PaymentAuthorized,
+    PaymentAuthorizationFailed { reason: Option<String> },
+}
+
+impl SagaReply {
+    /// The reply carried by a message of `message_type`, if it is one.
+    pub fn from_message_type(message_type: &str, reason: Option<String>) -> Option<Self> {
+        match message_type {
+            INVENTORY_RESERVED => Some(SagaReply::InventoryReserved),
+            INVENTORY_RESERVATION_FAILED => Some(SagaReply::InventoryReservationFailed { reason }),
+            INVENTORY_RELEASED => Some(SagaReply::InventoryReleased),
+            PAYMENT_AUTHORIZED => Some(SagaReply::PaymentAuthorized),
+            PAYMENT_AUTHORIZATION_FAILED => Some(SagaReply::PaymentAuthorizationFailed { reason }),
+            _ => None,
+        }
+    }
+}
+
+/// What a saga reacts to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SagaInput<'a> {
+    Reply(&'a SagaReply),
+    /// The current step's deadline may have passed.
+    Timeout,
+}
+
+/// How long each awaited step may take.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SagaTimeouts {
+    pub reserve_inventory: Duration,
+    pub authorize_payment: Duration,
+    /// After this, `ReleaseInventory` is sent again.
+    pub release_inventory: Duration,
+}
+
+impl Default for SagaTimeouts {
+    fn default() -> Self {
+        Self {
+            reserve_inventory: Duration::from_secs(30),
+            authorize_payment: Duration::from_secs(30),
+            release_inventory: Duration::from_secs(60),
+        }
+    }
+}
+
+/// A command for another service, appended to the outbox under
+/// [`SAGA_AGGREGATE`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SagaCommand {
+    pub command_type: &'static str,
+    pub payload: serde_json::Value,
+}
+
+impl SagaCommand {
+    /// The outbox event sending the command for `order_id`.
+    pub fn for_order(&self, order_id: Uuid) -> OutboxEvent {
+        OutboxEvent::new(
+            SAGA_AGGREGATE,
+            order_id.to_string(),
+            self.command_type,
+            self.payload.clone(),
+        )
+    }
+}
+
+/// The placement saga of one order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderSaga {
+    pub order_id: Uuid,
+    pub customer_id: Uuid,
+    /// Order total, as authorized with the payments service.
+    pub amount: BigDecimal,
+    pub state: SagaState,
+    /// Incremented by every transition, for optimistic locking.
+    pub version: i64,
+    /// When the awaited step times out; `None` once the saga has ended.
+    pub deadline: Option<DateTime<Utc>>,
+    pub failure_reason: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A new saga together with its first step.
+#[derive(Debug, Clone)]
+pub struct SagaStart {
+    pub saga: OrderSaga,
+    pub step: SagaStepRecord,
+    pub command: SagaCommand,
+}
+
+/// A compare-and-set of a saga from `version`, with everything it entails.
+#[derive(Debug, Clone)]
+pub struct SagaTransition {
+    pub order_id: Uuid,
+    pub version: i64,
+    pub to: SagaState,
+    pub deadline: Option<DateTime<Utc>>,
+    pub failure_reason: Option<String>,
+    pub steps: Vec<SagaStepRecord>,
+    pub commands: Vec<SagaCommand>,
+    /// Settles the order, which must still be `PENDING`.
+    pub order_change: Option<StatusChange>,
+}
+
+/// Saga state and journal, for inspection.
+#[derive(Debug, Clone)]
+pub struct SagaView {
+    pub saga: OrderSaga,
+    pub steps: Vec<SagaStepEntry>,
+}
+
+impl OrderSaga {
+    /// Saga of a newly placed order, starting with the inventory reservation.
+    pub fn start(
+        order_id: Uuid,
+        customer_id: Uuid,
+        lines: &[OrderLineInput],
+        timeouts: &SagaTimeouts,
+        now: DateTime<Utc>,
+    ) -> SagaStart {
+        let amount = lines
+            .iter()
+            .map(|l| &l.unit_price * BigDecimal::from(l.quantity))
+            .sum();
+        let items = lines
+            .iter()
+            .map(|l| ReservedItem {
+                product_id: l.product_id,
+                quantity: l.quantity,
+            })
+            .collect();
+        SagaStart {
+            saga: OrderSaga {
+                order_id,
+                customer_id,
+                amount,
+                state: SagaState::ReservingInventory,
+                version: 0,
+                deadline: Some(after(now, timeouts.reserve_inventory)),
+                failure_reason: None,
+                started_at: now,
+                updated_at: now,
+            },
+            step: SagaStepRecord::new(SagaStep::ReserveInventory, StepOutcome::Started),
+            command: SagaCommand {
+                command_type: RESERVE_INVENTORY,
+                payload: events::to_json(&ReserveInventoryPayload {
+                    order_id,
+                    lines: items,
+                }),
+            },
+        }
+    }
+
+    /// Whether the awaited step has run out of time at `now`.
+    pub fn is_overdue(&self, now: DateTime<Utc>) -> bool {
+        self.deadline.is_some_and(|deadline| deadline <= now)
+    }
+
+    /// How the saga reacts to `input` at `now`, or `None` if it does not
+    /// apply: a reply the current step is not waiting for (late, or a
+    /// repeat), or a timeout before the deadline.
+    pub fn decide(
+        &self,
+        input: SagaInput<'_>,
+        timeouts: &SagaTimeouts,
+        now: DateTime<Utc>,
+    ) -> Option<SagaTransition> {
+        use SagaInput::{Reply, Timeout};
+        use SagaState::*;
+        use SagaStep as Step;
+        use StepOutcome::{Started, Succeeded, TimedOut};
+
+        if input == Timeout && !self.is_overdue(now) {
+            return None;
+        }
+        let step = SagaStepRecord::new;
+        let transition = match (self.state, input) {
+            (ReservingInventory, Reply(SagaReply::InventoryReserved)) => self
+                .moving_to(
+                    AuthorizingPayment,
+                    Some(after(now, timeouts.authorize_payment)),
+                )
+                .step(step(Step::ReserveInventory, Succeeded))
+                .step(step(Step::AuthorizePayment, Started))
+                .command(self.authorize_payment()),
+            (ReservingInventory, Reply(SagaReply::InventoryReservationFailed { reason })) => {
+                // Nothing was reserved, so there is nothing to compensate.
+                let reason = failure("inventory reservation failed", reason.as_deref());
+                self.moving_to(Failed, None)
+                    .step(
+                        step(Step::ReserveInventory, StepOutcome::Failed)
+                            .with_detail(Some(reason.clone())),
+                    )
+                    .settle(self.order_change(OrderStatus::Cancelled, Some(&reason)))
+                    .failing(reason)
+            }
+            (ReservingInventory, Timeout) => {
+                // The reservation may still happen, so release it regardless.
+                self.compensating(timeouts, now, "inventory reservation timed out")
+                    .step(step(Step::ReserveInventory, TimedOut))
+                    .step(step(Step::ReleaseInventory, Started))
+                    .command(self.release_inventory())
+            }
+            (AuthorizingPayment, Reply(SagaReply::PaymentAuthorized)) => self
+                .moving_to(Completed, None)
+                .step(step(Step::AuthorizePayment, Succeeded))
+                .step(step(Step::Confirm, Succeeded))
+                .settle(self.order_change(OrderStatus::Confirmed, None)),
+            (AuthorizingPayment, Reply(SagaReply::PaymentAuthorizationFailed { reason })) => {
+                let reason = failure("payment authorization failed", reason.as_deref());
+                self.compensating(timeouts, now, &reason)
+                    .step(
+                        step(Step::AuthorizePayment, StepOutcome::Failed)
+                            .with_detail(Some(reason.clone())),
+                    )
+                    .step(step(Step::ReleaseInventory, Started))
+                    .command(self.release_inventory())
+            }
+            (AuthorizingPayment, Timeout) => {
+                // A late authorization must not hold the customer's funds.
+                self.compensating(timeouts, now, "payment authorization timed out")
+                    .step(step(Step::AuthorizePayment, TimedOut))
+                    .step(step(Step::VoidPayment, Started))
+                    .step(step(Step::ReleaseInventory, Started))
+                    .command(self.void_payment())
+                    .command(self.release_inventory())
+            }
+            (Compensating, Reply(SagaReply::InventoryReleased)) => {
+                let reason = self
+                    .failure_reason
+                    .clone()
+                    .unwrap_or_else(|| "compensated".to_string());
+                self.moving_to(Failed, None)
+                    .step(step(Step::ReleaseInventory, Succeeded))
+                    .settle(self.order_change(OrderStatus::Cancelled, Some(&reason)))
+                    .failing(reason)
+            }
+            (Compensating, Timeout) => {
+                // Compensation must eventually succeed: ask again.
+                let mut retry =
+                    self.moving_to(Compensating, Some(after(now, timeouts.release_inventory)));
+                retry.failure_reason = self.failure_reason.clone();
+                retry
+                    .step(step(Step::ReleaseInventory, TimedOut))
+                    .step(step(Step::ReleaseInventory, Started))
+                    .command(self.release_inventory())
+            }
+            _ => return None,
+        };
+        Some(transition)
+    }
+
+    fn moving_to(&self, to: SagaState, deadline: Option<DateTime<Utc>>) -> SagaTransition {
+        SagaTransition {
+            order_id: self.order_id,
+            version: self.version,
+            to,
+            deadline,
+            failure_reason: None,
+            steps: Vec::new(),
+            commands: Vec::new(),
+            order_change: None,
+        }
+    }
+
+    fn compensating(
+        &self,
+        timeouts: &SagaTimeouts,
+        now: DateTime<Utc>,
+        reason: &str,
+    ) -> SagaTransition {
+        self.moving_to(
+            SagaState::Compensating,
+            Some(after(now, timeouts.release_inventory)),
+        )
+        .failing(reason.to_string())
+    }
+
+    fn order_change(&self, to: OrderStatus, reason: Option<&str>) -> StatusChange {
+        let event_type = match to {
+            OrderStatus::Confirmed => ORDER_CONFIRMED,
+            _ => ORDER_CANCELLED,
+        };
+        StatusChange {
+            order_id: self.order_id,
+            from: OrderStatus::Pending,
+            to,
+            event_type,
+            payload: events::to_json(&OrderStatusChangedPayload {
+                order_id: self.order_id,
+                customer_id: self.customer_id,
+                status: to.as_str().to_string(),
+                previous_status: OrderStatus::Pending.as_str().to_string(),
+                reason: reason.map(str::to_string),
+            }),
+        }
+    }
+
+    fn authorize_payment(&self) -> SagaCommand {
+        SagaCommand {
+            command_type: AUTHORIZE_PAYMENT,
+            payload: events::to_json(&AuthorizePaymentPayload {
+                order_id: self.order_id,
+                customer_id: self.customer_id,
+                amount: self.amount.to_string(),
+            }),
+        }
+    }
+
+    fn release_inventory(&self) -> SagaCommand {
+        SagaCommand {
+            command_type: RELEASE_INVENTORY,
+            payload: events::to_json(&OrderCompensationPayload {
+                order_id: self.order_id,
+            }),
+        }
+    }
+
+    fn void_payment(&self) -> SagaCommand {
+        SagaCommand {
+            command_type: VOID_PAYMENT_AUTHORIZATION,
+            payload: events::to_json(&OrderCompensationPayload {
+                order_id: self.order_id,
+            }),
+        }
+    }
+}
+
+impl SagaTransition {
+    fn step(mut self, record: SagaStepRecord) -> Self {
+        self.steps.push(record);
+        self
+    }
+
+    fn command(mut self, command: SagaCommand) -> Self {
+        self.commands.push(command);
+        self
+    }
+
+    fn settle(mut self, change: StatusChange) -> Self {
+        self.order_change = Some(change);
+        self
+    }
+
+    fn failing(mut self, reason: String) -> Self {
+        self.failure_reason = Some(reason);
+        self
+    }
+}
+
+fn failure(what: &str, reason: Option<&str>) -> String {
+    match reason {
+        Some(reason) => format!("{}: {}", what, reason),
+        None => what.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn lines() -> Vec<OrderLineInput> {
+        vec![
+            OrderLineInput {
+                product_id: Uuid::new_v4(),
+                quantity: 2,
+                unit_price: BigDecimal::from_str("9.99").expect("decimal"),
+            },
+            OrderLineInput {
+                product_id: Uuid::new_v4(),
+                quantity: 1,
+                unit_price: BigDecimal::from_str("5.00").expect("decimal"),
+            },
+        ]
+    }
+
+    fn started(now: DateTime<Utc>) -> OrderSaga {
+        OrderSaga::start(
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            &lines(),
+            &SagaTimeouts::default(),
+            now,
+        )
+        .saga
+    }
+
+    /// `saga` after `transition` was persisted.
+    fn apply(saga: &OrderSaga, transition: SagaTransition) -> OrderSaga {
+        OrderSaga {
+            state: transition.to,
+            version: saga.version + 1,
+            deadline: transition.deadline,
+            failure_reason: transition.failure_reason,
+            ..saga.clone()
+        }
+    }
+
+    fn reply(saga: &OrderSaga, reply: SagaReply, now: DateTime<Utc>) -> SagaTransition {
+        saga.decide(SagaInput::Reply(&reply), &SagaTimeouts::default(), now)
+            .expect("reply applies")
+    }
+
+    fn timeout(saga: &OrderSaga) -> SagaTransition {
+        let later = saga.deadline.expect("deadline");
+        saga.decide(SagaInput::Timeout, &SagaTimeouts::default(), later)
+            .expect("timeout applies")
+    }
+
+    fn command_types(transition: &SagaTransition) -> Vec<&'static str> {
+        transition.commands.iter().map(|c| c.command_type).collect()
+    }
+
+    #[test]
+    fn state_round_trips_through_its_string_form() {
+        for state in [
+            SagaState::ReservingInventory,
+            SagaState::AuthorizingPayment,
+            SagaState::Compensating,
+            SagaState::Completed,
+            SagaState::Failed,
+        ] {
+            assert_eq!(SagaState::parse(state.as_str()), Some(state));
+        }
+        assert_eq!(SagaState::parse("PAUSED"), None);
+    }
+
+    #[test]
+    fn start_reserves_the_order_lines_and_totals_the_amount() {
+        let now = Utc::now();
+        let start = OrderSaga::start(
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            &lines(),
+            &SagaTimeouts::default(),
+            now,
+        );
+        assert_eq!(start.saga.state, SagaState::ReservingInventory);
+        assert_eq!(start.saga.amount.to_string(), "24.98");
+        assert_eq!(
+            start.saga.deadline,
+            Some(now + chrono::Duration::seconds(30))
+        );
+        assert_eq!(start.command.command_type, RESERVE_INVENTORY);
+        assert_eq!(start.command.payload["lines"][0]["quantity"], 2);
+    }
+
+    #[test]
+    fn successful_replies_confirm_the_order() {
+        let now = Utc::now();
+        let saga = started(now);
+
+        let reserved = reply(&saga, SagaReply::InventoryReserved, now);
+        assert_eq!(reserved.to, SagaState::AuthorizingPayment);
+        assert_eq!(command_types(&reserved), [AUTHORIZE_PAYMENT]);
+        assert_eq!(reserved.commands[0].payload["amount"], "24.98");
+        let saga = apply(&saga, reserved);
+
+        let authorized = reply(&saga, SagaReply::PaymentAuthorized, now);
+        assert_eq!(authorized.to, SagaState::Completed);
+        assert_eq!(authorized.deadline, None);
+        assert!(authorized.commands.is_empty());
+        let change = authorized.order_change.expect("order confirmed");
+        assert_eq!(change.to, OrderStatus::Confirmed);
+        assert_eq!(change.event_type, ORDER_CONFIRMED);
+        assert_eq!(
+            authorized.steps.last().map(|s| s.step),
+            Some(SagaStep::Confirm)
+        );
+    }
+
+    #[test]
+    fn failed_reservation_cancels_without_compensation() {
+        let now = Utc::now();
+        let saga = started(now);
+
+        let failed = reply(
+            &saga,
+            SagaReply::InventoryReservationFailed {
+                reason: Some("out of stock".to_string()),
+            },
+            now,
+        );
+        assert_eq!(failed.to, SagaState::Failed);
+        assert!(failed.commands.is_empty());
+        assert_eq!(
+            failed.failure_reason.as_deref(),
+            Some("inventory reservation failed: out of stock")
+        );
+        let change = failed.order_change.expect("order cancelled");
+        assert_eq!(change.to, OrderStatus::Cancelled);
+        assert_eq!(
+            change.payload["reason"],
+            "inventory reservation failed: out of stock"
+        );
+    }
+
+    #[test]
+    fn failed_authorization_releases_the_inventory_then_cancels() {
+        let now = Utc::now();
+        let saga = started(now);
+        let saga = apply(&saga, reply(&saga, SagaReply::InventoryReserved, now));
+
+        let failed = reply(
+            &saga,
+            SagaReply::PaymentAuthorizationFailed { reason: None },
+            now,
+        );
+        assert_eq!(failed.to, SagaState::Compensating);
+        assert_eq!(command_types(&failed), [RELEASE_INVENTORY]);
+        assert!(failed.order_change.is_none());
+        let saga = apply(&saga, failed);
+
+        let released = reply(&saga, SagaReply::InventoryReleased, now);
+        assert_eq!(released.to, SagaState::Failed);
+        assert_eq!(
+            released.failure_reason.as_deref(),
+            Some("payment authorization failed")
+        );
+        assert_eq!(
+            released.order_change.map(|c| c.to),
+            Some(OrderStatus::Cancelled)
+        );
+    }
+
+    #[test]
+    fn timeouts_apply_only_once_the_deadline_has_passed() {
+        let now = Utc::now();
+        let saga = started(now);
+        assert!(saga
+            .decide(SagaInput::Timeout, &SagaTimeouts::default(), now)
+            .is_none());
+
+        let timed_out = timeout(&saga);
+        assert_eq!(timed_out.to, SagaState::Compensating);
+        assert_eq!(command_types(&timed_out), [RELEASE_INVENTORY]);
+        assert_eq!(
+            timed_out.steps[0],
+            SagaStepRecord::new(SagaStep::ReserveInventory, StepOutcome::TimedOut)
+        );
+    }
+
+    #[test]
+    fn authorization_timeout_voids_the_payment_and_releases_the_inventory() {
+        let now = Utc::now();
+        let saga = started(now);
+        let saga = apply(&saga, reply(&saga, SagaReply::InventoryReserved, now));
+
+        let timed_out = timeout(&saga);
+        assert_eq!(timed_out.to, SagaState::Compensating);
+        assert_eq!(
+            command_types(&timed_out),
+            [VOID_PAYMENT_AUTHORIZATION, RELEASE_INVENTORY]
+        );
+    }
+
+    #[test]
+    fn compensation_is_retried_until_acknowledged() {
+        let now = Utc::now();
+        let saga = started(now);
+        let saga = apply(&saga, timeout(&saga));
+
+        let retry = timeout(&saga);
+        assert_eq!(retry.to, SagaState::Compensating);
+        assert_eq!(command_types(&retry), [RELEASE_INVENTORY]);
+        assert_eq!(
+            retry.failure_reason.as_deref(),
+            Some("inventory reservation timed out")
+        );
+        assert!(retry.deadline > saga.deadline);
+    }
+
+    #[test]
+    fn late_and_repeated_replies_are_ignored() {
+        let now = Utc::now();
+        let saga = started(now);
+        let saga = apply(&saga, timeout(&saga));
+        let timeouts = SagaTimeouts::default();
+
+        // The reservation arrived after the timeout: it is being released anyway.
+        assert!(saga
+            .decide(
+                SagaInput::Reply(&SagaReply::InventoryReserved),
+                &timeouts,
+                now
+            )
+            .is_none());
+        let saga = apply(&saga, reply(&saga, SagaReply::InventoryReleased, now));
+        assert_eq!(saga.state, SagaState::Failed);
+        assert!(saga
+            .decide(
+                SagaInput::Reply(&SagaReply::InventoryReleased),
+                &timeouts,
+                now
+            )
+            .is_none());
+        assert!(saga
+            .decide(
+                SagaInput::Timeout,
+                &timeouts,
+                now + chrono::Duration::days(1)
+            )
+            .is_none());
+    }
+}
@@ -0,0 +1,324 @@
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use url::{Host, Url};
+use uuid::Uuid;
+
+use super::errors::DomainError;
+
+/// Longest accepted subscription URL.
+const MAX_URL_LENGTH: usize = 2048;
+
+/// A partner endpoint notified of order events. The signing secret is only
+/// handed out when the subscription is created; it is derived from the
+/// subscription id with the [`WebhookSecretKey`], not stored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebhookSubscription {
+    pub id: Uuid,
+    pub url: String,
+    /// Event types delivered to the endpoint; empty means all of them.
+    pub event_types: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl WebhookSubscription {
+    pub fn wants(&self, event_type: &str) -> bool {
+        self.event_types.is_empty() || self.event_types.iter().any(|t| t == event_type)
+    }
+}
+
+/// What a caller may set on a subscription.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebhookSubscriptionInput {
+    pub url: String,
+    pub event_types: Vec<String>,
+}
+
+impl WebhookSubscriptionInput {
+    /// Trimmed and deduplicated copy, or why the input is unusable.
+    pub fn normalized(&self) -> Result<Self, DomainError> {
+        let url = self.url.trim();
+        let has_host = url
+            .strip_prefix("https://")
+            .or_else(|| url.strip_prefix("http://"))
+            .is_some_and(|rest| !rest.is_empty() && !rest.starts_with('/'));
+        if !has_host {
+            return Err(DomainError::InvalidInput(
+                "url must be an absolute http(s) URL".to_string(),
+            ));
+        }
+        if url.len() > MAX_URL_LENGTH {
+            return Err(DomainError::InvalidInput(format!(
+                "url must be at most {} characters",
+                MAX_URL_LENGTH
+            )));
+        }
+
+        let mut event_types: Vec<String> = Vec::with_capacity(self.event_types.len());
+        for event_type in &self.event_types {
+            let event_type = event_type.trim();
+            if event_type.is_empty() {
+                return Err(DomainError::InvalidInput(
+                    "event_types must not contain empty entries".to_string(),
+                ));
+            }
+            if !event_types.iter().any(|t| t == event_type) {
+                event_types.push(event_type.to_string());
+            }
+        }
+        Ok(Self {
+            url: url.to_string(),
+            event_types,
+        })
+    }
+}
+
+/// Host and port a webhook URL points to; the host of an IP URL is the bare
+/// address.
+pub fn destination(url: &str) -> Result<(String, u16), DomainError> {
+    let invalid = || DomainError::InvalidInput("url must be an absolute http(s) URL".to_string());
+    let url = Url::parse(url.trim()).map_err(|_| invalid())?;
+    let host = match url.host().ok_or_else(invalid)? {
+        Host::Domain(domain) => domain.to_string(),
+        Host::Ipv4(ip) => ip.to_string(),
+        Host::Ipv6(ip) => ip.to_string(),
+    };
+    Ok((host, url.port_or_known_default().ok_or_else(invalid)?))
+}
+
+/// Whether webhooks may be sent to `ip`: `false` for loopback, private,
+/// link-local (including cloud metadata endpoints), shared, reserved and
+/// other addresses that do not belong to the public internet.
+pub fn is_public_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(mapped) => is_public_ipv4(mapped),
+            None => is_public_ipv6(ip),
+        },
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        || a == 0
+        // Shared address space (carrier-grade NAT).
+        || (a == 100 && (64..128).contains(&b))
+        // IETF protocol assignments.
+        || (a == 192 && b == 0 && c == 0)
+        // Benchmarking.
+        || (a == 198 && (18..20).contains(&b))
+        // Reserved for future use.
+        || a >= 240)
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    let segments = ip.segments();
+    // NAT64 addresses carry an IPv4 address in their last 32 bits.
+    if segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0] {
+        let [.., hi, lo] = segments;
+        return is_public_ipv4(Ipv4Addr::from((u32::from(hi) << 16) | u32::from(lo)));
+    }
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // Unique local.
+        || (segments[0] & 0xfe00) == 0xfc00
+        // Link-local, and the deprecated site-local.
+        || (segments[0] & 0xffc0) == 0xfe80
+        || (segments[0] & 0xffc0) == 0xfec0
+        // Documentation.
+        || (segments[0] == 0x2001 && segments[1] == 0x0db8))
+}
+
+/// Key the signing secrets of subscriptions are derived from
+/// (`WEBHOOK_SECRET_KEY`), so that the secrets themselves are never stored.
+#[derive(Clone, PartialEq, Eq)]
+pub struct WebhookSecretKey(Vec<u8>);
+
+impl WebhookSecretKey {
+    pub fn new(key: impl Into<Vec<u8>>) -> Self {
+        Self(key.into())
+    }
+
+    /// Signing secret of subscription `id`: the hex HMAC-SHA256 of its id.
+    pub fn secret_for(&self, id: Uuid) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.0).expect("HMAC accepts any key length");
+        mac.update(id.as_bytes());
+        hex::encode(mac.finalize().into_bytes())
+    }
+}
+
+impl fmt::Debug for WebhookSecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("WebhookSecretKey(..)")
+    }
+}
+
+/// Lifecycle of a delivery, stored as its `as_str` form.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryStatus {
+    /// Not delivered yet; attempted again at `next_attempt_at`.
+    Pending,
+    Delivered,
+    /// Gave up after too many failed attempts (the dead-letter list).
+    Dead,
+}
+
+impl DeliveryStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DeliveryStatus::Pending => "PENDING",
+            DeliveryStatus::Delivered => "DELIVERED",
+            DeliveryStatus::Dead => "DEAD",
+        }
+    }
+}
+
+/// One event sent (or to be sent) to one subscription.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WebhookDelivery {
+    pub id: i64,
+    pub subscription_id: Uuid,
+    /// Id of the outbox event, also sent in the `X-Webhook-Id` header.
+    pub event_id: Uuid,
+    pub event_type: String,
+    pub status: String,
+    pub attempts: i32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(url: &str, event_types: &[&str]) -> WebhookSubscriptionInput {
+        WebhookSubscriptionInput {
+            url: url.to_string(),
+            event_types: event_types.iter().map(|t| t.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn input_is_trimmed_and_deduplicated() {
+        let normalized = input(
+            " https://partner.example/hooks ",
+            &["OrderPaid", " OrderPaid"],
+        )
+        .normalized()
+        .expect("valid");
+        assert_eq!(
+            normalized,
+            input("https://partner.example/hooks", &["OrderPaid"])
+        );
+    }
+
+    #[test]
+    fn only_absolute_http_urls_are_accepted() {
+        for url in [
+            "ftp://partner.example",
+            "partner.example/hooks",
+            "https://",
+            "http:///x",
+        ] {
+            assert!(
+                matches!(
+                    input(url, &[]).normalized(),
+                    Err(DomainError::InvalidInput(_))
+                ),
+                "{url} should be rejected"
+            );
+        }
+        assert!(input("http://localhost:8081/hook", &[])
+            .normalized()
+            .is_ok());
+    }
+
+    #[test]
+    fn destination_is_the_bare_host_and_effective_port() {
+        let destination = |url: &str| destination(url).expect("destination");
+        assert_eq!(
+            destination("https://partner.example/hooks"),
+            ("partner.example".to_string(), 443)
+        );
+        assert_eq!(
+            destination("http://user@[::1]:8081/hook"),
+            ("::1".to_string(), 8081)
+        );
+    }
+
+    #[test]
+    fn internal_addresses_are_not_public() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fd00:ec2::254",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "64:ff9b::a9fe:a9fe",
+        ] {
+            let ip: IpAddr = ip.parse().expect("ip");
+            assert!(!is_public_address(ip), "{ip} should be internal");
+        }
+        for ip in ["93.184.216.34", "2606:2800:220:1:248:1893:25c8:1946"] {
+            let ip: IpAddr = ip.parse().expect("ip");
+            assert!(is_public_address(ip), "{ip} should be public");
+        }
+    }
+
+    #[test]
+    fn secrets_are_derived_per_subscription_and_key() {
+        let key = WebhookSecretKey::new(*b"0123456789abcdef0123456789abcdef");
+        let id = Uuid::new_v4();
+        assert_eq!(key.secret_for(id).len(), 64);
+        assert_eq!(key.secret_for(id), key.secret_for(id));
+        assert_ne!(key.secret_for(id), key.secret_for(Uuid::new_v4()));
+        assert_ne!(
+            key.secret_for(id),
+            WebhookSecretKey::new(*b"another key, another secret.....").secret_for(id)
+        );
+        assert_eq!(format!("{key:?}"), "WebhookSecretKey(..)");
+    }
+
+    #[test]
+    fn empty_event_types_are_rejected() {
+        let result = input("https://partner.example", &["OrderPaid", " "]).normalized();
+        assert!(matches!(result, Err(DomainError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn subscription_without_filters_wants_every_event() {
+        let mut subscription = WebhookSubscription {
+            id: Uuid::new_v4(),
+            url: "https://partner.example".to_string(),
+            event_types: vec![],
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        };
+        assert!(subscription.wants("OrderCreated"));
+
+        subscription.event_types = vec!["OrderPaid".to_string()];
+        assert!(subscription.wants("OrderPaid"));
+        assert!(!subscription.wants("OrderCreated"));
+    }
+}
@@ -11,6 +11,15 @@ pub enum AppError {
     #[error("Bad request: {0}")]
     BadRequest(String),
 
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
+    #[error("Too many requests: retry after {retry_after_secs}s")]
+    TooManyRequests { retry_after_secs: u64 },
+
     #[error("Internal error: {0}")]
     Internal(String),
 }
@@ -20,6 +29,7 @@ impl From<DomainError> for AppError {
         match e {
             DomainError::NotFound => AppError::NotFound,
             DomainError::InvalidInput(msg) => AppError::BadRequest(msg),
+            DomainError::Forbidden(msg) => AppError::Forbidden(msg),
             DomainError::Internal(msg) => AppError::Internal(msg),
         }
     }
@@ -34,6 +44,19 @@ impl actix_web::ResponseError for AppError {
             AppError::BadRequest(_) => HttpResponse::BadRequest().json(serde_json::json!({
                 "error": self.to_string()
             })),
+            AppError::Unauthorized(_) => HttpResponse::Unauthorized()
+                .insert_header((actix_web::http::header::WWW_AUTHENTICATE, "Bearer"))
+                .json(serde_json::json!({
+                    "error": self.to_string()
+                })),
+            AppError::Forbidden(_) => HttpResponse::Forbidden().json(serde_json::json!({
+                "error": self.to_string()
+            })),
+            AppError::TooManyRequests { retry_after_secs } => HttpResponse::TooManyRequests()
+                .insert_header((actix_web::http::header::RETRY_AFTER, *retry_after_secs))
+                .json(serde_json::json!({
+                    "error": self.to_string()
+                })),
             AppError::Internal(_) => HttpResponse::InternalServerError().json(serde_json::json!({
                 "error": "Internal server error"
             })),
@@ -92,6 +115,12 @@ mod tests {
         assert!(matches!(app_err, AppError::BadRequest(_)));
     }
 
+    #[test]
+    fn domain_forbidden_maps_to_app_forbidden() {
+        let app_err: AppError = DomainError::Forbidden("not yours".to_string()).into();
+        assert!(matches!(app_err, AppError::Forbidden(_)));
+    }
+
     #[test]
     fn bad_request_returns_400() {
         let err = AppError::BadRequest("invalid field".to_string());
@@ -101,6 +130,45 @@ mod tests {
         );
     }
 
+    #[test]
+    fn unauthorized_returns_401_with_bearer_challenge() {
+        let resp = AppError::Unauthorized("missing bearer token".to_string()).error_response();
+        assert_eq!(resp.status(), actix_web::http::StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            resp.headers()
+                .get(actix_web::http::header::WWW_AUTHENTICATE)
+                .and_then(|v| v.to_str().ok()),
+            Some("Bearer")
+        );
+    }
+
+    #[test]
+    fn forbidden_returns_403() {
+        let err = AppError::Forbidden("missing scope 'orders:write'".to_string());
+        assert_eq!(
+            err.error_response().status(),
+            actix_web::http::StatusCode::FORBIDDEN
+        );
+    }
+
+    #[test]
+    fn too_many_requests_returns_429_with_retry_after() {
+        let resp = AppError::TooManyRequests {
+            retry_after_secs: 3,
+        }
+        .error_response();
+        assert_eq!(
+            resp.status(),
+            actix_web::http::StatusCode::TOO_MANY_REQUESTS
+        );
+        assert_eq!(
+            resp.headers()
+                .get(actix_web::http::header::RETRY_AFTER)
+                .and_then(|v| v.to_str().ok()),
+            Some("3")
+        );
+    }
+
     #[test]
     fn bad_request_display() {
         assert_eq!(
@@ -0,0 +1,139 @@
+//! Gap and duplicate detection for consumers of the outbox events.
+//!
+//! Every event carries a per-aggregate `sequence` (1, 2, 3, …) in its Kafka
+//! envelope. Feed the sequences of one partition to a [`SequenceTracker`] to
+//! find out whether an event is the next one, a redelivery, or comes after
+//! missing events.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SequenceCheck {
+    /// The event directly follows the last one seen for its aggregate.
+    InOrder,
+    /// The event was already seen (redelivery or outbox replay); skip it.
+    Duplicate { last_seen: i64 },
+    /// Events `expected..received` were missed or are arriving out of order.
+    Gap { expected: i64, received: i64 },
+}
+
+/// Tracks the last sequence seen per aggregate.
+///
+/// Aggregates start at sequence 1; a consumer that does not read from the
+/// beginning of the topic should [`resume`](Self::resume) each aggregate from
+/// its stored position first.
+#[derive(Debug, Clone)]
+pub struct SequenceTracker<K> {
+    last_seen: HashMap<K, i64>,
+}
+
+impl<K: Eq + Hash> SequenceTracker<K> {
+    pub fn new() -> Self {
+        Self {
+            last_seen: HashMap::new(),
+        }
+    }
+
+    /// Declare that every event of `aggregate` up to `sequence` was processed.
+    pub fn resume(&mut self, aggregate: K, sequence: i64) {
+        self.last_seen.insert(aggregate, sequence);
+    }
+
+    /// Last sequence recorded for `aggregate`, if any.
+    pub fn last_seen(&self, aggregate: &K) -> Option<i64> {
+        self.last_seen.get(aggregate).copied()
+    }
+
+    /// Classify an incoming event and record it unless it is a duplicate.
+    ///
+    /// After a [`Gap`](SequenceCheck::Gap) the tracker moves on to `sequence`,
+    /// so that a late event from the gap is then reported as a duplicate.
+    pub fn observe(&mut self, aggregate: K, sequence: i64) -> SequenceCheck {
+        let last_seen = self.last_seen.get(&aggregate).copied().unwrap_or(0);
+        let expected = last_seen + 1;
+        if sequence <= last_seen {
+            return SequenceCheck::Duplicate { last_seen };
+        }
+        self.last_seen.insert(aggregate, sequence);
+        if sequence == expected {
+            SequenceCheck::InOrder
+        } else {
+            SequenceCheck::Gap {
+                expected,
+                received: sequence,
+            }
+        }
+    }
+}
+
+impl<K: Eq + Hash> Default for SequenceTracker<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn consecutive_sequences_are_in_order() {
+        let mut tracker = SequenceTracker::new();
+        assert_eq!(tracker.observe("a", 1), SequenceCheck::InOrder);
+        assert_eq!(tracker.observe("a", 2), SequenceCheck::InOrder);
+        assert_eq!(tracker.observe("b", 1), SequenceCheck::InOrder);
+        assert_eq!(tracker.last_seen(&"a"), Some(2));
+    }
+
+    #[test]
+    fn redelivered_events_are_duplicates() {
+        let mut tracker = SequenceTracker::new();
+        tracker.observe("a", 1);
+        tracker.observe("a", 2);
+        assert_eq!(
+            tracker.observe("a", 2),
+            SequenceCheck::Duplicate { last_seen: 2 }
+        );
+        assert_eq!(
+            tracker.observe("a", 1),
+            SequenceCheck::Duplicate { last_seen: 2 }
+        );
+        assert_eq!(tracker.last_seen(&"a"), Some(2));
+    }
+
+    #[test]
+    fn skipped_sequences_are_gaps() {
+        let mut tracker = SequenceTracker::new();
+        tracker.observe("a", 1);
+        assert_eq!(
+            tracker.observe("a", 4),
+            SequenceCheck::Gap {
+                expected: 2,
+                received: 4
+            }
+        );
+        assert_eq!(tracker.observe("a", 5), SequenceCheck::InOrder);
+        // A straggler from the gap arrives late.
+        assert_eq!(
+            tracker.observe("a", 3),
+            SequenceCheck::Duplicate { last_seen: 5 }
+        );
+    }
+
+    #[test]
+    fn first_event_other_than_one_is_a_gap_unless_resumed() {
+        let mut tracker = SequenceTracker::new();
+        assert_eq!(
+            tracker.observe("a", 3),
+            SequenceCheck::Gap {
+                expected: 1,
+                received: 3
+            }
+        );
+
+        let mut resumed = SequenceTracker::new();
+        resumed.resume("a", 2);
+        assert_eq!(resumed.observe("a", 3), SequenceCheck::InOrder);
+    }
+}
@@ -0,0 +1,37 @@
+use actix_web::{web, HttpResponse};
+
+use crate::asyncapi;
+use crate::publishers::EventRoute;
+
+/// AsyncAPI document of the events published from the outbox, on the
+/// channels of the configured route (`OUTBOX_ROUTE_REPLACEMENT`), next to the
+/// OpenAPI one.
+pub async fn asyncapi_document(route: web::Data<EventRoute>) -> HttpResponse {
+    HttpResponse::Ok().json(asyncapi::document(&route))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+    use actix_web::{test as actix_test, App};
+    use serde_json::Value;
+
+    #[actix_web::test]
+    async fn serves_the_asyncapi_document_of_the_configured_route() {
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(EventRoute::new("outbox.${routedByValue}")))
+                .route("/api-docs/asyncapi.json", web::get().to(asyncapi_document)),
+        )
+        .await;
+        let req = actix_test::TestRequest::get()
+            .uri("/api-docs/asyncapi.json")
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: Value = actix_test::read_body_json(resp).await;
+        assert_eq!(body["asyncapi"], asyncapi::ASYNCAPI_VERSION);
+        assert_eq!(body["channels"]["outbox.Order"]["address"], "outbox.Order");
+    }
+}
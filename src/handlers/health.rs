@@ -0,0 +1,143 @@
+use actix_web::{web, HttpResponse};
+use serde_json::json;
+
+use crate::shutdown::Readiness;
+
+/// Liveness probe: the process is up and serving HTTP.
+#[utoipa::path(
+    get,
+    path = "/health",
+    tag = "health",
+    responses(
+        (status = 200, description = "The service is alive"),
+    )
+)]
+pub async fn health() -> HttpResponse {
+    HttpResponse::Ok().json(json!({ "status": "ok" }))
+}
+
+/// Readiness probe: fails as soon as shutdown starts so traffic drains away,
+/// and while a required readiness check is unhealthy. Other checks (e.g.
+/// `replication_slot`, unless configured as required) are only shown.
+#[utoipa::path(
+    get,
+    path = "/readiness",
+    tag = "health",
+    responses(
+        (status = 200, description = "The service accepts traffic"),
+        (status = 503, description = "The service is shutting down or a required check failed"),
+    )
+)]
+pub async fn readiness(readiness: web::Data<Readiness>) -> HttpResponse {
+    if !readiness.is_ready() {
+        return HttpResponse::ServiceUnavailable().json(json!({ "status": "shutting down" }));
+    }
+    let checks = readiness.checks();
+    if checks.is_empty() {
+        return HttpResponse::Ok().json(json!({ "status": "ready" }));
+    }
+    if checks
+        .values()
+        .all(|check| check.healthy || !check.required)
+    {
+        HttpResponse::Ok().json(json!({ "status": "ready", "checks": checks }))
+    } else {
+        HttpResponse::ServiceUnavailable().json(json!({ "status": "not ready", "checks": checks }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shutdown::ReadinessCheck;
+    use actix_web::http::StatusCode;
+    use actix_web::{test as actix_test, App};
+
+    #[actix_web::test]
+    async fn readiness_follows_the_flag() {
+        let flag = Readiness::new();
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(flag.clone()))
+                .route("/health", web::get().to(health))
+                .route("/readiness", web::get().to(readiness)),
+        )
+        .await;
+
+        let get = |uri: &str| actix_test::TestRequest::get().uri(uri).to_request();
+        assert_eq!(
+            actix_test::call_service(&app, get("/readiness"))
+                .await
+                .status(),
+            StatusCode::OK
+        );
+
+        flag.set_not_ready();
+        assert_eq!(
+            actix_test::call_service(&app, get("/readiness"))
+                .await
+                .status(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(
+            actix_test::call_service(&app, get("/health"))
+                .await
+                .status(),
+            StatusCode::OK
+        );
+    }
+
+    #[actix_web::test]
+    async fn readiness_reports_checks() {
+        let flag = Readiness::new();
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(flag.clone()))
+                .route("/readiness", web::get().to(readiness)),
+        )
+        .await;
+        let get = || {
+            actix_test::TestRequest::get()
+                .uri("/readiness")
+                .to_request()
+        };
+
+        let check = |healthy: bool, required: bool| ReadinessCheck {
+            healthy,
+            required,
+            details: json!({ "active": healthy }),
+        };
+
+        flag.report_check("replication_slot", check(true, true));
+        let response = actix_test::call_service(&app, get()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = actix_test::read_body_json(response).await;
+        assert_eq!(
+            body,
+            json!({
+                "status": "ready",
+                "checks": {
+                    "replication_slot": {
+                        "healthy": true,
+                        "required": true,
+                        "details": { "active": true }
+                    }
+                }
+            })
+        );
+
+        // Shown, but only a required check holds readiness back.
+        flag.report_check("replication_slot", check(false, false));
+        let response = actix_test::call_service(&app, get()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = actix_test::read_body_json(response).await;
+        assert_eq!(body["checks"]["replication_slot"]["healthy"], false);
+
+        flag.report_check("replication_slot", check(false, true));
+        let response = actix_test::call_service(&app, get()).await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body: serde_json::Value = actix_test::read_body_json(response).await;
+        assert_eq!(body["status"], "not ready");
+        assert_eq!(body["checks"]["replication_slot"]["healthy"], false);
+    }
+}
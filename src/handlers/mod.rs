@@ -1 +1,5 @@
+pub mod docs;
+pub mod health;
 pub mod orders;
+pub mod outbox;
+pub mod webhooks;
@@ -1,4 +1,5 @@
-use actix_web::{web, HttpResponse};
+use actix_web::http::header;
+use actix_web::{web, HttpRequest, HttpResponse};
 use bigdecimal::BigDecimal;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
@@ -7,9 +8,12 @@ use utoipa::ToSchema;
 use uuid::Uuid;
 
 use crate::application::order_service::OrderService;
-use crate::domain::order::OrderLineInput;
-use crate::domain::ports::OrderRepository;
+use crate::application::saga_service::SagaService;
+use crate::domain::caller::Caller;
+use crate::domain::order::{OrderEventFilter, OrderLineInput};
+use crate::domain::ports::{OrderRepository, SagaRepository, UnitOfWork};
 use crate::errors::AppError;
+use crate::order_stream::OrderEventStreams;
 
 // ── Request / response DTOs ──────────────────────────────────────────────────
 
@@ -49,6 +53,47 @@ pub struct OrderResponse {
     pub lines: Vec<OrderLineResponse>,
 }
 
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OrderEventResponse {
+    pub id: Uuid,
+    pub event_type: String,
+    pub sequence: i64,
+    pub occurred_at: String,
+    #[schema(value_type = Object)]
+    pub payload: serde_json::Value,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OrderHistoryResponse {
+    pub order_id: Uuid,
+    /// `false` when older events have been cleaned up from the outbox.
+    pub complete: bool,
+    pub events: Vec<OrderEventResponse>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SagaStepResponse {
+    pub step: String,
+    /// `STARTED`, `SUCCEEDED`, `FAILED` or `TIMED_OUT`.
+    pub outcome: String,
+    pub detail: Option<String>,
+    pub recorded_at: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SagaResponse {
+    pub order_id: Uuid,
+    pub state: String,
+    /// When the awaited step times out; absent once the saga has ended.
+    pub step_deadline: Option<String>,
+    /// The deadline has passed but the timeout has not been handled yet.
+    pub overdue: bool,
+    pub failure_reason: Option<String>,
+    pub started_at: String,
+    pub updated_at: String,
+    pub steps: Vec<SagaStepResponse>,
+}
+
 // ── Pagination ───────────────────────────────────────────────────────────────
 
 #[derive(Debug, Deserialize, ToSchema)]
@@ -69,6 +114,26 @@ fn default_limit() -> i64 {
     20
 }
 
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct StreamOrderEventsParams {
+    /// Comma-separated event types, e.g. `OrderPaid,OrderCancelled`.
+    #[serde(default)]
+    pub event_types: Option<String>,
+}
+
+impl StreamOrderEventsParams {
+    /// The requested event types; empty for all of them.
+    pub fn event_types(self) -> Vec<String> {
+        self.event_types
+            .iter()
+            .flat_map(|types| types.split(','))
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+}
+
 impl ListOrdersParams {
     /// Returns `(page, limit)` after clamping inputs to valid ranges.
     pub fn into_query_params(self) -> (i64, i64) {
@@ -100,12 +165,17 @@ pub struct ListOrdersResponse {
     request_body = CreateOrderRequest,
     responses(
         (status = 201, description = "Order created successfully", body = CreateOrderResponse),
+        (status = 401, description = "Missing or invalid bearer token"),
+        (status = 403, description = "Token lacks the orders:write scope or orders for another customer"),
+        (status = 429, description = "Write rate limit exceeded; see Retry-After"),
         (status = 500, description = "Internal server error"),
     ),
+    security(("bearer_auth" = ["orders:write"])),
     tag = "orders"
 )]
-pub async fn create_order<R: OrderRepository>(
-    service: web::Data<OrderService<R>>,
+pub async fn create_order<R: OrderRepository, U: UnitOfWork>(
+    service: web::Data<OrderService<R, U>>,
+    caller: Caller,
     body: web::Json<CreateOrderRequest>,
 ) -> Result<HttpResponse, AppError> {
     let body = body.into_inner();
@@ -130,7 +200,7 @@ pub async fn create_order<R: OrderRepository>(
     let lines = lines?;
 
     let svc = service.clone();
-    let id = web::block(move || svc.create_order(customer_id, lines))
+    let id = web::block(move || svc.create_order(&caller, customer_id, lines))
         .await
         .map_err(|e| AppError::Internal(e.to_string()))?
         .map_err(AppError::from)?;
@@ -140,7 +210,8 @@ pub async fn create_order<R: OrderRepository>(
 
 /// GET /orders/{id}
 ///
-/// Returns the order together with its order lines.
+/// Returns the order together with its order lines. Customers only see their
+/// own orders; anybody else's order is reported as not found.
 #[utoipa::path(
     get,
     path = "/orders/{id}",
@@ -149,19 +220,24 @@ pub async fn create_order<R: OrderRepository>(
     ),
     responses(
         (status = 200, description = "Order found", body = OrderResponse),
+        (status = 401, description = "Missing or invalid bearer token"),
+        (status = 403, description = "Token lacks the orders:read scope"),
         (status = 404, description = "Order not found"),
+        (status = 429, description = "Read rate limit exceeded; see Retry-After"),
         (status = 500, description = "Internal server error"),
     ),
+    security(("bearer_auth" = ["orders:read"])),
     tag = "orders"
 )]
-pub async fn get_order<R: OrderRepository>(
-    service: web::Data<OrderService<R>>,
+pub async fn get_order<R: OrderRepository, U: UnitOfWork>(
+    service: web::Data<OrderService<R, U>>,
+    caller: Caller,
     path: web::Path<Uuid>,
 ) -> Result<HttpResponse, AppError> {
     let order_id = path.into_inner();
 
     let svc = service.clone();
-    let result = web::block(move || svc.get_order(order_id))
+    let result = web::block(move || svc.get_order(&caller, order_id))
         .await
         .map_err(|e| AppError::Internal(e.to_string()))?
         .map_err(AppError::from)?;
@@ -190,10 +266,241 @@ pub async fn get_order<R: OrderRepository>(
     }
 }
 
+/// GET /orders/{id}/events
+///
+/// Returns the order's domain events, oldest first, as recorded in the
+/// outbox. `complete` is `false` when the oldest events are no longer
+/// retained. Visibility follows `GET /orders/{id}`.
+#[utoipa::path(
+    get,
+    path = "/orders/{id}/events",
+    params(
+        ("id" = Uuid, Path, description = "Order UUID"),
+    ),
+    responses(
+        (status = 200, description = "Order history", body = OrderHistoryResponse),
+        (status = 401, description = "Missing or invalid bearer token"),
+        (status = 403, description = "Token lacks the orders:read scope"),
+        (status = 404, description = "Order not found"),
+        (status = 429, description = "Read rate limit exceeded; see Retry-After"),
+        (status = 500, description = "Internal server error"),
+    ),
+    security(("bearer_auth" = ["orders:read"])),
+    tag = "orders"
+)]
+pub async fn get_order_events<R: OrderRepository, U: UnitOfWork>(
+    service: web::Data<OrderService<R, U>>,
+    caller: Caller,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let order_id = path.into_inner();
+
+    let svc = service.clone();
+    let history = web::block(move || svc.order_history(&caller, order_id))
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?
+        .map_err(AppError::from)?
+        .ok_or(AppError::NotFound)?;
+
+    Ok(HttpResponse::Ok().json(OrderHistoryResponse {
+        order_id,
+        complete: history.complete,
+        events: history
+            .events
+            .into_iter()
+            .map(|e| OrderEventResponse {
+                id: e.id,
+                event_type: e.event_type,
+                sequence: e.sequence,
+                occurred_at: e.occurred_at.to_rfc3339(),
+                payload: e.payload,
+            })
+            .collect(),
+    }))
+}
+
+/// GET /orders/{id}/stream
+///
+/// Pushes the order's events as Server-Sent Events as soon as they are
+/// committed: `id` is the outbox event id, `event` the event type and `data`
+/// the event as JSON (like an item of `GET /orders/{id}/events`, plus
+/// `order_id`). A reconnecting client sending `Last-Event-ID` first receives
+/// the events it missed. Visibility follows `GET /orders/{id}`.
+#[utoipa::path(
+    get,
+    path = "/orders/{id}/stream",
+    params(
+        ("id" = Uuid, Path, description = "Order UUID"),
+        ("Last-Event-ID" = Option<Uuid>, Header, description = "Id of the last event received; replay the events after it"),
+    ),
+    responses(
+        (status = 200, description = "Event stream", content_type = "text/event-stream", body = String),
+        (status = 400, description = "Last-Event-ID is not an event id"),
+        (status = 401, description = "Missing or invalid bearer token"),
+        (status = 403, description = "Token lacks the orders:read scope"),
+        (status = 404, description = "Order not found"),
+        (status = 429, description = "Read rate limit exceeded; see Retry-After"),
+        (status = 500, description = "Internal server error"),
+    ),
+    security(("bearer_auth" = ["orders:read"])),
+    tag = "orders"
+)]
+pub async fn stream_order_events<R: OrderRepository, U: UnitOfWork>(
+    service: web::Data<OrderService<R, U>>,
+    streams: web::Data<OrderEventStreams>,
+    caller: Caller,
+    path: web::Path<Uuid>,
+    request: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let order_id = path.into_inner();
+
+    let svc = service.clone();
+    web::block(move || svc.get_order(&caller, order_id))
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?
+        .map_err(AppError::from)?
+        .ok_or(AppError::NotFound)?;
+
+    let filter = OrderEventFilter {
+        order_id: Some(order_id),
+        event_types: vec![],
+    };
+    event_stream(&streams, filter, &request).await
+}
+
+/// GET /orders/stream
+///
+/// Pushes the events of all orders, optionally restricted to some event
+/// types, in the format of `GET /orders/{id}/stream`. Staff only.
+#[utoipa::path(
+    get,
+    path = "/orders/stream",
+    params(
+        ("event_types" = Option<String>, Query, description = "Comma-separated event types to stream (default: all)"),
+        ("Last-Event-ID" = Option<Uuid>, Header, description = "Id of the last event received; replay the events after it"),
+    ),
+    responses(
+        (status = 200, description = "Event stream", content_type = "text/event-stream", body = String),
+        (status = 400, description = "Last-Event-ID is not an event id"),
+        (status = 401, description = "Missing or invalid bearer token"),
+        (status = 403, description = "Caller is not staff"),
+        (status = 429, description = "Read rate limit exceeded; see Retry-After"),
+        (status = 500, description = "Internal server error"),
+    ),
+    security(("bearer_auth" = ["orders:read", "orders:staff"])),
+    tag = "orders"
+)]
+pub async fn stream_all_order_events(
+    streams: web::Data<OrderEventStreams>,
+    caller: Caller,
+    query: web::Query<StreamOrderEventsParams>,
+    request: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    if caller != Caller::Staff {
+        return Err(AppError::Forbidden(
+            "only staff may stream the events of all orders".to_string(),
+        ));
+    }
+    let filter = OrderEventFilter {
+        order_id: None,
+        event_types: query.into_inner().event_types(),
+    };
+    event_stream(&streams, filter, &request).await
+}
+
+async fn event_stream(
+    streams: &OrderEventStreams,
+    filter: OrderEventFilter,
+    request: &HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let last_event_id = match request.headers().get("Last-Event-ID") {
+        Some(value) => Some(
+            value
+                .to_str()
+                .ok()
+                .and_then(|id| Uuid::parse_str(id.trim()).ok())
+                .ok_or_else(|| {
+                    AppError::BadRequest("Last-Event-ID must be an event id".to_string())
+                })?,
+        ),
+        None => None,
+    };
+    let body = streams
+        .open(filter, last_event_id)
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        // Keep reverse proxies such as nginx from buffering the stream.
+        .insert_header(("X-Accel-Buffering", "no"))
+        .streaming(body))
+}
+
+/// GET /orders/{id}/saga
+///
+/// Returns the order's placement saga with its step journal, oldest first,
+/// for debugging orders stuck in `PENDING`. Visibility follows
+/// `GET /orders/{id}`; orders placed while the saga was disabled have none.
+#[utoipa::path(
+    get,
+    path = "/orders/{id}/saga",
+    params(
+        ("id" = Uuid, Path, description = "Order UUID"),
+    ),
+    responses(
+        (status = 200, description = "Saga state and journal", body = SagaResponse),
+        (status = 401, description = "Missing or invalid bearer token"),
+        (status = 403, description = "Token lacks the orders:read scope"),
+        (status = 404, description = "Order or saga not found"),
+        (status = 429, description = "Read rate limit exceeded; see Retry-After"),
+        (status = 500, description = "Internal server error"),
+    ),
+    security(("bearer_auth" = ["orders:read"])),
+    tag = "orders"
+)]
+pub async fn get_order_saga<S: SagaRepository, U: UnitOfWork>(
+    service: web::Data<SagaService<S, U>>,
+    caller: Caller,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let order_id = path.into_inner();
+
+    let svc = service.clone();
+    let view = web::block(move || svc.saga(&caller, order_id))
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?
+        .map_err(AppError::from)?
+        .ok_or(AppError::NotFound)?;
+
+    let overdue = view.saga.is_overdue(chrono::Utc::now());
+    let saga = view.saga;
+    Ok(HttpResponse::Ok().json(SagaResponse {
+        order_id,
+        state: saga.state.as_str().to_string(),
+        step_deadline: saga.deadline.map(|d| d.to_rfc3339()),
+        overdue,
+        failure_reason: saga.failure_reason,
+        started_at: saga.started_at.to_rfc3339(),
+        updated_at: saga.updated_at.to_rfc3339(),
+        steps: view
+            .steps
+            .into_iter()
+            .map(|s| SagaStepResponse {
+                step: s.step,
+                outcome: s.outcome,
+                detail: s.detail,
+                recorded_at: s.recorded_at.to_rfc3339(),
+            })
+            .collect(),
+    }))
+}
+
 /// GET /orders
 ///
 /// Returns a paginated list of orders (without their lines).
-/// Use `page` (1-based) and `limit` to control pagination.
+/// Use `page` (1-based) and `limit` to control pagination. Customers only get
+/// their own orders; staff get every order.
 #[utoipa::path(
     get,
     path = "/orders",
@@ -203,18 +510,23 @@ pub async fn get_order<R: OrderRepository>(
     ),
     responses(
         (status = 200, description = "Paginated list of orders", body = ListOrdersResponse),
+        (status = 401, description = "Missing or invalid bearer token"),
+        (status = 403, description = "Token lacks the orders:read scope"),
+        (status = 429, description = "Read rate limit exceeded; see Retry-After"),
         (status = 500, description = "Internal server error"),
     ),
+    security(("bearer_auth" = ["orders:read"])),
     tag = "orders"
 )]
-pub async fn list_orders<R: OrderRepository>(
-    service: web::Data<OrderService<R>>,
+pub async fn list_orders<R: OrderRepository, U: UnitOfWork>(
+    service: web::Data<OrderService<R, U>>,
+    caller: Caller,
     query: web::Query<ListOrdersParams>,
 ) -> Result<HttpResponse, AppError> {
     let (page, limit) = query.into_inner().into_query_params();
 
     let svc = service.clone();
-    let result = web::block(move || svc.list_orders(page, limit))
+    let result = web::block(move || svc.list_orders(&caller, page, limit))
         .await
         .map_err(|e| AppError::Internal(e.to_string()))?
         .map_err(AppError::from)?;
@@ -405,33 +717,32 @@ mod tests {
 
     // ── Handler tests (in-memory stub, no Docker) ─────────────────────────────
 
+    use actix_web::dev::ServiceResponse;
     use actix_web::{http::StatusCode, test as actix_test, App};
     use bigdecimal::BigDecimal;
-    use chrono::Utc;
+    use chrono::{DateTime, Utc};
 
+    use actix_web::body::{BoxBody, MessageBody as _};
+    use diesel::r2d2::ConnectionManager;
+
+    use crate::auth::{Authentication, Principal};
     use crate::domain::errors::DomainError;
-    use crate::domain::order::{ListResult, OrderLineView, OrderView};
+    use crate::domain::order::{ListResult, OrderEvent, OrderLineView, OrderView};
+    use crate::domain::ports::Transaction;
+    use crate::domain::saga::{OrderSaga, SagaStepEntry, SagaTimeouts};
+    use crate::infrastructure::in_memory::InMemoryUnitOfWork;
+    use crate::order_stream::OrderEventBus;
+    use crate::shutdown::Readiness;
 
     #[derive(Default)]
     struct InMemoryOrderRepo {
         find_result: Option<OrderView>,
-        create_error: Option<String>,
+        events: Vec<OrderEvent>,
         find_error: Option<String>,
         list_error: Option<String>,
     }
 
     impl OrderRepository for InMemoryOrderRepo {
-        fn create(
-            &self,
-            _customer_id: Uuid,
-            _lines: Vec<OrderLineInput>,
-        ) -> Result<Uuid, DomainError> {
-            if let Some(msg) = &self.create_error {
-                return Err(DomainError::Internal(msg.clone()));
-            }
-            Ok(Uuid::new_v4())
-        }
-
         fn find_by_id(&self, _id: Uuid) -> Result<Option<OrderView>, DomainError> {
             if let Some(msg) = &self.find_error {
                 return Err(DomainError::Internal(msg.clone()));
@@ -439,7 +750,12 @@ mod tests {
             Ok(self.find_result.clone())
         }
 
-        fn list(&self, page: i64, limit: i64) -> Result<ListResult, DomainError> {
+        fn list(
+            &self,
+            page: i64,
+            limit: i64,
+            _customer_id: Option<Uuid>,
+        ) -> Result<ListResult, DomainError> {
             if let Some(msg) = &self.list_error {
                 return Err(DomainError::Internal(msg.clone()));
             }
@@ -461,10 +777,33 @@ mod tests {
             let _ = limit; // limit is validated by the handler before reaching the repo
             Ok(r)
         }
+
+        fn events(&self, _order_id: Uuid) -> Result<Vec<OrderEvent>, DomainError> {
+            Ok(self.events.clone())
+        }
+    }
+
+    /// Runs units of work in memory, or fails them all with `error`.
+    #[derive(Default)]
+    struct StubUnitOfWork {
+        inner: InMemoryUnitOfWork,
+        error: Option<String>,
     }
 
-    fn make_service<R: OrderRepository>(repo: R) -> web::Data<OrderService<R>> {
-        web::Data::new(OrderService::new(repo))
+    impl UnitOfWork for StubUnitOfWork {
+        fn run<T, E>(&self, work: impl FnOnce(&mut dyn Transaction) -> Result<T, E>) -> Result<T, E>
+        where
+            E: From<DomainError>,
+        {
+            if let Some(msg) = &self.error {
+                return Err(DomainError::Internal(msg.clone()).into());
+            }
+            self.inner.run(work)
+        }
+    }
+
+    fn make_service<R: OrderRepository>(repo: R) -> web::Data<OrderService<R, StubUnitOfWork>> {
+        web::Data::new(OrderService::new(repo, StubUnitOfWork::default()))
     }
 
     #[actix_web::test]
@@ -473,7 +812,11 @@ mod tests {
         let app = actix_test::init_service(
             App::new()
                 .app_data(svc)
-                .route("/orders", web::post().to(create_order::<InMemoryOrderRepo>)),
+                .wrap(Authentication::new(None))
+                .route(
+                    "/orders",
+                    web::post().to(create_order::<InMemoryOrderRepo, StubUnitOfWork>),
+                ),
         )
         .await;
 
@@ -504,7 +847,11 @@ mod tests {
         let app = actix_test::init_service(
             App::new()
                 .app_data(svc)
-                .route("/orders", web::post().to(create_order::<InMemoryOrderRepo>)),
+                .wrap(Authentication::new(None))
+                .route(
+                    "/orders",
+                    web::post().to(create_order::<InMemoryOrderRepo, StubUnitOfWork>),
+                ),
         )
         .await;
 
@@ -526,15 +873,21 @@ mod tests {
 
     #[actix_web::test]
     async fn create_order_returns_500_on_repo_internal_error() {
-        let repo = InMemoryOrderRepo {
-            create_error: Some("db unavailable".to_string()),
-            ..Default::default()
-        };
-        let svc = make_service(repo);
+        let svc = web::Data::new(OrderService::new(
+            InMemoryOrderRepo::default(),
+            StubUnitOfWork {
+                error: Some("db unavailable".to_string()),
+                ..Default::default()
+            },
+        ));
         let app = actix_test::init_service(
             App::new()
                 .app_data(svc)
-                .route("/orders", web::post().to(create_order::<InMemoryOrderRepo>)),
+                .wrap(Authentication::new(None))
+                .route(
+                    "/orders",
+                    web::post().to(create_order::<InMemoryOrderRepo, StubUnitOfWork>),
+                ),
         )
         .await;
 
@@ -561,10 +914,15 @@ mod tests {
             ..Default::default()
         };
         let svc = make_service(repo);
-        let app = actix_test::init_service(App::new().app_data(svc).route(
-            "/orders/{id}",
-            web::get().to(get_order::<InMemoryOrderRepo>),
-        ))
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(svc)
+                .wrap(Authentication::new(None))
+                .route(
+                    "/orders/{id}",
+                    web::get().to(get_order::<InMemoryOrderRepo, StubUnitOfWork>),
+                ),
+        )
         .await;
 
         let req = actix_test::TestRequest::get()
@@ -589,7 +947,11 @@ mod tests {
         let app = actix_test::init_service(
             App::new()
                 .app_data(svc)
-                .route("/orders", web::get().to(list_orders::<InMemoryOrderRepo>)),
+                .wrap(Authentication::new(None))
+                .route(
+                    "/orders",
+                    web::get().to(list_orders::<InMemoryOrderRepo, StubUnitOfWork>),
+                ),
         )
         .await;
 
@@ -606,10 +968,15 @@ mod tests {
     #[actix_web::test]
     async fn get_order_returns_404_for_unknown_id() {
         let svc = make_service(InMemoryOrderRepo::default()); // find_result = None
-        let app = actix_test::init_service(App::new().app_data(svc).route(
-            "/orders/{id}",
-            web::get().to(get_order::<InMemoryOrderRepo>),
-        ))
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(svc)
+                .wrap(Authentication::new(None))
+                .route(
+                    "/orders/{id}",
+                    web::get().to(get_order::<InMemoryOrderRepo, StubUnitOfWork>),
+                ),
+        )
         .await;
 
         let req = actix_test::TestRequest::get()
@@ -648,10 +1015,15 @@ mod tests {
         };
 
         let svc = make_service(repo);
-        let app = actix_test::init_service(App::new().app_data(svc).route(
-            "/orders/{id}",
-            web::get().to(get_order::<InMemoryOrderRepo>),
-        ))
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(svc)
+                .wrap(Authentication::new(None))
+                .route(
+                    "/orders/{id}",
+                    web::get().to(get_order::<InMemoryOrderRepo, StubUnitOfWork>),
+                ),
+        )
         .await;
 
         let req = actix_test::TestRequest::get()
@@ -682,7 +1054,11 @@ mod tests {
         let app = actix_test::init_service(
             App::new()
                 .app_data(svc)
-                .route("/orders", web::get().to(list_orders::<InMemoryOrderRepo>)),
+                .wrap(Authentication::new(None))
+                .route(
+                    "/orders",
+                    web::get().to(list_orders::<InMemoryOrderRepo, StubUnitOfWork>),
+                ),
         )
         .await;
 
@@ -716,7 +1092,11 @@ mod tests {
         let app = actix_test::init_service(
             App::new()
                 .app_data(svc)
-                .route("/orders", web::get().to(list_orders::<InMemoryOrderRepo>)),
+                .wrap(Authentication::new(None))
+                .route(
+                    "/orders",
+                    web::get().to(list_orders::<InMemoryOrderRepo, StubUnitOfWork>),
+                ),
         )
         .await;
 
@@ -745,4 +1125,433 @@ mod tests {
             "total should be 0 when the repository is empty"
         );
     }
+
+    #[actix_web::test]
+    async fn get_order_events_returns_history() {
+        let order = OrderView {
+            id: Uuid::new_v4(),
+            customer_id: Uuid::new_v4(),
+            status: "PENDING".to_string(),
+            created_at: Utc::now(),
+            lines: vec![],
+        };
+        let order_id = order.id;
+        let repo = InMemoryOrderRepo {
+            find_result: Some(order),
+            events: vec![OrderEvent {
+                id: Uuid::new_v4(),
+                order_id,
+                event_type: "OrderCreated".to_string(),
+                sequence: 1,
+                occurred_at: Utc::now(),
+                payload: serde_json::json!({ "order_id": order_id }),
+            }],
+            ..Default::default()
+        };
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(make_service(repo))
+                .wrap(Authentication::new(None))
+                .route(
+                    "/orders/{id}/events",
+                    web::get().to(get_order_events::<InMemoryOrderRepo, StubUnitOfWork>),
+                ),
+        )
+        .await;
+
+        let req = actix_test::TestRequest::get()
+            .uri(&format!("/orders/{}/events", order_id))
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = actix_test::read_body_json(resp).await;
+        assert_eq!(
+            body["order_id"].as_str(),
+            Some(order_id.to_string().as_str())
+        );
+        assert_eq!(body["complete"].as_bool(), Some(true));
+        assert_eq!(
+            body["events"][0]["event_type"].as_str(),
+            Some("OrderCreated")
+        );
+        assert_eq!(
+            body["events"][0]["payload"]["order_id"].as_str(),
+            Some(order_id.to_string().as_str())
+        );
+    }
+
+    #[actix_web::test]
+    async fn get_order_events_returns_404_for_unknown_order() {
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(make_service(InMemoryOrderRepo::default()))
+                .wrap(Authentication::new(None))
+                .route(
+                    "/orders/{id}/events",
+                    web::get().to(get_order_events::<InMemoryOrderRepo, StubUnitOfWork>),
+                ),
+        )
+        .await;
+
+        let req = actix_test::TestRequest::get()
+            .uri(&format!("/orders/{}/events", Uuid::new_v4()))
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    struct StubSagas(Option<OrderSaga>);
+
+    impl SagaRepository for StubSagas {
+        fn find(&self, order_id: Uuid) -> Result<Option<OrderSaga>, DomainError> {
+            Ok(self.0.clone().filter(|s| s.order_id == order_id))
+        }
+
+        fn steps(&self, _order_id: Uuid) -> Result<Vec<SagaStepEntry>, DomainError> {
+            Ok(vec![SagaStepEntry {
+                step: "RESERVE_INVENTORY".to_string(),
+                outcome: "STARTED".to_string(),
+                detail: None,
+                recorded_at: Utc::now(),
+            }])
+        }
+
+        fn overdue(&self, _now: DateTime<Utc>, _limit: i64) -> Result<Vec<Uuid>, DomainError> {
+            Ok(vec![])
+        }
+    }
+
+    async fn get_saga(saga: Option<OrderSaga>, order_id: Uuid) -> ServiceResponse {
+        let svc = web::Data::new(SagaService::new(
+            StubSagas(saga),
+            InMemoryUnitOfWork::new(),
+            SagaTimeouts::default(),
+        ));
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(svc)
+                .wrap(Authentication::new(None))
+                .route(
+                    "/orders/{id}/saga",
+                    web::get().to(get_order_saga::<StubSagas, InMemoryUnitOfWork>),
+                ),
+        )
+        .await;
+        let req = actix_test::TestRequest::get()
+            .uri(&format!("/orders/{}/saga", order_id))
+            .to_request();
+        actix_test::call_service(&app, req).await
+    }
+
+    #[actix_web::test]
+    async fn get_order_saga_returns_state_and_journal() {
+        let lines = [OrderLineInput {
+            product_id: Uuid::new_v4(),
+            quantity: 1,
+            unit_price: BigDecimal::from(5),
+        }];
+        let start = OrderSaga::start(
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            &lines,
+            &SagaTimeouts::default(),
+            Utc::now(),
+        );
+        let order_id = start.saga.order_id;
+
+        let resp = get_saga(Some(start.saga), order_id).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = actix_test::read_body_json(resp).await;
+        assert_eq!(body["state"].as_str(), Some("RESERVING_INVENTORY"));
+        assert_eq!(body["overdue"].as_bool(), Some(false));
+        assert!(body["step_deadline"].is_string());
+        assert_eq!(body["steps"][0]["step"].as_str(), Some("RESERVE_INVENTORY"));
+    }
+
+    #[actix_web::test]
+    async fn get_order_saga_returns_404_without_a_saga() {
+        let resp = get_saga(None, Uuid::new_v4()).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    // ── Event streams ─────────────────────────────────────────────────────────
+
+    /// Streams over a pool that never connects: without `Last-Event-ID` a
+    /// stream only relays what is published on `bus`.
+    fn offline_streams(bus: OrderEventBus) -> web::Data<OrderEventStreams> {
+        let pool = r2d2::Pool::builder()
+            .min_idle(Some(0))
+            .build_unchecked(ConnectionManager::new("postgres://unused"));
+        web::Data::new(OrderEventStreams::new(pool, bus, Readiness::new()))
+    }
+
+    fn published(order_id: Uuid, event_type: &str) -> OrderEvent {
+        OrderEvent {
+            id: Uuid::new_v4(),
+            order_id,
+            event_type: event_type.to_string(),
+            sequence: 2,
+            occurred_at: Utc::now(),
+            payload: serde_json::json!({ "status": "PAID" }),
+        }
+    }
+
+    async fn next_chunk(body: &mut BoxBody) -> web::Bytes {
+        let chunk = tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            std::future::poll_fn(|cx| std::pin::Pin::new(&mut *body).poll_next(cx)),
+        )
+        .await
+        .expect("chunk in time");
+        chunk.expect("stream open").expect("chunk")
+    }
+
+    #[actix_web::test]
+    async fn order_stream_pushes_the_orders_events() {
+        let order = order_owned_by(Uuid::new_v4());
+        let order_id = order.id;
+        let bus = OrderEventBus::new();
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(make_service(InMemoryOrderRepo {
+                    find_result: Some(order),
+                    ..Default::default()
+                }))
+                .app_data(offline_streams(bus.clone()))
+                .wrap(Authentication::new(None))
+                .route(
+                    "/orders/{id}/stream",
+                    web::get().to(stream_order_events::<InMemoryOrderRepo, StubUnitOfWork>),
+                ),
+        )
+        .await;
+
+        let req = actix_test::TestRequest::get()
+            .uri(&format!("/orders/{}/stream", order_id))
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers()
+                .get(header::CONTENT_TYPE)
+                .expect("content type"),
+            "text/event-stream"
+        );
+
+        bus.publish(published(Uuid::new_v4(), "OrderPaid"));
+        let event = published(order_id, "OrderPaid");
+        bus.publish(event.clone());
+
+        let mut body = resp.into_body();
+        let chunk = next_chunk(&mut body).await;
+        let text = std::str::from_utf8(&chunk).expect("utf-8");
+        assert!(
+            text.starts_with(&format!("id: {}\nevent: OrderPaid\ndata: ", event.id)),
+            "unexpected message {text:?}"
+        );
+        let data: serde_json::Value = serde_json::from_str(
+            text.lines()
+                .find_map(|line| line.strip_prefix("data: "))
+                .expect("data line"),
+        )
+        .expect("json data");
+        assert_eq!(data["order_id"], order_id.to_string());
+        assert_eq!(data["payload"]["status"], "PAID");
+    }
+
+    #[actix_web::test]
+    async fn stream_of_all_orders_filters_by_event_type() {
+        let bus = OrderEventBus::new();
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(offline_streams(bus.clone()))
+                .wrap(Authentication::new(None))
+                .route("/orders/stream", web::get().to(stream_all_order_events)),
+        )
+        .await;
+
+        let req = actix_test::TestRequest::get()
+            .uri("/orders/stream?event_types=OrderCancelled,%20OrderPaid")
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        bus.publish(published(Uuid::new_v4(), "OrderCreated"));
+        let paid = published(Uuid::new_v4(), "OrderPaid");
+        bus.publish(paid.clone());
+
+        let mut body = resp.into_body();
+        let chunk = next_chunk(&mut body).await;
+        assert!(std::str::from_utf8(&chunk)
+            .expect("utf-8")
+            .starts_with(&format!("id: {}\n", paid.id)));
+    }
+
+    #[actix_web::test]
+    async fn malformed_last_event_id_returns_400() {
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(offline_streams(OrderEventBus::new()))
+                .wrap(Authentication::new(None))
+                .route("/orders/stream", web::get().to(stream_all_order_events)),
+        )
+        .await;
+
+        let req = actix_test::TestRequest::get()
+            .uri("/orders/stream")
+            .insert_header(("Last-Event-ID", "42"))
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    // ── Customer-level authorization ──────────────────────────────────────────
+
+    fn order_owned_by(customer_id: Uuid) -> OrderView {
+        OrderView {
+            id: Uuid::new_v4(),
+            customer_id,
+            status: "PENDING".to_string(),
+            created_at: Utc::now(),
+            lines: vec![],
+        }
+    }
+
+    /// App whose requests are all authenticated as `principal`.
+    macro_rules! app_as {
+        ($principal:expr, $repo:expr) => {{
+            use actix_web::dev::Service as _;
+            use actix_web::HttpMessage as _;
+            let principal: Principal = $principal;
+            actix_test::init_service(
+                App::new()
+                    .app_data(make_service($repo))
+                    .wrap_fn(move |req, srv| {
+                        req.extensions_mut().insert(principal.clone());
+                        srv.call(req)
+                    })
+                    .route("/orders/stream", web::get().to(stream_all_order_events))
+                    .route(
+                        "/orders/{id}",
+                        web::get().to(get_order::<InMemoryOrderRepo, StubUnitOfWork>),
+                    )
+                    .route(
+                        "/orders",
+                        web::post().to(create_order::<InMemoryOrderRepo, StubUnitOfWork>),
+                    )
+                    .app_data(offline_streams(OrderEventBus::new()))
+                    .route(
+                        "/orders/{id}/stream",
+                        web::get().to(stream_order_events::<InMemoryOrderRepo, StubUnitOfWork>),
+                    ),
+            )
+            .await
+        }};
+    }
+
+    fn customer(customer_id: Uuid) -> Principal {
+        Principal::new("customer", ["orders:read".to_string()]).with_customer(customer_id)
+    }
+
+    #[actix_web::test]
+    async fn get_order_returns_404_for_another_customers_order() {
+        let order = order_owned_by(Uuid::new_v4());
+        let order_id = order.id;
+        let app = app_as!(
+            customer(Uuid::new_v4()),
+            InMemoryOrderRepo {
+                find_result: Some(order),
+                ..Default::default()
+            }
+        );
+
+        let req = actix_test::TestRequest::get()
+            .uri(&format!("/orders/{}", order_id))
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(
+            resp.status(),
+            StatusCode::NOT_FOUND,
+            "a foreign order must look exactly like a missing one"
+        );
+    }
+
+    #[actix_web::test]
+    async fn get_order_returns_200_for_own_order() {
+        let owner = Uuid::new_v4();
+        let order = order_owned_by(owner);
+        let order_id = order.id;
+        let app = app_as!(
+            customer(owner),
+            InMemoryOrderRepo {
+                find_result: Some(order),
+                ..Default::default()
+            }
+        );
+
+        let req = actix_test::TestRequest::get()
+            .uri(&format!("/orders/{}", order_id))
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn create_order_for_another_customer_returns_403() {
+        let app = app_as!(customer(Uuid::new_v4()), InMemoryOrderRepo::default());
+
+        let req = actix_test::TestRequest::post()
+            .uri("/orders")
+            .set_json(serde_json::json!({
+                "customer_id": Uuid::new_v4(),
+                "lines": [{"product_id": Uuid::new_v4(), "quantity": 1, "unit_price": "1.00"}]
+            }))
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[actix_web::test]
+    async fn stream_order_events_returns_404_for_another_customers_order() {
+        let order = order_owned_by(Uuid::new_v4());
+        let order_id = order.id;
+        let app = app_as!(
+            customer(Uuid::new_v4()),
+            InMemoryOrderRepo {
+                find_result: Some(order),
+                ..Default::default()
+            }
+        );
+
+        let req = actix_test::TestRequest::get()
+            .uri(&format!("/orders/{}/stream", order_id))
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn stream_of_all_orders_is_staff_only() {
+        let app = app_as!(customer(Uuid::new_v4()), InMemoryOrderRepo::default());
+
+        let req = actix_test::TestRequest::get()
+            .uri("/orders/stream")
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[actix_web::test]
+    async fn token_without_customer_or_staff_identity_returns_403() {
+        let app = app_as!(
+            Principal::new("service-account", ["orders:read".to_string()]),
+            InMemoryOrderRepo::default()
+        );
+
+        let req = actix_test::TestRequest::get()
+            .uri(&format!("/orders/{}", Uuid::new_v4()))
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    }
 }
@@ -0,0 +1,318 @@
+use actix_web::{web, HttpResponse};
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::application::outbox_service::OutboxDeadLetterService;
+use crate::domain::outbox::OutboxDeadLetter;
+use crate::domain::ports::OutboxDeadLetterRepository;
+use crate::errors::AppError;
+
+// ── Response DTOs ────────────────────────────────────────────────────────────
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OutboxDeadLetterResponse {
+    pub event_id: Uuid,
+    pub aggregate_type: String,
+    pub aggregate_id: String,
+    pub event_type: String,
+    pub sequence: i64,
+    #[schema(value_type = Object)]
+    pub payload: serde_json::Value,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub occurred_at: String,
+    pub dead_lettered_at: String,
+}
+
+impl From<OutboxDeadLetter> for OutboxDeadLetterResponse {
+    fn from(dead_letter: OutboxDeadLetter) -> Self {
+        OutboxDeadLetterResponse {
+            event_id: dead_letter.event_id,
+            aggregate_type: dead_letter.aggregate_type,
+            aggregate_id: dead_letter.aggregate_id,
+            event_type: dead_letter.event_type,
+            sequence: dead_letter.sequence,
+            payload: dead_letter.payload,
+            attempts: dead_letter.attempts,
+            last_error: dead_letter.last_error,
+            occurred_at: dead_letter.occurred_at.to_rfc3339(),
+            dead_lettered_at: dead_letter.dead_lettered_at.to_rfc3339(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OutboxDeadLettersResponse {
+    pub items: Vec<OutboxDeadLetterResponse>,
+}
+
+// ── Handlers ─────────────────────────────────────────────────────────────────
+
+/// GET /admin/outbox/dead-letters
+///
+/// Outbox events the relay gave up on, most recently dead-lettered first (at
+/// most 100).
+#[utoipa::path(
+    get,
+    path = "/admin/outbox/dead-letters",
+    responses(
+        (status = 200, description = "Dead-lettered outbox events", body = OutboxDeadLettersResponse),
+        (status = 401, description = "Missing or invalid bearer token"),
+        (status = 403, description = "Token lacks the outbox:admin scope"),
+        (status = 500, description = "Internal server error"),
+    ),
+    security(("bearer_auth" = ["outbox:admin"])),
+    tag = "outbox"
+)]
+pub async fn list_outbox_dead_letters<R: OutboxDeadLetterRepository>(
+    service: web::Data<OutboxDeadLetterService<R>>,
+) -> Result<HttpResponse, AppError> {
+    let svc = service.clone();
+    let dead_letters = web::block(move || svc.dead_letters())
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?
+        .map_err(AppError::from)?;
+    Ok(HttpResponse::Ok().json(OutboxDeadLettersResponse {
+        items: dead_letters.into_iter().map(Into::into).collect(),
+    }))
+}
+
+/// GET /admin/outbox/dead-letters/{event_id}
+#[utoipa::path(
+    get,
+    path = "/admin/outbox/dead-letters/{event_id}",
+    params(("event_id" = Uuid, Path, description = "Outbox event UUID")),
+    responses(
+        (status = 200, description = "Dead-lettered outbox event", body = OutboxDeadLetterResponse),
+        (status = 401, description = "Missing or invalid bearer token"),
+        (status = 403, description = "Token lacks the outbox:admin scope"),
+        (status = 404, description = "No such dead letter"),
+        (status = 500, description = "Internal server error"),
+    ),
+    security(("bearer_auth" = ["outbox:admin"])),
+    tag = "outbox"
+)]
+pub async fn get_outbox_dead_letter<R: OutboxDeadLetterRepository>(
+    service: web::Data<OutboxDeadLetterService<R>>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let event_id = path.into_inner();
+    let svc = service.clone();
+    let dead_letter = web::block(move || svc.dead_letter(event_id))
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?
+        .map_err(AppError::from)?;
+    Ok(HttpResponse::Ok().json(OutboxDeadLetterResponse::from(dead_letter)))
+}
+
+/// POST /admin/outbox/dead-letters/{event_id}/retry
+///
+/// Queues a dead-lettered event for publishing again with a fresh attempt
+/// budget.
+#[utoipa::path(
+    post,
+    path = "/admin/outbox/dead-letters/{event_id}/retry",
+    params(("event_id" = Uuid, Path, description = "Outbox event UUID")),
+    responses(
+        (status = 202, description = "Event queued again"),
+        (status = 401, description = "Missing or invalid bearer token"),
+        (status = 403, description = "Token lacks the outbox:admin scope"),
+        (status = 404, description = "No such dead letter"),
+        (status = 500, description = "Internal server error"),
+    ),
+    security(("bearer_auth" = ["outbox:admin"])),
+    tag = "outbox"
+)]
+pub async fn retry_outbox_dead_letter<R: OutboxDeadLetterRepository>(
+    service: web::Data<OutboxDeadLetterService<R>>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let event_id = path.into_inner();
+    let svc = service.clone();
+    web::block(move || svc.requeue(event_id))
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?
+        .map_err(AppError::from)?;
+    Ok(HttpResponse::Accepted().finish())
+}
+
+/// DELETE /admin/outbox/dead-letters/{event_id}
+///
+/// Gives up on a dead-lettered event for good. The outbox row is kept (for
+/// Debezium and the order history); the relay just never publishes it.
+#[utoipa::path(
+    delete,
+    path = "/admin/outbox/dead-letters/{event_id}",
+    params(("event_id" = Uuid, Path, description = "Outbox event UUID")),
+    responses(
+        (status = 204, description = "Event discarded"),
+        (status = 401, description = "Missing or invalid bearer token"),
+        (status = 403, description = "Token lacks the outbox:admin scope"),
+        (status = 404, description = "No such dead letter"),
+        (status = 500, description = "Internal server error"),
+    ),
+    security(("bearer_auth" = ["outbox:admin"])),
+    tag = "outbox"
+)]
+pub async fn discard_outbox_dead_letter<R: OutboxDeadLetterRepository>(
+    service: web::Data<OutboxDeadLetterService<R>>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let event_id = path.into_inner();
+    let svc = service.clone();
+    web::block(move || svc.discard(event_id))
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?
+        .map_err(AppError::from)?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use actix_web::{http::StatusCode, test as actix_test, App};
+    use chrono::Utc;
+
+    use super::*;
+    use crate::domain::errors::DomainError;
+
+    /// Dead letters that disappear once requeued or discarded.
+    #[derive(Default)]
+    struct InMemoryDeadLetters(Mutex<Vec<OutboxDeadLetter>>);
+
+    impl InMemoryDeadLetters {
+        fn take(&self, event_id: Uuid) -> bool {
+            let mut dead_letters = self.0.lock().expect("lock");
+            let before = dead_letters.len();
+            dead_letters.retain(|d| d.event_id != event_id);
+            dead_letters.len() < before
+        }
+    }
+
+    impl OutboxDeadLetterRepository for InMemoryDeadLetters {
+        fn list(&self, limit: i64) -> Result<Vec<OutboxDeadLetter>, DomainError> {
+            let dead_letters = self.0.lock().expect("lock");
+            Ok(dead_letters.iter().take(limit as usize).cloned().collect())
+        }
+
+        fn find(&self, event_id: Uuid) -> Result<Option<OutboxDeadLetter>, DomainError> {
+            Ok(self
+                .list(i64::MAX)?
+                .into_iter()
+                .find(|d| d.event_id == event_id))
+        }
+
+        fn requeue(&self, event_id: Uuid) -> Result<bool, DomainError> {
+            Ok(self.take(event_id))
+        }
+
+        fn discard(&self, event_id: Uuid) -> Result<bool, DomainError> {
+            Ok(self.take(event_id))
+        }
+    }
+
+    fn dead_letter() -> OutboxDeadLetter {
+        OutboxDeadLetter {
+            event_id: Uuid::new_v4(),
+            aggregate_type: "Order".to_string(),
+            aggregate_id: Uuid::new_v4().to_string(),
+            event_type: "OrderCreated".to_string(),
+            sequence: 1,
+            payload: serde_json::json!({ "status": "PENDING" }),
+            attempts: 8,
+            last_error: Some("rejected: message too large".to_string()),
+            occurred_at: Utc::now(),
+            dead_lettered_at: Utc::now(),
+        }
+    }
+
+    macro_rules! app {
+        ($dead_letters:expr) => {
+            actix_test::init_service(
+                App::new()
+                    .app_data(web::Data::new(OutboxDeadLetterService::new(
+                        InMemoryDeadLetters(Mutex::new($dead_letters)),
+                    )))
+                    .route(
+                        "/admin/outbox/dead-letters",
+                        web::get().to(list_outbox_dead_letters::<InMemoryDeadLetters>),
+                    )
+                    .route(
+                        "/admin/outbox/dead-letters/{event_id}",
+                        web::get().to(get_outbox_dead_letter::<InMemoryDeadLetters>),
+                    )
+                    .route(
+                        "/admin/outbox/dead-letters/{event_id}",
+                        web::delete().to(discard_outbox_dead_letter::<InMemoryDeadLetters>),
+                    )
+                    .route(
+                        "/admin/outbox/dead-letters/{event_id}/retry",
+                        web::post().to(retry_outbox_dead_letter::<InMemoryDeadLetters>),
+                    ),
+            )
+            .await
+        };
+    }
+
+    #[actix_web::test]
+    async fn dead_letters_can_be_inspected() {
+        let stored = dead_letter();
+        let app = app!(vec![stored.clone()]);
+
+        let req = actix_test::TestRequest::get()
+            .uri("/admin/outbox/dead-letters")
+            .to_request();
+        let listed: serde_json::Value =
+            actix_test::read_body_json(actix_test::call_service(&app, req).await).await;
+        assert_eq!(listed["items"].as_array().map(Vec::len), Some(1));
+
+        let req = actix_test::TestRequest::get()
+            .uri(&format!("/admin/outbox/dead-letters/{}", stored.event_id))
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let fetched: serde_json::Value = actix_test::read_body_json(resp).await;
+        assert_eq!(fetched["event_type"], "OrderCreated");
+        assert_eq!(fetched["payload"]["status"], "PENDING");
+        assert_eq!(fetched["last_error"], "rejected: message too large");
+    }
+
+    #[actix_web::test]
+    async fn dead_letters_can_be_retried_or_discarded_once() {
+        let (retried, discarded) = (dead_letter(), dead_letter());
+        let app = app!(vec![retried.clone(), discarded.clone()]);
+        let retry_uri = format!("/admin/outbox/dead-letters/{}/retry", retried.event_id);
+        let discard_uri = format!("/admin/outbox/dead-letters/{}", discarded.event_id);
+
+        let req = actix_test::TestRequest::post().uri(&retry_uri).to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::ACCEPTED);
+        let req = actix_test::TestRequest::delete()
+            .uri(&discard_uri)
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+        let req = actix_test::TestRequest::post().uri(&retry_uri).to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let req = actix_test::TestRequest::delete()
+            .uri(&discard_uri)
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn unknown_dead_letter_is_not_found() {
+        let app = app!(vec![]);
+
+        let req = actix_test::TestRequest::get()
+            .uri(&format!("/admin/outbox/dead-letters/{}", Uuid::new_v4()))
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+}
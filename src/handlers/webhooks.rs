@@ -0,0 +1,545 @@
+use actix_web::{web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::application::webhook_service::WebhookService;
+use crate::domain::ports::WebhookRepository;
+use crate::domain::webhook::{WebhookDelivery, WebhookSubscription, WebhookSubscriptionInput};
+use crate::errors::AppError;
+
+// ── Request / response DTOs ──────────────────────────────────────────────────
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct WebhookRequest {
+    /// Absolute `http(s)` URL the events are POSTed to.
+    pub url: String,
+    /// Event types to deliver, e.g. `OrderPaid`; omitted or empty for all.
+    #[serde(default)]
+    pub event_types: Vec<String>,
+}
+
+impl From<WebhookRequest> for WebhookSubscriptionInput {
+    fn from(request: WebhookRequest) -> Self {
+        WebhookSubscriptionInput {
+            url: request.url,
+            event_types: request.event_types,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WebhookResponse {
+    pub id: Uuid,
+    pub url: String,
+    pub event_types: Vec<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl From<WebhookSubscription> for WebhookResponse {
+    fn from(subscription: WebhookSubscription) -> Self {
+        WebhookResponse {
+            id: subscription.id,
+            url: subscription.url,
+            event_types: subscription.event_types,
+            created_at: subscription.created_at.to_rfc3339(),
+            updated_at: subscription.updated_at.to_rfc3339(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CreateWebhookResponse {
+    #[serde(flatten)]
+    pub webhook: WebhookResponse,
+    /// Key of the `X-Webhook-Signature` HMAC. Only returned here.
+    pub secret: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ListWebhooksResponse {
+    pub items: Vec<WebhookResponse>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WebhookDeliveryResponse {
+    pub id: i64,
+    pub event_id: Uuid,
+    pub event_type: String,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl From<WebhookDelivery> for WebhookDeliveryResponse {
+    fn from(delivery: WebhookDelivery) -> Self {
+        WebhookDeliveryResponse {
+            id: delivery.id,
+            event_id: delivery.event_id,
+            event_type: delivery.event_type,
+            attempts: delivery.attempts,
+            last_error: delivery.last_error,
+            created_at: delivery.created_at.to_rfc3339(),
+            updated_at: delivery.updated_at.to_rfc3339(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DeadLettersResponse {
+    pub items: Vec<WebhookDeliveryResponse>,
+}
+
+// ── Handlers ─────────────────────────────────────────────────────────────────
+
+/// POST /webhooks
+///
+/// Registers an endpoint for order events. The response carries the signing
+/// secret, which cannot be retrieved later.
+#[utoipa::path(
+    post,
+    path = "/webhooks",
+    request_body = WebhookRequest,
+    responses(
+        (status = 201, description = "Subscription created", body = CreateWebhookResponse),
+        (status = 400, description = "Invalid or internal URL, or invalid event types"),
+        (status = 401, description = "Missing or invalid bearer token"),
+        (status = 403, description = "Token lacks the webhooks:manage scope"),
+        (status = 500, description = "Internal server error"),
+    ),
+    security(("bearer_auth" = ["webhooks:manage"])),
+    tag = "webhooks"
+)]
+pub async fn create_webhook<W: WebhookRepository>(
+    service: web::Data<WebhookService<W>>,
+    body: web::Json<WebhookRequest>,
+) -> Result<HttpResponse, AppError> {
+    let input = WebhookSubscriptionInput::from(body.into_inner());
+    let svc = service.clone();
+    let (subscription, secret) = web::block(move || svc.subscribe(&input))
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?
+        .map_err(AppError::from)?;
+    Ok(HttpResponse::Created().json(CreateWebhookResponse {
+        webhook: subscription.into(),
+        secret,
+    }))
+}
+
+/// GET /webhooks
+#[utoipa::path(
+    get,
+    path = "/webhooks",
+    responses(
+        (status = 200, description = "All subscriptions, oldest first", body = ListWebhooksResponse),
+        (status = 401, description = "Missing or invalid bearer token"),
+        (status = 403, description = "Token lacks the webhooks:manage scope"),
+        (status = 500, description = "Internal server error"),
+    ),
+    security(("bearer_auth" = ["webhooks:manage"])),
+    tag = "webhooks"
+)]
+pub async fn list_webhooks<W: WebhookRepository>(
+    service: web::Data<WebhookService<W>>,
+) -> Result<HttpResponse, AppError> {
+    let svc = service.clone();
+    let subscriptions = web::block(move || svc.subscriptions())
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?
+        .map_err(AppError::from)?;
+    Ok(HttpResponse::Ok().json(ListWebhooksResponse {
+        items: subscriptions.into_iter().map(Into::into).collect(),
+    }))
+}
+
+/// GET /webhooks/{id}
+#[utoipa::path(
+    get,
+    path = "/webhooks/{id}",
+    params(("id" = Uuid, Path, description = "Subscription UUID")),
+    responses(
+        (status = 200, description = "Subscription", body = WebhookResponse),
+        (status = 401, description = "Missing or invalid bearer token"),
+        (status = 403, description = "Token lacks the webhooks:manage scope"),
+        (status = 404, description = "Subscription not found"),
+        (status = 500, description = "Internal server error"),
+    ),
+    security(("bearer_auth" = ["webhooks:manage"])),
+    tag = "webhooks"
+)]
+pub async fn get_webhook<W: WebhookRepository>(
+    service: web::Data<WebhookService<W>>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let id = path.into_inner();
+    let svc = service.clone();
+    let subscription = web::block(move || svc.subscription(id))
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?
+        .map_err(AppError::from)?;
+    Ok(HttpResponse::Ok().json(WebhookResponse::from(subscription)))
+}
+
+/// PUT /webhooks/{id}
+///
+/// Replaces URL and event types; the secret is kept.
+#[utoipa::path(
+    put,
+    path = "/webhooks/{id}",
+    params(("id" = Uuid, Path, description = "Subscription UUID")),
+    request_body = WebhookRequest,
+    responses(
+        (status = 200, description = "Subscription updated", body = WebhookResponse),
+        (status = 400, description = "Invalid or internal URL, or invalid event types"),
+        (status = 401, description = "Missing or invalid bearer token"),
+        (status = 403, description = "Token lacks the webhooks:manage scope"),
+        (status = 404, description = "Subscription not found"),
+        (status = 500, description = "Internal server error"),
+    ),
+    security(("bearer_auth" = ["webhooks:manage"])),
+    tag = "webhooks"
+)]
+pub async fn update_webhook<W: WebhookRepository>(
+    service: web::Data<WebhookService<W>>,
+    path: web::Path<Uuid>,
+    body: web::Json<WebhookRequest>,
+) -> Result<HttpResponse, AppError> {
+    let id = path.into_inner();
+    let input = WebhookSubscriptionInput::from(body.into_inner());
+    let svc = service.clone();
+    let subscription = web::block(move || svc.update(id, &input))
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?
+        .map_err(AppError::from)?;
+    Ok(HttpResponse::Ok().json(WebhookResponse::from(subscription)))
+}
+
+/// DELETE /webhooks/{id}
+///
+/// Removes the subscription together with its pending and dead deliveries.
+#[utoipa::path(
+    delete,
+    path = "/webhooks/{id}",
+    params(("id" = Uuid, Path, description = "Subscription UUID")),
+    responses(
+        (status = 204, description = "Subscription deleted"),
+        (status = 401, description = "Missing or invalid bearer token"),
+        (status = 403, description = "Token lacks the webhooks:manage scope"),
+        (status = 404, description = "Subscription not found"),
+        (status = 500, description = "Internal server error"),
+    ),
+    security(("bearer_auth" = ["webhooks:manage"])),
+    tag = "webhooks"
+)]
+pub async fn delete_webhook<W: WebhookRepository>(
+    service: web::Data<WebhookService<W>>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let id = path.into_inner();
+    let svc = service.clone();
+    web::block(move || svc.unsubscribe(id))
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?
+        .map_err(AppError::from)?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// GET /webhooks/{id}/dead-letters
+///
+/// Deliveries that failed too often and are no longer retried, newest first
+/// (at most 100).
+#[utoipa::path(
+    get,
+    path = "/webhooks/{id}/dead-letters",
+    params(("id" = Uuid, Path, description = "Subscription UUID")),
+    responses(
+        (status = 200, description = "Dead-lettered deliveries", body = DeadLettersResponse),
+        (status = 401, description = "Missing or invalid bearer token"),
+        (status = 403, description = "Token lacks the webhooks:manage scope"),
+        (status = 404, description = "Subscription not found"),
+        (status = 500, description = "Internal server error"),
+    ),
+    security(("bearer_auth" = ["webhooks:manage"])),
+    tag = "webhooks"
+)]
+pub async fn list_dead_letters<W: WebhookRepository>(
+    service: web::Data<WebhookService<W>>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let id = path.into_inner();
+    let svc = service.clone();
+    let deliveries = web::block(move || svc.dead_letters(id))
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?
+        .map_err(AppError::from)?;
+    Ok(HttpResponse::Ok().json(DeadLettersResponse {
+        items: deliveries.into_iter().map(Into::into).collect(),
+    }))
+}
+
+/// POST /webhooks/{id}/dead-letters/{delivery_id}/retry
+///
+/// Queues a dead-lettered delivery again with a fresh attempt budget.
+#[utoipa::path(
+    post,
+    path = "/webhooks/{id}/dead-letters/{delivery_id}/retry",
+    params(
+        ("id" = Uuid, Path, description = "Subscription UUID"),
+        ("delivery_id" = i64, Path, description = "Delivery id"),
+    ),
+    responses(
+        (status = 202, description = "Delivery queued again"),
+        (status = 401, description = "Missing or invalid bearer token"),
+        (status = 403, description = "Token lacks the webhooks:manage scope"),
+        (status = 404, description = "No such dead letter"),
+        (status = 500, description = "Internal server error"),
+    ),
+    security(("bearer_auth" = ["webhooks:manage"])),
+    tag = "webhooks"
+)]
+pub async fn retry_dead_letter<W: WebhookRepository>(
+    service: web::Data<WebhookService<W>>,
+    path: web::Path<(Uuid, i64)>,
+) -> Result<HttpResponse, AppError> {
+    let (id, delivery_id) = path.into_inner();
+    let svc = service.clone();
+    web::block(move || svc.redeliver(id, delivery_id))
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?
+        .map_err(AppError::from)?;
+    Ok(HttpResponse::Accepted().finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use actix_web::{http::StatusCode, test as actix_test, App};
+    use chrono::Utc;
+
+    use super::*;
+    use crate::domain::errors::DomainError;
+    use crate::domain::webhook::WebhookSecretKey;
+    use crate::infrastructure::host_resolver::StaticHostResolver;
+
+    #[derive(Default)]
+    struct InMemoryWebhookRepo {
+        subscriptions: Mutex<Vec<WebhookSubscription>>,
+    }
+
+    impl WebhookRepository for InMemoryWebhookRepo {
+        fn create(
+            &self,
+            input: &WebhookSubscriptionInput,
+        ) -> Result<WebhookSubscription, DomainError> {
+            let subscription = WebhookSubscription {
+                id: Uuid::new_v4(),
+                url: input.url.clone(),
+                event_types: input.event_types.clone(),
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+            };
+            self.subscriptions
+                .lock()
+                .expect("lock")
+                .push(subscription.clone());
+            Ok(subscription)
+        }
+
+        fn list(&self) -> Result<Vec<WebhookSubscription>, DomainError> {
+            Ok(self.subscriptions.lock().expect("lock").clone())
+        }
+
+        fn find(&self, id: Uuid) -> Result<Option<WebhookSubscription>, DomainError> {
+            Ok(self.list()?.into_iter().find(|s| s.id == id))
+        }
+
+        fn update(
+            &self,
+            id: Uuid,
+            input: &WebhookSubscriptionInput,
+        ) -> Result<Option<WebhookSubscription>, DomainError> {
+            let mut subscriptions = self.subscriptions.lock().expect("lock");
+            Ok(subscriptions.iter_mut().find(|s| s.id == id).map(|s| {
+                s.url = input.url.clone();
+                s.event_types = input.event_types.clone();
+                s.clone()
+            }))
+        }
+
+        fn delete(&self, id: Uuid) -> Result<bool, DomainError> {
+            let mut subscriptions = self.subscriptions.lock().expect("lock");
+            let before = subscriptions.len();
+            subscriptions.retain(|s| s.id != id);
+            Ok(subscriptions.len() < before)
+        }
+
+        fn dead_letters(
+            &self,
+            _subscription_id: Uuid,
+            _limit: i64,
+        ) -> Result<Vec<WebhookDelivery>, DomainError> {
+            Ok(vec![])
+        }
+
+        fn requeue(&self, _subscription_id: Uuid, _delivery_id: i64) -> Result<bool, DomainError> {
+            Ok(false)
+        }
+    }
+
+    fn service() -> WebhookService<InMemoryWebhookRepo> {
+        let public = vec!["93.184.216.34".parse().expect("ip")];
+        let resolver = StaticHostResolver::new()
+            .with_host("partner.example", public.clone())
+            .with_host("a.example", public.clone())
+            .with_host("b.example", public)
+            .with_host("intranet.example", vec!["10.0.0.5".parse().expect("ip")]);
+        WebhookService::new(
+            InMemoryWebhookRepo::default(),
+            Arc::new(resolver),
+            Some(WebhookSecretKey::new(*b"0123456789abcdef0123456789abcdef")),
+        )
+    }
+
+    macro_rules! app {
+        () => {
+            actix_test::init_service(
+                App::new()
+                    .app_data(web::Data::new(service()))
+                    .route(
+                        "/webhooks",
+                        web::post().to(create_webhook::<InMemoryWebhookRepo>),
+                    )
+                    .route(
+                        "/webhooks",
+                        web::get().to(list_webhooks::<InMemoryWebhookRepo>),
+                    )
+                    .route(
+                        "/webhooks/{id}",
+                        web::get().to(get_webhook::<InMemoryWebhookRepo>),
+                    )
+                    .route(
+                        "/webhooks/{id}",
+                        web::put().to(update_webhook::<InMemoryWebhookRepo>),
+                    )
+                    .route(
+                        "/webhooks/{id}",
+                        web::delete().to(delete_webhook::<InMemoryWebhookRepo>),
+                    )
+                    .route(
+                        "/webhooks/{id}/dead-letters/{delivery_id}/retry",
+                        web::post().to(retry_dead_letter::<InMemoryWebhookRepo>),
+                    ),
+            )
+            .await
+        };
+    }
+
+    #[actix_web::test]
+    async fn created_webhook_returns_its_secret_once() {
+        let app = app!();
+
+        let req = actix_test::TestRequest::post()
+            .uri("/webhooks")
+            .set_json(serde_json::json!({
+                "url": "https://partner.example/hook",
+                "event_types": ["OrderPaid"]
+            }))
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        let created: serde_json::Value = actix_test::read_body_json(resp).await;
+        assert_eq!(created["secret"].as_str().map(str::len), Some(64));
+        assert_eq!(created["event_types"][0], "OrderPaid");
+
+        let req = actix_test::TestRequest::get()
+            .uri(&format!(
+                "/webhooks/{}",
+                created["id"].as_str().expect("id")
+            ))
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let fetched: serde_json::Value = actix_test::read_body_json(resp).await;
+        assert_eq!(fetched["url"], "https://partner.example/hook");
+        assert!(fetched.get("secret").is_none());
+    }
+
+    #[actix_web::test]
+    async fn invalid_url_is_rejected() {
+        let app = app!();
+
+        let req = actix_test::TestRequest::post()
+            .uri("/webhooks")
+            .set_json(serde_json::json!({ "url": "partner.example/hook" }))
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn internal_destinations_are_rejected() {
+        let app = app!();
+
+        for url in [
+            "http://169.254.169.254/latest/meta-data",
+            "https://intranet.example/hook",
+        ] {
+            let req = actix_test::TestRequest::post()
+                .uri("/webhooks")
+                .set_json(serde_json::json!({ "url": url }))
+                .to_request();
+            let resp = actix_test::call_service(&app, req).await;
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{url}");
+        }
+    }
+
+    #[actix_web::test]
+    async fn webhook_can_be_updated_and_deleted() {
+        let app = app!();
+        let req = actix_test::TestRequest::post()
+            .uri("/webhooks")
+            .set_json(serde_json::json!({ "url": "https://a.example/hook" }))
+            .to_request();
+        let created: serde_json::Value =
+            actix_test::read_body_json(actix_test::call_service(&app, req).await).await;
+        let uri = format!("/webhooks/{}", created["id"].as_str().expect("id"));
+
+        let req = actix_test::TestRequest::put()
+            .uri(&uri)
+            .set_json(serde_json::json!({ "url": "https://b.example/hook" }))
+            .to_request();
+        let updated: serde_json::Value =
+            actix_test::read_body_json(actix_test::call_service(&app, req).await).await;
+        assert_eq!(updated["url"], "https://b.example/hook");
+
+        let req = actix_test::TestRequest::delete().uri(&uri).to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+        let req = actix_test::TestRequest::get().uri(&uri).to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let req = actix_test::TestRequest::get().uri("/webhooks").to_request();
+        let listed: serde_json::Value =
+            actix_test::read_body_json(actix_test::call_service(&app, req).await).await;
+        assert_eq!(listed["items"].as_array().map(Vec::len), Some(0));
+    }
+
+    #[actix_web::test]
+    async fn retrying_an_unknown_dead_letter_is_not_found() {
+        let app = app!();
+
+        let req = actix_test::TestRequest::post()
+            .uri(&format!(
+                "/webhooks/{}/dead-letters/42/retry",
+                Uuid::new_v4()
+            ))
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+}
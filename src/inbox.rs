@@ -0,0 +1,488 @@
+//! Idempotent consumption of events published by other services.
+//!
+//! An [`Inbox`] dispatches every inbound message to the [`MessageHandler`]
+//! of its type. The handler records the message in `processed_messages` in
+//! the same transaction as its side effects: if it fails, both roll back and
+//! the message can be redelivered; if the message was already processed, the
+//! insert conflicts and nothing is applied. [`apply_once`] does this for
+//! handlers writing through a connection; the application services do it in
+//! their unit of work (see [`TransactionalInbox`]).
+//!
+//! [`consume`] retries a failing message in place until it succeeds, so that
+//! transports acknowledging by offset never commit past it.
+//!
+//! [`TransactionalInbox`]: crate::domain::ports::TransactionalInbox
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Duration;
+
+use diesel::prelude::*;
+use opentelemetry::metrics::Counter;
+use opentelemetry::{global, KeyValue};
+use serde_json::Value;
+use thiserror::Error;
+use tokio::sync::mpsc;
+
+use crate::infrastructure::models::NewProcessedMessageRow;
+use crate::schema::processed_messages;
+use crate::shutdown::ShutdownSignal;
+use crate::telemetry::{in_db_span, INSTRUMENTATION_SCOPE};
+use crate::DbPool;
+
+const INITIAL_RETRY_DELAY: Duration = Duration::from_millis(200);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Error)]
+pub enum InboxError {
+    #[error("Database error: {0}")]
+    Database(#[from] diesel::result::Error),
+
+    #[error("Connection pool error: {0}")]
+    Pool(#[from] r2d2::Error),
+
+    /// The handler refused the message; it is not recorded and may be retried.
+    #[error("Handler error: {0}")]
+    Handler(String),
+}
+
+/// A message received from another service.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InboundMessage {
+    /// Unique id assigned by the producer (e.g. the Debezium `event_id`).
+    pub id: String,
+    pub message_type: String,
+    pub payload: Value,
+}
+
+/// Applies some types of inbound message, at most once per message id.
+pub trait MessageHandler: Send + Sync + 'static {
+    /// The `message_type`s this handler consumes, e.g. `PaymentCaptured`.
+    fn message_types(&self) -> &[&'static str];
+
+    /// Apply `message`, recording it in the transaction that applies it.
+    ///
+    /// An error is transient: nothing is recorded and the message is retried.
+    /// A message that can never be applied is reported
+    /// [`Unhandled`](InboxOutcome::Unhandled) instead.
+    fn handle(&self, message: &InboundMessage) -> Result<InboxOutcome, InboxError>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InboxOutcome {
+    /// The handler applied the message and recorded it.
+    Processed,
+    /// The message had already been processed; nothing was done.
+    Duplicate,
+    /// No handler is registered for the message type, or the handler cannot
+    /// apply the message; nothing was recorded.
+    Unhandled,
+}
+
+impl InboxOutcome {
+    fn as_str(self) -> &'static str {
+        match self {
+            InboxOutcome::Processed => "processed",
+            InboxOutcome::Duplicate => "duplicate",
+            InboxOutcome::Unhandled => "unhandled",
+        }
+    }
+}
+
+/// Dispatches inbound messages to their handlers.
+#[derive(Default)]
+pub struct Inbox {
+    handlers: HashMap<&'static str, Arc<dyn MessageHandler>>,
+}
+
+impl Inbox {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `handler` for its message types, replacing any previous ones.
+    pub fn with_handler(mut self, handler: impl MessageHandler) -> Self {
+        let handler: Arc<dyn MessageHandler> = Arc::new(handler);
+        for message_type in handler.message_types() {
+            self.handlers.insert(message_type, Arc::clone(&handler));
+        }
+        self
+    }
+
+    pub fn process(&self, message: &InboundMessage) -> Result<InboxOutcome, InboxError> {
+        let outcome = match self.handlers.get(message.message_type.as_str()) {
+            None => InboxOutcome::Unhandled,
+            Some(handler) => handler.handle(message)?,
+        };
+        record_outcome(&message.message_type, outcome);
+        Ok(outcome)
+    }
+}
+
+/// Record `message` and run `apply` in one transaction on a connection from
+/// `pool`. `apply` is skipped if the message was already processed; if it
+/// fails, the record is rolled back with its writes.
+pub fn apply_once(
+    pool: &DbPool,
+    message: &InboundMessage,
+    apply: impl FnOnce(&mut PgConnection) -> Result<(), InboxError>,
+) -> Result<InboxOutcome, InboxError> {
+    let mut conn = pool.get()?;
+    conn.transaction(|conn| {
+        if !record_message(conn, &message.id, &message.message_type)? {
+            return Ok(InboxOutcome::Duplicate);
+        }
+        apply(conn)?;
+        Ok(InboxOutcome::Processed)
+    })
+}
+
+/// Record `message_id` as processed; `false` if it already was.
+///
+/// Call inside the transaction that applies the message, so that the record
+/// and the side effects commit or roll back together.
+pub fn record_message(
+    conn: &mut PgConnection,
+    message_id: &str,
+    message_type: &str,
+) -> QueryResult<bool> {
+    let inserted = in_db_span("INSERT", "processed_messages", || {
+        diesel::insert_into(processed_messages::table)
+            .values(&NewProcessedMessageRow {
+                message_id,
+                message_type,
+            })
+            .on_conflict_do_nothing()
+            .execute(conn)
+    })?;
+    Ok(inserted == 1)
+}
+
+static INBOX_MESSAGES: LazyLock<Counter<u64>> = LazyLock::new(|| {
+    global::meter(INSTRUMENTATION_SCOPE)
+        .u64_counter("messaging.inbox.messages")
+        .with_description("Inbound messages handled by the inbox")
+        .build()
+});
+
+fn record_outcome(message_type: &str, outcome: InboxOutcome) {
+    INBOX_MESSAGES.add(
+        1,
+        &[
+            KeyValue::new("messaging.message.type", message_type.to_string()),
+            KeyValue::new("inbox.outcome", outcome.as_str()),
+        ],
+    );
+}
+
+// ── Transports ───────────────────────────────────────────────────────────────
+
+/// Source of inbound messages, e.g. a Kafka consumer.
+pub trait MessageTransport: Send {
+    /// The next message, or `None` once the transport is closed.
+    fn receive(&mut self) -> impl Future<Output = Option<InboundMessage>> + Send;
+
+    /// Confirm that `message` needs no redelivery. Called after it was
+    /// processed, found to be a duplicate, or has no handler, and never for a
+    /// later message before an earlier one.
+    fn acknowledge(&mut self, message: &InboundMessage) -> impl Future<Output = ()> + Send;
+}
+
+/// Feed messages from `transport` through `inbox` until the transport closes
+/// or shutdown is signalled. Meant to run as a
+/// [`ShutdownCoordinator`](crate::shutdown::ShutdownCoordinator) worker.
+///
+/// A message whose handler fails or panics is retried with backoff before the
+/// next one is received. It stays unacknowledged if shutdown interrupts the
+/// retries, so it is redelivered after a restart.
+pub async fn consume<T: MessageTransport>(
+    inbox: Arc<Inbox>,
+    mut transport: T,
+    mut shutdown: ShutdownSignal,
+) {
+    loop {
+        let message = tokio::select! {
+            _ = shutdown.triggered() => return,
+            message = transport.receive() => match message {
+                Some(message) => Arc::new(message),
+                None => return,
+            },
+        };
+
+        let mut delay = INITIAL_RETRY_DELAY;
+        loop {
+            let inbox = Arc::clone(&inbox);
+            let attempt = Arc::clone(&message);
+            let error = match tokio::task::spawn_blocking(move || inbox.process(&attempt)).await {
+                Ok(Ok(_)) => break,
+                Ok(Err(e)) => e.to_string(),
+                Err(e) => format!("handler panicked: {}", e),
+            };
+            log::error!(
+                "Failed to process message {} (retrying in {:?}): {}",
+                message.id,
+                delay,
+                error
+            );
+            tokio::select! {
+                _ = shutdown.triggered() => return,
+                _ = tokio::time::sleep(delay) => {}
+            }
+            delay = (delay * 2).min(MAX_RETRY_DELAY);
+        }
+        transport.acknowledge(&message).await;
+    }
+}
+
+/// In-process transport, for tests and for wiring services together locally.
+pub struct InMemoryTransport {
+    receiver: mpsc::UnboundedReceiver<InboundMessage>,
+    acknowledged: Acknowledgements,
+}
+
+/// Sending half of an [`InMemoryTransport`]. The transport closes once every
+/// sender is dropped.
+#[derive(Clone)]
+pub struct InMemorySender(mpsc::UnboundedSender<InboundMessage>);
+
+/// Ids of the messages an [`InMemoryTransport`] acknowledged, in order.
+#[derive(Clone, Default)]
+pub struct Acknowledgements(Arc<Mutex<Vec<String>>>);
+
+impl InMemoryTransport {
+    pub fn new() -> (InMemorySender, Self) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        (
+            InMemorySender(sender),
+            Self {
+                receiver,
+                acknowledged: Acknowledgements::default(),
+            },
+        )
+    }
+
+    pub fn acknowledgements(&self) -> Acknowledgements {
+        self.acknowledged.clone()
+    }
+}
+
+impl InMemorySender {
+    /// Queue `message`; returns `false` if the transport is gone.
+    pub fn send(&self, message: InboundMessage) -> bool {
+        self.0.send(message).is_ok()
+    }
+}
+
+impl Acknowledgements {
+    pub fn ids(&self) -> Vec<String> {
+        self.0.lock().expect("lock poisoned").clone()
+    }
+}
+
+impl MessageTransport for InMemoryTransport {
+    async fn receive(&mut self) -> Option<InboundMessage> {
+        self.receiver.recv().await
+    }
+
+    async fn acknowledge(&mut self, message: &InboundMessage) {
+        self.acknowledged
+            .0
+            .lock()
+            .expect("lock poisoned")
+            .push(message.id.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use super::*;
+    use crate::schema::commerce_order_outbox;
+    use crate::test_support::{append_event, setup_db};
+
+    /// Records a `PaymentAcknowledged` outbox event per message, and fails
+    /// after writing it while `fail` is set.
+    struct PaymentCapturedHandler {
+        pool: DbPool,
+        fail: Arc<AtomicBool>,
+    }
+
+    impl MessageHandler for PaymentCapturedHandler {
+        fn message_types(&self) -> &[&'static str] {
+            &["PaymentCaptured"]
+        }
+
+        fn handle(&self, message: &InboundMessage) -> Result<InboxOutcome, InboxError> {
+            apply_once(&self.pool, message, |conn| {
+                append_event(
+                    conn,
+                    "Order",
+                    message.payload["order_id"].as_str().unwrap_or_default(),
+                    "PaymentAcknowledged",
+                    message.payload.clone(),
+                )
+                .map_err(|e| InboxError::Handler(e.to_string()))?;
+                if self.fail.load(Ordering::SeqCst) {
+                    return Err(InboxError::Handler("payment service unavailable".into()));
+                }
+                Ok(())
+            })
+        }
+    }
+
+    fn payment_captured(id: &str) -> InboundMessage {
+        InboundMessage {
+            id: id.to_string(),
+            message_type: "PaymentCaptured".to_string(),
+            payload: serde_json::json!({ "order_id": "order-1" }),
+        }
+    }
+
+    fn counts(pool: &DbPool) -> (i64, i64) {
+        let mut conn = pool.get().expect("connection");
+        let processed = processed_messages::table
+            .count()
+            .get_result(&mut conn)
+            .expect("count");
+        let events = commerce_order_outbox::table
+            .count()
+            .get_result(&mut conn)
+            .expect("count");
+        (processed, events)
+    }
+
+    fn inbox(pool: &DbPool) -> (Arc<AtomicBool>, Arc<Inbox>) {
+        let fail = Arc::new(AtomicBool::new(false));
+        let inbox = Inbox::new().with_handler(PaymentCapturedHandler {
+            pool: pool.clone(),
+            fail: Arc::clone(&fail),
+        });
+        (fail, Arc::new(inbox))
+    }
+
+    #[tokio::test]
+    async fn duplicate_deliveries_are_applied_once() {
+        let (_container, pool) = setup_db().await;
+        let (_, inbox) = inbox(&pool);
+        let (sender, transport) = InMemoryTransport::new();
+        let (_stop, signal) = ShutdownSignal::for_test();
+
+        for id in ["m-1", "m-1", "m-2", "m-1"] {
+            sender.send(payment_captured(id));
+        }
+        drop(sender);
+        let acknowledged = transport.acknowledgements();
+        consume(inbox, transport, signal).await;
+
+        assert_eq!(counts(&pool), (2, 2));
+        assert_eq!(acknowledged.ids(), ["m-1", "m-1", "m-2", "m-1"]);
+    }
+
+    #[tokio::test]
+    async fn failed_handler_rolls_back_and_can_be_retried() {
+        let (_container, pool) = setup_db().await;
+        let (fail, inbox) = inbox(&pool);
+
+        fail.store(true, Ordering::SeqCst);
+        let result = inbox.process(&payment_captured("m-1"));
+        assert!(matches!(result, Err(InboxError::Handler(_))));
+        assert_eq!(counts(&pool), (0, 0));
+
+        fail.store(false, Ordering::SeqCst);
+        assert_eq!(
+            inbox.process(&payment_captured("m-1")).expect("process"),
+            InboxOutcome::Processed
+        );
+        assert_eq!(counts(&pool), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn failed_messages_are_not_acknowledged() {
+        let (_container, pool) = setup_db().await;
+        let (fail, inbox) = inbox(&pool);
+        fail.store(true, Ordering::SeqCst);
+        let (sender, transport) = InMemoryTransport::new();
+        let (stop, signal) = ShutdownSignal::for_test();
+
+        sender.send(payment_captured("m-1"));
+        drop(sender);
+        let acknowledged = transport.acknowledgements();
+        let consumer = tokio::spawn(consume(inbox, transport, signal));
+        tokio::time::sleep(INITIAL_RETRY_DELAY).await;
+        stop.send(true).expect("consumer listening");
+        tokio::time::timeout(Duration::from_secs(5), consumer)
+            .await
+            .expect("consumer stopped")
+            .expect("consumer did not panic");
+
+        assert!(acknowledged.ids().is_empty());
+        assert_eq!(counts(&pool), (0, 0));
+    }
+
+    #[tokio::test]
+    async fn failed_message_is_retried_before_the_next_one() {
+        let (_container, pool) = setup_db().await;
+        let (fail, inbox) = inbox(&pool);
+        fail.store(true, Ordering::SeqCst);
+        let (sender, transport) = InMemoryTransport::new();
+        let (_stop, signal) = ShutdownSignal::for_test();
+
+        sender.send(payment_captured("m-1"));
+        sender.send(payment_captured("m-2"));
+        drop(sender);
+        let acknowledged = transport.acknowledgements();
+        let consumer = tokio::spawn(consume(inbox, transport, signal));
+
+        // m-2 would succeed, but is not received while m-1 keeps failing.
+        tokio::time::sleep(INITIAL_RETRY_DELAY * 2).await;
+        assert!(acknowledged.ids().is_empty());
+        assert_eq!(counts(&pool), (0, 0));
+
+        fail.store(false, Ordering::SeqCst);
+        tokio::time::timeout(Duration::from_secs(5), consumer)
+            .await
+            .expect("consumer drained the transport")
+            .expect("consumer did not panic");
+        assert_eq!(acknowledged.ids(), ["m-1", "m-2"]);
+        assert_eq!(counts(&pool), (2, 2));
+    }
+
+    #[tokio::test]
+    async fn messages_without_a_handler_are_not_recorded() {
+        let (_container, pool) = setup_db().await;
+        let (_, inbox) = inbox(&pool);
+
+        let message = InboundMessage {
+            message_type: "ShipmentDispatched".to_string(),
+            ..payment_captured("m-1")
+        };
+        assert_eq!(
+            inbox.process(&message).expect("process"),
+            InboxOutcome::Unhandled
+        );
+        assert_eq!(counts(&pool), (0, 0));
+    }
+
+    #[tokio::test]
+    async fn consumer_stops_on_shutdown() {
+        let (_container, pool) = setup_db().await;
+        let (_, inbox) = inbox(&pool);
+        let (sender, transport) = InMemoryTransport::new();
+        let (stop, signal) = ShutdownSignal::for_test();
+
+        let acknowledged = transport.acknowledgements();
+        let consumer = tokio::spawn(consume(inbox, transport, signal));
+        sender.send(payment_captured("m-1"));
+        while acknowledged.ids().is_empty() {
+            tokio::task::yield_now().await;
+        }
+        stop.send(true).expect("consumer listening");
+        tokio::time::timeout(Duration::from_secs(5), consumer)
+            .await
+            .expect("consumer stopped")
+            .expect("consumer did not panic");
+        assert_eq!(counts(&pool), (1, 1));
+    }
+}
@@ -0,0 +1,50 @@
+//! [`HostResolver`]s for webhook destinations.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, ToSocketAddrs};
+
+use crate::domain::errors::DomainError;
+use crate::domain::ports::HostResolver;
+
+/// Resolves through the operating system, as the HTTP client does.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemHostResolver;
+
+impl HostResolver for SystemHostResolver {
+    fn resolve(&self, host: &str, port: u16) -> Result<Vec<IpAddr>, DomainError> {
+        let addrs = (host, port).to_socket_addrs().map_err(|e| {
+            DomainError::InvalidInput(format!("url host {} does not resolve: {}", host, e))
+        })?;
+        Ok(addrs.map(|addr| addr.ip()).collect())
+    }
+}
+
+/// Fixed answers, for tests and local experiments. IP addresses resolve to
+/// themselves; unknown names do not resolve.
+#[derive(Debug, Clone, Default)]
+pub struct StaticHostResolver {
+    hosts: HashMap<String, Vec<IpAddr>>,
+}
+
+impl StaticHostResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_host(mut self, host: impl Into<String>, addrs: Vec<IpAddr>) -> Self {
+        self.hosts.insert(host.into(), addrs);
+        self
+    }
+}
+
+impl HostResolver for StaticHostResolver {
+    fn resolve(&self, host: &str, _port: u16) -> Result<Vec<IpAddr>, DomainError> {
+        if let Ok(ip) = host.parse() {
+            return Ok(vec![ip]);
+        }
+        self.hosts
+            .get(host)
+            .cloned()
+            .ok_or_else(|| DomainError::InvalidInput(format!("url host {} does not resolve", host)))
+    }
+}
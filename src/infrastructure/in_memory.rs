@@ -0,0 +1,268 @@
+//! In-memory [`UnitOfWork`] for tests and local experiments.
+//!
+//! Each unit works on a copy of the store that replaces it only when the unit
+//! succeeds, so a failed unit leaves nothing behind, as a rolled-back
+//! transaction would. Units run one at a time.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::domain::errors::DomainError;
+use crate::domain::order::{OrderLineInput, OrderLineView, OrderStatus, OrderView, SourceMessage};
+use crate::domain::outbox::{OutboxEvent, OutboxMessage};
+use crate::domain::ports::{
+    Transaction, TransactionalInbox, TransactionalOrderRepository, TransactionalOutbox,
+    TransactionalSagaRepository, UnitOfWork,
+};
+use crate::domain::saga::{OrderSaga, SagaStart, SagaTransition};
+
+#[derive(Debug, Clone, Default)]
+struct Store {
+    orders: HashMap<Uuid, OrderView>,
+    sagas: HashMap<Uuid, OrderSaga>,
+    /// Appended events, oldest first.
+    outbox: Vec<OutboxEvent>,
+    processed_messages: HashSet<String>,
+}
+
+/// Clones share the store.
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryUnitOfWork {
+    store: Arc<Mutex<Store>>,
+}
+
+impl InMemoryUnitOfWork {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn store(&self) -> MutexGuard<'_, Store> {
+        // A panicking unit never replaced the store, so it is still consistent.
+        self.store.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn order(&self, id: Uuid) -> Option<OrderView> {
+        self.store().orders.get(&id).cloned()
+    }
+
+    pub fn saga(&self, order_id: Uuid) -> Option<OrderSaga> {
+        self.store().sagas.get(&order_id).cloned()
+    }
+
+    /// Committed outbox events, oldest first.
+    pub fn outbox(&self) -> Vec<OutboxEvent> {
+        self.store().outbox.clone()
+    }
+}
+
+impl UnitOfWork for InMemoryUnitOfWork {
+    fn run<T, E>(&self, work: impl FnOnce(&mut dyn Transaction) -> Result<T, E>) -> Result<T, E>
+    where
+        E: From<DomainError>,
+    {
+        let mut store = self.store();
+        let mut copy = store.clone();
+        let value = work(&mut copy)?;
+        *store = copy;
+        Ok(value)
+    }
+}
+
+impl Transaction for Store {
+    fn orders(&mut self) -> &mut dyn TransactionalOrderRepository {
+        self
+    }
+
+    fn sagas(&mut self) -> &mut dyn TransactionalSagaRepository {
+        self
+    }
+
+    fn outbox(&mut self) -> &mut dyn TransactionalOutbox {
+        self
+    }
+
+    fn inbox(&mut self) -> &mut dyn TransactionalInbox {
+        self
+    }
+}
+
+impl TransactionalOrderRepository for Store {
+    fn find_by_id(&mut self, id: Uuid) -> Result<Option<OrderView>, DomainError> {
+        Ok(self.orders.get(&id).cloned())
+    }
+
+    fn insert(
+        &mut self,
+        order_id: Uuid,
+        customer_id: Uuid,
+        lines: &[OrderLineInput],
+    ) -> Result<(), DomainError> {
+        if self.orders.contains_key(&order_id) {
+            return Err(DomainError::Internal(format!(
+                "order {} already exists",
+                order_id
+            )));
+        }
+        let order = OrderView {
+            id: order_id,
+            customer_id,
+            status: OrderStatus::Pending.as_str().to_string(),
+            created_at: Utc::now(),
+            lines: lines
+                .iter()
+                .map(|l| OrderLineView {
+                    id: Uuid::new_v4(),
+                    product_id: l.product_id,
+                    quantity: l.quantity,
+                    unit_price: l.unit_price.clone(),
+                })
+                .collect(),
+        };
+        self.orders.insert(order_id, order);
+        Ok(())
+    }
+
+    fn update_status(
+        &mut self,
+        order_id: Uuid,
+        from: OrderStatus,
+        to: OrderStatus,
+    ) -> Result<bool, DomainError> {
+        match self.orders.get_mut(&order_id) {
+            Some(order) if order.status == from.as_str() => {
+                order.status = to.as_str().to_string();
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+}
+
+impl TransactionalSagaRepository for Store {
+    fn start(&mut self, start: &SagaStart) -> Result<(), DomainError> {
+        let order_id = start.saga.order_id;
+        if self.sagas.contains_key(&order_id) {
+            return Err(DomainError::Internal(format!(
+                "order {} already has a saga",
+                order_id
+            )));
+        }
+        self.sagas.insert(order_id, start.saga.clone());
+        self.append(&start.command.for_order(order_id))
+    }
+
+    fn find(&mut self, order_id: Uuid) -> Result<Option<OrderSaga>, DomainError> {
+        Ok(self.sagas.get(&order_id).cloned())
+    }
+
+    fn advance(&mut self, transition: &SagaTransition) -> Result<bool, DomainError> {
+        match self.sagas.get_mut(&transition.order_id) {
+            Some(saga) if saga.version == transition.version => {
+                saga.state = transition.to;
+                saga.version += 1;
+                saga.deadline = transition.deadline;
+                saga.failure_reason = transition.failure_reason.clone();
+                saga.updated_at = Utc::now();
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+}
+
+impl TransactionalOutbox for Store {
+    fn append(&mut self, message: &dyn OutboxMessage) -> Result<(), DomainError> {
+        let mut event = OutboxEvent::new(
+            message.aggregate_type(),
+            message.aggregate_id(),
+            message.event_type(),
+            message.payload(),
+        );
+        event.headers = message.headers();
+        self.outbox.push(event);
+        Ok(())
+    }
+}
+
+impl TransactionalInbox for Store {
+    fn record(&mut self, message: &SourceMessage) -> Result<bool, DomainError> {
+        Ok(self.processed_messages.insert(message.id.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order_created(tx: &mut dyn Transaction, order_id: Uuid) -> Result<(), DomainError> {
+        tx.orders().insert(order_id, Uuid::new_v4(), &[])?;
+        tx.outbox().append(&OutboxEvent::new(
+            "Order",
+            order_id.to_string(),
+            "OrderCreated",
+            serde_json::Value::Null,
+        ))
+    }
+
+    #[test]
+    fn commits_a_successful_unit() {
+        let uow = InMemoryUnitOfWork::new();
+        let order_id = Uuid::new_v4();
+
+        uow.run(|tx| order_created(tx, order_id)).expect("commit");
+
+        assert_eq!(
+            uow.order(order_id).map(|o| o.status),
+            Some("PENDING".to_string())
+        );
+        assert_eq!(uow.outbox().len(), 1);
+    }
+
+    #[test]
+    fn rolls_back_a_failed_unit() {
+        let uow = InMemoryUnitOfWork::new();
+        let order_id = Uuid::new_v4();
+        uow.run(|tx| order_created(tx, order_id)).expect("commit");
+
+        let result: Result<(), DomainError> = uow.run(|tx| {
+            assert!(tx.orders().update_status(
+                order_id,
+                OrderStatus::Pending,
+                OrderStatus::Paid
+            )?);
+            tx.outbox().append(&OutboxEvent::new(
+                "Order",
+                order_id.to_string(),
+                "OrderPaid",
+                serde_json::Value::Null,
+            ))?;
+            Err(DomainError::Internal(
+                "payment service unavailable".to_string(),
+            ))
+        });
+
+        assert!(result.is_err());
+        assert_eq!(
+            uow.order(order_id).map(|o| o.status),
+            Some("PENDING".to_string())
+        );
+        assert_eq!(uow.outbox().len(), 1);
+    }
+
+    #[test]
+    fn records_each_message_once() {
+        let uow = InMemoryUnitOfWork::new();
+        let message = SourceMessage {
+            id: "payment-1".to_string(),
+            message_type: "PaymentCaptured".to_string(),
+        };
+
+        let first = uow.run(|tx| tx.inbox().record(&message)).expect("record");
+        let again = uow.run(|tx| tx.inbox().record(&message)).expect("record");
+        assert!(first);
+        assert!(!again);
+    }
+}
@@ -1,2 +1,9 @@
+pub mod host_resolver;
+pub mod in_memory;
 pub mod models;
 pub mod order_repo;
+pub mod outbox;
+pub mod outbox_publication_repo;
+pub mod saga_repo;
+pub mod unit_of_work;
+pub mod webhook_repo;
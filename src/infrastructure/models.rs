@@ -5,7 +5,11 @@ use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use uuid::Uuid;
 
-use crate::schema::{commerce_order_outbox, order_lines, orders};
+use crate::schema::{
+    commerce_order_outbox, commerce_order_outbox_publications, debezium_signal, order_lines,
+    order_saga_steps, order_sagas, orders, outbox_snapshot_requests, processed_messages,
+    webhook_deliveries, webhook_subscriptions,
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize, Queryable, Selectable, Identifiable)]
 #[diesel(table_name = orders)]
@@ -51,7 +55,9 @@ pub struct NewOrderLineRow {
     pub unit_price: BigDecimal,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Selectable, Identifiable)]
+#[derive(
+    Debug, Clone, Serialize, Deserialize, Queryable, QueryableByName, Selectable, Identifiable,
+)]
 #[diesel(table_name = commerce_order_outbox)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
 pub struct OutboxEventRow {
@@ -61,6 +67,10 @@ pub struct OutboxEventRow {
     pub event_type: String,
     pub payload: Value,
     pub created_at: DateTime<Utc>,
+    /// Position of the event within its aggregate, starting at 1.
+    pub sequence: i64,
+    /// Message headers, a JSON object of strings.
+    pub headers: Value,
 }
 
 #[derive(Debug, Insertable)]
@@ -71,4 +81,151 @@ pub struct NewOutboxEventRow {
     pub aggregate_id: String,
     pub event_type: String,
     pub payload: Value,
+    pub sequence: i64,
+    pub headers: Value,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = processed_messages)]
+pub struct NewProcessedMessageRow<'a> {
+    pub message_id: &'a str,
+    pub message_type: &'a str,
+}
+
+#[derive(Debug, Clone, Queryable, Selectable, Identifiable)]
+#[diesel(table_name = order_sagas)]
+#[diesel(primary_key(order_id))]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct OrderSagaRow {
+    pub order_id: Uuid,
+    pub customer_id: Uuid,
+    pub amount: BigDecimal,
+    pub state: String,
+    pub version: i64,
+    pub step_deadline: Option<DateTime<Utc>>,
+    pub failure_reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = order_sagas)]
+pub struct NewOrderSagaRow<'a> {
+    pub order_id: Uuid,
+    pub customer_id: Uuid,
+    pub amount: &'a BigDecimal,
+    pub state: &'a str,
+    pub step_deadline: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Queryable, Selectable)]
+#[diesel(table_name = order_saga_steps)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct OrderSagaStepRow {
+    pub id: i64,
+    pub order_id: Uuid,
+    pub step: String,
+    pub outcome: String,
+    pub detail: Option<String>,
+    pub recorded_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = order_saga_steps)]
+pub struct NewOrderSagaStepRow<'a> {
+    pub order_id: Uuid,
+    pub step: &'a str,
+    pub outcome: &'a str,
+    pub detail: Option<&'a str>,
+}
+
+#[derive(Debug, Clone, Queryable, Selectable, Identifiable)]
+#[diesel(table_name = webhook_subscriptions)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct WebhookSubscriptionRow {
+    pub id: Uuid,
+    pub url: String,
+    pub event_types: Vec<Option<String>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = webhook_subscriptions)]
+pub struct NewWebhookSubscriptionRow<'a> {
+    pub id: Uuid,
+    pub url: &'a str,
+    pub event_types: &'a [String],
+}
+
+#[derive(Debug, Clone, Queryable, Selectable, Identifiable)]
+#[diesel(table_name = webhook_deliveries)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct WebhookDeliveryRow {
+    pub id: i64,
+    pub subscription_id: Uuid,
+    pub event_id: Uuid,
+    pub event_type: String,
+    pub body: Value,
+    pub status: String,
+    pub attempts: i32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = webhook_deliveries)]
+pub struct NewWebhookDeliveryRow<'a> {
+    pub subscription_id: Uuid,
+    pub event_id: Uuid,
+    pub event_type: &'a str,
+    pub body: &'a Value,
+}
+
+#[derive(Debug, Clone, Queryable, Selectable, Identifiable)]
+#[diesel(table_name = commerce_order_outbox_publications)]
+#[diesel(primary_key(event_id))]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct OutboxPublicationRow {
+    pub event_id: Uuid,
+    pub status: String,
+    pub attempts: i32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = debezium_signal)]
+pub struct NewDebeziumSignalRow<'a> {
+    pub id: &'a str,
+    pub signal_type: &'a str,
+    pub data: Option<&'a str>,
+}
+
+#[derive(Debug, Clone, Serialize, Queryable, Selectable, Identifiable)]
+#[diesel(table_name = outbox_snapshot_requests)]
+#[diesel(primary_key(signal_id))]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct SnapshotRequestRow {
+    pub signal_id: String,
+    pub data_collection: String,
+    pub condition: Option<String>,
+    pub matching_events: i64,
+    pub signal_lsn: Option<i64>,
+    pub status: String,
+    pub requested_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = outbox_snapshot_requests)]
+pub struct NewSnapshotRequestRow<'a> {
+    pub signal_id: &'a str,
+    pub data_collection: &'a str,
+    pub condition: Option<&'a str>,
+    pub matching_events: i64,
 }
@@ -0,0 +1,368 @@
+use std::collections::BTreeMap;
+
+use diesel::prelude::*;
+use diesel::sql_types;
+use serde_json::Value;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::domain::errors::DomainError;
+use crate::domain::outbox::OutboxMessage;
+use crate::telemetry::in_db_span;
+
+use super::models::OutboxEventRow;
+
+/// Header of an order outbox row re-inserted by `order-admin outbox replay`,
+/// carrying the id of the event it replays. Such copies are published again
+/// but left out of the order's history and of webhook fan-out.
+pub const REPLAY_OF_HEADER: &str = "replay_of";
+
+#[derive(Debug, Error)]
+pub enum OutboxWriteError {
+    /// The message carries headers the writer has no column for.
+    #[error("{table} has no headers column, refusing a message with headers")]
+    HeadersRefused { table: &'static str },
+
+    #[error("Database error: {0}")]
+    Database(#[from] diesel::result::Error),
+}
+
+impl From<OutboxWriteError> for DomainError {
+    fn from(e: OutboxWriteError) -> Self {
+        DomainError::Internal(e.to_string())
+    }
+}
+
+/// Writes [`OutboxMessage`]s to an outbox table, inside the caller's
+/// transaction.
+///
+/// The table needs the columns of `commerce_order_outbox` other than
+/// `headers`; headers are stored only by writers given a headers column.
+/// Sequence numbers come from a counter table with the columns of
+/// `outbox_aggregate_sequences`, that one unless configured otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutboxWriter {
+    table: &'static str,
+    headers_column: Option<&'static str>,
+    sequence_table: &'static str,
+}
+
+/// Writer of the order service's own outbox.
+pub const ORDER_OUTBOX: OutboxWriter =
+    OutboxWriter::new("commerce_order_outbox").with_headers_column("headers");
+
+impl OutboxWriter {
+    /// A writer of `table`, which may be schema-qualified (`schema.table`).
+    pub const fn new(table: &'static str) -> Self {
+        Self {
+            table,
+            headers_column: None,
+            sequence_table: "outbox_aggregate_sequences",
+        }
+    }
+
+    /// Store message headers as a JSON object in `column`.
+    pub const fn with_headers_column(mut self, column: &'static str) -> Self {
+        self.headers_column = Some(column);
+        self
+    }
+
+    /// Count sequence numbers in `table` (possibly schema-qualified), e.g. to
+    /// keep them next to an outbox in another schema.
+    pub const fn with_sequence_table(mut self, table: &'static str) -> Self {
+        self.sequence_table = table;
+        self
+    }
+
+    pub fn table(&self) -> &'static str {
+        self.table
+    }
+
+    /// Hand out the next sequence number of an aggregate.
+    ///
+    /// The upsert keeps the counter row locked until the surrounding
+    /// transaction ends, so concurrent writers of one aggregate are
+    /// serialised and numbers are never reused; a rolled-back transaction
+    /// gives its number back.
+    pub fn next_sequence(
+        &self,
+        conn: &mut PgConnection,
+        aggregate_type: &str,
+        aggregate_id: &str,
+    ) -> QueryResult<i64> {
+        #[derive(QueryableByName)]
+        struct Sequence {
+            #[diesel(sql_type = sql_types::BigInt)]
+            last_sequence: i64,
+        }
+
+        let table = quoted(self.sequence_table);
+        let query = diesel::sql_query(format!(
+            "INSERT INTO {table} (aggregate_type, aggregate_id, last_sequence) VALUES ($1, $2, 1) \
+             ON CONFLICT (aggregate_type, aggregate_id) \
+             DO UPDATE SET last_sequence = {table}.last_sequence + 1 \
+             RETURNING last_sequence",
+        ))
+        .bind::<sql_types::Text, _>(aggregate_type)
+        .bind::<sql_types::Text, _>(aggregate_id);
+        in_db_span("INSERT", self.sequence_table, || {
+            query.get_result::<Sequence>(conn)
+        })
+        .map(|sequence| sequence.last_sequence)
+    }
+
+    /// Append `message` with its aggregate's next sequence number.
+    ///
+    /// Must run inside the transaction that changes the aggregate. Messages
+    /// with headers are refused by a writer without a headers column rather
+    /// than losing them.
+    pub fn append<M: OutboxMessage + ?Sized>(
+        &self,
+        conn: &mut PgConnection,
+        message: &M,
+    ) -> Result<OutboxEventRow, OutboxWriteError> {
+        let headers = message.headers();
+        if !headers.is_empty() && self.headers_column.is_none() {
+            return Err(OutboxWriteError::HeadersRefused { table: self.table });
+        }
+
+        let aggregate_type = message.aggregate_type();
+        let aggregate_id = message.aggregate_id();
+        let sequence = self.next_sequence(conn, aggregate_type, &aggregate_id)?;
+
+        let mut columns =
+            "id, aggregate_type, aggregate_id, event_type, payload, sequence".to_string();
+        let mut values = "$1, $2, $3, $4, $5, $6".to_string();
+        let returned_headers = match self.headers_column {
+            Some(column) => {
+                columns.push_str(&format!(", {}", quoted(column)));
+                values.push_str(", $7");
+                quoted(column)
+            }
+            None => "'{}'::jsonb".to_string(),
+        };
+        let query = diesel::sql_query(format!(
+            "INSERT INTO {} ({}) VALUES ({}) RETURNING id, aggregate_type, aggregate_id, \
+             event_type, payload, created_at, sequence, {} AS headers",
+            quoted(self.table),
+            columns,
+            values,
+            returned_headers
+        ))
+        .bind::<sql_types::Uuid, _>(Uuid::new_v4())
+        .bind::<sql_types::Text, _>(aggregate_type)
+        .bind::<sql_types::Text, _>(&aggregate_id)
+        .bind::<sql_types::Text, _>(message.event_type())
+        .bind::<sql_types::Jsonb, _>(message.payload())
+        .bind::<sql_types::BigInt, _>(sequence)
+        .into_boxed();
+        let query = match self.headers_column {
+            Some(_) => query.bind::<sql_types::Jsonb, _>(headers_json(headers)),
+            None => query,
+        };
+        Ok(in_db_span("INSERT", self.table, || query.get_result(conn))?)
+    }
+}
+
+/// `name`, possibly schema-qualified, as a quoted SQL identifier.
+fn quoted(name: &str) -> String {
+    name.split('.')
+        .map(|part| format!("\"{}\"", part.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+fn headers_json(headers: BTreeMap<String, String>) -> Value {
+    Value::Object(
+        headers
+            .into_iter()
+            .map(|(name, value)| (name, Value::String(value)))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Barrier};
+
+    use diesel::connection::SimpleConnection;
+    use serde_json::json;
+
+    use super::*;
+    use crate::domain::outbox::OutboxEvent;
+    use crate::test_support::{append_event, setup_db};
+
+    #[tokio::test]
+    async fn sequences_are_per_aggregate_and_start_at_one() {
+        let (_container, pool) = setup_db().await;
+        let mut conn = pool.get().expect("connection");
+
+        let a1 =
+            append_event(&mut conn, "Order", "a", "OrderCreated", Value::Null).expect("append");
+        let a2 = append_event(&mut conn, "Order", "a", "OrderPaid", Value::Null).expect("append");
+        let b1 =
+            append_event(&mut conn, "Order", "b", "OrderCreated", Value::Null).expect("append");
+        assert_eq!((a1.sequence, a2.sequence, b1.sequence), (1, 2, 1));
+    }
+
+    #[tokio::test]
+    async fn rolled_back_transactions_do_not_leave_gaps() {
+        let (_container, pool) = setup_db().await;
+        let mut conn = pool.get().expect("connection");
+
+        append_event(&mut conn, "Order", "a", "OrderCreated", Value::Null).expect("append");
+        let _ = conn.transaction::<(), OutboxWriteError, _>(|conn| {
+            append_event(conn, "Order", "a", "OrderPaid", Value::Null)?;
+            Err(diesel::result::Error::RollbackTransaction.into())
+        });
+        let next = append_event(&mut conn, "Order", "a", "OrderPaid", Value::Null).expect("append");
+        assert_eq!(next.sequence, 2);
+    }
+
+    #[tokio::test]
+    async fn concurrent_writers_never_share_a_sequence() {
+        let (_container, pool) = setup_db().await;
+        let writers = 8;
+        let barrier = Arc::new(Barrier::new(writers));
+
+        let handles: Vec<_> = (0..writers)
+            .map(|_| {
+                let (pool, barrier) = (pool.clone(), Arc::clone(&barrier));
+                std::thread::spawn(move || {
+                    let mut conn = pool.get().expect("connection");
+                    barrier.wait();
+                    conn.transaction(|conn| {
+                        append_event(conn, "Order", "shared", "OrderPaid", Value::Null)
+                    })
+                    .expect("append")
+                    .sequence
+                })
+            })
+            .collect();
+
+        let mut sequences: Vec<i64> = handles
+            .into_iter()
+            .map(|h| h.join().expect("writer thread"))
+            .collect();
+        sequences.sort_unstable();
+        assert_eq!(sequences, (1..=writers as i64).collect::<Vec<_>>());
+    }
+
+    #[derive(QueryableByName)]
+    struct Headers {
+        #[diesel(sql_type = sql_types::Jsonb)]
+        headers: Value,
+    }
+
+    #[tokio::test]
+    async fn writes_to_any_outbox_table_with_its_headers() {
+        let (_container, pool) = setup_db().await;
+        let mut conn = pool.get().expect("connection");
+        diesel::sql_query(
+            "CREATE TABLE payment_outbox (LIKE commerce_order_outbox INCLUDING ALL, \
+             metadata JSONB NOT NULL DEFAULT '{}')",
+        )
+        .execute(&mut conn)
+        .expect("create table");
+        let writer = OutboxWriter::new("payment_outbox").with_headers_column("metadata");
+
+        let event = OutboxEvent::new(
+            "Payment",
+            "p-1",
+            "PaymentCaptured",
+            json!({ "amount": "9.99" }),
+        )
+        .with_header("tenant", "acme");
+        let row = conn
+            .transaction(|conn| writer.append(conn, &event))
+            .expect("append");
+        assert_eq!(row.aggregate_id, "p-1");
+        assert_eq!(row.payload, json!({ "amount": "9.99" }));
+        assert_eq!(row.sequence, 1);
+        assert_eq!(row.headers, json!({ "tenant": "acme" }));
+
+        let stored =
+            diesel::sql_query("SELECT metadata AS headers FROM payment_outbox WHERE id = $1")
+                .bind::<sql_types::Uuid, _>(row.id)
+                .get_result::<Headers>(&mut conn)
+                .expect("stored headers");
+        assert_eq!(stored.headers, json!({ "tenant": "acme" }));
+        // Not in the order outbox.
+        assert!(append_event(&mut conn, "Order", "o-1", "OrderCreated", Value::Null).is_ok());
+        let orders: i64 = crate::schema::commerce_order_outbox::table
+            .count()
+            .get_result(&mut conn)
+            .expect("count");
+        assert_eq!(orders, 1);
+    }
+
+    #[tokio::test]
+    async fn refuses_headers_without_a_headers_column() {
+        let (_container, pool) = setup_db().await;
+        let mut conn = pool.get().expect("connection");
+
+        let writer = OutboxWriter::new("commerce_order_outbox");
+        let event = OutboxEvent::new("Order", "a", "OrderCreated", Value::Null);
+        let row = writer.append(&mut conn, &event).expect("append");
+        assert_eq!(row.headers, json!({}));
+
+        let result = writer.append(&mut conn, &event.with_header("tenant", "acme"));
+        assert!(matches!(
+            result,
+            Err(OutboxWriteError::HeadersRefused {
+                table: "commerce_order_outbox"
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn sequences_can_be_counted_in_another_table() {
+        let (_container, pool) = setup_db().await;
+        let mut conn = pool.get().expect("connection");
+        conn.batch_execute(
+            "CREATE SCHEMA payments; \
+             CREATE TABLE payments.outbox (LIKE commerce_order_outbox INCLUDING ALL); \
+             CREATE TABLE payments.sequences (LIKE outbox_aggregate_sequences INCLUDING ALL);",
+        )
+        .expect("create tables");
+        let writer = OutboxWriter::new("payments.outbox").with_sequence_table("payments.sequences");
+
+        let event = OutboxEvent::new("Payment", "p-1", "PaymentCaptured", Value::Null);
+        writer.append(&mut conn, &event).expect("append");
+        let second = writer.append(&mut conn, &event).expect("append");
+        assert_eq!(second.sequence, 2);
+
+        let counted: i64 = crate::schema::outbox_aggregate_sequences::table
+            .count()
+            .get_result(&mut conn)
+            .expect("count");
+        assert_eq!(counted, 0, "the default counters are untouched");
+        // The order outbox still counts on its own.
+        let order =
+            append_event(&mut conn, "Payment", "p-1", "OrderCreated", Value::Null).expect("append");
+        assert_eq!(order.sequence, 1);
+    }
+
+    #[tokio::test]
+    async fn order_outbox_keeps_headers() {
+        let (_container, pool) = setup_db().await;
+        let mut conn = pool.get().expect("connection");
+
+        let event = OutboxEvent::new("Order", "a", "OrderCreated", Value::Null).with_header(
+            "traceparent",
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+        );
+        let row = ORDER_OUTBOX.append(&mut conn, &event).expect("append");
+
+        let stored: Value = crate::schema::commerce_order_outbox::table
+            .find(row.id)
+            .select(crate::schema::commerce_order_outbox::headers)
+            .get_result(&mut conn)
+            .expect("stored headers");
+        assert_eq!(
+            stored,
+            json!({ "traceparent": "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01" })
+        );
+        assert_eq!(row.headers, stored);
+    }
+}
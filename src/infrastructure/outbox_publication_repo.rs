@@ -0,0 +1,495 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use diesel::dsl::{exists, not};
+use diesel::prelude::*;
+use diesel::sql_types::{self, Text};
+use uuid::Uuid;
+
+use crate::db::DbPool;
+use crate::domain::errors::DomainError;
+use crate::domain::outbox::{OutboxDeadLetter, PublicationStatus};
+use crate::domain::ports::OutboxDeadLetterRepository;
+use crate::schema::{
+    commerce_order_outbox, commerce_order_outbox_publications, outbox_relay_baseline,
+};
+use crate::telemetry::{in_db_span, in_span};
+
+use super::models::{OutboxEventRow, OutboxPublicationRow};
+
+pub struct DieselOutboxDeadLetterRepository {
+    pool: DbPool,
+}
+
+impl DieselOutboxDeadLetterRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+}
+
+impl OutboxDeadLetterRepository for DieselOutboxDeadLetterRepository {
+    fn list(&self, limit: i64) -> Result<Vec<OutboxDeadLetter>, DomainError> {
+        in_span("DieselOutboxDeadLetterRepository.list", || {
+            let mut conn = self.pool.get()?;
+            let rows = in_db_span("SELECT", "commerce_order_outbox_publications", || {
+                commerce_order_outbox_publications::table
+                    .inner_join(commerce_order_outbox::table)
+                    .filter(
+                        commerce_order_outbox_publications::status
+                            .eq(PublicationStatus::Dead.as_str()),
+                    )
+                    .order(commerce_order_outbox_publications::updated_at.desc())
+                    .limit(limit)
+                    .select((
+                        OutboxPublicationRow::as_select(),
+                        OutboxEventRow::as_select(),
+                    ))
+                    .load(&mut conn)
+            })?;
+            Ok(rows.into_iter().map(dead_letter).collect())
+        })
+    }
+
+    fn find(&self, event_id: Uuid) -> Result<Option<OutboxDeadLetter>, DomainError> {
+        in_span("DieselOutboxDeadLetterRepository.find", || {
+            let mut conn = self.pool.get()?;
+            let row = in_db_span("SELECT", "commerce_order_outbox_publications", || {
+                commerce_order_outbox_publications::table
+                    .inner_join(commerce_order_outbox::table)
+                    .filter(commerce_order_outbox_publications::event_id.eq(event_id))
+                    .filter(
+                        commerce_order_outbox_publications::status
+                            .eq(PublicationStatus::Dead.as_str()),
+                    )
+                    .select((
+                        OutboxPublicationRow::as_select(),
+                        OutboxEventRow::as_select(),
+                    ))
+                    .first(&mut conn)
+                    .optional()
+            })?;
+            Ok(row.map(dead_letter))
+        })
+    }
+
+    fn requeue(&self, event_id: Uuid) -> Result<bool, DomainError> {
+        in_span("DieselOutboxDeadLetterRepository.requeue", || {
+            let mut conn = self.pool.get()?;
+            let updated = in_db_span("UPDATE", "commerce_order_outbox_publications", || {
+                diesel::update(
+                    commerce_order_outbox_publications::table
+                        .find(event_id)
+                        .filter(
+                            commerce_order_outbox_publications::status
+                                .eq(PublicationStatus::Dead.as_str()),
+                        ),
+                )
+                .set((
+                    commerce_order_outbox_publications::status
+                        .eq(PublicationStatus::Pending.as_str()),
+                    commerce_order_outbox_publications::attempts.eq(0),
+                    commerce_order_outbox_publications::next_attempt_at.eq(diesel::dsl::now),
+                    commerce_order_outbox_publications::updated_at.eq(diesel::dsl::now),
+                ))
+                .execute(&mut conn)
+            })?;
+            Ok(updated == 1)
+        })
+    }
+
+    fn discard(&self, event_id: Uuid) -> Result<bool, DomainError> {
+        in_span("DieselOutboxDeadLetterRepository.discard", || {
+            let mut conn = self.pool.get()?;
+            let updated = in_db_span("UPDATE", "commerce_order_outbox_publications", || {
+                diesel::update(
+                    commerce_order_outbox_publications::table
+                        .find(event_id)
+                        .filter(
+                            commerce_order_outbox_publications::status
+                                .eq(PublicationStatus::Dead.as_str()),
+                        ),
+                )
+                .set((
+                    commerce_order_outbox_publications::status
+                        .eq(PublicationStatus::Discarded.as_str()),
+                    commerce_order_outbox_publications::updated_at.eq(diesel::dsl::now),
+                ))
+                .execute(&mut conn)
+            })?;
+            Ok(updated == 1)
+        })
+    }
+}
+
+fn dead_letter((publication, event): (OutboxPublicationRow, OutboxEventRow)) -> OutboxDeadLetter {
+    OutboxDeadLetter {
+        event_id: event.id,
+        aggregate_type: event.aggregate_type,
+        aggregate_id: event.aggregate_id,
+        event_type: event.event_type,
+        sequence: event.sequence,
+        payload: event.payload,
+        attempts: publication.attempts,
+        last_error: publication.last_error,
+        occurred_at: event.created_at,
+        dead_lettered_at: publication.updated_at,
+    }
+}
+
+// ── Relay queue ──────────────────────────────────────────────────────────────
+
+/// Start tracking up to `limit` outbox rows the relay has not seen yet, oldest
+/// first, as pending publications due now. Returns how many were added.
+///
+/// The first call ever records the rows already in the outbox as skipped, so
+/// the relay only publishes events written from then on.
+pub fn track_new(conn: &mut PgConnection, limit: i64) -> QueryResult<usize> {
+    conn.transaction(|conn| {
+        if take_baseline(conn)? {
+            return Ok(0);
+        }
+        let ids: Vec<Uuid> = in_db_span("SELECT", "commerce_order_outbox", || {
+            commerce_order_outbox::table
+                .filter(not(exists(
+                    commerce_order_outbox_publications::table.filter(
+                        commerce_order_outbox_publications::event_id.eq(commerce_order_outbox::id),
+                    ),
+                )))
+                .order((
+                    commerce_order_outbox::created_at.asc(),
+                    commerce_order_outbox::sequence.asc(),
+                ))
+                .limit(limit)
+                .select(commerce_order_outbox::id)
+                .for_update()
+                .skip_locked()
+                .load(conn)
+        })?;
+        if ids.is_empty() {
+            return Ok(0);
+        }
+        let rows: Vec<_> = ids
+            .iter()
+            .map(|id| commerce_order_outbox_publications::event_id.eq(id))
+            .collect();
+        in_db_span("INSERT", "commerce_order_outbox_publications", || {
+            diesel::insert_into(commerce_order_outbox_publications::table)
+                .values(&rows)
+                .on_conflict_do_nothing()
+                .execute(conn)
+        })
+    })
+}
+
+/// Mark every untracked outbox row as skipped if the relay has never run;
+/// `true` if this call did it.
+fn take_baseline(conn: &mut PgConnection) -> QueryResult<bool> {
+    let taken: bool = in_db_span("SELECT", "outbox_relay_baseline", || {
+        diesel::select(exists(
+            outbox_relay_baseline::table.select(outbox_relay_baseline::id),
+        ))
+        .get_result(conn)
+    })?;
+    if taken {
+        return Ok(false);
+    }
+    // Concurrent relays wait here for the first one to commit.
+    let inserted = in_db_span("INSERT", "outbox_relay_baseline", || {
+        diesel::insert_into(outbox_relay_baseline::table)
+            .values(outbox_relay_baseline::id.eq(1))
+            .on_conflict_do_nothing()
+            .execute(conn)
+    })?;
+    if inserted == 0 {
+        return Ok(false);
+    }
+    let skipped = in_db_span("INSERT", "commerce_order_outbox_publications", || {
+        diesel::insert_into(commerce_order_outbox_publications::table)
+            .values(
+                commerce_order_outbox::table
+                    .filter(not(exists(
+                        commerce_order_outbox_publications::table.filter(
+                            commerce_order_outbox_publications::event_id
+                                .eq(commerce_order_outbox::id),
+                        ),
+                    )))
+                    .select((
+                        commerce_order_outbox::id,
+                        PublicationStatus::Skipped.as_str().into_sql::<Text>(),
+                    )),
+            )
+            .into_columns((
+                commerce_order_outbox_publications::event_id,
+                commerce_order_outbox_publications::status,
+            ))
+            .on_conflict_do_nothing()
+            .execute(conn)
+    })?;
+    log::info!(
+        "Outbox relay started; skipped {} events already in the outbox",
+        skipped
+    );
+    Ok(true)
+}
+
+/// An outbox event to publish.
+#[derive(Debug, Clone)]
+pub struct DuePublication {
+    pub event: OutboxEventRow,
+    /// Attempts made before this one.
+    pub attempts: i32,
+}
+
+#[derive(QueryableByName)]
+struct DueRow {
+    #[diesel(sql_type = sql_types::Uuid)]
+    event_id: Uuid,
+    #[diesel(sql_type = sql_types::Integer)]
+    attempts: i32,
+}
+
+/// Claim up to `limit` pending publications due at `now`, oldest event first.
+///
+/// An event is only claimed once every earlier event of its aggregate is
+/// published, dead-lettered, discarded or skipped, so a retried event holds
+/// back the rest of its aggregate but no other one. Claimed publications are
+/// not due again before `lease_until`, so other workers skip them while this
+/// one is publishing.
+pub fn claim_due(
+    conn: &mut PgConnection,
+    now: DateTime<Utc>,
+    lease_until: DateTime<Utc>,
+    limit: i64,
+) -> QueryResult<Vec<DuePublication>> {
+    conn.transaction(|conn| {
+        // Only heads of their aggregate, before the LIMIT: followers of a
+        // retried event must not fill the batch.
+        let due: Vec<DueRow> = in_db_span("SELECT", "commerce_order_outbox_publications", || {
+            diesel::sql_query(
+                "SELECT p.event_id, p.attempts \
+                 FROM commerce_order_outbox_publications p \
+                 JOIN commerce_order_outbox o ON o.id = p.event_id \
+                 WHERE p.status = $1 AND p.next_attempt_at <= $2 \
+                 AND NOT EXISTS ( \
+                     SELECT 1 FROM commerce_order_outbox earlier \
+                     LEFT JOIN commerce_order_outbox_publications ep \
+                         ON ep.event_id = earlier.id \
+                     WHERE earlier.aggregate_type = o.aggregate_type \
+                     AND earlier.aggregate_id = o.aggregate_id \
+                     AND earlier.sequence < o.sequence \
+                     AND (ep.status IS NULL OR ep.status = $1)) \
+                 ORDER BY p.next_attempt_at \
+                 LIMIT $3 \
+                 FOR UPDATE OF p SKIP LOCKED",
+            )
+            .bind::<sql_types::Text, _>(PublicationStatus::Pending.as_str())
+            .bind::<sql_types::Timestamptz, _>(now)
+            .bind::<sql_types::BigInt, _>(limit)
+            .load(conn)
+        })?;
+        if due.is_empty() {
+            return Ok(vec![]);
+        }
+        let ids: Vec<Uuid> = due.iter().map(|row| row.event_id).collect();
+        let events: Vec<OutboxEventRow> = in_db_span("SELECT", "commerce_order_outbox", || {
+            commerce_order_outbox::table
+                .filter(commerce_order_outbox::id.eq_any(&ids))
+                .order((
+                    commerce_order_outbox::created_at.asc(),
+                    commerce_order_outbox::sequence.asc(),
+                ))
+                .select(OutboxEventRow::as_select())
+                .load(conn)
+        })?;
+
+        in_db_span("UPDATE", "commerce_order_outbox_publications", || {
+            diesel::update(
+                commerce_order_outbox_publications::table
+                    .filter(commerce_order_outbox_publications::event_id.eq_any(&ids)),
+            )
+            .set(commerce_order_outbox_publications::next_attempt_at.eq(lease_until))
+            .execute(conn)
+        })?;
+        let attempts: HashMap<Uuid, i32> = due
+            .into_iter()
+            .map(|row| (row.event_id, row.attempts))
+            .collect();
+        Ok(events
+            .into_iter()
+            .map(|event| DuePublication {
+                attempts: attempts.get(&event.id).copied().unwrap_or_default(),
+                event,
+            })
+            .collect())
+    })
+}
+
+/// Record a successful attempt.
+pub fn mark_published(conn: &mut PgConnection, event_id: Uuid) -> QueryResult<()> {
+    in_db_span("UPDATE", "commerce_order_outbox_publications", || {
+        diesel::update(commerce_order_outbox_publications::table.find(event_id))
+            .set((
+                commerce_order_outbox_publications::status
+                    .eq(PublicationStatus::Published.as_str()),
+                commerce_order_outbox_publications::attempts
+                    .eq(commerce_order_outbox_publications::attempts + 1),
+                commerce_order_outbox_publications::last_error.eq(None::<String>),
+                commerce_order_outbox_publications::updated_at.eq(diesel::dsl::now),
+            ))
+            .execute(conn)
+    })?;
+    Ok(())
+}
+
+/// Record a failed attempt: retry at `retry_at`, or dead-letter the event when
+/// there is none.
+pub fn mark_failed(
+    conn: &mut PgConnection,
+    event_id: Uuid,
+    error: &str,
+    retry_at: Option<DateTime<Utc>>,
+) -> QueryResult<()> {
+    let status = match retry_at {
+        Some(_) => PublicationStatus::Pending,
+        None => PublicationStatus::Dead,
+    };
+    in_db_span("UPDATE", "commerce_order_outbox_publications", || {
+        diesel::update(commerce_order_outbox_publications::table.find(event_id))
+            .set((
+                commerce_order_outbox_publications::status.eq(status.as_str()),
+                commerce_order_outbox_publications::attempts
+                    .eq(commerce_order_outbox_publications::attempts + 1),
+                commerce_order_outbox_publications::last_error.eq(error),
+                commerce_order_outbox_publications::next_attempt_at
+                    .eq(retry_at.unwrap_or_else(Utc::now)),
+                commerce_order_outbox_publications::updated_at.eq(diesel::dsl::now),
+            ))
+            .execute(conn)
+    })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_support::{append_event, setup_db};
+
+    fn claim(conn: &mut PgConnection) -> Vec<Uuid> {
+        let now = Utc::now();
+        claim_due(conn, now, now + chrono::Duration::minutes(1), 10)
+            .expect("claim")
+            .into_iter()
+            .map(|c| c.event.id)
+            .collect()
+    }
+
+    /// Take the relay's baseline while the outbox is still empty.
+    fn start_relay(conn: &mut PgConnection) {
+        assert_eq!(track_new(conn, 10).expect("baseline"), 0);
+    }
+
+    #[tokio::test]
+    async fn claimed_events_are_leased_to_one_worker() {
+        let (_container, pool) = setup_db().await;
+        let mut conn = pool.get().expect("connection");
+        start_relay(&mut conn);
+        let first =
+            append_event(&mut conn, "Order", "a", "OrderCreated", json!({})).expect("append");
+        append_event(&mut conn, "Order", "a", "OrderPaid", json!({})).expect("append");
+
+        assert_eq!(track_new(&mut conn, 10).expect("track"), 2);
+        assert_eq!(track_new(&mut conn, 10).expect("track"), 0);
+        assert_eq!(
+            claim(&mut conn),
+            [first.id],
+            "only the head of the aggregate"
+        );
+        assert!(claim(&mut conn).is_empty(), "leased until published");
+    }
+
+    #[tokio::test]
+    async fn blocked_followers_do_not_crowd_out_other_aggregates() {
+        let (_container, pool) = setup_db().await;
+        let mut conn = pool.get().expect("connection");
+        start_relay(&mut conn);
+        let head =
+            append_event(&mut conn, "Order", "a", "OrderCreated", json!({})).expect("append");
+        for _ in 0..15 {
+            append_event(&mut conn, "Order", "a", "OrderUpdated", json!({})).expect("append");
+        }
+        let other =
+            append_event(&mut conn, "Order", "b", "OrderCreated", json!({})).expect("append");
+        assert_eq!(track_new(&mut conn, 100).expect("track"), 17);
+
+        // The head of `a` is retrying; its followers are due but blocked.
+        mark_failed(
+            &mut conn,
+            head.id,
+            "broker down",
+            Some(Utc::now() + chrono::Duration::minutes(5)),
+        )
+        .expect("retry later");
+        assert_eq!(claim(&mut conn), [other.id]);
+    }
+
+    #[tokio::test]
+    async fn first_run_skips_the_existing_outbox() {
+        let (_container, pool) = setup_db().await;
+        let mut conn = pool.get().expect("connection");
+        let history =
+            append_event(&mut conn, "Order", "a", "OrderCreated", json!({})).expect("append");
+
+        assert_eq!(track_new(&mut conn, 10).expect("baseline"), 0);
+        let paid = append_event(&mut conn, "Order", "a", "OrderPaid", json!({})).expect("append");
+        assert_eq!(track_new(&mut conn, 10).expect("track"), 1);
+        assert_eq!(
+            claim(&mut conn),
+            [paid.id],
+            "not held back by skipped history"
+        );
+
+        let status: String = commerce_order_outbox_publications::table
+            .find(history.id)
+            .select(commerce_order_outbox_publications::status)
+            .get_result(&mut conn)
+            .expect("skipped");
+        assert_eq!(status, PublicationStatus::Skipped.as_str());
+    }
+
+    #[tokio::test]
+    async fn dead_letters_can_be_requeued_or_discarded_once() {
+        let (_container, pool) = setup_db().await;
+        let repo = DieselOutboxDeadLetterRepository::new(pool.clone());
+        let mut conn = pool.get().expect("connection");
+        start_relay(&mut conn);
+        let mut dead = Vec::new();
+        for aggregate_id in ["a", "b"] {
+            let event = append_event(&mut conn, "Order", aggregate_id, "OrderCreated", json!({}))
+                .expect("append");
+            track_new(&mut conn, 10).expect("track");
+            mark_failed(&mut conn, event.id, "too large", None).expect("dead-letter");
+            dead.push(event.id);
+        }
+        let listed: Vec<Uuid> = repo
+            .list(10)
+            .expect("list")
+            .into_iter()
+            .map(|d| d.event_id)
+            .collect();
+        assert_eq!(listed, [dead[1], dead[0]], "most recent first");
+
+        assert!(repo.requeue(dead[0]).expect("requeue"));
+        assert!(!repo.requeue(dead[0]).expect("requeue"));
+        assert!(repo.discard(dead[1]).expect("discard"));
+        assert!(!repo.discard(dead[1]).expect("discard"));
+        assert!(repo.find(dead[1]).expect("find").is_none());
+        assert!(repo.list(10).expect("list").is_empty());
+        assert_eq!(
+            claim(&mut conn),
+            [dead[0]],
+            "discarded events stay unpublished"
+        );
+    }
+}
@@ -0,0 +1,361 @@
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::db::DbPool;
+use crate::domain::errors::DomainError;
+use crate::domain::ports::SagaRepository;
+use crate::domain::saga::{
+    OrderSaga, SagaStart, SagaState, SagaStepEntry, SagaStepRecord, SagaTransition,
+};
+use crate::schema::{order_saga_steps, order_sagas};
+use crate::telemetry::{in_db_span, in_span};
+
+use super::models::{NewOrderSagaRow, NewOrderSagaStepRow, OrderSagaRow, OrderSagaStepRow};
+use super::outbox::{OutboxWriteError, ORDER_OUTBOX};
+
+pub struct DieselSagaRepository {
+    pool: DbPool,
+}
+
+impl DieselSagaRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+}
+
+impl SagaRepository for DieselSagaRepository {
+    fn find(&self, order_id: Uuid) -> Result<Option<OrderSaga>, DomainError> {
+        in_span("DieselSagaRepository.find", || self.load_saga(order_id))
+    }
+
+    fn steps(&self, order_id: Uuid) -> Result<Vec<SagaStepEntry>, DomainError> {
+        in_span("DieselSagaRepository.steps", || self.load_steps(order_id))
+    }
+
+    fn overdue(&self, now: DateTime<Utc>, limit: i64) -> Result<Vec<Uuid>, DomainError> {
+        in_span("DieselSagaRepository.overdue", || {
+            let mut conn = self.pool.get()?;
+            Ok(in_db_span("SELECT", "order_sagas", || {
+                order_sagas::table
+                    .filter(order_sagas::step_deadline.le(now))
+                    .order(order_sagas::step_deadline.asc())
+                    .limit(limit)
+                    .select(order_sagas::order_id)
+                    .load(&mut conn)
+            })?)
+        })
+    }
+}
+
+impl DieselSagaRepository {
+    fn load_saga(&self, order_id: Uuid) -> Result<Option<OrderSaga>, DomainError> {
+        let mut conn = self.pool.get()?;
+        find(&mut conn, order_id)
+    }
+
+    fn load_steps(&self, order_id: Uuid) -> Result<Vec<SagaStepEntry>, DomainError> {
+        let mut conn = self.pool.get()?;
+
+        let rows = in_db_span("SELECT", "order_saga_steps", || {
+            order_saga_steps::table
+                .filter(order_saga_steps::order_id.eq(order_id))
+                .select(OrderSagaStepRow::as_select())
+                .order(order_saga_steps::id.asc())
+                .load(&mut conn)
+        })?;
+
+        Ok(rows
+            .into_iter()
+            .map(|s| SagaStepEntry {
+                step: s.step,
+                outcome: s.outcome,
+                detail: s.detail,
+                recorded_at: s.recorded_at,
+            })
+            .collect())
+    }
+}
+
+/// The saga of `order_id`, if the order was placed with one.
+pub fn find(conn: &mut PgConnection, order_id: Uuid) -> Result<Option<OrderSaga>, DomainError> {
+    let row = in_db_span("SELECT", "order_sagas", || {
+        order_sagas::table
+            .filter(order_sagas::order_id.eq(order_id))
+            .select(OrderSagaRow::as_select())
+            .first(conn)
+            .optional()
+    })?;
+
+    row.map(|row| {
+        let state = SagaState::parse(&row.state).ok_or_else(|| {
+            DomainError::Internal(format!(
+                "saga of order {} has unknown state {}",
+                row.order_id, row.state
+            ))
+        })?;
+        Ok(OrderSaga {
+            order_id: row.order_id,
+            customer_id: row.customer_id,
+            amount: row.amount,
+            state,
+            version: row.version,
+            deadline: row.step_deadline,
+            failure_reason: row.failure_reason,
+            started_at: row.created_at,
+            updated_at: row.updated_at,
+        })
+    })
+    .transpose()
+}
+
+/// Insert a new saga and send its first command.
+///
+/// Must run inside the transaction that creates the order.
+pub fn insert(conn: &mut PgConnection, start: &SagaStart) -> Result<(), OutboxWriteError> {
+    let saga = &start.saga;
+    in_db_span("INSERT", "order_sagas", || {
+        diesel::insert_into(order_sagas::table)
+            .values(&NewOrderSagaRow {
+                order_id: saga.order_id,
+                customer_id: saga.customer_id,
+                amount: &saga.amount,
+                state: saga.state.as_str(),
+                step_deadline: saga.deadline,
+            })
+            .execute(conn)
+    })?;
+    record_steps(conn, saga.order_id, std::slice::from_ref(&start.step))?;
+    ORDER_OUTBOX.append(conn, &start.command.for_order(saga.order_id))?;
+    Ok(())
+}
+
+/// Move a saga from `transition.version` and journal the transition's steps;
+/// `false` (with nothing written) if the saga is at another version.
+///
+/// Must run inside the transaction that sends the transition's commands.
+pub fn advance(conn: &mut PgConnection, transition: &SagaTransition) -> QueryResult<bool> {
+    let updated = in_db_span("UPDATE", "order_sagas", || {
+        diesel::update(
+            order_sagas::table
+                .filter(order_sagas::order_id.eq(transition.order_id))
+                .filter(order_sagas::version.eq(transition.version)),
+        )
+        .set((
+            order_sagas::state.eq(transition.to.as_str()),
+            order_sagas::version.eq(order_sagas::version + 1),
+            order_sagas::step_deadline.eq(transition.deadline),
+            order_sagas::failure_reason.eq(&transition.failure_reason),
+            order_sagas::updated_at.eq(diesel::dsl::now),
+        ))
+        .execute(conn)
+    })?;
+    if updated == 0 {
+        return Ok(false);
+    }
+    record_steps(conn, transition.order_id, &transition.steps)?;
+    Ok(true)
+}
+
+fn record_steps(
+    conn: &mut PgConnection,
+    order_id: Uuid,
+    steps: &[SagaStepRecord],
+) -> QueryResult<()> {
+    let rows: Vec<NewOrderSagaStepRow> = steps
+        .iter()
+        .map(|s| NewOrderSagaStepRow {
+            order_id,
+            step: s.step.as_str(),
+            outcome: s.outcome.as_str(),
+            detail: s.detail.as_deref(),
+        })
+        .collect();
+    in_db_span("INSERT", "order_saga_steps", || {
+        diesel::insert_into(order_saga_steps::table)
+            .values(&rows)
+            .execute(conn)
+    })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bigdecimal::BigDecimal;
+    use diesel::prelude::*;
+    use uuid::Uuid;
+
+    use super::DieselSagaRepository;
+    use crate::application::order_service::OrderService;
+    use crate::domain::caller::Caller;
+    use crate::domain::order::{OrderLineInput, ORDER_CREATED};
+    use crate::domain::ports::{OrderRepository, SagaRepository, UnitOfWork};
+    use crate::domain::saga::{
+        SagaInput, SagaReply, SagaState, SagaTimeouts, RESERVE_INVENTORY, SAGA_AGGREGATE,
+    };
+    use crate::infrastructure::models::OutboxEventRow;
+    use crate::infrastructure::order_repo::{self, DieselOrderRepository};
+    use crate::infrastructure::unit_of_work::DieselUnitOfWork;
+    use crate::schema::commerce_order_outbox;
+    use crate::test_support::setup_db;
+    use crate::DbPool;
+
+    fn place_order(pool: &DbPool, timeouts: SagaTimeouts) -> Uuid {
+        OrderService::new(
+            DieselOrderRepository::new(pool.clone()),
+            DieselUnitOfWork::new(pool.clone()),
+        )
+        .with_saga(timeouts)
+        .create_order(
+            &Caller::Staff,
+            Uuid::new_v4(),
+            vec![OrderLineInput {
+                product_id: Uuid::new_v4(),
+                quantity: 3,
+                unit_price: BigDecimal::from_str("2.50").expect("decimal"),
+            }],
+        )
+        .expect("create order")
+    }
+
+    fn saga_commands(pool: &DbPool, order_id: Uuid) -> Vec<OutboxEventRow> {
+        let mut conn = pool.get().expect("connection");
+        commerce_order_outbox::table
+            .filter(commerce_order_outbox::aggregate_type.eq(SAGA_AGGREGATE))
+            .filter(commerce_order_outbox::aggregate_id.eq(order_id.to_string()))
+            .order(commerce_order_outbox::sequence.asc())
+            .select(OutboxEventRow::as_select())
+            .load(&mut conn)
+            .expect("query")
+    }
+
+    fn reservation_failed() -> SagaReply {
+        SagaReply::InventoryReservationFailed {
+            reason: Some("out of stock".to_string()),
+        }
+    }
+
+    #[tokio::test]
+    async fn creating_an_order_starts_its_saga() {
+        let (_container, pool) = setup_db().await;
+        let order_id = place_order(&pool, SagaTimeouts::default());
+        let sagas = DieselSagaRepository::new(pool.clone());
+
+        let saga = sagas.find(order_id).expect("find").expect("saga started");
+        assert_eq!(saga.state, SagaState::ReservingInventory);
+        assert_eq!(saga.amount, BigDecimal::from_str("7.50").expect("decimal"));
+        assert!(saga.deadline.is_some());
+
+        let commands = saga_commands(&pool, order_id);
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].event_type, RESERVE_INVENTORY);
+        assert_eq!(commands[0].sequence, 1);
+
+        let steps = sagas.steps(order_id).expect("steps");
+        assert_eq!(steps.len(), 1);
+        assert_eq!(
+            (steps[0].step.as_str(), steps[0].outcome.as_str()),
+            ("RESERVE_INVENTORY", "STARTED")
+        );
+
+        // Commands are not part of the order's own history.
+        let events = DieselOrderRepository::new(pool)
+            .events(order_id)
+            .expect("events");
+        let types: Vec<&str> = events.iter().map(|e| e.event_type.as_str()).collect();
+        assert_eq!(types, [ORDER_CREATED]);
+    }
+
+    #[tokio::test]
+    async fn orders_without_saga_support_have_no_saga() {
+        let (_container, pool) = setup_db().await;
+        let order_id =
+            order_repo::tests::place_order(&pool, Uuid::new_v4(), vec![]).expect("create order");
+
+        let sagas = DieselSagaRepository::new(pool.clone());
+        assert!(sagas.find(order_id).expect("find").is_none());
+        assert!(saga_commands(&pool, order_id).is_empty());
+    }
+
+    #[tokio::test]
+    async fn advance_moves_the_saga_and_journals_its_steps() {
+        let (_container, pool) = setup_db().await;
+        let order_id = place_order(&pool, SagaTimeouts::default());
+        let sagas = DieselSagaRepository::new(pool.clone());
+        let saga = sagas.find(order_id).expect("find").expect("saga");
+
+        let reply = reservation_failed();
+        let transition = saga
+            .decide(
+                SagaInput::Reply(&reply),
+                &SagaTimeouts::default(),
+                chrono::Utc::now(),
+            )
+            .expect("applies");
+        let advanced = DieselUnitOfWork::new(pool.clone())
+            .run(|tx| tx.sagas().advance(&transition))
+            .expect("advance");
+        assert!(advanced);
+
+        let saga = sagas.find(order_id).expect("find").expect("saga");
+        assert_eq!(saga.state, SagaState::Failed);
+        assert_eq!(saga.version, 1);
+        assert_eq!(saga.deadline, None);
+        assert_eq!(
+            saga.failure_reason.as_deref(),
+            Some("inventory reservation failed: out of stock")
+        );
+        let steps = sagas.steps(order_id).expect("steps");
+        assert_eq!(steps[1].outcome, "FAILED");
+        assert_eq!(steps[1].detail, saga.failure_reason);
+    }
+
+    #[tokio::test]
+    async fn advance_from_an_outdated_version_leaves_no_trace() {
+        let (_container, pool) = setup_db().await;
+        let order_id = place_order(&pool, SagaTimeouts::default());
+        let sagas = DieselSagaRepository::new(pool.clone());
+        let saga = sagas.find(order_id).expect("find").expect("saga");
+        let reply = SagaReply::InventoryReserved;
+        let transition = saga
+            .decide(
+                SagaInput::Reply(&reply),
+                &SagaTimeouts::default(),
+                chrono::Utc::now(),
+            )
+            .expect("applies");
+        let uow = DieselUnitOfWork::new(pool.clone());
+        assert!(uow
+            .run(|tx| tx.sagas().advance(&transition))
+            .expect("advance"));
+
+        let again = uow
+            .run(|tx| tx.sagas().advance(&transition))
+            .expect("advance");
+        assert!(!again);
+        assert_eq!(
+            sagas.find(order_id).expect("find").expect("saga").version,
+            1
+        );
+        assert_eq!(sagas.steps(order_id).expect("steps").len(), 3);
+    }
+
+    #[tokio::test]
+    async fn overdue_lists_running_sagas_past_their_deadline() {
+        let (_container, pool) = setup_db().await;
+        let expired = place_order(
+            &pool,
+            SagaTimeouts {
+                reserve_inventory: std::time::Duration::ZERO,
+                ..SagaTimeouts::default()
+            },
+        );
+        place_order(&pool, SagaTimeouts::default());
+        let sagas = DieselSagaRepository::new(pool);
+
+        let overdue = sagas.overdue(chrono::Utc::now(), 10).expect("overdue");
+        assert_eq!(overdue, [expired]);
+    }
+}
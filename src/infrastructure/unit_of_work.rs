@@ -0,0 +1,315 @@
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::db::DbPool;
+use crate::domain::errors::DomainError;
+use crate::domain::order::{OrderLineInput, OrderStatus, OrderView, SourceMessage};
+use crate::domain::outbox::OutboxMessage;
+use crate::domain::ports::{
+    Transaction, TransactionalInbox, TransactionalOrderRepository, TransactionalOutbox,
+    TransactionalSagaRepository, UnitOfWork,
+};
+use crate::domain::saga::{OrderSaga, SagaStart, SagaTransition};
+use crate::inbox;
+use crate::telemetry::in_span;
+
+use super::order_repo::{find_order, insert_order, update_status};
+use super::outbox::ORDER_OUTBOX;
+use super::saga_repo;
+
+/// Runs each unit of work in a database transaction on a pooled connection.
+pub struct DieselUnitOfWork {
+    pool: DbPool,
+}
+
+impl DieselUnitOfWork {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+}
+
+impl UnitOfWork for DieselUnitOfWork {
+    fn run<T, E>(&self, work: impl FnOnce(&mut dyn Transaction) -> Result<T, E>) -> Result<T, E>
+    where
+        E: From<DomainError>,
+    {
+        in_span("DieselUnitOfWork.run", || {
+            let mut conn = self.pool.get().map_err(DomainError::from)?;
+            // Diesel rolls back on its own error type; the caller's error is
+            // kept aside and returned once the transaction is rolled back.
+            let mut failure = None;
+            let result = conn.transaction(|conn| {
+                work(&mut DieselTransaction { conn }).map_err(|e| {
+                    failure = Some(e);
+                    diesel::result::Error::RollbackTransaction
+                })
+            });
+            match (result, failure) {
+                (Ok(value), _) => Ok(value),
+                (Err(_), Some(e)) => Err(e),
+                (Err(e), None) => Err(DomainError::from(e).into()),
+            }
+        })
+    }
+}
+
+/// The repositories of a unit of work, all on its connection.
+struct DieselTransaction<'c> {
+    conn: &'c mut PgConnection,
+}
+
+impl Transaction for DieselTransaction<'_> {
+    fn orders(&mut self) -> &mut dyn TransactionalOrderRepository {
+        self
+    }
+
+    fn sagas(&mut self) -> &mut dyn TransactionalSagaRepository {
+        self
+    }
+
+    fn outbox(&mut self) -> &mut dyn TransactionalOutbox {
+        self
+    }
+
+    fn inbox(&mut self) -> &mut dyn TransactionalInbox {
+        self
+    }
+}
+
+impl TransactionalOrderRepository for DieselTransaction<'_> {
+    fn find_by_id(&mut self, id: Uuid) -> Result<Option<OrderView>, DomainError> {
+        Ok(find_order(self.conn, id, true)?)
+    }
+
+    fn insert(
+        &mut self,
+        order_id: Uuid,
+        customer_id: Uuid,
+        lines: &[OrderLineInput],
+    ) -> Result<(), DomainError> {
+        Ok(insert_order(self.conn, order_id, customer_id, lines)?)
+    }
+
+    fn update_status(
+        &mut self,
+        order_id: Uuid,
+        from: OrderStatus,
+        to: OrderStatus,
+    ) -> Result<bool, DomainError> {
+        Ok(update_status(self.conn, order_id, from, to)?)
+    }
+}
+
+impl TransactionalSagaRepository for DieselTransaction<'_> {
+    fn start(&mut self, start: &SagaStart) -> Result<(), DomainError> {
+        Ok(saga_repo::insert(self.conn, start)?)
+    }
+
+    fn find(&mut self, order_id: Uuid) -> Result<Option<OrderSaga>, DomainError> {
+        saga_repo::find(self.conn, order_id)
+    }
+
+    fn advance(&mut self, transition: &SagaTransition) -> Result<bool, DomainError> {
+        Ok(saga_repo::advance(self.conn, transition)?)
+    }
+}
+
+impl TransactionalOutbox for DieselTransaction<'_> {
+    fn append(&mut self, message: &dyn OutboxMessage) -> Result<(), DomainError> {
+        ORDER_OUTBOX.append(self.conn, message)?;
+        Ok(())
+    }
+}
+
+impl TransactionalInbox for DieselTransaction<'_> {
+    fn record(&mut self, message: &SourceMessage) -> Result<bool, DomainError> {
+        Ok(inbox::record_message(
+            self.conn,
+            &message.id,
+            &message.message_type,
+        )?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bigdecimal::BigDecimal;
+
+    use super::*;
+    use crate::application::order_service::{OrderService, PaymentApplication};
+    use crate::domain::order::{PaymentOutcome, ORDER_PAID};
+    use crate::domain::outbox::OutboxEvent;
+    use crate::domain::ports::OrderRepository;
+    use crate::infrastructure::order_repo::tests::place_order;
+    use crate::infrastructure::order_repo::DieselOrderRepository;
+    use crate::schema::{commerce_order_outbox, processed_messages};
+    use crate::test_support::setup_db;
+
+    fn line() -> OrderLineInput {
+        OrderLineInput {
+            product_id: Uuid::new_v4(),
+            quantity: 2,
+            unit_price: BigDecimal::from_str("3.00").expect("valid decimal"),
+        }
+    }
+
+    fn payment(id: &str) -> SourceMessage {
+        SourceMessage {
+            id: id.to_string(),
+            message_type: "PaymentCaptured".to_string(),
+        }
+    }
+
+    fn count_outbox(pool: &DbPool) -> i64 {
+        let mut conn = pool.get().expect("connection");
+        commerce_order_outbox::table
+            .count()
+            .get_result(&mut conn)
+            .expect("count")
+    }
+
+    fn count_processed(pool: &DbPool) -> i64 {
+        let mut conn = pool.get().expect("connection");
+        processed_messages::table
+            .count()
+            .get_result(&mut conn)
+            .expect("count")
+    }
+
+    #[tokio::test]
+    async fn a_failed_unit_leaves_no_trace() {
+        let (_container, pool) = setup_db().await;
+        let uow = DieselUnitOfWork::new(pool.clone());
+        let order_id = Uuid::new_v4();
+
+        let result: Result<(), DomainError> = uow.run(|tx| {
+            tx.orders().insert(order_id, Uuid::new_v4(), &[line()])?;
+            tx.outbox().append(&OutboxEvent::new(
+                "Order",
+                order_id.to_string(),
+                "OrderCreated",
+                serde_json::Value::Null,
+            ))?;
+            tx.inbox().record(&payment("payment-1"))?;
+            Err(DomainError::InvalidInput("changed my mind".to_string()))
+        });
+
+        assert!(matches!(result, Err(DomainError::InvalidInput(_))));
+        let orders = DieselOrderRepository::new(pool.clone());
+        assert!(orders.find_by_id(order_id).expect("find").is_none());
+        assert_eq!(count_outbox(&pool), 0);
+        assert_eq!(count_processed(&pool), 0);
+    }
+
+    #[tokio::test]
+    async fn create_emits_db_spans_under_the_unit_of_work_span() {
+        let exporter = crate::telemetry::test_span_exporter();
+        let (_container, pool) = setup_db().await;
+
+        place_order(&pool, Uuid::new_v4(), vec![line()]).expect("create failed");
+
+        let spans = exporter.get_finished_spans().expect("read finished spans");
+        let parent = spans
+            .iter()
+            .find(|s| s.name == "DieselUnitOfWork.run")
+            .expect("unit of work span should be exported");
+        let children: Vec<_> = spans
+            .iter()
+            .filter(|s| s.parent_span_id == parent.span_context.span_id())
+            .map(|s| s.name.to_string())
+            .collect();
+        assert_eq!(
+            children,
+            vec![
+                "INSERT orders",
+                "INSERT order_lines",
+                "INSERT outbox_aggregate_sequences",
+                "INSERT commerce_order_outbox"
+            ]
+        );
+
+        let outbox_span = spans
+            .iter()
+            .find(|s| {
+                s.name == "INSERT commerce_order_outbox"
+                    && s.parent_span_id == parent.span_context.span_id()
+            })
+            .expect("outbox insert span");
+        let attr = |key: &str| {
+            outbox_span
+                .attributes
+                .iter()
+                .find(|kv| kv.key.as_str() == key)
+                .map(|kv| kv.value.as_str().to_string())
+        };
+        assert_eq!(attr("db.system.name").as_deref(), Some("postgresql"));
+        assert_eq!(attr("db.operation.name").as_deref(), Some("INSERT"));
+        assert_eq!(
+            attr("db.collection.name").as_deref(),
+            Some("commerce_order_outbox")
+        );
+    }
+
+    #[tokio::test]
+    async fn payment_updates_the_order_and_appends_an_event() {
+        let (_container, pool) = setup_db().await;
+        let order_id = place_order(&pool, Uuid::new_v4(), vec![line()]).expect("create failed");
+        let orders = DieselOrderRepository::new(pool.clone());
+        let service = OrderService::new(
+            DieselOrderRepository::new(pool.clone()),
+            DieselUnitOfWork::new(pool.clone()),
+        );
+
+        let result = service
+            .apply_payment(order_id, &PaymentOutcome::Captured, &payment("payment-1"))
+            .expect("apply");
+        assert_eq!(result, PaymentApplication::Applied(OrderStatus::Paid));
+
+        let order = orders.find_by_id(order_id).expect("find").expect("order");
+        assert_eq!(order.status, "PAID");
+        let events = orders.events(order_id).expect("events");
+        assert_eq!(events[1].event_type, ORDER_PAID);
+        assert_eq!(events[1].sequence, 2);
+        assert_eq!(count_processed(&pool), 1);
+    }
+
+    #[tokio::test]
+    async fn stale_status_change_is_rolled_back_with_its_message() {
+        let (_container, pool) = setup_db().await;
+        let order_id = place_order(&pool, Uuid::new_v4(), vec![line()]).expect("create failed");
+        let uow = DieselUnitOfWork::new(pool.clone());
+        uow.run(|tx| {
+            tx.orders()
+                .update_status(order_id, OrderStatus::Pending, OrderStatus::Paid)
+        })
+        .expect("pay");
+
+        let result: Result<(), DomainError> = uow.run(|tx| {
+            assert!(tx.inbox().record(&payment("payment-2"))?);
+            if !tx
+                .orders()
+                .update_status(order_id, OrderStatus::Pending, OrderStatus::Paid)?
+            {
+                return Err(DomainError::Internal("stale".to_string()));
+            }
+            Ok(())
+        });
+
+        assert!(result.is_err());
+        assert_eq!(count_processed(&pool), 0);
+        assert_eq!(count_outbox(&pool), 1);
+    }
+
+    #[tokio::test]
+    async fn records_each_message_once() {
+        let (_container, pool) = setup_db().await;
+        let uow = DieselUnitOfWork::new(pool);
+
+        let first = uow.run(|tx| tx.inbox().record(&payment("payment-1")));
+        let again = uow.run(|tx| tx.inbox().record(&payment("payment-1")));
+        assert!(first.expect("first"));
+        assert!(!again.expect("again"));
+    }
+}
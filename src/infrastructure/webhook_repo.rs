@@ -0,0 +1,527 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use diesel::dsl::{exists, not};
+use diesel::prelude::*;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::db::DbPool;
+use crate::domain::errors::DomainError;
+use crate::domain::order::ORDER_AGGREGATE;
+use crate::domain::ports::WebhookRepository;
+use crate::domain::webhook::{
+    DeliveryStatus, WebhookDelivery, WebhookSubscription, WebhookSubscriptionInput,
+};
+use crate::schema::{
+    commerce_order_outbox, webhook_deliveries, webhook_dispatched_events, webhook_subscriptions,
+};
+use crate::telemetry::{in_db_span, in_span};
+
+use super::models::{
+    NewWebhookDeliveryRow, NewWebhookSubscriptionRow, OutboxEventRow, WebhookDeliveryRow,
+    WebhookSubscriptionRow,
+};
+use super::outbox::REPLAY_OF_HEADER;
+
+pub struct DieselWebhookRepository {
+    pool: DbPool,
+}
+
+impl DieselWebhookRepository {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+}
+
+impl WebhookRepository for DieselWebhookRepository {
+    fn create(&self, input: &WebhookSubscriptionInput) -> Result<WebhookSubscription, DomainError> {
+        in_span("DieselWebhookRepository.create", || {
+            let mut conn = self.pool.get()?;
+            let row = in_db_span("INSERT", "webhook_subscriptions", || {
+                diesel::insert_into(webhook_subscriptions::table)
+                    .values(&NewWebhookSubscriptionRow {
+                        id: Uuid::new_v4(),
+                        url: &input.url,
+                        event_types: &input.event_types,
+                    })
+                    .returning(WebhookSubscriptionRow::as_returning())
+                    .get_result(&mut conn)
+            })?;
+            Ok(subscription(row))
+        })
+    }
+
+    fn list(&self) -> Result<Vec<WebhookSubscription>, DomainError> {
+        in_span("DieselWebhookRepository.list", || {
+            let mut conn = self.pool.get()?;
+            Ok(load_subscriptions(&mut conn)?
+                .into_iter()
+                .map(subscription)
+                .collect())
+        })
+    }
+
+    fn find(&self, id: Uuid) -> Result<Option<WebhookSubscription>, DomainError> {
+        in_span("DieselWebhookRepository.find", || {
+            let mut conn = self.pool.get()?;
+            let row = in_db_span("SELECT", "webhook_subscriptions", || {
+                webhook_subscriptions::table
+                    .find(id)
+                    .select(WebhookSubscriptionRow::as_select())
+                    .first(&mut conn)
+                    .optional()
+            })?;
+            Ok(row.map(subscription))
+        })
+    }
+
+    fn update(
+        &self,
+        id: Uuid,
+        input: &WebhookSubscriptionInput,
+    ) -> Result<Option<WebhookSubscription>, DomainError> {
+        in_span("DieselWebhookRepository.update", || {
+            let mut conn = self.pool.get()?;
+            let row = in_db_span("UPDATE", "webhook_subscriptions", || {
+                diesel::update(webhook_subscriptions::table.find(id))
+                    .set((
+                        webhook_subscriptions::url.eq(&input.url),
+                        webhook_subscriptions::event_types.eq(&input.event_types),
+                        webhook_subscriptions::updated_at.eq(diesel::dsl::now),
+                    ))
+                    .returning(WebhookSubscriptionRow::as_returning())
+                    .get_result(&mut conn)
+                    .optional()
+            })?;
+            Ok(row.map(subscription))
+        })
+    }
+
+    fn delete(&self, id: Uuid) -> Result<bool, DomainError> {
+        in_span("DieselWebhookRepository.delete", || {
+            let mut conn = self.pool.get()?;
+            let deleted = in_db_span("DELETE", "webhook_subscriptions", || {
+                diesel::delete(webhook_subscriptions::table.find(id)).execute(&mut conn)
+            })?;
+            Ok(deleted == 1)
+        })
+    }
+
+    fn dead_letters(
+        &self,
+        subscription_id: Uuid,
+        limit: i64,
+    ) -> Result<Vec<WebhookDelivery>, DomainError> {
+        in_span("DieselWebhookRepository.dead_letters", || {
+            let mut conn = self.pool.get()?;
+            let rows = in_db_span("SELECT", "webhook_deliveries", || {
+                webhook_deliveries::table
+                    .filter(webhook_deliveries::subscription_id.eq(subscription_id))
+                    .filter(webhook_deliveries::status.eq(DeliveryStatus::Dead.as_str()))
+                    .order(webhook_deliveries::id.desc())
+                    .limit(limit)
+                    .select(WebhookDeliveryRow::as_select())
+                    .load(&mut conn)
+            })?;
+            Ok(rows.into_iter().map(delivery).collect())
+        })
+    }
+
+    fn requeue(&self, subscription_id: Uuid, delivery_id: i64) -> Result<bool, DomainError> {
+        in_span("DieselWebhookRepository.requeue", || {
+            let mut conn = self.pool.get()?;
+            let updated = in_db_span("UPDATE", "webhook_deliveries", || {
+                diesel::update(
+                    webhook_deliveries::table
+                        .filter(webhook_deliveries::id.eq(delivery_id))
+                        .filter(webhook_deliveries::subscription_id.eq(subscription_id))
+                        .filter(webhook_deliveries::status.eq(DeliveryStatus::Dead.as_str())),
+                )
+                .set((
+                    webhook_deliveries::status.eq(DeliveryStatus::Pending.as_str()),
+                    webhook_deliveries::attempts.eq(0),
+                    webhook_deliveries::next_attempt_at.eq(diesel::dsl::now),
+                    webhook_deliveries::updated_at.eq(diesel::dsl::now),
+                ))
+                .execute(&mut conn)
+            })?;
+            Ok(updated == 1)
+        })
+    }
+}
+
+fn subscription(row: WebhookSubscriptionRow) -> WebhookSubscription {
+    WebhookSubscription {
+        id: row.id,
+        url: row.url,
+        event_types: row.event_types.into_iter().flatten().collect(),
+        created_at: row.created_at,
+        updated_at: row.updated_at,
+    }
+}
+
+fn delivery(row: WebhookDeliveryRow) -> WebhookDelivery {
+    WebhookDelivery {
+        id: row.id,
+        subscription_id: row.subscription_id,
+        event_id: row.event_id,
+        event_type: row.event_type,
+        status: row.status,
+        attempts: row.attempts,
+        next_attempt_at: row.next_attempt_at,
+        last_error: row.last_error,
+        created_at: row.created_at,
+        updated_at: row.updated_at,
+    }
+}
+
+fn load_subscriptions(conn: &mut PgConnection) -> QueryResult<Vec<WebhookSubscriptionRow>> {
+    in_db_span("SELECT", "webhook_subscriptions", || {
+        webhook_subscriptions::table
+            .order((
+                webhook_subscriptions::created_at.asc(),
+                webhook_subscriptions::id.asc(),
+            ))
+            .select(WebhookSubscriptionRow::as_select())
+            .load(conn)
+    })
+}
+
+// ── Delivery queue ───────────────────────────────────────────────────────────
+
+/// JSON body POSTed to subscribers for an outbox event.
+pub fn delivery_body(event: &OutboxEventRow) -> Value {
+    json!({
+        "id": event.id,
+        "event_type": event.event_type,
+        "aggregate_type": event.aggregate_type,
+        "aggregate_id": event.aggregate_id,
+        "sequence": event.sequence,
+        "occurred_at": event.created_at.to_rfc3339(),
+        "payload": event.payload,
+    })
+}
+
+/// Queue a delivery of each not yet dispatched order event, oldest first, to
+/// every subscription that wants it and already existed when the event was
+/// written. Returns how many events were dispatched.
+///
+/// Dispatched events are remembered in `webhook_dispatched_events`, so every
+/// event is fanned out once even with several workers. Copies written by an
+/// outbox replay are never fanned out: subscribers got the original.
+pub fn fan_out(conn: &mut PgConnection, limit: i64) -> QueryResult<usize> {
+    conn.transaction(|conn| {
+        let events: Vec<OutboxEventRow> = in_db_span("SELECT", "commerce_order_outbox", || {
+            commerce_order_outbox::table
+                .filter(commerce_order_outbox::aggregate_type.eq(ORDER_AGGREGATE))
+                .filter(not(commerce_order_outbox::headers.has_key(REPLAY_OF_HEADER)))
+                .filter(not(exists(webhook_dispatched_events::table.filter(
+                    webhook_dispatched_events::event_id.eq(commerce_order_outbox::id),
+                ))))
+                .order((
+                    commerce_order_outbox::created_at.asc(),
+                    commerce_order_outbox::sequence.asc(),
+                ))
+                .limit(limit)
+                .select(OutboxEventRow::as_select())
+                .for_update()
+                .skip_locked()
+                .load(conn)
+        })?;
+        if events.is_empty() {
+            return Ok(0);
+        }
+        let subscriptions: Vec<WebhookSubscription> = load_subscriptions(conn)?
+            .into_iter()
+            .map(subscription)
+            .collect();
+
+        let mut dispatched = 0;
+        for event in &events {
+            let claimed = in_db_span("INSERT", "webhook_dispatched_events", || {
+                diesel::insert_into(webhook_dispatched_events::table)
+                    .values(webhook_dispatched_events::event_id.eq(event.id))
+                    .on_conflict_do_nothing()
+                    .execute(conn)
+            })?;
+            if claimed == 0 {
+                continue;
+            }
+            dispatched += 1;
+
+            let body = delivery_body(event);
+            let rows: Vec<NewWebhookDeliveryRow> = subscriptions
+                .iter()
+                .filter(|s| s.created_at <= event.created_at && s.wants(&event.event_type))
+                .map(|s| NewWebhookDeliveryRow {
+                    subscription_id: s.id,
+                    event_id: event.id,
+                    event_type: &event.event_type,
+                    body: &body,
+                })
+                .collect();
+            if !rows.is_empty() {
+                in_db_span("INSERT", "webhook_deliveries", || {
+                    diesel::insert_into(webhook_deliveries::table)
+                        .values(&rows)
+                        .on_conflict_do_nothing()
+                        .execute(conn)
+                })?;
+            }
+        }
+        Ok(dispatched)
+    })
+}
+
+/// A delivery to attempt, with its endpoint.
+#[derive(Debug, Clone)]
+pub struct DueDelivery {
+    pub id: i64,
+    pub subscription_id: Uuid,
+    pub event_id: Uuid,
+    pub event_type: String,
+    pub body: Value,
+    /// Attempts made before this one.
+    pub attempts: i32,
+    pub url: String,
+}
+
+/// Claim up to `limit` pending deliveries due at `now`, earliest first.
+///
+/// Claimed deliveries are not due again before `lease_until`, so other
+/// workers skip them while this one is sending.
+pub fn claim_due(
+    conn: &mut PgConnection,
+    now: DateTime<Utc>,
+    lease_until: DateTime<Utc>,
+    limit: i64,
+) -> QueryResult<Vec<DueDelivery>> {
+    conn.transaction(|conn| {
+        let due: Vec<WebhookDeliveryRow> = in_db_span("SELECT", "webhook_deliveries", || {
+            webhook_deliveries::table
+                .filter(webhook_deliveries::status.eq(DeliveryStatus::Pending.as_str()))
+                .filter(webhook_deliveries::next_attempt_at.le(now))
+                .order(webhook_deliveries::next_attempt_at.asc())
+                .limit(limit)
+                .select(WebhookDeliveryRow::as_select())
+                .for_update()
+                .skip_locked()
+                .load(conn)
+        })?;
+        if due.is_empty() {
+            return Ok(vec![]);
+        }
+        let ids: Vec<i64> = due.iter().map(|d| d.id).collect();
+        in_db_span("UPDATE", "webhook_deliveries", || {
+            diesel::update(webhook_deliveries::table.filter(webhook_deliveries::id.eq_any(&ids)))
+                .set(webhook_deliveries::next_attempt_at.eq(lease_until))
+                .execute(conn)
+        })?;
+
+        let subscription_ids: Vec<Uuid> = due.iter().map(|d| d.subscription_id).collect();
+        let urls: HashMap<Uuid, String> = in_db_span("SELECT", "webhook_subscriptions", || {
+            webhook_subscriptions::table
+                .filter(webhook_subscriptions::id.eq_any(&subscription_ids))
+                .select((webhook_subscriptions::id, webhook_subscriptions::url))
+                .load::<(Uuid, String)>(conn)
+        })?
+        .into_iter()
+        .collect();
+
+        Ok(due
+            .into_iter()
+            .filter_map(|d| {
+                // Deliveries go along with their subscription, so it exists.
+                let url = urls.get(&d.subscription_id)?.clone();
+                Some(DueDelivery {
+                    id: d.id,
+                    subscription_id: d.subscription_id,
+                    event_id: d.event_id,
+                    event_type: d.event_type,
+                    body: d.body,
+                    attempts: d.attempts,
+                    url,
+                })
+            })
+            .collect())
+    })
+}
+
+/// Record a successful attempt.
+pub fn mark_delivered(conn: &mut PgConnection, id: i64) -> QueryResult<()> {
+    in_db_span("UPDATE", "webhook_deliveries", || {
+        diesel::update(webhook_deliveries::table.find(id))
+            .set((
+                webhook_deliveries::status.eq(DeliveryStatus::Delivered.as_str()),
+                webhook_deliveries::attempts.eq(webhook_deliveries::attempts + 1),
+                webhook_deliveries::last_error.eq(None::<String>),
+                webhook_deliveries::updated_at.eq(diesel::dsl::now),
+            ))
+            .execute(conn)
+    })?;
+    Ok(())
+}
+
+/// Record a failed attempt: retry at `retry_at`, or dead-letter the delivery
+/// when there is none.
+pub fn mark_failed(
+    conn: &mut PgConnection,
+    id: i64,
+    error: &str,
+    retry_at: Option<DateTime<Utc>>,
+) -> QueryResult<()> {
+    let status = match retry_at {
+        Some(_) => DeliveryStatus::Pending,
+        None => DeliveryStatus::Dead,
+    };
+    in_db_span("UPDATE", "webhook_deliveries", || {
+        diesel::update(webhook_deliveries::table.find(id))
+            .set((
+                webhook_deliveries::status.eq(status.as_str()),
+                webhook_deliveries::attempts.eq(webhook_deliveries::attempts + 1),
+                webhook_deliveries::last_error.eq(error),
+                webhook_deliveries::next_attempt_at.eq(retry_at.unwrap_or_else(Utc::now)),
+                webhook_deliveries::updated_at.eq(diesel::dsl::now),
+            ))
+            .execute(conn)
+    })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_support::{append_event, setup_db};
+
+    fn input(url: &str, event_types: &[&str]) -> WebhookSubscriptionInput {
+        WebhookSubscriptionInput {
+            url: url.to_string(),
+            event_types: event_types.iter().map(|t| t.to_string()).collect(),
+        }
+    }
+
+    fn queued(pool: &DbPool) -> Vec<(Uuid, String)> {
+        let mut conn = pool.get().expect("connection");
+        webhook_deliveries::table
+            .order(webhook_deliveries::id.asc())
+            .select((
+                webhook_deliveries::subscription_id,
+                webhook_deliveries::event_type,
+            ))
+            .load(&mut conn)
+            .expect("query")
+    }
+
+    #[tokio::test]
+    async fn subscriptions_can_be_created_updated_and_deleted() {
+        let (_container, pool) = setup_db().await;
+        let repo = DieselWebhookRepository::new(pool);
+
+        let created = repo
+            .create(&input("https://a.example/hook", &["OrderPaid"]))
+            .expect("create");
+        assert_eq!(created.event_types, ["OrderPaid"]);
+        assert_eq!(repo.find(created.id).expect("find"), Some(created.clone()));
+
+        let updated = repo
+            .update(created.id, &input("https://b.example/hook", &[]))
+            .expect("update")
+            .expect("exists");
+        assert_eq!(updated.url, "https://b.example/hook");
+        assert!(updated.event_types.is_empty());
+        assert_eq!(repo.list().expect("list"), [updated]);
+
+        assert!(repo.delete(created.id).expect("delete"));
+        assert!(!repo.delete(created.id).expect("delete"));
+        assert!(repo
+            .update(created.id, &input("https://c.example", &[]))
+            .expect("update")
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn order_events_are_fanned_out_once_to_matching_subscriptions() {
+        let (_container, pool) = setup_db().await;
+        let repo = DieselWebhookRepository::new(pool.clone());
+        let mut conn = pool.get().expect("connection");
+        append_event(&mut conn, ORDER_AGGREGATE, "o-0", "OrderCreated", json!({})).expect("append");
+        // Let the next subscription be strictly newer than the first event.
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        let all = repo
+            .create(&input("https://all.example", &[]))
+            .expect("create");
+        let paid = repo
+            .create(&input("https://paid.example", &["OrderPaid"]))
+            .expect("create");
+
+        append_event(&mut conn, ORDER_AGGREGATE, "o-1", "OrderCreated", json!({})).expect("append");
+        append_event(&mut conn, ORDER_AGGREGATE, "o-1", "OrderPaid", json!({})).expect("append");
+        append_event(&mut conn, "OrderSaga", "o-1", "ReserveInventory", json!({})).expect("append");
+
+        assert_eq!(fan_out(&mut conn, 100).expect("fan out"), 3);
+        assert_eq!(fan_out(&mut conn, 100).expect("fan out"), 0);
+        assert_eq!(
+            queued(&pool),
+            [
+                (all.id, "OrderCreated".to_string()),
+                (all.id, "OrderPaid".to_string()),
+                (paid.id, "OrderPaid".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn failing_deliveries_are_retried_then_dead_lettered() {
+        let (_container, pool) = setup_db().await;
+        let repo = DieselWebhookRepository::new(pool.clone());
+        let subscription = repo
+            .create(&input("https://a.example", &[]))
+            .expect("create");
+        let mut conn = pool.get().expect("connection");
+        append_event(&mut conn, ORDER_AGGREGATE, "o-1", "OrderCreated", json!({})).expect("append");
+        fan_out(&mut conn, 100).expect("fan out");
+
+        let now = Utc::now();
+        let lease = now + chrono::Duration::seconds(30);
+        let due = claim_due(&mut conn, now, lease, 10).expect("claim");
+        assert_eq!(due.len(), 1);
+        assert_eq!(
+            (due[0].url.as_str(), due[0].subscription_id),
+            ("https://a.example", subscription.id)
+        );
+        assert_eq!(due[0].body["aggregate_id"], "o-1");
+        // Leased: not handed out twice.
+        assert!(claim_due(&mut conn, now, lease, 10)
+            .expect("claim")
+            .is_empty());
+
+        mark_failed(&mut conn, due[0].id, "HTTP 500", Some(now)).expect("mark");
+        let retry = claim_due(&mut conn, now, lease, 10).expect("claim");
+        assert_eq!(retry[0].attempts, 1);
+        assert!(repo
+            .dead_letters(subscription.id, 10)
+            .expect("dead")
+            .is_empty());
+
+        mark_failed(&mut conn, due[0].id, "HTTP 500", None).expect("mark");
+        let dead = repo.dead_letters(subscription.id, 10).expect("dead");
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].attempts, 2);
+        assert_eq!(dead[0].last_error.as_deref(), Some("HTTP 500"));
+        assert!(claim_due(&mut conn, Utc::now(), lease, 10)
+            .expect("claim")
+            .is_empty());
+
+        assert!(repo.requeue(subscription.id, dead[0].id).expect("requeue"));
+        assert!(!repo.requeue(subscription.id, dead[0].id).expect("requeue"));
+        let requeued = claim_due(&mut conn, Utc::now(), lease, 10).expect("claim");
+        assert_eq!(requeued[0].attempts, 0);
+        mark_delivered(&mut conn, requeued[0].id).expect("mark");
+        assert!(claim_due(&mut conn, lease, lease, 10)
+            .expect("claim")
+            .is_empty());
+    }
+}
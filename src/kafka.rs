@@ -0,0 +1,192 @@
+//! Kafka [`MessageTransport`] for events published through another service's
+//! Debezium outbox (enabled by the `kafka-consumer` feature).
+//!
+//! Records are expected as the EventRouter emits them with the Avro converter:
+//! the value is the Confluent-framed Avro string of the JSON payload, the
+//! event id is in the `id` header and the event type in the `eventType`
+//! header (`additional.placement` = `event_type:header:eventType`).
+
+use std::time::Duration;
+
+use rdkafka::config::ClientConfig;
+use rdkafka::consumer::{CommitMode, Consumer, StreamConsumer};
+use rdkafka::error::KafkaResult;
+use rdkafka::message::{BorrowedMessage, Headers, Message};
+use rdkafka::{Offset, TopicPartitionList};
+
+use crate::avro::decode_avro_string_payload;
+use crate::inbox::{InboundMessage, MessageTransport};
+
+pub const ID_HEADER: &str = "id";
+pub const EVENT_TYPE_HEADER: &str = "eventType";
+
+/// Consumes one topic with manual offset commits: an offset is committed only
+/// when its message is [acknowledged](MessageTransport::acknowledge).
+pub struct KafkaTransport {
+    consumer: StreamConsumer,
+    /// Position of the last message handed out and not yet acknowledged.
+    pending: Option<(String, i32, i64)>,
+}
+
+impl KafkaTransport {
+    pub fn subscribe(bootstrap_servers: &str, group_id: &str, topic: &str) -> KafkaResult<Self> {
+        let consumer: StreamConsumer = ClientConfig::new()
+            .set("bootstrap.servers", bootstrap_servers)
+            .set("group.id", group_id)
+            .set("enable.auto.commit", "false")
+            .set("auto.offset.reset", "earliest")
+            .create()?;
+        consumer.subscribe(&[topic])?;
+        Ok(Self {
+            consumer,
+            pending: None,
+        })
+    }
+
+    fn commit(&self, topic: &str, partition: i32, offset: i64) {
+        let mut offsets = TopicPartitionList::new();
+        let committed = offsets
+            .add_partition_offset(topic, partition, Offset::Offset(offset + 1))
+            .and_then(|()| self.consumer.commit(&offsets, CommitMode::Async));
+        if let Err(e) = committed {
+            log::error!(
+                "Failed to commit offset {} of {}[{}]: {}",
+                offset,
+                topic,
+                partition,
+                e
+            );
+        }
+    }
+}
+
+impl MessageTransport for KafkaTransport {
+    async fn receive(&mut self) -> Option<InboundMessage> {
+        loop {
+            let record = match self.consumer.recv().await {
+                Ok(record) => record,
+                Err(e) => {
+                    log::error!("Kafka consumer error: {}", e);
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    continue;
+                }
+            };
+            let position = (
+                record.topic().to_string(),
+                record.partition(),
+                record.offset(),
+            );
+            match decode_record(&record) {
+                Ok(message) => {
+                    self.pending = Some(position);
+                    return Some(message);
+                }
+                Err(e) => {
+                    log::warn!(
+                        "Skipping undecodable record {}[{}]@{}: {}",
+                        position.0,
+                        position.1,
+                        position.2,
+                        e
+                    );
+                    self.commit(&position.0, position.1, position.2);
+                }
+            }
+        }
+    }
+
+    async fn acknowledge(&mut self, _message: &InboundMessage) {
+        if let Some((topic, partition, offset)) = self.pending.take() {
+            self.commit(&topic, partition, offset);
+        }
+    }
+}
+
+fn decode_record(record: &BorrowedMessage<'_>) -> Result<InboundMessage, String> {
+    let header = |name: &str| {
+        record
+            .headers()
+            .and_then(|headers| headers.iter().find(|h| h.key == name).and_then(|h| h.value))
+    };
+    decode_message(
+        header(ID_HEADER),
+        header(EVENT_TYPE_HEADER),
+        record.payload(),
+    )
+}
+
+/// Build an [`InboundMessage`] from the raw `id` / `eventType` headers and
+/// the record value.
+pub fn decode_message(
+    id: Option<&[u8]>,
+    event_type: Option<&[u8]>,
+    value: Option<&[u8]>,
+) -> Result<InboundMessage, String> {
+    let text = |name: &str, bytes: Option<&[u8]>| {
+        let bytes = bytes.ok_or_else(|| format!("missing {} header", name))?;
+        // Header values may be quoted when written by a JSON header converter.
+        String::from_utf8(bytes.to_vec())
+            .map(|s| s.trim_matches('"').to_string())
+            .map_err(|_| format!("{} header is not UTF-8", name))
+    };
+    let id = text(ID_HEADER, id)?;
+    let message_type = text(EVENT_TYPE_HEADER, event_type)?;
+    let json = value
+        .and_then(decode_avro_string_payload)
+        .ok_or("value is not a Confluent-framed Avro string")?;
+    let payload = serde_json::from_str(&json).map_err(|e| format!("payload: {}", e))?;
+    Ok(InboundMessage {
+        id,
+        message_type,
+        payload,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Confluent frame (magic byte + schema id 1) around an Avro string.
+    fn framed(json: &str) -> Vec<u8> {
+        let mut bytes = vec![0, 0, 0, 0, 1];
+        // Zigzag varint length.
+        let mut n = (json.len() as u64) << 1;
+        while n >= 0x80 {
+            bytes.push((n as u8) | 0x80);
+            n >>= 7;
+        }
+        bytes.push(n as u8);
+        bytes.extend_from_slice(json.as_bytes());
+        bytes
+    }
+
+    #[test]
+    fn decodes_headers_and_avro_payload() {
+        let value = framed(r#"{"order_id":"a7b9c3d1-0000-0000-0000-000000000001"}"#);
+        let message = decode_message(
+            Some(b"\"e1\"".as_slice()),
+            Some(b"PaymentCaptured".as_slice()),
+            Some(&value),
+        )
+        .expect("decodable");
+        assert_eq!(message.id, "e1");
+        assert_eq!(message.message_type, "PaymentCaptured");
+        assert_eq!(
+            message.payload["order_id"],
+            "a7b9c3d1-0000-0000-0000-000000000001"
+        );
+    }
+
+    #[test]
+    fn rejects_records_without_headers_or_framing() {
+        let value = framed("{}");
+        assert!(decode_message(None, Some(b"PaymentCaptured".as_slice()), Some(&value)).is_err());
+        assert!(decode_message(Some(b"e1".as_slice()), None, Some(&value)).is_err());
+        assert!(decode_message(
+            Some(b"e1".as_slice()),
+            Some(b"PaymentCaptured".as_slice()),
+            Some(b"{}".as_slice())
+        )
+        .is_err());
+    }
+}
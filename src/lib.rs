@@ -1,20 +1,62 @@
+pub mod admin;
 pub mod application;
+pub mod asyncapi;
+pub mod auth;
 pub mod avro;
+pub mod config;
 pub mod db;
+pub mod debezium;
 pub mod domain;
 pub mod errors;
+pub mod event_sequence;
 pub mod handlers;
+pub mod inbox;
 pub mod infrastructure;
+#[cfg(feature = "kafka-consumer")]
+pub mod kafka;
+pub mod order_stream;
+pub mod outbox_listener;
+pub mod outbox_relay;
+pub mod payments;
+pub mod pg_tls;
+pub mod poll_loop;
+pub mod publishers;
+pub mod rate_limit;
+pub mod replication_monitor;
+pub mod replication_relay;
+pub mod saga;
 pub mod schema;
+pub mod shutdown;
+pub mod telemetry;
+#[cfg(test)]
+mod test_support;
+pub mod webhooks;
 
-use actix_web::{middleware::Logger, web, App, HttpServer};
+use std::sync::Arc;
+
+use actix_web::middleware::{from_fn, Logger};
+use actix_web::{web, App, HttpServer};
 use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
-use utoipa::OpenApi;
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
 use utoipa_swagger_ui::SwaggerUi;
 
 use application::order_service::OrderService;
+use application::outbox_service::OutboxDeadLetterService;
+use application::saga_service::SagaService;
+use application::webhook_service::WebhookService;
+use auth::{scopes, Authentication, JwtVerifier, RequireScope};
+use infrastructure::host_resolver::SystemHostResolver;
 use infrastructure::order_repo::DieselOrderRepository;
+use infrastructure::outbox_publication_repo::DieselOutboxDeadLetterRepository;
+use infrastructure::saga_repo::DieselSagaRepository;
+use infrastructure::unit_of_work::DieselUnitOfWork;
+use infrastructure::webhook_repo::DieselWebhookRepository;
+use order_stream::{OrderEventBus, OrderEventStreams};
+use rate_limit::{RateLimit, RateLimiter};
+use shutdown::Readiness;
 
+pub use config::Config;
 pub use db::{create_pool, DbPool};
 
 pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
@@ -22,9 +64,26 @@ pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
 #[derive(OpenApi)]
 #[openapi(
     paths(
+        handlers::health::health,
+        handlers::health::readiness,
         handlers::orders::create_order,
         handlers::orders::get_order,
+        handlers::orders::get_order_events,
+        handlers::orders::get_order_saga,
+        handlers::orders::stream_order_events,
+        handlers::orders::stream_all_order_events,
         handlers::orders::list_orders,
+        handlers::webhooks::create_webhook,
+        handlers::webhooks::list_webhooks,
+        handlers::webhooks::get_webhook,
+        handlers::webhooks::update_webhook,
+        handlers::webhooks::delete_webhook,
+        handlers::webhooks::list_dead_letters,
+        handlers::webhooks::retry_dead_letter,
+        handlers::outbox::list_outbox_dead_letters,
+        handlers::outbox::get_outbox_dead_letter,
+        handlers::outbox::retry_outbox_dead_letter,
+        handlers::outbox::discard_outbox_dead_letter,
     ),
     components(schemas(
         handlers::orders::CreateOrderRequest,
@@ -34,9 +93,26 @@ pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
         handlers::orders::OrderLineResponse,
         handlers::orders::ListOrdersParams,
         handlers::orders::ListOrdersResponse,
+        handlers::orders::StreamOrderEventsParams,
+        handlers::orders::OrderEventResponse,
+        handlers::orders::OrderHistoryResponse,
+        handlers::orders::SagaResponse,
+        handlers::orders::SagaStepResponse,
+        handlers::webhooks::WebhookRequest,
+        handlers::webhooks::WebhookResponse,
+        handlers::webhooks::CreateWebhookResponse,
+        handlers::webhooks::ListWebhooksResponse,
+        handlers::webhooks::WebhookDeliveryResponse,
+        handlers::webhooks::DeadLettersResponse,
+        handlers::outbox::OutboxDeadLetterResponse,
+        handlers::outbox::OutboxDeadLettersResponse,
     )),
+    modifiers(&SecurityAddon),
     tags(
-        (name = "orders", description = "Order management endpoints")
+        (name = "health", description = "Liveness and readiness probes"),
+        (name = "orders", description = "Order management endpoints"),
+        (name = "webhooks", description = "Webhook subscriptions for order events"),
+        (name = "outbox", description = "Dead letters of the outbox relay")
     ),
     info(
         title = "Order Service API",
@@ -46,6 +122,24 @@ pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
 )]
 pub struct ApiDoc;
 
+/// Declares the `bearer_auth` JWT scheme so Swagger UI can send tokens.
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer_auth",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .build(),
+            ),
+        );
+    }
+}
+
 /// Run any pending Diesel migrations against the pool's database.
 pub fn run_migrations(pool: &DbPool) {
     let mut conn = pool
@@ -55,40 +149,267 @@ pub fn run_migrations(pool: &DbPool) {
         .expect("Failed to run database migrations");
 }
 
-/// Build and return an actix-web `Server` bound to `host:port`.
+/// Build and return an actix-web `Server` bound to `config.host:config.port`.
 ///
 /// The caller is responsible for `.await`-ing (or `tokio::spawn`-ing) the
-/// returned server.
+/// returned server. The server does not handle signals itself: run it through
+/// a [`ShutdownCoordinator`](shutdown::ShutdownCoordinator), which flips
+/// `readiness` before draining requests. Order event streams relay what is
+/// published on `events`, normally by the [`order_stream::relay`] worker.
 pub fn build_server(
     pool: DbPool,
-    host: &str,
-    port: u16,
+    config: &Config,
+    readiness: Readiness,
+    events: OrderEventBus,
 ) -> std::io::Result<actix_web::dev::Server> {
     let openapi = ApiDoc::openapi();
+    let verifier = match &config.auth {
+        Some(auth) => Some(Arc::new(
+            JwtVerifier::from_config(auth).map_err(std::io::Error::other)?,
+        )),
+        None => {
+            log::warn!(
+                "No AUTH_JWT_* key configured: the API is unauthenticated and /webhooks and \
+                 /admin/outbox are refused"
+            );
+            None
+        }
+    };
+    if config.webhook_security.secret_key.is_none() {
+        log::warn!("No WEBHOOK_SECRET_KEY configured: webhook subscriptions cannot be created");
+    }
+    let webhook_security = config.webhook_security.clone();
+
+    let rate_limiter = config.rate_limit.clone().map(RateLimiter::new);
+    let saga_timeouts = config.saga.as_ref().map(|saga| saga.timeouts);
+    // The route events are published on, by Debezium or the outbox relay.
+    let event_route = web::Data::new(config.debezium.route.clone());
+    let streams = web::Data::new(OrderEventStreams::new(
+        pool.clone(),
+        events,
+        readiness.clone(),
+    ));
+
     Ok(HttpServer::new(move || {
-        let service = web::Data::new(OrderService::new(DieselOrderRepository::new(pool.clone())));
+        let mut orders = OrderService::new(
+            DieselOrderRepository::new(pool.clone()),
+            DieselUnitOfWork::new(pool.clone()),
+        );
+        if let Some(timeouts) = saga_timeouts {
+            orders = orders.with_saga(timeouts);
+        }
+        let service = web::Data::new(orders);
+        let sagas = web::Data::new(SagaService::new(
+            DieselSagaRepository::new(pool.clone()),
+            DieselUnitOfWork::new(pool.clone()),
+            saga_timeouts.unwrap_or_default(),
+        ));
+        let webhooks = web::Data::new(
+            WebhookService::new(
+                DieselWebhookRepository::new(pool.clone()),
+                Arc::new(SystemHostResolver),
+                webhook_security.secret_key.clone(),
+            )
+            .with_private_destinations(webhook_security.allow_private_destinations),
+        );
+        let dead_letters = web::Data::new(OutboxDeadLetterService::new(
+            DieselOutboxDeadLetterRepository::new(pool.clone()),
+        ));
         App::new()
             .app_data(service)
+            .app_data(sagas)
+            .app_data(webhooks)
+            .app_data(dead_letters)
+            .app_data(streams.clone())
+            .app_data(web::Data::new(readiness.clone()))
+            .app_data(event_route.clone())
+            .wrap(from_fn(shutdown::close_connections_when_draining))
             .wrap(Logger::default())
+            .route("/health", web::get().to(handlers::health::health))
+            .route("/readiness", web::get().to(handlers::health::readiness))
             .service(
                 SwaggerUi::new("/swagger-ui/{_:.*}").url("/api-docs/openapi.json", openapi.clone()),
             )
+            .route(
+                "/api-docs/asyncapi.json",
+                web::get().to(handlers::docs::asyncapi_document),
+            )
             .service(
                 web::scope("/orders")
+                    .wrap(RateLimit::new(rate_limiter.clone()))
+                    .wrap(Authentication::new(verifier.clone()))
+                    .route(
+                        "",
+                        web::post()
+                            .to(handlers::orders::create_order::<
+                                DieselOrderRepository,
+                                DieselUnitOfWork,
+                            >)
+                            .wrap(RequireScope::new(scopes::ORDERS_WRITE)),
+                    )
+                    .route(
+                        "",
+                        web::get()
+                            .to(handlers::orders::list_orders::<
+                                DieselOrderRepository,
+                                DieselUnitOfWork,
+                            >)
+                            .wrap(RequireScope::new(scopes::ORDERS_READ)),
+                    )
+                    // Before `/{id}`, which would reject "stream" as an order id.
+                    .route(
+                        "/stream",
+                        web::get()
+                            .to(handlers::orders::stream_all_order_events)
+                            .wrap(RequireScope::new(scopes::ORDERS_READ)),
+                    )
+                    .route(
+                        "/{id}",
+                        web::get()
+                            .to(handlers::orders::get_order::<
+                                DieselOrderRepository,
+                                DieselUnitOfWork,
+                            >)
+                            .wrap(RequireScope::new(scopes::ORDERS_READ)),
+                    )
+                    .route(
+                        "/{id}/events",
+                        web::get()
+                            .to(handlers::orders::get_order_events::<
+                                DieselOrderRepository,
+                                DieselUnitOfWork,
+                            >)
+                            .wrap(RequireScope::new(scopes::ORDERS_READ)),
+                    )
+                    .route(
+                        "/{id}/stream",
+                        web::get()
+                            .to(handlers::orders::stream_order_events::<
+                                DieselOrderRepository,
+                                DieselUnitOfWork,
+                            >)
+                            .wrap(RequireScope::new(scopes::ORDERS_READ)),
+                    )
+                    .route(
+                        "/{id}/saga",
+                        web::get()
+                            .to(handlers::orders::get_order_saga::<
+                                DieselSagaRepository,
+                                DieselUnitOfWork,
+                            >)
+                            .wrap(RequireScope::new(scopes::ORDERS_READ)),
+                    ),
+            )
+            .service(
+                web::scope("/webhooks")
+                    .wrap(RequireScope::new(scopes::WEBHOOKS_MANAGE))
+                    .wrap(RateLimit::new(rate_limiter.clone()))
+                    // Subscribers receive every order's events: never open.
+                    .wrap(Authentication::required(verifier.clone()))
                     .route(
                         "",
-                        web::post().to(handlers::orders::create_order::<DieselOrderRepository>),
+                        web::post()
+                            .to(handlers::webhooks::create_webhook::<DieselWebhookRepository>),
                     )
                     .route(
                         "",
-                        web::get().to(handlers::orders::list_orders::<DieselOrderRepository>),
+                        web::get().to(handlers::webhooks::list_webhooks::<DieselWebhookRepository>),
+                    )
+                    .route(
+                        "/{id}",
+                        web::get().to(handlers::webhooks::get_webhook::<DieselWebhookRepository>),
+                    )
+                    .route(
+                        "/{id}",
+                        web::put()
+                            .to(handlers::webhooks::update_webhook::<DieselWebhookRepository>),
                     )
                     .route(
                         "/{id}",
-                        web::get().to(handlers::orders::get_order::<DieselOrderRepository>),
+                        web::delete()
+                            .to(handlers::webhooks::delete_webhook::<DieselWebhookRepository>),
+                    )
+                    .route(
+                        "/{id}/dead-letters",
+                        web::get()
+                            .to(handlers::webhooks::list_dead_letters::<DieselWebhookRepository>),
+                    )
+                    .route(
+                        "/{id}/dead-letters/{delivery_id}/retry",
+                        web::post()
+                            .to(handlers::webhooks::retry_dead_letter::<DieselWebhookRepository>),
+                    ),
+            )
+            .service(
+                web::scope("/admin/outbox")
+                    .wrap(RequireScope::new(scopes::OUTBOX_ADMIN))
+                    .wrap(RateLimit::new(rate_limiter.clone()))
+                    // Dead letters hold any order's payloads: never open.
+                    .wrap(Authentication::required(verifier.clone()))
+                    .route(
+                        "/dead-letters",
+                        web::get().to(handlers::outbox::list_outbox_dead_letters::<
+                            DieselOutboxDeadLetterRepository,
+                        >),
+                    )
+                    .route(
+                        "/dead-letters/{event_id}",
+                        web::get().to(handlers::outbox::get_outbox_dead_letter::<
+                            DieselOutboxDeadLetterRepository,
+                        >),
+                    )
+                    .route(
+                        "/dead-letters/{event_id}",
+                        web::delete().to(handlers::outbox::discard_outbox_dead_letter::<
+                            DieselOutboxDeadLetterRepository,
+                        >),
+                    )
+                    .route(
+                        "/dead-letters/{event_id}/retry",
+                        web::post().to(handlers::outbox::retry_outbox_dead_letter::<
+                            DieselOutboxDeadLetterRepository,
+                        >),
                     ),
             )
     })
-    .bind((host.to_string(), port))?
+    .disable_signals()
+    .shutdown_timeout(config.shutdown.drain_timeout.as_secs())
+    .bind((config.host.clone(), config.port))?
     .run())
 }
+
+#[cfg(test)]
+mod tests {
+    use reqwest::StatusCode;
+
+    use super::*;
+    use crate::test_support::{free_port, setup_db};
+
+    #[actix_web::test]
+    async fn sensitive_routes_are_refused_without_authentication() {
+        let (_container, pool) = setup_db().await;
+        let port = free_port();
+        let config = Config {
+            host: "127.0.0.1".into(),
+            port,
+            rate_limit: None,
+            ..Config::new("unused")
+        };
+        assert!(config.auth.is_none());
+        let server = build_server(pool, &config, Readiness::new(), OrderEventBus::new())
+            .expect("server binds");
+        let handle = server.handle();
+        actix_web::rt::spawn(server);
+
+        let http = reqwest::Client::new();
+        for path in ["/admin/outbox/dead-letters", "/webhooks"] {
+            let resp = http
+                .get(format!("http://127.0.0.1:{}{}", port, path))
+                .send()
+                .await
+                .expect("server answers");
+            assert_eq!(resp.status(), StatusCode::UNAUTHORIZED, "{}", path);
+        }
+        handle.stop(true).await;
+    }
+}
@@ -1,23 +1,313 @@
 use dotenvy::dotenv;
-use order_service::{build_server, create_pool, run_migrations};
-use std::env;
+use order_service::order_stream::{self, OrderEventBus};
+use order_service::outbox_listener::{self, OutboxWakeups, WakeupReceiver};
+use order_service::outbox_relay::EventPublisher;
+use order_service::payments::PaymentsConsumerConfig;
+use order_service::publishers::PublisherConfig;
+use order_service::replication_monitor::ReplicationMonitorConfig;
+use order_service::saga::SagaConfig;
+use order_service::shutdown::ShutdownCoordinator;
+use order_service::webhooks::{WebhookConfig, WebhookSecurity};
+use order_service::{build_server, create_pool, run_migrations, telemetry, Config, DbPool};
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     dotenv().ok();
     env_logger::init_from_env(env_logger::Env::default().default_filter_or("info"));
+    let _telemetry = telemetry::init();
 
-    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-    let host = env::var("HOST").unwrap_or_else(|_| "0.0.0.0".to_string());
-    let port: u16 = env::var("PORT")
-        .unwrap_or_else(|_| "8080".to_string())
-        .parse()
-        .expect("PORT must be a valid number");
+    let mut config = Config::from_env().unwrap_or_else(|e| panic!("Invalid configuration: {}", e));
 
-    let pool = create_pool(&database_url);
+    let pool = create_pool(&config.database_url);
     run_migrations(&pool);
 
-    log::info!("Starting server at http://{}:{}", host, port);
+    log::info!("Starting server at http://{}:{}", config.host, config.port);
 
-    build_server(pool, &host, port)?.await
+    let mut coordinator = ShutdownCoordinator::new(config.shutdown);
+    if let Some(payments) = &config.payments {
+        spawn_payments_consumer(&mut coordinator, payments, pool.clone());
+    }
+    if let Some(saga) = &config.saga {
+        if !spawn_saga_workers(&mut coordinator, saga, pool.clone()) {
+            config.saga = None;
+        }
+    }
+    let wakeups = OutboxWakeups::new();
+    if let Some(webhooks) = &config.webhooks {
+        spawn_webhook_delivery(
+            &mut coordinator,
+            webhooks,
+            &config.webhook_security,
+            pool.clone(),
+            wakeups.subscribe(),
+        );
+    }
+    if let Some(publisher) = &config.outbox_publisher {
+        spawn_outbox_relay(
+            &mut coordinator,
+            publisher,
+            &config,
+            pool.clone(),
+            wakeups.subscribe(),
+        )
+        .await;
+    }
+    let events = OrderEventBus::new();
+    let relay_pool = pool.clone();
+    let bus = events.clone();
+    let relayed = wakeups.subscribe();
+    coordinator.spawn_worker("order-event-relay", |signal| {
+        order_stream::relay(relay_pool, bus, relayed, signal)
+    });
+    let database_url = config.database_url.clone();
+    let listener_config = config.outbox_listener;
+    coordinator.spawn_worker("outbox-listener", |signal| {
+        outbox_listener::listen(database_url, listener_config, wakeups, signal)
+    });
+    if let Some(monitor) = &config.replication_monitor {
+        spawn_replication_monitor(&mut coordinator, monitor, pool.clone());
+    }
+    let server = build_server(pool.clone(), &config, coordinator.readiness(), events)?;
+    coordinator.close_pool(pool);
+    coordinator.run(server).await
+}
+
+fn spawn_webhook_delivery(
+    coordinator: &mut ShutdownCoordinator,
+    config: &WebhookConfig,
+    security: &WebhookSecurity,
+    pool: DbPool,
+    wakeups: WakeupReceiver,
+) {
+    use std::sync::Arc;
+
+    use order_service::webhooks::{self, WebhookDispatcher, WebhookError};
+
+    let dispatcher = match WebhookDispatcher::new(pool, config.clone(), security.clone()) {
+        Ok(dispatcher) => dispatcher,
+        Err(WebhookError::MissingSecretKey) => {
+            log::warn!("No WEBHOOK_SECRET_KEY configured: webhook delivery is disabled");
+            return;
+        }
+        Err(e) => panic!("Cannot create the webhook HTTP client: {}", e),
+    };
+    coordinator.spawn_worker("webhook-delivery", |signal| {
+        webhooks::run(Arc::new(dispatcher), wakeups, signal)
+    });
+}
+
+fn spawn_replication_monitor(
+    coordinator: &mut ShutdownCoordinator,
+    config: &ReplicationMonitorConfig,
+    pool: DbPool,
+) {
+    use std::sync::Arc;
+
+    use order_service::replication_monitor::{self, ReplicationMonitor};
+
+    let monitor = ReplicationMonitor::new(pool, config.clone(), coordinator.readiness());
+    log::info!("Watching replication slot {}", config.slot_name);
+    coordinator.spawn_worker("replication-monitor", |signal| {
+        replication_monitor::run(Arc::new(monitor), signal)
+    });
+}
+
+async fn spawn_outbox_relay(
+    coordinator: &mut ShutdownCoordinator,
+    config: &PublisherConfig,
+    service: &Config,
+    pool: DbPool,
+    wakeups: WakeupReceiver,
+) {
+    use order_service::publishers::file::FilePublisher;
+    use order_service::publishers::PublisherBackend;
+
+    // Watching Debezium's slot suggests Debezium still runs. Nothing here
+    // checks for a connector running without the monitor; see the README.
+    let debezium_slot = service.debezium.slot();
+    if service
+        .replication_monitor
+        .as_ref()
+        .is_some_and(|monitor| monitor.slot_name == debezium_slot)
+    {
+        log::warn!(
+            "The replication monitor watches Debezium's slot {}: if Debezium captures the \
+             outbox, every event is published twice",
+            debezium_slot
+        );
+    }
+    let mapper = config.mapper();
+    match &config.backend {
+        PublisherBackend::File { path } => {
+            let publisher = FilePublisher::open(path, mapper)
+                .unwrap_or_else(|e| panic!("Cannot open {}: {}", path.display(), e));
+            log::info!("Publishing outbox events to {}", path.display());
+            spawn_relay(coordinator, publisher, service, pool, wakeups);
+        }
+        #[cfg(feature = "nats")]
+        PublisherBackend::Nats { url } => {
+            use order_service::publishers::nats::NatsPublisher;
+
+            let publisher = NatsPublisher::connect(url, mapper)
+                .await
+                .unwrap_or_else(|e| panic!("Cannot create the NATS client: {}", e));
+            log::info!("Publishing outbox events to NATS JetStream");
+            spawn_relay(coordinator, publisher, service, pool, wakeups);
+        }
+        #[cfg(feature = "amqp")]
+        PublisherBackend::Amqp { url } => {
+            use order_service::publishers::amqp::AmqpPublisher;
+
+            log::info!("Publishing outbox events over AMQP");
+            spawn_relay(
+                coordinator,
+                AmqpPublisher::new(url, mapper),
+                service,
+                pool,
+                wakeups,
+            );
+        }
+        #[cfg(feature = "redis-streams")]
+        PublisherBackend::RedisStreams { url } => {
+            use order_service::publishers::redis_streams::RedisStreamPublisher;
+
+            let publisher = RedisStreamPublisher::new(url, mapper)
+                .unwrap_or_else(|e| panic!("Invalid OUTBOX_PUBLISHER_URL: {}", e));
+            log::info!("Publishing outbox events to Redis Streams");
+            spawn_relay(coordinator, publisher, service, pool, wakeups);
+        }
+        #[allow(unreachable_patterns)]
+        backend => {
+            let feature = match backend {
+                PublisherBackend::RedisStreams { .. } => "redis-streams",
+                other => other.name(),
+            };
+            log::warn!(
+                "OUTBOX_PUBLISHER is {} but the service was built without the `{}` \
+                 feature; outbox events are left to Debezium",
+                backend.name(),
+                feature
+            );
+        }
+    }
+}
+
+/// Polls the outbox, or streams it through logical replication with
+/// `OUTBOX_RELAY_MODE=replication`.
+fn spawn_relay<P: EventPublisher>(
+    coordinator: &mut ShutdownCoordinator,
+    publisher: P,
+    config: &Config,
+    pool: DbPool,
+    wakeups: WakeupReceiver,
+) {
+    use std::sync::Arc;
+
+    use order_service::outbox_relay::{self, OutboxRelay};
+    use order_service::replication_relay::{self, ReplicationRelay};
+
+    if let Some(replication) = &config.outbox_replication {
+        let relay = ReplicationRelay::new(&config.database_url, publisher, replication.clone());
+        coordinator.spawn_worker("outbox-replication-relay", |signal| {
+            replication_relay::run(Arc::new(relay), signal)
+        });
+        return;
+    }
+    let relay = OutboxRelay::new(pool, publisher, config.outbox_relay.clone());
+    coordinator.spawn_worker("outbox-relay", |signal| {
+        outbox_relay::run(Arc::new(relay), wakeups, signal)
+    });
+}
+
+#[cfg(feature = "kafka-consumer")]
+fn spawn_payments_consumer(
+    coordinator: &mut ShutdownCoordinator,
+    config: &PaymentsConsumerConfig,
+    pool: DbPool,
+) {
+    use std::sync::Arc;
+
+    use order_service::application::order_service::OrderService;
+    use order_service::inbox::{self, Inbox};
+    use order_service::infrastructure::order_repo::DieselOrderRepository;
+    use order_service::infrastructure::unit_of_work::DieselUnitOfWork;
+    use order_service::kafka::KafkaTransport;
+    use order_service::payments::PaymentHandler;
+
+    let transport =
+        KafkaTransport::subscribe(&config.bootstrap_servers, &config.group_id, &config.topic)
+            .unwrap_or_else(|e| panic!("Cannot create the payments consumer: {}", e));
+    let service = Arc::new(OrderService::new(
+        DieselOrderRepository::new(pool.clone()),
+        DieselUnitOfWork::new(pool),
+    ));
+    let inbox = Arc::new(Inbox::new().with_handler(PaymentHandler::new(service)));
+    log::info!("Consuming payment events from {}", config.topic);
+    coordinator.spawn_worker("payments-consumer", |signal| {
+        inbox::consume(inbox, transport, signal)
+    });
+}
+
+#[cfg(not(feature = "kafka-consumer"))]
+fn spawn_payments_consumer(
+    _coordinator: &mut ShutdownCoordinator,
+    _config: &PaymentsConsumerConfig,
+    _pool: DbPool,
+) {
+    log::warn!(
+        "PAYMENTS_KAFKA_BOOTSTRAP_SERVERS is set but the service was built without the \
+         `kafka-consumer` feature; payment events are not consumed"
+    );
+}
+
+/// Returns whether the saga can run; orders are placed without one otherwise.
+#[cfg(feature = "kafka-consumer")]
+fn spawn_saga_workers(
+    coordinator: &mut ShutdownCoordinator,
+    config: &SagaConfig,
+    pool: DbPool,
+) -> bool {
+    use std::sync::Arc;
+
+    use order_service::application::saga_service::SagaService;
+    use order_service::inbox::{self, Inbox};
+    use order_service::infrastructure::saga_repo::DieselSagaRepository;
+    use order_service::infrastructure::unit_of_work::DieselUnitOfWork;
+    use order_service::kafka::KafkaTransport;
+    use order_service::saga::{self, SagaReplyHandler};
+
+    let transport = KafkaTransport::subscribe(
+        &config.bootstrap_servers,
+        &config.group_id,
+        &config.reply_topic,
+    )
+    .unwrap_or_else(|e| panic!("Cannot create the saga reply consumer: {}", e));
+    let service = Arc::new(SagaService::new(
+        DieselSagaRepository::new(pool.clone()),
+        DieselUnitOfWork::new(pool),
+        config.timeouts,
+    ));
+    log::info!("Consuming saga replies from {}", config.reply_topic);
+    let replies = Arc::new(Inbox::new().with_handler(SagaReplyHandler::new(Arc::clone(&service))));
+    coordinator.spawn_worker("saga-replies", |signal| {
+        inbox::consume(replies, transport, signal)
+    });
+    let interval = config.timeout_check_interval;
+    coordinator.spawn_worker("saga-timeouts", move |signal| {
+        saga::expire_timeouts(service, interval, signal)
+    });
+    true
+}
+
+#[cfg(not(feature = "kafka-consumer"))]
+fn spawn_saga_workers(
+    _coordinator: &mut ShutdownCoordinator,
+    _config: &SagaConfig,
+    _pool: DbPool,
+) -> bool {
+    log::warn!(
+        "SAGA_KAFKA_BOOTSTRAP_SERVERS is set but the service was built without the \
+         `kafka-consumer` feature; orders are placed without a saga"
+    );
+    false
 }
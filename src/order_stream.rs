@@ -0,0 +1,536 @@
+//! Server-Sent Events streams of order events.
+//!
+//! The [`relay`] worker loads the order events announced by the
+//! [outbox listener](crate::outbox_listener) and publishes them on the
+//! [`OrderEventBus`]; every open stream relays the bus events its
+//! [`OrderEventFilter`] matches. A client reconnecting with
+//! `Last-Event-ID` first catches up from the outbox rows after that event.
+
+use std::collections::HashSet;
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_web::web::Bytes;
+use diesel::prelude::*;
+use futures::Stream;
+use serde_json::json;
+use thiserror::Error;
+use tokio::sync::{broadcast, mpsc};
+use tokio::time::Instant;
+use uuid::Uuid;
+
+use crate::domain::order::{OrderEvent, OrderEventFilter};
+use crate::infrastructure::order_repo;
+use crate::outbox_listener::{Wakeup, WakeupReceiver};
+use crate::shutdown::{Readiness, ShutdownSignal};
+use crate::DbPool;
+
+/// Events buffered per stream before a slow client lags and has to catch up
+/// from the outbox.
+const BUS_CAPACITY: usize = 1024;
+/// Outbox rows loaded per query while catching up.
+const CATCH_UP_PAGE: i64 = 500;
+/// Comment sent on idle streams so that proxies keep them open.
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+/// How often an idle stream checks whether the service is shutting down.
+const READINESS_CHECK: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Error)]
+pub enum StreamError {
+    #[error("Database error: {0}")]
+    Database(#[from] diesel::result::Error),
+
+    #[error("Connection pool error: {0}")]
+    Pool(#[from] r2d2::Error),
+
+    #[error("Worker error: {0}")]
+    Worker(#[from] tokio::task::JoinError),
+}
+
+/// Committed order events, fanned out to the open streams of this process.
+#[derive(Clone)]
+pub struct OrderEventBus(broadcast::Sender<Arc<OrderEvent>>);
+
+impl OrderEventBus {
+    pub fn new() -> Self {
+        Self(broadcast::channel(BUS_CAPACITY).0)
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<OrderEvent>> {
+        self.0.subscribe()
+    }
+
+    pub fn publish(&self, event: OrderEvent) {
+        // No receiver just means that no stream is open.
+        let _ = self.0.send(Arc::new(event));
+    }
+}
+
+impl Default for OrderEventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ── Relay ────────────────────────────────────────────────────────────────────
+
+/// Publish the order events announced by `wakeups` on `bus` until shutdown is
+/// signalled. Meant to run as a [`ShutdownCoordinator`](crate::shutdown::ShutdownCoordinator)
+/// worker.
+///
+/// On [`Wakeup::Poll`] the events written after the last one published are
+/// read from the outbox, so none is lost while the outbox listener reconnects.
+pub async fn relay(
+    pool: DbPool,
+    bus: OrderEventBus,
+    mut wakeups: WakeupReceiver,
+    mut shutdown: ShutdownSignal,
+) {
+    let mut last: Option<OrderEvent> = None;
+    loop {
+        let wakeup = tokio::select! {
+            _ = shutdown.triggered() => return,
+            wakeup = wakeups.next() => match wakeup {
+                Some(wakeup) => wakeup,
+                None => return,
+            },
+        };
+        // Handle everything already queued in one go.
+        let mut ids = Vec::new();
+        let mut poll = false;
+        for wakeup in std::iter::once(wakeup).chain(std::iter::from_fn(|| wakeups.try_next())) {
+            match wakeup {
+                Wakeup::Inserted(id) => ids.push(id),
+                Wakeup::Poll => poll = true,
+            }
+        }
+
+        let pool = pool.clone();
+        let after = last.clone();
+        let loaded = tokio::task::spawn_blocking(move || {
+            let mut conn = pool.get()?;
+            match after {
+                Some(after) if poll => missed_events(&mut conn, &after),
+                _ => Ok(order_repo::load_events_by_id(&mut conn, &ids)?),
+            }
+        })
+        .await;
+        match loaded {
+            Ok(Ok(events)) => publish_all(&bus, &mut last, events),
+            Ok(Err(e)) => log::error!("Cannot load order events to stream: {}", e),
+            Err(e) => log::error!("Cannot load order events to stream: {}", e),
+        }
+    }
+}
+
+/// Events written after `after`, e.g. while the outbox listener was
+/// disconnected.
+fn missed_events(
+    conn: &mut PgConnection,
+    after: &OrderEvent,
+) -> Result<Vec<OrderEvent>, StreamError> {
+    let filter = OrderEventFilter::default();
+    let mut missed = Vec::new();
+    loop {
+        let page = order_repo::load_events_after(
+            conn,
+            &filter,
+            Some(missed.last().unwrap_or(after)),
+            CATCH_UP_PAGE,
+        )?;
+        let done = page.len() < CATCH_UP_PAGE as usize;
+        missed.extend(page);
+        if done {
+            return Ok(missed);
+        }
+    }
+}
+
+/// Publish `events`, remembering the latest one as where to catch up from.
+fn publish_all(bus: &OrderEventBus, last: &mut Option<OrderEvent>, events: Vec<OrderEvent>) {
+    for event in events {
+        let later = last
+            .as_ref()
+            .is_none_or(|last| (event.occurred_at, event.id) > (last.occurred_at, last.id));
+        if later {
+            *last = Some(event.clone());
+        }
+        bus.publish(event);
+    }
+}
+
+// ── Streams ──────────────────────────────────────────────────────────────────
+
+/// Opens SSE streams of the events published on an [`OrderEventBus`].
+#[derive(Clone)]
+pub struct OrderEventStreams {
+    pool: DbPool,
+    bus: OrderEventBus,
+    readiness: Readiness,
+}
+
+impl OrderEventStreams {
+    pub fn new(pool: DbPool, bus: OrderEventBus, readiness: Readiness) -> Self {
+        Self {
+            pool,
+            bus,
+            readiness,
+        }
+    }
+
+    /// A `text/event-stream` body with the events matching `filter`.
+    ///
+    /// With `last_event_id`, the events after it are replayed first. For a
+    /// single order, an id that is not one of its events replays the whole
+    /// history; for other filters it is ignored. The stream ends when the
+    /// client goes away or the service starts shutting down.
+    pub async fn open(
+        &self,
+        filter: OrderEventFilter,
+        last_event_id: Option<Uuid>,
+    ) -> Result<impl Stream<Item = Result<Bytes, Infallible>>, StreamError> {
+        // Subscribe before reading the outbox, so no event falls in between.
+        let events = self.bus.subscribe();
+
+        let cursor = match last_event_id {
+            Some(id) => {
+                let pool = self.pool.clone();
+                let found = tokio::task::spawn_blocking(move || {
+                    let mut conn = pool.get()?;
+                    Ok::<_, StreamError>(order_repo::load_events_by_id(&mut conn, &[id])?)
+                })
+                .await??;
+                found.into_iter().find(|event| filter.matches(event))
+            }
+            None => None,
+        };
+        let catch_up = cursor.is_some() || (last_event_id.is_some() && filter.order_id.is_some());
+
+        let (tx, rx) = mpsc::channel(16);
+        let pump = Pump {
+            pool: self.pool.clone(),
+            filter,
+            cursor,
+            events,
+            tx,
+            readiness: self.readiness.clone(),
+            last_write: Instant::now(),
+        };
+        actix_web::rt::spawn(pump.run(catch_up));
+
+        Ok(futures::stream::unfold(rx, |mut rx| async move {
+            let chunk = rx.recv().await?;
+            Some((Ok(chunk), rx))
+        }))
+    }
+}
+
+/// Moves the events of one stream from the outbox and the bus to the client.
+struct Pump {
+    pool: DbPool,
+    filter: OrderEventFilter,
+    /// Last event sent, where catching up resumes.
+    cursor: Option<OrderEvent>,
+    events: broadcast::Receiver<Arc<OrderEvent>>,
+    tx: mpsc::Sender<Bytes>,
+    readiness: Readiness,
+    last_write: Instant,
+}
+
+impl Pump {
+    async fn run(mut self, mut catch_up: bool) {
+        // Events sent while catching up, which may also still be on the bus.
+        let mut replayed = HashSet::new();
+        let mut ticks = tokio::time::interval(READINESS_CHECK);
+        loop {
+            if catch_up {
+                let page = match self.next_page().await {
+                    Ok(page) => page,
+                    Err(e) => {
+                        log::error!("Cannot replay order events: {}", e);
+                        return;
+                    }
+                };
+                catch_up = page.len() == CATCH_UP_PAGE as usize;
+                for event in page {
+                    replayed.insert(event.id);
+                    if !self.send(event).await {
+                        return;
+                    }
+                }
+                continue;
+            }
+
+            tokio::select! {
+                received = self.events.recv() => match received {
+                    Ok(event) => {
+                        if self.filter.matches(&event)
+                            && !replayed.contains(&event.id)
+                            && !self.send(OrderEvent::clone(&event)).await
+                        {
+                            return;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        log::warn!("Order event stream lagged behind by {} events", skipped);
+                        // Without a cursor a single order replays its history;
+                        // any other stream can only go on.
+                        if self.cursor.is_some() || self.filter.order_id.is_some() {
+                            replayed.clear();
+                            catch_up = true;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return,
+                },
+                _ = ticks.tick() => {
+                    if !self.readiness.is_ready() {
+                        return;
+                    }
+                    if self.last_write.elapsed() >= KEEP_ALIVE
+                        && !self.write(Bytes::from_static(b": keep-alive\n\n")).await
+                    {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    async fn next_page(&self) -> Result<Vec<OrderEvent>, StreamError> {
+        let pool = self.pool.clone();
+        let filter = self.filter.clone();
+        let after = self.cursor.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut conn = pool.get()?;
+            Ok(order_repo::load_events_after(
+                &mut conn,
+                &filter,
+                after.as_ref(),
+                CATCH_UP_PAGE,
+            )?)
+        })
+        .await?
+    }
+
+    /// `false` once the client is gone.
+    async fn send(&mut self, event: OrderEvent) -> bool {
+        let sent = self.write(sse_event(&event)).await;
+        self.cursor = Some(event);
+        sent
+    }
+
+    async fn write(&mut self, chunk: Bytes) -> bool {
+        self.last_write = Instant::now();
+        self.tx.send(chunk).await.is_ok()
+    }
+}
+
+/// One SSE message: the outbox id as `id`, the event type as `event` and the
+/// event as JSON `data`.
+pub fn sse_event(event: &OrderEvent) -> Bytes {
+    let data = json!({
+        "id": event.id,
+        "order_id": event.order_id,
+        "event_type": event.event_type,
+        "sequence": event.sequence,
+        "occurred_at": event.occurred_at.to_rfc3339(),
+        "payload": event.payload,
+    });
+    Bytes::from(format!(
+        "id: {}\nevent: {}\ndata: {}\n\n",
+        event.id, event.event_type, data
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::pin::Pin;
+
+    use chrono::Utc;
+    use diesel::result::Error as DieselError;
+    use futures::StreamExt;
+    use serde_json::Value;
+
+    use super::*;
+    use crate::domain::order::ORDER_AGGREGATE;
+    use crate::domain::saga::SAGA_AGGREGATE;
+    use crate::infrastructure::outbox::OutboxWriteError;
+    use crate::outbox_listener::{self, OutboxListenerConfig, OutboxWakeups};
+    use crate::test_support::{append_event, setup_db, setup_db_with_url};
+
+    fn append(pool: &DbPool, aggregate_type: &str, order_id: Uuid, event_type: &str) -> Uuid {
+        let mut conn = pool.get().expect("connection");
+        append_event(
+            &mut conn,
+            aggregate_type,
+            &order_id.to_string(),
+            event_type,
+            serde_json::json!({}),
+        )
+        .expect("append")
+        .id
+    }
+
+    async fn next_id(stream: &mut Pin<Box<impl Stream<Item = Result<Bytes, Infallible>>>>) -> Uuid {
+        let chunk = tokio::time::timeout(Duration::from_secs(5), stream.next())
+            .await
+            .expect("event in time")
+            .expect("stream open")
+            .expect("chunk");
+        let text = std::str::from_utf8(&chunk).expect("utf-8");
+        let id = text
+            .strip_prefix("id: ")
+            .and_then(|rest| rest.lines().next())
+            .expect("id line");
+        Uuid::parse_str(id).expect("event id")
+    }
+
+    #[test]
+    fn sse_event_carries_id_type_and_json_data() {
+        let event = OrderEvent {
+            id: Uuid::new_v4(),
+            order_id: Uuid::new_v4(),
+            event_type: "OrderPaid".to_string(),
+            sequence: 3,
+            occurred_at: Utc::now(),
+            payload: serde_json::json!({ "status": "PAID" }),
+        };
+
+        let message = sse_event(&event);
+        let text = std::str::from_utf8(&message).expect("utf-8");
+        let lines: Vec<&str> = text.split('\n').collect();
+        assert_eq!(lines[0], format!("id: {}", event.id));
+        assert_eq!(lines[1], "event: OrderPaid");
+        let data: Value =
+            serde_json::from_str(lines[2].strip_prefix("data: ").expect("data")).expect("json");
+        assert_eq!(data["sequence"], 3);
+        assert_eq!(data["payload"]["status"], "PAID");
+        assert_eq!(&lines[3..], ["", ""]);
+    }
+
+    #[actix_web::test]
+    async fn relay_publishes_committed_order_events() {
+        let (_container, pool, url) = setup_db_with_url().await;
+        let bus = OrderEventBus::new();
+        let mut received = bus.subscribe();
+        let wakeups = OutboxWakeups::new();
+        let mut listening = wakeups.subscribe();
+        let relayed = wakeups.subscribe();
+        let (stop, signal) = ShutdownSignal::for_test();
+        let listener = actix_web::rt::spawn(outbox_listener::listen(
+            url,
+            OutboxListenerConfig::default(),
+            wakeups,
+            signal.clone(),
+        ));
+        let worker = actix_web::rt::spawn(relay(pool.clone(), bus.clone(), relayed, signal));
+        // The listener polls once it has subscribed.
+        let first = tokio::time::timeout(Duration::from_secs(5), listening.next())
+            .await
+            .expect("listening in time");
+        assert_eq!(first, Some(Wakeup::Poll));
+
+        let order_id = Uuid::new_v4();
+        let mut conn = pool.get().expect("connection");
+        conn.transaction::<(), OutboxWriteError, _>(|conn| {
+            append_event(
+                conn,
+                ORDER_AGGREGATE,
+                &order_id.to_string(),
+                "OrderCancelled",
+                serde_json::json!({}),
+            )?;
+            Err(DieselError::RollbackTransaction.into())
+        })
+        .expect_err("rolled back");
+        drop(conn);
+        append(&pool, SAGA_AGGREGATE, order_id, "ReserveInventory");
+        let committed = append(&pool, ORDER_AGGREGATE, order_id, "OrderCreated");
+
+        let event = tokio::time::timeout(Duration::from_secs(5), received.recv())
+            .await
+            .expect("event in time")
+            .expect("event");
+        assert_eq!(event.id, committed);
+        assert_eq!(event.order_id, order_id);
+        assert_eq!(event.event_type, "OrderCreated");
+
+        stop.send(true).expect("workers listening");
+        for worker in [listener, worker] {
+            tokio::time::timeout(Duration::from_secs(5), worker)
+                .await
+                .expect("worker stopped")
+                .expect("worker did not panic");
+        }
+        assert!(received.try_recv().is_err(), "only the committed event");
+    }
+
+    #[actix_web::test]
+    async fn stream_replays_events_after_last_event_id_then_goes_live() {
+        let (_container, pool) = setup_db().await;
+        let bus = OrderEventBus::new();
+        let streams = OrderEventStreams::new(pool.clone(), bus.clone(), Readiness::new());
+        let order_id = Uuid::new_v4();
+        let seen = append(&pool, ORDER_AGGREGATE, order_id, "OrderCreated");
+        append(&pool, ORDER_AGGREGATE, Uuid::new_v4(), "OrderCreated");
+        let missed = append(&pool, ORDER_AGGREGATE, order_id, "OrderPaid");
+
+        let filter = OrderEventFilter {
+            order_id: Some(order_id),
+            event_types: vec![],
+        };
+        let mut stream = Box::pin(streams.open(filter, Some(seen)).await.expect("open"));
+        assert_eq!(next_id(&mut stream).await, missed);
+
+        // A replayed event still on the bus is not sent twice.
+        let mut conn = pool.get().expect("connection");
+        let replayed = order_repo::load_events_by_id(&mut conn, &[missed]).expect("load");
+        bus.publish(replayed[0].clone());
+        let live = append(&pool, ORDER_AGGREGATE, order_id, "OrderShipped");
+        let live = order_repo::load_events_by_id(&mut conn, &[live]).expect("load");
+        bus.publish(live[0].clone());
+        assert_eq!(next_id(&mut stream).await, live[0].id);
+    }
+
+    #[actix_web::test]
+    async fn unknown_last_event_id_replays_the_whole_order() {
+        let (_container, pool) = setup_db().await;
+        let streams = OrderEventStreams::new(pool.clone(), OrderEventBus::new(), Readiness::new());
+        let order_id = Uuid::new_v4();
+        let first = append(&pool, ORDER_AGGREGATE, order_id, "OrderCreated");
+        let second = append(&pool, ORDER_AGGREGATE, order_id, "OrderPaid");
+
+        let filter = OrderEventFilter {
+            order_id: Some(order_id),
+            event_types: vec![],
+        };
+        let mut stream = Box::pin(
+            streams
+                .open(filter, Some(Uuid::new_v4()))
+                .await
+                .expect("open"),
+        );
+        assert_eq!(next_id(&mut stream).await, first);
+        assert_eq!(next_id(&mut stream).await, second);
+    }
+
+    #[actix_web::test]
+    async fn stream_ends_when_the_service_stops_being_ready() {
+        let (_container, pool) = setup_db().await;
+        let readiness = Readiness::new();
+        let streams = OrderEventStreams::new(pool, OrderEventBus::new(), readiness.clone());
+        let mut stream = Box::pin(
+            streams
+                .open(OrderEventFilter::default(), None)
+                .await
+                .expect("open"),
+        );
+
+        readiness.set_not_ready();
+        let end = tokio::time::timeout(READINESS_CHECK * 3, stream.next())
+            .await
+            .expect("stream ended in time");
+        assert!(end.is_none());
+    }
+}
@@ -0,0 +1,341 @@
+//! Low-latency wakeups for in-process consumers of the outbox.
+//!
+//! An `AFTER INSERT` trigger sends the id of every outbox row on the
+//! [`CHANNEL`] notification channel when its transaction commits. The
+//! [`listen`] worker keeps a dedicated connection (outside the Diesel pool)
+//! that `LISTEN`s on it and broadcasts a [`Wakeup`] per row to every
+//! [`WakeupReceiver`], so that relays and streams react within milliseconds
+//! instead of polling.
+//!
+//! Notifications are not persisted: while the listener is disconnected they
+//! are lost. It then hands out [`Wakeup::Poll`] at the configured interval
+//! until it has reconnected, and once more right after, so consumers fall
+//! back to reading the outbox itself.
+
+use std::future::poll_fn;
+use std::time::Duration;
+
+use thiserror::Error;
+use tokio::sync::{broadcast, mpsc};
+use tokio_postgres::AsyncMessage;
+use uuid::Uuid;
+
+use crate::pg_tls::{self, MakeTlsConnector, PgTlsError};
+use crate::shutdown::ShutdownSignal;
+
+/// Notification channel the outbox trigger publishes row ids on.
+pub const CHANNEL: &str = "commerce_order_outbox";
+
+/// First reconnection delay; doubled after each failed attempt up to the poll
+/// interval.
+const RECONNECT_DELAY: Duration = Duration::from_millis(500);
+const KEEPALIVE_IDLE: Duration = Duration::from_secs(30);
+/// Wakeups buffered per receiver; a receiver falling further behind gets a
+/// [`Wakeup::Poll`] instead.
+const CAPACITY: usize = 1024;
+
+/// Outbox listener settings (`OUTBOX_*` variables).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutboxListenerConfig {
+    /// How often consumers are told to poll while there is no listening
+    /// connection.
+    pub poll_interval: Duration,
+}
+
+impl Default for OutboxListenerConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(5),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wakeup {
+    /// The outbox row with this id was committed.
+    Inserted(Uuid),
+    /// Rows may have been committed without a notification reaching this
+    /// receiver: look at the outbox itself.
+    Poll,
+}
+
+#[derive(Debug, Error)]
+enum ListenError {
+    #[error("cannot connect: {0}")]
+    Connect(tokio_postgres::Error),
+
+    #[error("{0}")]
+    Tls(PgTlsError),
+
+    #[error("connection lost: {0}")]
+    Lost(String),
+}
+
+/// Hands out [`WakeupReceiver`]s; cheap to clone.
+#[derive(Clone)]
+pub struct OutboxWakeups(broadcast::Sender<Wakeup>);
+
+impl OutboxWakeups {
+    pub fn new() -> Self {
+        Self(broadcast::channel(CAPACITY).0)
+    }
+
+    /// Wakeups from now on.
+    pub fn subscribe(&self) -> WakeupReceiver {
+        WakeupReceiver(self.0.subscribe())
+    }
+
+    fn send(&self, wakeup: Wakeup) {
+        // No receiver just means that nobody is interested yet.
+        let _ = self.0.send(wakeup);
+    }
+}
+
+impl Default for OutboxWakeups {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// One consumer's stream of wakeups.
+pub struct WakeupReceiver(broadcast::Receiver<Wakeup>);
+
+impl WakeupReceiver {
+    /// The next wakeup; `None` once the [`OutboxWakeups`] are gone.
+    pub async fn next(&mut self) -> Option<Wakeup> {
+        match self.0.recv().await {
+            Ok(wakeup) => Some(wakeup),
+            Err(broadcast::error::RecvError::Lagged(_)) => Some(Wakeup::Poll),
+            Err(broadcast::error::RecvError::Closed) => None,
+        }
+    }
+
+    /// The next wakeup if one is already queued.
+    pub fn try_next(&mut self) -> Option<Wakeup> {
+        match self.0.try_recv() {
+            Ok(wakeup) => Some(wakeup),
+            Err(broadcast::error::TryRecvError::Lagged(_)) => Some(Wakeup::Poll),
+            Err(_) => None,
+        }
+    }
+}
+
+/// Broadcast outbox inserts on `wakeups` until shutdown is signalled. Meant to
+/// run as a [`ShutdownCoordinator`](crate::shutdown::ShutdownCoordinator)
+/// worker.
+pub async fn listen(
+    database_url: String,
+    config: OutboxListenerConfig,
+    wakeups: OutboxWakeups,
+    mut shutdown: ShutdownSignal,
+) {
+    let mut delay = RECONNECT_DELAY.min(config.poll_interval);
+    loop {
+        match relay(&database_url, &wakeups, &mut shutdown).await {
+            Ok(()) => return,
+            Err(e) => {
+                if matches!(e, ListenError::Lost(_)) {
+                    delay = RECONNECT_DELAY.min(config.poll_interval);
+                }
+                log::warn!(
+                    "Outbox listener {}; polling until it is back (next attempt in {:?})",
+                    e,
+                    delay
+                );
+            }
+        }
+        wakeups.send(Wakeup::Poll);
+        tokio::select! {
+            _ = shutdown.triggered() => return,
+            _ = tokio::time::sleep(delay) => {}
+        }
+        delay = (delay * 2).min(config.poll_interval);
+    }
+}
+
+/// Relay notifications of one connection until shutdown (`Ok`) or until the
+/// connection fails.
+async fn relay(
+    database_url: &str,
+    wakeups: &OutboxWakeups,
+    shutdown: &mut ShutdownSignal,
+) -> Result<(), ListenError> {
+    let (mut config, tls) = pg_tls::parse(database_url).map_err(ListenError::Tls)?;
+    // Notice a silently dropped connection instead of waiting forever.
+    config.keepalives_idle(KEEPALIVE_IDLE);
+    let tls = MakeTlsConnector::new(&tls).map_err(ListenError::Tls)?;
+    let (client, mut connection) = config.connect(tls).await.map_err(ListenError::Connect)?;
+
+    // The connection performs the actual I/O and has to be polled for the
+    // client's queries to complete as well.
+    let (notifications, mut received) = mpsc::unbounded_channel();
+    let io = tokio::spawn(async move {
+        loop {
+            match poll_fn(|cx| connection.poll_message(cx)).await {
+                Some(Ok(AsyncMessage::Notification(notification))) => {
+                    if notifications.send(notification).is_err() {
+                        return "listener stopped".to_string();
+                    }
+                }
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return e.to_string(),
+                None => return "closed by the server".to_string(),
+            }
+        }
+    });
+
+    let listening = client
+        .batch_execute(&format!("LISTEN {}", CHANNEL))
+        .await
+        .map_err(ListenError::Connect);
+    if let Err(e) = listening {
+        io.abort();
+        return Err(e);
+    }
+    log::info!("Listening for outbox inserts on channel {}", CHANNEL);
+    // Rows committed before LISTEN took effect were not announced.
+    wakeups.send(Wakeup::Poll);
+
+    loop {
+        tokio::select! {
+            _ = shutdown.triggered() => {
+                io.abort();
+                return Ok(());
+            }
+            notification = received.recv() => {
+                let Some(notification) = notification else {
+                    let reason = io.await.unwrap_or_else(|e| e.to_string());
+                    return Err(ListenError::Lost(reason));
+                };
+                match Uuid::parse_str(notification.payload()) {
+                    Ok(id) => wakeups.send(Wakeup::Inserted(id)),
+                    Err(_) => log::warn!(
+                        "Ignoring {} notification {:?}",
+                        notification.channel(),
+                        notification.payload()
+                    ),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use diesel::prelude::*;
+    use diesel::result::Error as DieselError;
+
+    use super::*;
+    use crate::domain::saga::SAGA_AGGREGATE;
+    use crate::infrastructure::outbox::OutboxWriteError;
+    use crate::test_support::{append_event, free_port, setup_db_with_url};
+    use crate::DbPool;
+
+    fn config() -> OutboxListenerConfig {
+        OutboxListenerConfig {
+            poll_interval: Duration::from_millis(50),
+        }
+    }
+
+    fn append(conn: &mut PgConnection) -> Result<Uuid, OutboxWriteError> {
+        append_event(
+            conn,
+            SAGA_AGGREGATE,
+            &Uuid::new_v4().to_string(),
+            "ReserveInventory",
+            serde_json::json!({}),
+        )
+        .map(|row| row.id)
+    }
+
+    async fn next(receiver: &mut WakeupReceiver) -> Wakeup {
+        tokio::time::timeout(Duration::from_secs(5), receiver.next())
+            .await
+            .expect("wakeup in time")
+            .expect("listener running")
+    }
+
+    fn terminate_listener(pool: &DbPool) -> usize {
+        #[derive(QueryableByName)]
+        struct Terminated {
+            #[diesel(sql_type = diesel::sql_types::Bool)]
+            #[allow(dead_code)]
+            terminated: bool,
+        }
+        let mut conn = pool.get().expect("connection");
+        diesel::sql_query(
+            "SELECT pg_terminate_backend(pid) AS terminated FROM pg_stat_activity \
+             WHERE query = 'LISTEN commerce_order_outbox'",
+        )
+        .load::<Terminated>(&mut conn)
+        .expect("terminate")
+        .len()
+    }
+
+    #[actix_web::test]
+    async fn announces_committed_inserts_only() {
+        let (_container, pool, url) = setup_db_with_url().await;
+        let wakeups = OutboxWakeups::new();
+        let mut received = wakeups.subscribe();
+        let (stop, signal) = ShutdownSignal::for_test();
+        let worker = actix_web::rt::spawn(listen(url, config(), wakeups, signal));
+        assert_eq!(next(&mut received).await, Wakeup::Poll);
+
+        let mut conn = pool.get().expect("connection");
+        conn.transaction::<(), OutboxWriteError, _>(|conn| {
+            append(conn)?;
+            Err(DieselError::RollbackTransaction.into())
+        })
+        .expect_err("rolled back");
+        let committed = append(&mut conn).expect("append");
+
+        assert_eq!(next(&mut received).await, Wakeup::Inserted(committed));
+        stop.send(true).expect("worker listening");
+        tokio::time::timeout(Duration::from_secs(5), worker)
+            .await
+            .expect("worker stopped")
+            .expect("worker did not panic");
+        assert_eq!(received.try_next(), None);
+    }
+
+    #[actix_web::test]
+    async fn polls_while_the_database_is_unreachable() {
+        let url = format!("postgres://postgres@127.0.0.1:{}/postgres", free_port());
+        let wakeups = OutboxWakeups::new();
+        let mut received = wakeups.subscribe();
+        let (stop, signal) = ShutdownSignal::for_test();
+        let worker = actix_web::rt::spawn(listen(url, config(), wakeups, signal));
+
+        for _ in 0..3 {
+            assert_eq!(next(&mut received).await, Wakeup::Poll);
+        }
+        stop.send(true).expect("worker listening");
+        tokio::time::timeout(Duration::from_secs(5), worker)
+            .await
+            .expect("worker stopped")
+            .expect("worker did not panic");
+    }
+
+    #[actix_web::test]
+    async fn reconnects_after_losing_the_connection() {
+        let (_container, pool, url) = setup_db_with_url().await;
+        let wakeups = OutboxWakeups::new();
+        let mut received = wakeups.subscribe();
+        let (stop, signal) = ShutdownSignal::for_test();
+        let worker = actix_web::rt::spawn(listen(url, config(), wakeups, signal));
+        assert_eq!(next(&mut received).await, Wakeup::Poll);
+
+        assert_eq!(terminate_listener(&pool), 1);
+        // One poll for the lost connection and one once listening again.
+        assert_eq!(next(&mut received).await, Wakeup::Poll);
+        assert_eq!(next(&mut received).await, Wakeup::Poll);
+        let committed = append(&mut pool.get().expect("connection")).expect("append");
+        assert_eq!(next(&mut received).await, Wakeup::Inserted(committed));
+
+        stop.send(true).expect("worker listening");
+        tokio::time::timeout(Duration::from_secs(5), worker)
+            .await
+            .expect("worker stopped")
+            .expect("worker did not panic");
+    }
+}
@@ -0,0 +1,422 @@
+//! In-process publishing of outbox events, as an alternative to Debezium.
+//!
+//! The [`OutboxRelay`] takes up new `commerce_order_outbox` rows and hands
+//! them to an [`EventPublisher`], one aggregate's events in sequence order.
+//! Failed attempts are retried with exponential backoff; an event that is
+//! rejected outright, or still fails after `max_attempts`, is dead-lettered so
+//! that it neither blocks the relay nor disappears silently. Operators inspect,
+//! requeue or discard dead letters through `/admin/outbox/dead-letters`.
+
+use std::future::Future;
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use diesel::pg::PgConnection;
+use diesel::QueryResult;
+use opentelemetry::metrics::Counter;
+use opentelemetry::{global, KeyValue};
+use thiserror::Error;
+
+use crate::domain::retry::RetryPolicy;
+use crate::infrastructure::models::OutboxEventRow;
+use crate::infrastructure::outbox_publication_repo::{self, DuePublication};
+use crate::outbox_listener::WakeupReceiver;
+use crate::poll_loop::{self, LeasedWork, Outcome, PollSettings, PollSummary};
+use crate::shutdown::ShutdownSignal;
+use crate::telemetry::INSTRUMENTATION_SCOPE;
+use crate::DbPool;
+
+/// Why an event could not be published.
+#[derive(Debug, Error)]
+pub enum PublishError {
+    /// Worth another attempt later, e.g. the broker is unreachable.
+    #[error("{0}")]
+    Unavailable(String),
+
+    /// The event itself is unpublishable (too large, not serializable, …);
+    /// it is dead-lettered without further attempts.
+    #[error("rejected: {0}")]
+    Rejected(String),
+}
+
+/// Destination of outbox events, e.g. a message broker.
+pub trait EventPublisher: Send + Sync + 'static {
+    /// Publish one event. Returns once the destination has accepted it.
+    fn publish(
+        &self,
+        event: &OutboxEventRow,
+    ) -> impl Future<Output = Result<(), PublishError>> + Send;
+}
+
+/// Outbox relay settings (`OUTBOX_RELAY_*` variables).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutboxRelayConfig {
+    pub retry: RetryPolicy,
+    /// Longest pause between polls without an outbox wakeup.
+    pub poll_interval: Duration,
+    /// Bound for a single publish attempt.
+    pub publish_timeout: Duration,
+    /// Events taken up, and publications attempted, per poll.
+    pub batch_size: i64,
+}
+
+impl Default for OutboxRelayConfig {
+    fn default() -> Self {
+        Self {
+            retry: RetryPolicy::default(),
+            poll_interval: Duration::from_secs(1),
+            publish_timeout: Duration::from_secs(10),
+            batch_size: 100,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum RelayError {
+    #[error("Database error: {0}")]
+    Database(#[from] diesel::result::Error),
+
+    #[error("Connection pool error: {0}")]
+    Pool(#[from] r2d2::Error),
+
+    #[error("Worker error: {0}")]
+    Worker(#[from] tokio::task::JoinError),
+}
+
+pub struct OutboxRelay<P> {
+    pool: DbPool,
+    publisher: P,
+    config: OutboxRelayConfig,
+}
+
+impl<P: EventPublisher> OutboxRelay<P> {
+    pub fn new(pool: DbPool, publisher: P, config: OutboxRelayConfig) -> Self {
+        Self {
+            pool,
+            publisher,
+            config,
+        }
+    }
+
+    /// Take up new outbox rows, then attempt the publications that are due.
+    pub async fn run_once(self: &Arc<Self>) -> Result<PollSummary, RelayError> {
+        poll_loop::run_once(self).await
+    }
+}
+
+impl<P: EventPublisher> LeasedWork for OutboxRelay<P> {
+    const NAME: &'static str = "Outbox relay";
+
+    type Item = DuePublication;
+    type Failure = PublishError;
+    type Error = RelayError;
+
+    fn pool(&self) -> &DbPool {
+        &self.pool
+    }
+
+    fn settings(&self) -> PollSettings {
+        PollSettings {
+            retry: self.config.retry,
+            poll_interval: self.config.poll_interval,
+            attempt_timeout: self.config.publish_timeout,
+            batch_size: self.config.batch_size,
+        }
+    }
+
+    fn take_up(&self, conn: &mut PgConnection, limit: i64) -> QueryResult<usize> {
+        outbox_publication_repo::track_new(conn, limit)
+    }
+
+    fn claim_due(
+        &self,
+        conn: &mut PgConnection,
+        now: DateTime<Utc>,
+        lease_until: DateTime<Utc>,
+        limit: i64,
+    ) -> QueryResult<Vec<DuePublication>> {
+        outbox_publication_repo::claim_due(conn, now, lease_until, limit)
+    }
+
+    async fn attempt(&self, publication: &DuePublication) -> Result<(), PublishError> {
+        let timeout = self.config.publish_timeout;
+        match tokio::time::timeout(timeout, self.publisher.publish(&publication.event)).await {
+            Ok(result) => result,
+            Err(_) => Err(PublishError::Unavailable(format!(
+                "no answer within {:?}",
+                timeout
+            ))),
+        }
+    }
+
+    fn attempts(publication: &DuePublication) -> i32 {
+        publication.attempts
+    }
+
+    fn is_permanent(error: &PublishError) -> bool {
+        matches!(error, PublishError::Rejected(_))
+    }
+
+    fn mark_succeeded(
+        &self,
+        conn: &mut PgConnection,
+        publication: &DuePublication,
+    ) -> QueryResult<()> {
+        outbox_publication_repo::mark_published(conn, publication.event.id)
+    }
+
+    fn mark_failed(
+        &self,
+        conn: &mut PgConnection,
+        publication: &DuePublication,
+        error: &str,
+        retry_at: Option<DateTime<Utc>>,
+    ) -> QueryResult<()> {
+        outbox_publication_repo::mark_failed(conn, publication.event.id, error, retry_at)
+    }
+
+    fn describe(publication: &DuePublication) -> String {
+        let event = &publication.event;
+        format!(
+            "Outbox event {} ({} {})",
+            event.id, event.aggregate_type, event.event_type
+        )
+    }
+
+    fn record_outcome(outcome: Outcome) {
+        record_outcome(outcome);
+    }
+}
+
+static PUBLISH_ATTEMPTS: LazyLock<Counter<u64>> = LazyLock::new(|| {
+    global::meter(INSTRUMENTATION_SCOPE)
+        .u64_counter("outbox.publish.attempts")
+        .with_description("Outbox event publish attempts")
+        .build()
+});
+
+/// Count a publish attempt, by the relay or the
+/// [`ReplicationRelay`](crate::replication_relay::ReplicationRelay).
+pub(crate) fn record_outcome(outcome: Outcome) {
+    let outcome = match outcome {
+        Outcome::Succeeded => "published",
+        Outcome::Retrying => "retrying",
+        Outcome::DeadLettered => "dead_lettered",
+    };
+    PUBLISH_ATTEMPTS.add(1, &[KeyValue::new("outbox.outcome", outcome)]);
+}
+
+/// Relay outbox events until shutdown is signalled; see [`poll_loop::run`].
+pub async fn run<P: EventPublisher>(
+    relay: Arc<OutboxRelay<P>>,
+    wakeups: WakeupReceiver,
+    shutdown: ShutdownSignal,
+) {
+    poll_loop::run(relay, wakeups, shutdown).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    use serde_json::json;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::domain::ports::OutboxDeadLetterRepository;
+    use crate::infrastructure::outbox_publication_repo::DieselOutboxDeadLetterRepository;
+    use crate::outbox_listener::OutboxWakeups;
+    use crate::test_support::{append_event, setup_db};
+
+    /// How to fail an event, e.g. `PublishError::Rejected`.
+    type Failure = fn(String) -> PublishError;
+
+    /// Records what it publishes; fails the events it was told to.
+    #[derive(Default)]
+    struct StubPublisher {
+        published: Mutex<Vec<Uuid>>,
+        failures: Mutex<HashMap<Uuid, Failure>>,
+    }
+
+    impl StubPublisher {
+        fn fail(&self, event_id: Uuid, error: Failure) {
+            self.failures.lock().expect("lock").insert(event_id, error);
+        }
+
+        fn heal(&self, event_id: Uuid) {
+            self.failures.lock().expect("lock").remove(&event_id);
+        }
+
+        fn published(&self) -> Vec<Uuid> {
+            self.published.lock().expect("lock").clone()
+        }
+    }
+
+    impl EventPublisher for StubPublisher {
+        async fn publish(&self, event: &OutboxEventRow) -> Result<(), PublishError> {
+            if let Some(error) = self.failures.lock().expect("lock").get(&event.id) {
+                return Err(error(format!("cannot publish {}", event.event_type)));
+            }
+            self.published.lock().expect("lock").push(event.id);
+            Ok(())
+        }
+    }
+
+    fn config(max_attempts: u32) -> OutboxRelayConfig {
+        OutboxRelayConfig {
+            retry: RetryPolicy {
+                max_attempts,
+                // Due again right away.
+                initial_backoff: Duration::ZERO,
+                max_backoff: Duration::ZERO,
+            },
+            poll_interval: Duration::from_millis(10),
+            ..OutboxRelayConfig::default()
+        }
+    }
+
+    /// A relay past its first poll, which skips what was already in the outbox.
+    async fn started_relay(
+        pool: &DbPool,
+        config: OutboxRelayConfig,
+    ) -> Arc<OutboxRelay<StubPublisher>> {
+        let relay = Arc::new(OutboxRelay::new(
+            pool.clone(),
+            StubPublisher::default(),
+            config,
+        ));
+        assert!(relay.run_once().await.expect("first poll").is_idle());
+        relay
+    }
+
+    fn append(pool: &DbPool, aggregate_id: &str, event_type: &str) -> Uuid {
+        let mut conn = pool.get().expect("connection");
+        append_event(&mut conn, "Order", aggregate_id, event_type, json!({}))
+            .expect("append")
+            .id
+    }
+
+    #[actix_web::test]
+    async fn publishes_each_event_once() {
+        let (_container, pool) = setup_db().await;
+        let relay = started_relay(&pool, config(3)).await;
+        let created = append(&pool, "a", "OrderCreated");
+        let other = append(&pool, "b", "OrderCreated");
+
+        let summary = relay.run_once().await.expect("poll");
+        assert_eq!((summary.taken_up, summary.succeeded), (2, 2));
+        let mut published = relay.publisher.published();
+        published.sort();
+        let mut expected = vec![created, other];
+        expected.sort();
+        assert_eq!(published, expected);
+
+        assert!(relay.run_once().await.expect("poll").is_idle());
+    }
+
+    #[actix_web::test]
+    async fn retried_event_holds_back_its_aggregate_only() {
+        let (_container, pool) = setup_db().await;
+        let relay = started_relay(&pool, config(5)).await;
+        let created = append(&pool, "a", "OrderCreated");
+        let paid = append(&pool, "a", "OrderPaid");
+        let other = append(&pool, "b", "OrderCreated");
+        relay.publisher.fail(created, PublishError::Unavailable);
+
+        let summary = relay.run_once().await.expect("poll");
+        assert_eq!((summary.succeeded, summary.retrying), (1, 1));
+        assert_eq!(relay.publisher.published(), [other]);
+        let summary = relay.run_once().await.expect("poll");
+        assert_eq!((summary.succeeded, summary.retrying), (0, 1));
+
+        relay.publisher.heal(created);
+        relay.run_once().await.expect("poll");
+        relay.run_once().await.expect("poll");
+        assert_eq!(relay.publisher.published(), [other, created, paid]);
+    }
+
+    #[actix_web::test]
+    async fn rejected_event_is_dead_lettered_and_skipped() {
+        let (_container, pool) = setup_db().await;
+        let relay = started_relay(&pool, config(5)).await;
+        let created = append(&pool, "a", "OrderCreated");
+        let paid = append(&pool, "a", "OrderPaid");
+        relay.publisher.fail(created, PublishError::Rejected);
+
+        let summary = relay.run_once().await.expect("poll");
+        assert_eq!(summary.dead_lettered, 1);
+        relay.run_once().await.expect("poll");
+        assert_eq!(relay.publisher.published(), [paid]);
+
+        let dead_letters = DieselOutboxDeadLetterRepository::new(pool.clone());
+        let dead = dead_letters
+            .find(created)
+            .expect("find")
+            .expect("dead letter");
+        assert_eq!(dead.attempts, 1);
+        assert_eq!(
+            dead.last_error.as_deref(),
+            Some("rejected: cannot publish OrderCreated")
+        );
+    }
+
+    #[actix_web::test]
+    async fn exhausted_retries_dead_letter_until_requeued() {
+        let (_container, pool) = setup_db().await;
+        let relay = started_relay(&pool, config(2)).await;
+        let created = append(&pool, "a", "OrderCreated");
+        relay.publisher.fail(created, PublishError::Unavailable);
+
+        assert_eq!(relay.run_once().await.expect("poll").retrying, 1);
+        assert_eq!(relay.run_once().await.expect("poll").dead_lettered, 1);
+        assert!(relay.run_once().await.expect("poll").is_idle());
+
+        let dead_letters = DieselOutboxDeadLetterRepository::new(pool.clone());
+        assert_eq!(dead_letters.list(10).expect("list").len(), 1);
+        relay.publisher.heal(created);
+        assert!(dead_letters.requeue(created).expect("requeue"));
+        assert_eq!(relay.run_once().await.expect("poll").succeeded, 1);
+        assert_eq!(relay.publisher.published(), [created]);
+        assert!(dead_letters.list(10).expect("list").is_empty());
+    }
+
+    #[actix_web::test]
+    async fn events_written_before_the_first_poll_are_skipped() {
+        let (_container, pool) = setup_db().await;
+        let history = append(&pool, "a", "OrderCreated");
+        let relay = started_relay(&pool, config(3)).await;
+        let paid = append(&pool, "a", "OrderPaid");
+
+        let summary = relay.run_once().await.expect("poll");
+        assert_eq!((summary.taken_up, summary.succeeded), (1, 1));
+        assert_eq!(relay.publisher.published(), [paid]);
+        assert!(!relay.publisher.published().contains(&history));
+    }
+
+    #[actix_web::test]
+    async fn worker_publishes_until_shutdown() {
+        let (_container, pool) = setup_db().await;
+        let relay = started_relay(&pool, config(3)).await;
+        let wakeups = OutboxWakeups::new();
+        let (stop, signal) = ShutdownSignal::for_test();
+
+        let worker = actix_web::rt::spawn(run(Arc::clone(&relay), wakeups.subscribe(), signal));
+        let created = append(&pool, "a", "OrderCreated");
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while relay.publisher.published().is_empty() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("published in time");
+        assert_eq!(relay.publisher.published(), [created]);
+
+        stop.send(true).expect("worker listening");
+        tokio::time::timeout(Duration::from_secs(5), worker)
+            .await
+            .expect("worker stopped")
+            .expect("worker did not panic");
+    }
+}
@@ -0,0 +1,402 @@
+//! Order status driven by the payments service.
+//!
+//! [`PaymentHandler`] applies `PaymentCaptured` / `PaymentFailed` messages
+//! through [`OrderService::apply_payment`], which records the message with the
+//! status change. Driven by [`inbox::consume`](crate::inbox::consume), a
+//! message is acknowledged (its offset committed, for Kafka) only after that
+//! change has been committed to the database; transient failures are retried
+//! in place until they succeed or the service shuts down.
+
+use std::sync::Arc;
+
+use serde::Deserialize;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::application::order_service::{OrderService, PaymentApplication};
+use crate::domain::errors::DomainError;
+use crate::domain::order::{PaymentOutcome, SourceMessage};
+use crate::domain::ports::{OrderRepository, UnitOfWork};
+use crate::inbox::{InboundMessage, InboxError, InboxOutcome, MessageHandler};
+
+pub const PAYMENT_CAPTURED: &str = "PaymentCaptured";
+pub const PAYMENT_FAILED: &str = "PaymentFailed";
+
+/// Where to read payment events from (`PAYMENTS_*` variables).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaymentsConsumerConfig {
+    pub bootstrap_servers: String,
+    pub topic: String,
+    pub group_id: String,
+}
+
+impl PaymentsConsumerConfig {
+    pub fn new(bootstrap_servers: impl Into<String>) -> Self {
+        Self {
+            bootstrap_servers: bootstrap_servers.into(),
+            topic: "public.commerce.payment.c2.v1".to_string(),
+            group_id: "order-service".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum PaymentEventError {
+    #[error("unsupported message type {0}")]
+    UnsupportedType(String),
+
+    #[error("invalid payload: {0}")]
+    InvalidPayload(String),
+}
+
+/// A payment event as published by the payments service.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaymentEvent {
+    pub order_id: Uuid,
+    pub outcome: PaymentOutcome,
+}
+
+#[derive(Deserialize)]
+struct PaymentPayload {
+    order_id: Uuid,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+impl PaymentEvent {
+    pub fn from_message(message: &InboundMessage) -> Result<Self, PaymentEventError> {
+        let payload: PaymentPayload = serde_json::from_value(message.payload.clone())
+            .map_err(|e| PaymentEventError::InvalidPayload(e.to_string()))?;
+        let outcome = match message.message_type.as_str() {
+            PAYMENT_CAPTURED => PaymentOutcome::Captured,
+            PAYMENT_FAILED => PaymentOutcome::Failed {
+                reason: payload.reason,
+            },
+            other => return Err(PaymentEventError::UnsupportedType(other.to_string())),
+        };
+        Ok(Self {
+            order_id: payload.order_id,
+            outcome,
+        })
+    }
+}
+
+/// Applies payment events to their orders.
+///
+/// Messages that can never be applied (undecodable, unknown order, status
+/// conflict) are logged and reported unhandled, so they are acknowledged and
+/// do not block the partition.
+pub struct PaymentHandler<R, U> {
+    service: Arc<OrderService<R, U>>,
+}
+
+impl<R, U> PaymentHandler<R, U> {
+    pub fn new(service: Arc<OrderService<R, U>>) -> Self {
+        Self { service }
+    }
+}
+
+impl<R: OrderRepository, U: UnitOfWork> MessageHandler for PaymentHandler<R, U> {
+    fn message_types(&self) -> &[&'static str] {
+        &[PAYMENT_CAPTURED, PAYMENT_FAILED]
+    }
+
+    fn handle(&self, message: &InboundMessage) -> Result<InboxOutcome, InboxError> {
+        let event = match PaymentEvent::from_message(message) {
+            Ok(event) => event,
+            Err(e) => {
+                log::warn!("Skipping payment message {}: {}", message.id, e);
+                return Ok(InboxOutcome::Unhandled);
+            }
+        };
+        let source = SourceMessage {
+            id: message.id.clone(),
+            message_type: message.message_type.clone(),
+        };
+        match self
+            .service
+            .apply_payment(event.order_id, &event.outcome, &source)
+        {
+            Ok(applied) => {
+                log_application(message, &event, &applied);
+                Ok(match applied {
+                    PaymentApplication::Applied(_) => InboxOutcome::Processed,
+                    PaymentApplication::AlreadyApplied => InboxOutcome::Duplicate,
+                    PaymentApplication::Rejected { .. } | PaymentApplication::InSaga { .. } => {
+                        InboxOutcome::Unhandled
+                    }
+                })
+            }
+            Err(DomainError::Internal(e)) => Err(InboxError::Handler(e)),
+            Err(e) => {
+                log::warn!("Skipping payment message {}: {}", message.id, e);
+                Ok(InboxOutcome::Unhandled)
+            }
+        }
+    }
+}
+
+fn log_application(message: &InboundMessage, event: &PaymentEvent, applied: &PaymentApplication) {
+    match applied {
+        PaymentApplication::Applied(status) => log::info!(
+            "Order {} is now {} after {} {}",
+            event.order_id,
+            status.as_str(),
+            message.message_type,
+            message.id
+        ),
+        PaymentApplication::AlreadyApplied => log::debug!(
+            "{} {} was already applied to order {}",
+            message.message_type,
+            message.id,
+            event.order_id
+        ),
+        PaymentApplication::Rejected { status } => log::warn!(
+            "Ignoring {} {}: order {} is {}",
+            message.message_type,
+            message.id,
+            event.order_id,
+            status
+        ),
+        PaymentApplication::InSaga { state } => log::warn!(
+            "Ignoring {} {}: order {} is settled by its saga, which is {}",
+            message.message_type,
+            message.id,
+            event.order_id,
+            state.as_str()
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use bigdecimal::BigDecimal;
+    use serde_json::json;
+
+    use super::*;
+    use crate::domain::caller::Caller;
+    use crate::domain::order::{OrderLineInput, ORDER_PAID};
+    use crate::domain::ports::Transaction;
+    use crate::inbox::{self, InMemoryTransport, Inbox};
+    use crate::infrastructure::order_repo::DieselOrderRepository;
+    use crate::infrastructure::unit_of_work::DieselUnitOfWork;
+    use crate::shutdown::ShutdownSignal;
+    use crate::test_support::setup_db;
+
+    /// Fails the first `failures` units of work, like a database outage.
+    struct FlakyUnitOfWork {
+        inner: DieselUnitOfWork,
+        failures: Arc<AtomicUsize>,
+    }
+
+    impl UnitOfWork for FlakyUnitOfWork {
+        fn run<T, E>(&self, work: impl FnOnce(&mut dyn Transaction) -> Result<T, E>) -> Result<T, E>
+        where
+            E: From<DomainError>,
+        {
+            let remaining = self.failures.load(Ordering::SeqCst);
+            if remaining > 0 {
+                self.failures.store(remaining - 1, Ordering::SeqCst);
+                return Err(DomainError::Internal("connection refused".to_string()).into());
+            }
+            self.inner.run(work)
+        }
+    }
+
+    type FlakyService = OrderService<DieselOrderRepository, FlakyUnitOfWork>;
+
+    fn flaky_service(
+        pool: &crate::DbPool,
+        failures: Arc<AtomicUsize>,
+    ) -> (Arc<FlakyService>, Uuid) {
+        let order_id = OrderService::new(
+            DieselOrderRepository::new(pool.clone()),
+            DieselUnitOfWork::new(pool.clone()),
+        )
+        .create_order(
+            &Caller::Staff,
+            Uuid::new_v4(),
+            vec![OrderLineInput {
+                product_id: Uuid::new_v4(),
+                quantity: 1,
+                unit_price: BigDecimal::from_str("9.99").expect("decimal"),
+            }],
+        )
+        .expect("create order");
+        let uow = FlakyUnitOfWork {
+            inner: DieselUnitOfWork::new(pool.clone()),
+            failures,
+        };
+        let service = OrderService::new(DieselOrderRepository::new(pool.clone()), uow);
+        (Arc::new(service), order_id)
+    }
+
+    fn service(pool: &crate::DbPool, failures: usize) -> (Arc<FlakyService>, Uuid) {
+        flaky_service(pool, Arc::new(AtomicUsize::new(failures)))
+    }
+
+    /// Feed `transport` through a payments inbox until it closes or `signal`
+    /// fires.
+    async fn consume(
+        service: Arc<FlakyService>,
+        transport: InMemoryTransport,
+        signal: ShutdownSignal,
+    ) {
+        let inbox = Inbox::new().with_handler(PaymentHandler::new(service));
+        inbox::consume(Arc::new(inbox), transport, signal).await;
+    }
+
+    fn payment(id: &str, message_type: &str, order_id: Uuid) -> InboundMessage {
+        InboundMessage {
+            id: id.to_string(),
+            message_type: message_type.to_string(),
+            payload: json!({ "order_id": order_id, "reason": "card declined" }),
+        }
+    }
+
+    fn status_and_event_types(service: &FlakyService, order_id: Uuid) -> (String, Vec<String>) {
+        let caller = Caller::Staff;
+        let order = service
+            .get_order(&caller, order_id)
+            .expect("get")
+            .expect("order");
+        let history = service
+            .order_history(&caller, order_id)
+            .expect("history")
+            .expect("order");
+        (
+            order.status,
+            history.events.into_iter().map(|e| e.event_type).collect(),
+        )
+    }
+
+    #[test]
+    fn payment_events_are_decoded_from_messages() {
+        let order_id = Uuid::new_v4();
+        assert_eq!(
+            PaymentEvent::from_message(&payment("m", PAYMENT_CAPTURED, order_id)),
+            Ok(PaymentEvent {
+                order_id,
+                outcome: PaymentOutcome::Captured
+            })
+        );
+        assert_eq!(
+            PaymentEvent::from_message(&payment("m", PAYMENT_FAILED, order_id)),
+            Ok(PaymentEvent {
+                order_id,
+                outcome: PaymentOutcome::Failed {
+                    reason: Some("card declined".to_string())
+                }
+            })
+        );
+        assert!(matches!(
+            PaymentEvent::from_message(&payment("m", "PaymentRefunded", order_id)),
+            Err(PaymentEventError::UnsupportedType(_))
+        ));
+        let mut invalid = payment("m", PAYMENT_CAPTURED, order_id);
+        invalid.payload = json!({ "order_id": "not-a-uuid" });
+        assert!(matches!(
+            PaymentEvent::from_message(&invalid),
+            Err(PaymentEventError::InvalidPayload(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn captured_payment_marks_the_order_paid_once() {
+        let (_container, pool) = setup_db().await;
+        let (service, order_id) = service(&pool, 0);
+        let (sender, transport) = InMemoryTransport::new();
+        let acknowledged = transport.acknowledgements();
+        let (_stop, signal) = ShutdownSignal::for_test();
+
+        sender.send(payment("p-1", PAYMENT_CAPTURED, order_id));
+        sender.send(payment("p-1", PAYMENT_CAPTURED, order_id));
+        drop(sender);
+        consume(Arc::clone(&service), transport, signal).await;
+
+        assert_eq!(acknowledged.ids(), ["p-1", "p-1"]);
+        let (status, events) = status_and_event_types(&service, order_id);
+        assert_eq!(status, "PAID");
+        assert_eq!(events, ["OrderCreated", ORDER_PAID]);
+    }
+
+    #[tokio::test]
+    async fn failed_then_captured_payment_ends_paid() {
+        let (_container, pool) = setup_db().await;
+        let (service, order_id) = service(&pool, 0);
+        let (sender, transport) = InMemoryTransport::new();
+        let (_stop, signal) = ShutdownSignal::for_test();
+
+        sender.send(payment("p-1", PAYMENT_FAILED, order_id));
+        sender.send(payment("p-2", PAYMENT_CAPTURED, order_id));
+        // A late failure no longer affects the paid order.
+        sender.send(payment("p-3", PAYMENT_FAILED, order_id));
+        drop(sender);
+        consume(Arc::clone(&service), transport, signal).await;
+
+        let (status, events) = status_and_event_types(&service, order_id);
+        assert_eq!(status, "PAID");
+        assert_eq!(events, ["OrderCreated", "OrderPaymentFailed", ORDER_PAID]);
+    }
+
+    #[tokio::test]
+    async fn unusable_messages_are_acknowledged_and_skipped() {
+        let (_container, pool) = setup_db().await;
+        let (service, order_id) = service(&pool, 0);
+        let (sender, transport) = InMemoryTransport::new();
+        let acknowledged = transport.acknowledgements();
+        let (_stop, signal) = ShutdownSignal::for_test();
+
+        sender.send(payment("unknown-order", PAYMENT_CAPTURED, Uuid::new_v4()));
+        sender.send(payment("refund", "PaymentRefunded", order_id));
+        drop(sender);
+        consume(Arc::clone(&service), transport, signal).await;
+
+        assert_eq!(acknowledged.ids(), ["unknown-order", "refund"]);
+        assert_eq!(status_and_event_types(&service, order_id).0, "PENDING");
+    }
+
+    #[tokio::test]
+    async fn transient_failures_are_retried_before_acknowledging() {
+        let (_container, pool) = setup_db().await;
+        let (service, order_id) = service(&pool, 2);
+        let (sender, transport) = InMemoryTransport::new();
+        let acknowledged = transport.acknowledgements();
+        let (_stop, signal) = ShutdownSignal::for_test();
+
+        sender.send(payment("p-1", PAYMENT_CAPTURED, order_id));
+        drop(sender);
+        consume(Arc::clone(&service), transport, signal).await;
+
+        assert_eq!(acknowledged.ids(), ["p-1"]);
+        assert_eq!(status_and_event_types(&service, order_id).0, "PAID");
+    }
+
+    #[tokio::test]
+    async fn shutdown_during_retries_leaves_the_message_unacknowledged() {
+        let (_container, pool) = setup_db().await;
+        let failures = Arc::new(AtomicUsize::new(usize::MAX));
+        let (service, order_id) = flaky_service(&pool, Arc::clone(&failures));
+        let (sender, transport) = InMemoryTransport::new();
+        let acknowledged = transport.acknowledgements();
+        let (stop, signal) = ShutdownSignal::for_test();
+
+        let consumer = tokio::spawn(consume(Arc::clone(&service), transport, signal));
+        sender.send(payment("p-1", PAYMENT_CAPTURED, order_id));
+        while failures.load(Ordering::SeqCst) == usize::MAX {
+            tokio::task::yield_now().await;
+        }
+        stop.send(true).expect("consumer listening");
+        tokio::time::timeout(Duration::from_secs(5), consumer)
+            .await
+            .expect("consumer stopped")
+            .expect("consumer did not panic");
+
+        assert!(acknowledged.ids().is_empty());
+        assert_eq!(status_and_event_types(&service, order_id).0, "PENDING");
+    }
+}
@@ -0,0 +1,278 @@
+//! TLS for the Postgres connections made outside the Diesel pool (the
+//! [`outbox_listener`](crate::outbox_listener) and the replication stream of
+//! the [`replication_relay`](crate::replication_relay)).
+//!
+//! `sslmode` is honoured like libpq, which the pool connects through, does
+//! it: `prefer` (the default) and `require` encrypt without verifying the
+//! server certificate, `verify-ca` also verifies that a trusted authority
+//! signed it and `verify-full` that it names the host as well, `disable` does
+//! not encrypt. The certificate is checked against `sslrootcert` if the URL
+//! sets it, otherwise against the system's trusted roots.
+//!
+//! `tokio-postgres` knows neither the `verify-*` modes nor `sslrootcert`, so
+//! [`parse`] takes them out of the URL before handing it over.
+
+use std::future::Future;
+use std::io;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio_postgres::config::SslMode;
+use tokio_postgres::tls::{self, ChannelBinding, MakeTlsConnect, TlsConnect};
+use url::Url;
+
+#[derive(Debug, Error)]
+pub enum PgTlsError {
+    #[error("invalid database URL: {0}")]
+    Url(String),
+
+    #[error("cannot read sslrootcert {path}: {reason}")]
+    RootCert { path: PathBuf, reason: String },
+
+    #[error("cannot set up TLS: {0}")]
+    Tls(#[from] native_tls::Error),
+}
+
+/// How much of the server's certificate is verified; see the module
+/// documentation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verification {
+    None,
+    Ca,
+    Full,
+}
+
+/// The TLS settings of a database URL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PgTls {
+    pub verification: Verification,
+    /// PEM file of the authorities to trust instead of the system's.
+    pub root_cert: Option<PathBuf>,
+}
+
+/// Split `database_url` into the `tokio-postgres` configuration and the TLS
+/// settings. With `verify-ca` or `verify-full` the configuration requires
+/// TLS.
+///
+/// Only URLs (`postgres://…?sslmode=verify-full`) are looked into; key/value
+/// connection strings go to `tokio-postgres` as they are.
+pub fn parse(database_url: &str) -> Result<(tokio_postgres::Config, PgTls), PgTlsError> {
+    let mut tls = PgTls {
+        verification: Verification::None,
+        root_cert: None,
+    };
+    let mut url = match Url::parse(database_url) {
+        Ok(url) if matches!(url.scheme(), "postgres" | "postgresql") => url,
+        _ => {
+            let config = database_url
+                .parse()
+                .map_err(|e: tokio_postgres::Error| PgTlsError::Url(e.to_string()))?;
+            return Ok((config, tls));
+        }
+    };
+
+    let mut ssl_mode = None;
+    let mut rest = Vec::new();
+    for (key, value) in url.query_pairs() {
+        match &*key {
+            "sslmode" => ssl_mode = Some(value.into_owned()),
+            "sslrootcert" => tls.root_cert = Some(PathBuf::from(&*value)),
+            _ => rest.push((key.into_owned(), value.into_owned())),
+        }
+    }
+    if rest.is_empty() {
+        url.set_query(None);
+    } else {
+        url.query_pairs_mut().clear().extend_pairs(rest);
+    }
+
+    let mut config: tokio_postgres::Config = url
+        .as_str()
+        .parse()
+        .map_err(|e: tokio_postgres::Error| PgTlsError::Url(e.to_string()))?;
+    let (mode, verification) = match ssl_mode.as_deref() {
+        None | Some("prefer") => (SslMode::Prefer, Verification::None),
+        Some("disable") => (SslMode::Disable, Verification::None),
+        Some("require") => (SslMode::Require, Verification::None),
+        Some("verify-ca") => (SslMode::Require, Verification::Ca),
+        Some("verify-full") => (SslMode::Require, Verification::Full),
+        Some(other) => return Err(PgTlsError::Url(format!("unsupported sslmode {}", other))),
+    };
+    config.ssl_mode(mode);
+    tls.verification = verification;
+    Ok((config, tls))
+}
+
+/// A `tokio-postgres` TLS connector; cheap to clone.
+#[derive(Clone)]
+pub struct MakeTlsConnector(tokio_native_tls::TlsConnector);
+
+impl MakeTlsConnector {
+    /// A connector verifying server certificates as `tls` asks.
+    pub fn new(tls: &PgTls) -> Result<Self, PgTlsError> {
+        let mut builder = native_tls::TlsConnector::builder();
+        builder
+            .danger_accept_invalid_certs(tls.verification == Verification::None)
+            .danger_accept_invalid_hostnames(tls.verification != Verification::Full);
+        if let Some(path) = &tls.root_cert {
+            let root_cert_error = |reason: String| PgTlsError::RootCert {
+                path: path.clone(),
+                reason,
+            };
+            let pem = std::fs::read(path).map_err(|e| root_cert_error(e.to_string()))?;
+            let certs = native_tls::Certificate::stack_from_pem(&pem)
+                .map_err(|e| root_cert_error(e.to_string()))?;
+            if certs.is_empty() {
+                return Err(root_cert_error("no certificate in the file".to_string()));
+            }
+            builder.disable_built_in_roots(true);
+            for cert in certs {
+                builder.add_root_certificate(cert);
+            }
+        }
+        Ok(Self(builder.build()?.into()))
+    }
+
+    /// Wrap `stream` in a TLS session with the server at `domain`.
+    pub async fn connect<S>(
+        &self,
+        domain: &str,
+        stream: S,
+    ) -> Result<TlsStream<S>, native_tls::Error>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        Ok(TlsStream(self.0.connect(domain, stream).await?))
+    }
+}
+
+impl<S> MakeTlsConnect<S> for MakeTlsConnector
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    type Stream = TlsStream<S>;
+    type TlsConnect = TlsConnector;
+    type Error = native_tls::Error;
+
+    fn make_tls_connect(&mut self, domain: &str) -> Result<TlsConnector, native_tls::Error> {
+        Ok(TlsConnector {
+            connector: self.clone(),
+            domain: domain.to_string(),
+        })
+    }
+}
+
+pub struct TlsConnector {
+    connector: MakeTlsConnector,
+    domain: String,
+}
+
+impl<S> TlsConnect<S> for TlsConnector
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    type Stream = TlsStream<S>;
+    type Error = native_tls::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<TlsStream<S>, native_tls::Error>> + Send>>;
+
+    fn connect(self, stream: S) -> Self::Future {
+        Box::pin(async move { self.connector.connect(&self.domain, stream).await })
+    }
+}
+
+pub struct TlsStream<S>(tokio_native_tls::TlsStream<S>);
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncRead for TlsStream<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_read(cx, buf)
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncWrite for TlsStream<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_shutdown(cx)
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> tls::TlsStream for TlsStream<S> {
+    fn channel_binding(&self) -> ChannelBinding {
+        // Not offered: unless sslmode verifies the server certificate,
+        // binding to it proves nothing.
+        ChannelBinding::none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_modes_require_tls_and_leave_the_url_to_tokio_postgres() {
+        let (config, tls) = parse(
+            "postgres://app@db:5433/orders?sslmode=verify-full&sslrootcert=/etc/ca.pem&application_name=relay",
+        )
+        .expect("valid URL");
+        assert_eq!(config.get_ssl_mode(), SslMode::Require);
+        assert_eq!(config.get_dbname(), Some("orders"));
+        assert_eq!(config.get_application_name(), Some("relay"));
+        assert_eq!(
+            tls,
+            PgTls {
+                verification: Verification::Full,
+                root_cert: Some(PathBuf::from("/etc/ca.pem")),
+            }
+        );
+
+        let (config, tls) = parse("postgres://app@db/orders?sslmode=verify-ca").expect("valid");
+        assert_eq!(config.get_ssl_mode(), SslMode::Require);
+        assert_eq!(tls.verification, Verification::Ca);
+    }
+
+    #[test]
+    fn other_modes_verify_nothing() {
+        for (url, mode) in [
+            ("postgres://app@db/orders", SslMode::Prefer),
+            ("postgres://app@db/orders?sslmode=disable", SslMode::Disable),
+            ("postgres://app@db/orders?sslmode=require", SslMode::Require),
+            ("host=db user=app sslmode=require", SslMode::Require),
+        ] {
+            let (config, tls) = parse(url).expect("valid URL");
+            assert_eq!(config.get_ssl_mode(), mode, "{}", url);
+            assert_eq!(tls.verification, Verification::None, "{}", url);
+        }
+    }
+
+    #[test]
+    fn unknown_modes_are_rejected() {
+        let err = parse("postgres://app@db/orders?sslmode=allow").expect_err("unsupported");
+        assert!(matches!(err, PgTlsError::Url(_)));
+    }
+
+    #[test]
+    fn unreadable_root_certificates_are_reported() {
+        let (_, tls) =
+            parse("postgres://app@db/orders?sslmode=verify-full&sslrootcert=/nonexistent/ca.pem")
+                .expect("valid URL");
+        let err = MakeTlsConnector::new(&tls).err().expect("no such file");
+        assert!(matches!(err, PgTlsError::RootCert { .. }));
+    }
+}
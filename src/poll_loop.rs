@@ -0,0 +1,239 @@
+//! The poll loop behind the [`OutboxRelay`](crate::outbox_relay::OutboxRelay)
+//! and the [`WebhookDispatcher`](crate::webhooks::WebhookDispatcher).
+//!
+//! Each poll takes up new outbox rows as work items, claims the items that
+//! are due under a lease, attempts them concurrently and records the results.
+//! Failed attempts are retried with exponential backoff; a permanent failure,
+//! or one still failing after `max_attempts`, is dead-lettered.
+
+use std::fmt::Display;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use diesel::pg::PgConnection;
+use diesel::QueryResult;
+use tokio::task::{JoinError, JoinSet};
+
+use crate::domain::retry::{after, RetryPolicy};
+use crate::outbox_listener::WakeupReceiver;
+use crate::shutdown::ShutdownSignal;
+use crate::DbPool;
+
+/// How a [`LeasedWork`] is polled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PollSettings {
+    pub retry: RetryPolicy,
+    /// Longest pause between polls without an outbox wakeup.
+    pub poll_interval: Duration,
+    /// Bound for a single attempt.
+    pub attempt_timeout: Duration,
+    /// Outbox rows taken up, and attempts made, per poll.
+    pub batch_size: i64,
+}
+
+/// Work items kept in the database and attempted by [`run`].
+pub trait LeasedWork: Send + Sync + 'static {
+    /// Names the worker in logs.
+    const NAME: &'static str;
+
+    type Item: Send + 'static;
+    type Failure: Display + Send + 'static;
+    type Error: Display
+        + From<diesel::result::Error>
+        + From<r2d2::Error>
+        + From<JoinError>
+        + Send
+        + 'static;
+
+    fn pool(&self) -> &DbPool;
+
+    fn settings(&self) -> PollSettings;
+
+    /// Turn up to `limit` new outbox rows into items; returns how many rows
+    /// were taken up.
+    fn take_up(&self, conn: &mut PgConnection, limit: i64) -> QueryResult<usize>;
+
+    /// Claim up to `limit` items due at `now`. Claimed items are not due
+    /// again before `lease_until`, so other workers skip them meanwhile.
+    fn claim_due(
+        &self,
+        conn: &mut PgConnection,
+        now: DateTime<Utc>,
+        lease_until: DateTime<Utc>,
+        limit: i64,
+    ) -> QueryResult<Vec<Self::Item>>;
+
+    /// Attempt `item`, within [`PollSettings::attempt_timeout`].
+    fn attempt(&self, item: &Self::Item) -> impl Future<Output = Result<(), Self::Failure>> + Send;
+
+    /// Attempts made before the current one.
+    fn attempts(item: &Self::Item) -> i32;
+
+    /// Whether `failure` is not worth another attempt.
+    fn is_permanent(_failure: &Self::Failure) -> bool {
+        false
+    }
+
+    fn mark_succeeded(&self, conn: &mut PgConnection, item: &Self::Item) -> QueryResult<()>;
+
+    /// Record a failed attempt; without `retry_at` the item is dead-lettered.
+    fn mark_failed(
+        &self,
+        conn: &mut PgConnection,
+        item: &Self::Item,
+        error: &str,
+        retry_at: Option<DateTime<Utc>>,
+    ) -> QueryResult<()>;
+
+    /// `item` as the logs name it.
+    fn describe(item: &Self::Item) -> String;
+
+    /// Count `outcome` in the worker's metric.
+    fn record_outcome(outcome: Outcome);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Succeeded,
+    Retrying,
+    DeadLettered,
+}
+
+/// What one poll of [`run_once`] did.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PollSummary {
+    /// Outbox rows taken up as items.
+    pub taken_up: usize,
+    pub succeeded: usize,
+    /// Failed attempts that will be retried.
+    pub retrying: usize,
+    pub dead_lettered: usize,
+}
+
+impl PollSummary {
+    pub fn is_idle(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Take up new outbox rows, then attempt the items that are due.
+pub async fn run_once<W: LeasedWork>(work: &Arc<W>) -> Result<PollSummary, W::Error> {
+    let settings = work.settings();
+    let this = Arc::clone(work);
+    let (taken_up, due) = tokio::task::spawn_blocking(move || {
+        let mut conn = this.pool().get()?;
+        let taken_up = this.take_up(&mut conn, settings.batch_size)?;
+        let now = Utc::now();
+        // Long enough for every attempt of the batch to finish.
+        let lease_until = after(now, settings.attempt_timeout.saturating_mul(2));
+        let due = this.claim_due(&mut conn, now, lease_until, settings.batch_size)?;
+        Ok::<_, W::Error>((taken_up, due))
+    })
+    .await??;
+
+    let mut summary = PollSummary {
+        taken_up,
+        ..PollSummary::default()
+    };
+    // Claimed items do not depend on each other (the outbox relay claims at
+    // most one event per aggregate), so their order does not matter.
+    let mut attempts = JoinSet::new();
+    for item in due {
+        let this = Arc::clone(work);
+        attempts.spawn(async move {
+            let result = this.attempt(&item).await;
+            (item, result)
+        });
+    }
+    while let Some(attempt) = attempts.join_next().await {
+        let (item, result) = attempt?;
+        let outcome = record(work, item, result).await?;
+        match outcome {
+            Outcome::Succeeded => summary.succeeded += 1,
+            Outcome::Retrying => summary.retrying += 1,
+            Outcome::DeadLettered => summary.dead_lettered += 1,
+        }
+        W::record_outcome(outcome);
+    }
+    Ok(summary)
+}
+
+async fn record<W: LeasedWork>(
+    work: &Arc<W>,
+    item: W::Item,
+    result: Result<(), W::Failure>,
+) -> Result<Outcome, W::Error> {
+    let this = Arc::clone(work);
+    tokio::task::spawn_blocking(move || {
+        let mut conn = this.pool().get()?;
+        let error = match result {
+            Ok(()) => {
+                this.mark_succeeded(&mut conn, &item)?;
+                return Ok(Outcome::Succeeded);
+            }
+            Err(error) => error,
+        };
+        let attempts = u32::try_from(W::attempts(&item) + 1).unwrap_or(u32::MAX);
+        let retry_at = if W::is_permanent(&error) {
+            None
+        } else {
+            this.settings().retry.backoff(attempts)
+        }
+        .map(|delay| after(Utc::now(), delay));
+        this.mark_failed(&mut conn, &item, &error.to_string(), retry_at)?;
+        if retry_at.is_some() {
+            log::warn!(
+                "{} failed (attempt {}): {}",
+                W::describe(&item),
+                attempts,
+                error
+            );
+            Ok(Outcome::Retrying)
+        } else {
+            log::error!(
+                "{} dead-lettered after {} attempts: {}",
+                W::describe(&item),
+                attempts,
+                error
+            );
+            Ok(Outcome::DeadLettered)
+        }
+    })
+    .await?
+}
+
+/// Poll `work` until shutdown is signalled. Meant to run as a
+/// [`ShutdownCoordinator`](crate::shutdown::ShutdownCoordinator) worker.
+///
+/// Polls again right away while there is work. Otherwise, or after an error,
+/// it waits for the next outbox wakeup, but no longer than `poll_interval`,
+/// which is when retries become due.
+pub async fn run<W: LeasedWork>(
+    work: Arc<W>,
+    mut wakeups: WakeupReceiver,
+    mut shutdown: ShutdownSignal,
+) {
+    loop {
+        let idle = match run_once(&work).await {
+            Ok(summary) => summary.is_idle(),
+            Err(e) => {
+                log::error!("{} failed: {}", W::NAME, e);
+                true
+            }
+        };
+        if shutdown.is_triggered() {
+            return;
+        }
+        if idle {
+            tokio::select! {
+                _ = shutdown.triggered() => return,
+                _ = wakeups.next() => {}
+                _ = tokio::time::sleep(work.settings().poll_interval) => {}
+            }
+            // One poll covers every wakeup queued meanwhile.
+            while wakeups.try_next().is_some() {}
+        }
+    }
+}
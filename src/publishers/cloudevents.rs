@@ -0,0 +1,174 @@
+//! [CloudEvents 1.0](https://github.com/cloudevents/spec) representations of
+//! outbox events.
+//!
+//! | Attribute         | Value                                        |
+//! |-------------------|----------------------------------------------|
+//! | `id`              | event id                                     |
+//! | `source`          | [`CloudEventsConfig::source`]                |
+//! | `type`            | `event_type`                                 |
+//! | `subject`         | `aggregate_id`                               |
+//! | `time`            | `created_at`                                 |
+//! | `datacontenttype` | `application/json`                           |
+//! | `dataschema`      | [`CloudEventsConfig::dataschema`], if set    |
+//! | `partitionkey`    | `aggregate_id` (partitioning extension)      |
+//! | `sequence`        | aggregate position, zero-padded (extension)  |
+//!
+//! In structured mode the whole event is the JSON body; in binary mode the
+//! body is the payload and the attributes travel as prefixed headers.
+
+use serde_json::{Map, Value};
+
+use crate::infrastructure::models::OutboxEventRow;
+
+pub const SPEC_VERSION: &str = "1.0";
+
+/// Content type of a structured-mode message.
+pub const STRUCTURED_CONTENT_TYPE: &str = "application/cloudevents+json";
+
+/// Content type of the event data, the outbox payload.
+pub const DATA_CONTENT_TYPE: &str = "application/json";
+
+/// Stands for the `event_type` in [`CloudEventsConfig::dataschema`].
+pub const EVENT_TYPE_PLACEHOLDER: &str = "${eventType}";
+
+/// Producer-specific attributes (`OUTBOX_CLOUDEVENTS_*` variables).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CloudEventsConfig {
+    /// URI reference identifying this service as the producer.
+    pub source: String,
+    /// URI of the data's schema, with [`EVENT_TYPE_PLACEHOLDER`] replaced by
+    /// the event type; `None` omits the attribute.
+    pub dataschema: Option<String>,
+}
+
+impl Default for CloudEventsConfig {
+    fn default() -> Self {
+        Self {
+            source: "/order-service".to_string(),
+            dataschema: None,
+        }
+    }
+}
+
+impl CloudEventsConfig {
+    /// The context attributes of `event` but `datacontenttype`, which binary
+    /// mode carries as the message's content type. All are strings, in both
+    /// modes: the `sequence` extension is a string attribute, compared
+    /// lexicographically, so the position is padded to the 20 digits any
+    /// `i64` fits in.
+    fn attributes(&self, event: &OutboxEventRow) -> Vec<(&'static str, String)> {
+        let mut attributes = vec![
+            ("specversion", SPEC_VERSION.to_string()),
+            ("id", event.id.to_string()),
+            ("source", self.source.clone()),
+            ("type", event.event_type.clone()),
+            ("subject", event.aggregate_id.clone()),
+            ("time", event.created_at.to_rfc3339()),
+        ];
+        if let Some(dataschema) = &self.dataschema {
+            attributes.push((
+                "dataschema",
+                dataschema.replace(EVENT_TYPE_PLACEHOLDER, &event.event_type),
+            ));
+        }
+        attributes.push(("partitionkey", event.aggregate_id.clone()));
+        attributes.push(("sequence", format!("{:020}", event.sequence)));
+        attributes
+    }
+
+    /// The event as a structured-mode JSON object.
+    pub fn structured(&self, event: &OutboxEventRow) -> Value {
+        let mut object: Map<String, Value> = self
+            .attributes(event)
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), Value::String(value)))
+            .collect();
+        object.insert("datacontenttype".to_string(), DATA_CONTENT_TYPE.into());
+        object.insert("data".to_string(), event.payload.clone());
+        Value::Object(object)
+    }
+
+    /// The attributes as binary-mode headers named `prefix` + attribute, e.g.
+    /// `ce_id` for Kafka. The body is the payload, of [`DATA_CONTENT_TYPE`].
+    pub fn binary_headers(&self, event: &OutboxEventRow, prefix: &str) -> Vec<(String, String)> {
+        self.attributes(event)
+            .into_iter()
+            .map(|(name, value)| (format!("{}{}", prefix, name), value))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::publishers::tests::event;
+
+    fn config() -> CloudEventsConfig {
+        CloudEventsConfig {
+            source: "/order-service".to_string(),
+            dataschema: Some("https://schemas.example/${eventType}.json".to_string()),
+        }
+    }
+
+    #[test]
+    fn structured_mode_wraps_the_payload() {
+        let event = event("Order");
+        assert_eq!(
+            config().structured(&event),
+            json!({
+                "specversion": "1.0",
+                "id": event.id,
+                "source": "/order-service",
+                "type": "OrderPaid",
+                "subject": "order-1",
+                "time": "2026-10-18T12:00:00+00:00",
+                "datacontenttype": "application/json",
+                "dataschema": "https://schemas.example/OrderPaid.json",
+                "partitionkey": "order-1",
+                "sequence": "00000000000000000002",
+                "data": { "status": "PAID" },
+            })
+        );
+    }
+
+    #[test]
+    fn binary_mode_prefixes_the_attributes() {
+        let event = event("Order");
+        let headers = CloudEventsConfig::default().binary_headers(&event, "ce_");
+        let header = |name: &str| {
+            headers
+                .iter()
+                .find(|(header, _)| header == name)
+                .map(|(_, value)| value.as_str())
+        };
+        assert_eq!(header("ce_specversion"), Some("1.0"));
+        assert_eq!(header("ce_id"), Some(event.id.to_string().as_str()));
+        assert_eq!(header("ce_type"), Some("OrderPaid"));
+        assert_eq!(header("ce_subject"), Some("order-1"));
+        assert_eq!(header("ce_time"), Some("2026-10-18T12:00:00+00:00"));
+        assert_eq!(header("ce_sequence"), Some("00000000000000000002"));
+        // Optional and unset.
+        assert_eq!(header("ce_dataschema"), None);
+        // The message's own content type instead.
+        assert_eq!(header("ce_datacontenttype"), None);
+    }
+
+    #[test]
+    fn sequences_order_lexicographically() {
+        let config = CloudEventsConfig::default();
+        let sequence = |sequence: i64| {
+            let mut event = event("Order");
+            event.sequence = sequence;
+            config.structured(&event)["sequence"]
+                .as_str()
+                .expect("string")
+                .to_string()
+        };
+        assert!(sequence(9) < sequence(10));
+        assert!(sequence(99) < sequence(100));
+        assert!(sequence(100) < sequence(i64::MAX));
+        assert_eq!(sequence(i64::MAX).len(), 20);
+    }
+}
@@ -0,0 +1,143 @@
+//! Append-only NDJSON sink for local development: one line per event with
+//! its destination, key, headers, content type and body (`value`), e.g. for
+//! `tail -f | jq`.
+
+use std::fs::OpenOptions;
+use std::io;
+use std::path::Path;
+
+use serde_json::{json, Map, Value};
+use tokio::fs::File;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+use super::MessageMapper;
+use crate::infrastructure::models::OutboxEventRow;
+use crate::outbox_relay::{EventPublisher, PublishError};
+
+/// Prefix of binary-mode CloudEvents attributes, as in Kafka headers.
+const CLOUDEVENTS_PREFIX: &str = "ce_";
+
+pub struct FilePublisher {
+    file: Mutex<File>,
+    mapper: MessageMapper,
+}
+
+impl FilePublisher {
+    /// Open `path` for appending, creating it if needed.
+    pub fn open(path: impl AsRef<Path>, mapper: MessageMapper) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            file: Mutex::new(File::from_std(file)),
+            mapper,
+        })
+    }
+}
+
+impl EventPublisher for FilePublisher {
+    async fn publish(&self, event: &OutboxEventRow) -> Result<(), PublishError> {
+        let message = self.mapper.message(event, CLOUDEVENTS_PREFIX);
+        let headers: Map<String, Value> = message
+            .headers
+            .into_iter()
+            .map(|(name, value)| (name, Value::String(value)))
+            .collect();
+        let entry = json!({
+            "destination": message.destination,
+            "key": message.key,
+            "headers": headers,
+            "content_type": message.content_type,
+            "value": message.body,
+        });
+        let mut line =
+            serde_json::to_vec(&entry).map_err(|e| PublishError::Rejected(e.to_string()))?;
+        line.push(b'\n');
+
+        let mut file = self.file.lock().await;
+        file.write_all(&line)
+            .await
+            .map_err(|e| PublishError::Unavailable(e.to_string()))?;
+        file.flush()
+            .await
+            .map_err(|e| PublishError::Unavailable(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::publishers::cloudevents::CloudEventsConfig;
+    use crate::publishers::tests::event;
+    use crate::publishers::{envelope, EventFormat, EventRoute};
+
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new() -> Self {
+            Self(std::env::temp_dir().join(format!("outbox-{}.ndjson", Uuid::new_v4())))
+        }
+
+        fn lines(&self) -> Vec<Value> {
+            std::fs::read_to_string(&self.0)
+                .expect("read sink")
+                .lines()
+                .map(|line| serde_json::from_str(line).expect("JSON line"))
+                .collect()
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    #[actix_web::test]
+    async fn appends_one_routed_line_per_event() {
+        let sink = TempFile::new();
+        let mapper = MessageMapper::new(
+            EventRoute::new("outbox.${routedByValue}"),
+            EventFormat::EventRouter,
+        );
+        let publisher = FilePublisher::open(&sink.0, mapper.clone()).expect("open");
+        let order = event("Order");
+        let payment = event("Payment");
+        publisher.publish(&order).await.expect("publish");
+        publisher.publish(&payment).await.expect("publish");
+
+        let lines = sink.lines();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["destination"], "outbox.Order");
+        assert_eq!(lines[0]["key"], "order-1");
+        assert_eq!(lines[0]["headers"]["id"], order.id.to_string());
+        assert_eq!(lines[0]["value"], envelope(&order));
+        assert_eq!(lines[1]["destination"], "outbox.Payment");
+
+        // Reopening keeps what was written before.
+        let publisher = FilePublisher::open(&sink.0, mapper).expect("reopen");
+        publisher.publish(&order).await.expect("publish");
+        assert_eq!(sink.lines().len(), 3);
+    }
+
+    #[actix_web::test]
+    async fn writes_binary_cloudevents_attributes_as_headers() {
+        let sink = TempFile::new();
+        let mapper = MessageMapper::new(
+            EventRoute::default(),
+            EventFormat::CloudEventsBinary(CloudEventsConfig::default()),
+        );
+        let publisher = FilePublisher::open(&sink.0, mapper).expect("open");
+        let order = event("Order");
+        publisher.publish(&order).await.expect("publish");
+
+        let line = &sink.lines()[0];
+        assert_eq!(line["headers"]["ce_type"], "OrderPaid");
+        assert_eq!(line["headers"]["ce_source"], "/order-service");
+        assert_eq!(line["content_type"], "application/json");
+        assert_eq!(line["value"], order.payload);
+    }
+}
@@ -0,0 +1,372 @@
+//! [`EventPublisher`] backends for the [outbox relay](crate::outbox_relay).
+//!
+//! Every backend routes events like the Debezium EventRouter of
+//! `debezium/register-connector.json`, so consumers see the same destinations
+//! whichever transport carries them:
+//!
+//! - the destination (NATS subject, AMQP exchange, Redis stream, file entry)
+//!   is [`EventRoute::destination`] of the event's `aggregate_type`: saga
+//!   commands go to [`SAGA_COMMAND_ROUTE`], everything else to the
+//!   connector's `route.topic.replacement`;
+//! - the message key is `aggregate_id` (the [`KEY_HEADER`] where the
+//!   transport has no keys) and the event id is in the [`ID_HEADER`];
+//! - the body is shaped by the [`EventFormat`]: by default the EventRouter's
+//!   [`envelope`], otherwise a CloudEvent.
+//!
+//! The file sink is always available; the broker backends sit behind the
+//! `nats`, `amqp` and `redis-streams` features.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use serde_json::{json, Value};
+
+use self::cloudevents::CloudEventsConfig;
+use crate::domain::saga::SAGA_AGGREGATE;
+use crate::infrastructure::models::OutboxEventRow;
+use crate::outbox_relay::PublishError;
+
+#[cfg(feature = "amqp")]
+pub mod amqp;
+pub mod cloudevents;
+pub mod file;
+#[cfg(feature = "nats")]
+pub mod nats;
+#[cfg(feature = "redis-streams")]
+pub mod redis_streams;
+
+/// Stands for the `aggregate_type` in a route replacement, as in Debezium.
+pub const ROUTED_BY_VALUE: &str = "${routedByValue}";
+
+/// Destination of every event unless configured otherwise; the
+/// `route.topic.replacement` of the Debezium connector.
+pub const DEFAULT_ROUTE: &str = "public.commerce.order.c2.v1";
+
+/// Destination of the saga commands unless configured otherwise. They are
+/// meant for the inventory and payment services, not for consumers of order
+/// events.
+pub const SAGA_COMMAND_ROUTE: &str = "public.commerce.order-saga-command.c2.v1";
+
+/// Header (or field) carrying the event id, like the one Debezium adds.
+pub const ID_HEADER: &str = "id";
+
+/// Header (or field) carrying the `aggregate_id`, for transports without
+/// message keys.
+pub const KEY_HEADER: &str = "key";
+
+/// Maps an event's `aggregate_type` to its destination, like the
+/// EventRouter's `route.topic.replacement` (`OUTBOX_ROUTE_REPLACEMENT`), with
+/// a destination of their own for some aggregates (`SAGA_COMMAND_TOPIC`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventRoute {
+    replacement: String,
+    /// Destinations of the aggregates not routed through `replacement`, by
+    /// `aggregate_type`.
+    aggregates: BTreeMap<String, String>,
+}
+
+impl EventRoute {
+    /// Routes saga commands to [`SAGA_COMMAND_ROUTE`] and every other
+    /// aggregate through `replacement`.
+    pub fn new(replacement: impl Into<String>) -> Self {
+        Self {
+            replacement: replacement.into(),
+            aggregates: BTreeMap::from([(
+                SAGA_AGGREGATE.to_string(),
+                SAGA_COMMAND_ROUTE.to_string(),
+            )]),
+        }
+    }
+
+    /// Route `aggregate_type` to `destination` instead of through the
+    /// replacement.
+    pub fn with_aggregate(
+        mut self,
+        aggregate_type: impl Into<String>,
+        destination: impl Into<String>,
+    ) -> Self {
+        self.aggregates
+            .insert(aggregate_type.into(), destination.into());
+        self
+    }
+
+    pub fn destination(&self, aggregate_type: &str) -> String {
+        match self.aggregates.get(aggregate_type) {
+            Some(destination) => destination.clone(),
+            None => self.replacement.replace(ROUTED_BY_VALUE, aggregate_type),
+        }
+    }
+
+    pub fn replacement(&self) -> &str {
+        &self.replacement
+    }
+
+    /// The aggregates with a destination of their own, and that destination.
+    pub fn aggregates(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.aggregates
+            .iter()
+            .map(|(aggregate_type, destination)| (aggregate_type.as_str(), destination.as_str()))
+    }
+}
+
+impl Default for EventRoute {
+    fn default() -> Self {
+        Self::new(DEFAULT_ROUTE)
+    }
+}
+
+/// The transport the outbox relay publishes to (`OUTBOX_PUBLISHER`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PublisherBackend {
+    /// Append-only NDJSON file, for local development.
+    File { path: PathBuf },
+    /// NATS JetStream; needs a stream capturing the routed subjects.
+    Nats { url: String },
+    /// AMQP 0-9-1 (RabbitMQ) topic exchanges.
+    Amqp { url: String },
+    /// Redis Streams.
+    RedisStreams { url: String },
+}
+
+impl PublisherBackend {
+    /// The `OUTBOX_PUBLISHER` value selecting this backend.
+    pub fn name(&self) -> &'static str {
+        match self {
+            PublisherBackend::File { .. } => "file",
+            PublisherBackend::Nats { .. } => "nats",
+            PublisherBackend::Amqp { .. } => "amqp",
+            PublisherBackend::RedisStreams { .. } => "redis",
+        }
+    }
+}
+
+/// How events are represented on the wire (`OUTBOX_EVENT_FORMAT`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum EventFormat {
+    /// The EventRouter's [`envelope`], as published through Debezium.
+    #[default]
+    EventRouter,
+    /// A CloudEvent as the JSON body.
+    CloudEventsStructured(CloudEventsConfig),
+    /// The payload as the body, CloudEvents attributes as headers.
+    CloudEventsBinary(CloudEventsConfig),
+}
+
+/// Where the outbox relay publishes (`OUTBOX_PUBLISHER*` variables), and how.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublisherConfig {
+    pub backend: PublisherBackend,
+    pub route: EventRoute,
+    pub format: EventFormat,
+}
+
+impl PublisherConfig {
+    pub fn mapper(&self) -> MessageMapper {
+        MessageMapper::new(self.route.clone(), self.format.clone())
+    }
+}
+
+/// An outbox event ready for a transport.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    pub destination: String,
+    /// The `aggregate_id`.
+    pub key: String,
+    /// The [`ID_HEADER`] and [`KEY_HEADER`], the event's own headers, then any
+    /// CloudEvents attributes.
+    pub headers: Vec<(String, String)>,
+    pub content_type: &'static str,
+    pub body: Value,
+}
+
+impl Message {
+    pub fn encoded_body(&self) -> Result<Vec<u8>, PublishError> {
+        serde_json::to_vec(&self.body).map_err(|e| PublishError::Rejected(e.to_string()))
+    }
+}
+
+/// Turns outbox events into [`Message`]s.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MessageMapper {
+    route: EventRoute,
+    format: EventFormat,
+}
+
+impl MessageMapper {
+    pub fn new(route: EventRoute, format: EventFormat) -> Self {
+        Self { route, format }
+    }
+
+    /// `event` as a message; binary-mode CloudEvents attributes are named
+    /// with the transport's `prefix`, e.g. `ce-` for NATS.
+    pub fn message(&self, event: &OutboxEventRow, prefix: &str) -> Message {
+        let mut headers = vec![
+            (ID_HEADER.to_string(), event.id.to_string()),
+            (KEY_HEADER.to_string(), event.aggregate_id.clone()),
+        ];
+        headers.extend(event_headers(event));
+        let (content_type, body) = match &self.format {
+            EventFormat::EventRouter => (cloudevents::DATA_CONTENT_TYPE, envelope(event)),
+            EventFormat::CloudEventsStructured(config) => (
+                cloudevents::STRUCTURED_CONTENT_TYPE,
+                config.structured(event),
+            ),
+            EventFormat::CloudEventsBinary(config) => {
+                headers.extend(config.binary_headers(event, prefix));
+                (cloudevents::DATA_CONTENT_TYPE, event.payload.clone())
+            }
+        };
+        Message {
+            destination: self.route.destination(&event.aggregate_type),
+            key: event.aggregate_id.clone(),
+            headers,
+            content_type,
+            body,
+        }
+    }
+}
+
+/// The string entries of the event's `headers` object.
+fn event_headers(event: &OutboxEventRow) -> impl Iterator<Item = (String, String)> + '_ {
+    event
+        .headers
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter_map(|(name, value)| Some((name.clone(), value.as_str()?.to_string())))
+}
+
+/// The message body, as the EventRouter builds it with the connector's
+/// `additional.placement` and `expand.json.payload`.
+pub fn envelope(event: &OutboxEventRow) -> Value {
+    json!({
+        "event_id": event.id,
+        "event_type": event.event_type,
+        "event_date": event.created_at.to_rfc3339(),
+        "sequence": event.sequence,
+        "payload": event.payload,
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use chrono::{TimeZone, Utc};
+    use uuid::Uuid;
+
+    use super::*;
+
+    pub(crate) fn event(aggregate_type: &str) -> OutboxEventRow {
+        OutboxEventRow {
+            id: Uuid::new_v4(),
+            aggregate_type: aggregate_type.to_string(),
+            aggregate_id: "order-1".to_string(),
+            event_type: "OrderPaid".to_string(),
+            payload: json!({ "status": "PAID" }),
+            created_at: Utc.with_ymd_and_hms(2026, 10, 18, 12, 0, 0).unwrap(),
+            sequence: 2,
+            headers: json!({}),
+        }
+    }
+
+    #[test]
+    fn default_route_matches_the_debezium_connector() {
+        let connector: Value =
+            serde_json::from_str(include_str!("../../debezium/register-connector.json"))
+                .expect("connector config");
+        assert_eq!(
+            connector["config"]["transforms.outbox.route.topic.replacement"],
+            DEFAULT_ROUTE
+        );
+        assert_eq!(EventRoute::default().destination("Order"), DEFAULT_ROUTE);
+    }
+
+    #[test]
+    fn route_substitutes_the_aggregate_type() {
+        let route = EventRoute::new("outbox.event.${routedByValue}");
+        assert_eq!(route.destination("Order"), "outbox.event.Order");
+        assert_eq!(route.destination("Payment"), "outbox.event.Payment");
+    }
+
+    #[test]
+    fn saga_commands_are_routed_apart_from_order_events() {
+        let route = EventRoute::default();
+        assert_eq!(route.destination("Order"), DEFAULT_ROUTE);
+        assert_eq!(route.destination(SAGA_AGGREGATE), SAGA_COMMAND_ROUTE);
+
+        let route = EventRoute::new("outbox.${routedByValue}")
+            .with_aggregate(SAGA_AGGREGATE, "inventory.commands");
+        assert_eq!(route.destination("Order"), "outbox.Order");
+        assert_eq!(route.destination(SAGA_AGGREGATE), "inventory.commands");
+        assert_eq!(
+            route.aggregates().collect::<Vec<_>>(),
+            [(SAGA_AGGREGATE, "inventory.commands")]
+        );
+    }
+
+    #[test]
+    fn mapper_shapes_the_body_by_format() {
+        let event = event("Order");
+        let config = CloudEventsConfig::default();
+        let route = EventRoute::default();
+
+        let message = MessageMapper::default().message(&event, "ce_");
+        assert_eq!(message.destination, DEFAULT_ROUTE);
+        assert_eq!(message.key, "order-1");
+        assert_eq!(message.content_type, "application/json");
+        assert_eq!(message.body, envelope(&event));
+        assert_eq!(message.headers.len(), 2);
+
+        let structured = EventFormat::CloudEventsStructured(config.clone());
+        let message = MessageMapper::new(route.clone(), structured).message(&event, "ce_");
+        assert_eq!(message.content_type, "application/cloudevents+json");
+        assert_eq!(message.body, config.structured(&event));
+
+        let binary = EventFormat::CloudEventsBinary(config.clone());
+        let message = MessageMapper::new(route, binary).message(&event, "ce_");
+        assert_eq!(message.content_type, "application/json");
+        assert_eq!(message.body, event.payload);
+        assert_eq!(
+            message.headers[..2],
+            [
+                (ID_HEADER.to_string(), event.id.to_string()),
+                (KEY_HEADER.to_string(), "order-1".to_string()),
+            ]
+        );
+        assert_eq!(message.headers[2..], config.binary_headers(&event, "ce_"));
+    }
+
+    #[test]
+    fn mapper_passes_on_the_event_headers() {
+        let event = OutboxEventRow {
+            headers: json!({ "tenant": "acme", "traceparent": "00-abc-def-01" }),
+            ..event("Order")
+        };
+        let config = CloudEventsConfig::default();
+        let binary = EventFormat::CloudEventsBinary(config.clone());
+
+        let message = MessageMapper::new(EventRoute::default(), binary).message(&event, "ce_");
+        assert_eq!(
+            message.headers[2..4],
+            [
+                ("tenant".to_string(), "acme".to_string()),
+                ("traceparent".to_string(), "00-abc-def-01".to_string()),
+            ]
+        );
+        assert_eq!(message.headers[4..], config.binary_headers(&event, "ce_"));
+    }
+
+    #[test]
+    fn envelope_carries_the_event_router_fields() {
+        let event = event("Order");
+        assert_eq!(
+            envelope(&event),
+            json!({
+                "event_id": event.id,
+                "event_type": "OrderPaid",
+                "event_date": "2026-10-18T12:00:00+00:00",
+                "sequence": 2,
+                "payload": { "status": "PAID" },
+            })
+        );
+    }
+}
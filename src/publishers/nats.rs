@@ -0,0 +1,141 @@
+//! NATS JetStream publisher (`nats` feature).
+//!
+//! Events go to the subject named by the route, with the event id in the
+//! [`ID_HEADER`] and in `Nats-Msg-Id`, so JetStream drops the duplicates of a
+//! retried publish within the stream's duplicate window. Binary-mode
+//! CloudEvents attributes are `ce-` headers. A stream capturing
+//! the subjects must exist; until it does, publishes fail and are retried.
+//!
+//! [`ID_HEADER`]: super::ID_HEADER
+
+use async_nats::jetstream::{self, Context};
+use async_nats::{Client, ConnectError, ConnectOptions, HeaderMap};
+
+use super::MessageMapper;
+use crate::infrastructure::models::OutboxEventRow;
+use crate::outbox_relay::{EventPublisher, PublishError};
+
+/// JetStream's deduplication header.
+const MSG_ID_HEADER: &str = "Nats-Msg-Id";
+
+const CONTENT_TYPE_HEADER: &str = "Content-Type";
+
+/// Prefix of binary-mode CloudEvents attributes (NATS protocol binding).
+const CLOUDEVENTS_PREFIX: &str = "ce-";
+
+pub struct NatsPublisher {
+    client: Client,
+    jetstream: Context,
+    mapper: MessageMapper,
+}
+
+impl NatsPublisher {
+    /// Connects in the background, so the relay starts while NATS is down.
+    pub async fn connect(url: &str, mapper: MessageMapper) -> Result<Self, ConnectError> {
+        let client = ConnectOptions::new()
+            .retry_on_initial_connect()
+            .connect(url)
+            .await?;
+        Ok(Self {
+            jetstream: jetstream::new(client.clone()),
+            client,
+            mapper,
+        })
+    }
+}
+
+impl EventPublisher for NatsPublisher {
+    async fn publish(&self, event: &OutboxEventRow) -> Result<(), PublishError> {
+        let message = self.mapper.message(event, CLOUDEVENTS_PREFIX);
+        let body = message.encoded_body()?;
+        // Zero until the first connection.
+        let max_payload = self.client.server_info().max_payload;
+        if max_payload > 0 && body.len() > max_payload {
+            return Err(PublishError::Rejected(format!(
+                "{} bytes exceed the server's max_payload of {}",
+                body.len(),
+                max_payload
+            )));
+        }
+
+        let mut headers = HeaderMap::new();
+        headers.insert(MSG_ID_HEADER, event.id.to_string().as_str());
+        headers.insert(CONTENT_TYPE_HEADER, message.content_type);
+        for (name, value) in &message.headers {
+            headers.insert(name.as_str(), value.as_str());
+        }
+        let ack = self
+            .jetstream
+            .publish_with_headers(message.destination, headers, body.into())
+            .await
+            .map_err(|e| PublishError::Unavailable(e.to_string()))?;
+        ack.await
+            .map(|_| ())
+            .map_err(|e| PublishError::Unavailable(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+    use testcontainers::core::{ContainerPort, WaitFor};
+    use testcontainers::runners::AsyncRunner;
+    use testcontainers::{GenericImage, ImageExt};
+
+    use super::*;
+    use crate::publishers::tests::event;
+    use crate::publishers::{envelope, EventFormat, EventRoute, ID_HEADER, KEY_HEADER};
+    use crate::test_support::free_port;
+
+    #[actix_web::test]
+    async fn publishes_deduplicated_events_to_the_routed_subject() {
+        let port = free_port();
+        let _container = GenericImage::new("nats", "2.10-alpine")
+            .with_wait_for(WaitFor::message_on_stderr("Server is ready"))
+            .with_mapped_port(port, ContainerPort::Tcp(4222))
+            .with_cmd(["-js"])
+            .start()
+            .await
+            .expect("Failed to start NATS container");
+        let url = format!("nats://127.0.0.1:{}", port);
+        let publisher = NatsPublisher::connect(
+            &url,
+            MessageMapper::new(
+                EventRoute::new("outbox.${routedByValue}"),
+                EventFormat::EventRouter,
+            ),
+        )
+        .await
+        .expect("connect");
+        let stream = publisher
+            .jetstream
+            .create_stream(jetstream::stream::Config {
+                name: "OUTBOX".to_string(),
+                subjects: vec!["outbox.>".to_string()],
+                ..Default::default()
+            })
+            .await
+            .expect("create stream");
+
+        let order = event("Order");
+        publisher.publish(&order).await.expect("publish");
+        // A retry after a lost acknowledgement.
+        publisher.publish(&order).await.expect("publish again");
+
+        let info = stream.get_info().await.expect("stream info");
+        assert_eq!(info.state.messages, 1);
+        let message = stream.get_raw_message(1).await.expect("stored message");
+        assert_eq!(message.subject.as_str(), "outbox.Order");
+        let id = order.id.to_string();
+        assert_eq!(
+            message.headers.get(ID_HEADER).map(|v| v.as_str()),
+            Some(id.as_str())
+        );
+        assert_eq!(
+            message.headers.get(KEY_HEADER).map(|v| v.as_str()),
+            Some("order-1")
+        );
+        let body: Value = serde_json::from_slice(&message.payload).expect("JSON body");
+        assert_eq!(body, envelope(&order));
+    }
+}
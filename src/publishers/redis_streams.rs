@@ -0,0 +1,130 @@
+//! Redis Streams publisher (`redis-streams` feature).
+//!
+//! Each event is appended (`XADD`) to the stream named by the route. The
+//! entry's fields are the message headers (the [`ID_HEADER`], [`KEY_HEADER`]
+//! and `ce_` CloudEvents attributes in binary mode), then the
+//! [`CONTENT_TYPE_FIELD`] and the JSON body as the [`VALUE_FIELD`].
+//!
+//! [`ID_HEADER`]: super::ID_HEADER
+//! [`KEY_HEADER`]: super::KEY_HEADER
+
+use redis::aio::ConnectionManager;
+use redis::{Client, RedisError};
+use tokio::sync::Mutex;
+
+use super::MessageMapper;
+use crate::infrastructure::models::OutboxEventRow;
+use crate::outbox_relay::{EventPublisher, PublishError};
+
+/// Stream entry field holding the body.
+pub const VALUE_FIELD: &str = "value";
+
+pub const CONTENT_TYPE_FIELD: &str = "content_type";
+
+/// Prefix of binary-mode CloudEvents attributes, as in Kafka headers.
+const CLOUDEVENTS_PREFIX: &str = "ce_";
+
+pub struct RedisStreamPublisher {
+    client: Client,
+    mapper: MessageMapper,
+    /// Reconnects by itself once established.
+    connection: Mutex<Option<ConnectionManager>>,
+}
+
+impl RedisStreamPublisher {
+    /// Checks the URL only; connects on the first publish.
+    pub fn new(url: &str, mapper: MessageMapper) -> Result<Self, RedisError> {
+        Ok(Self {
+            client: Client::open(url)?,
+            mapper,
+            connection: Mutex::new(None),
+        })
+    }
+
+    async fn connection(&self) -> Result<ConnectionManager, RedisError> {
+        let mut connection = self.connection.lock().await;
+        if let Some(open) = connection.as_ref() {
+            return Ok(open.clone());
+        }
+        let open = ConnectionManager::new(self.client.clone()).await?;
+        Ok(connection.insert(open).clone())
+    }
+}
+
+impl EventPublisher for RedisStreamPublisher {
+    async fn publish(&self, event: &OutboxEventRow) -> Result<(), PublishError> {
+        let message = self.mapper.message(event, CLOUDEVENTS_PREFIX);
+        let body = message.encoded_body()?;
+        let unavailable = |e: RedisError| PublishError::Unavailable(e.to_string());
+        let mut connection = self.connection().await.map_err(unavailable)?;
+        let mut command = redis::cmd("XADD");
+        command.arg(&message.destination).arg("*");
+        for (name, value) in &message.headers {
+            command.arg(name).arg(value);
+        }
+        command
+            .arg(CONTENT_TYPE_FIELD)
+            .arg(message.content_type)
+            .arg(VALUE_FIELD)
+            .arg(body)
+            .query_async::<String>(&mut connection)
+            .await
+            .map(|_| ())
+            .map_err(unavailable)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use redis::streams::StreamRangeReply;
+    use serde_json::Value;
+    use testcontainers::core::{ContainerPort, WaitFor};
+    use testcontainers::runners::AsyncRunner;
+    use testcontainers::{GenericImage, ImageExt};
+
+    use super::*;
+    use crate::publishers::tests::event;
+    use crate::publishers::{envelope, EventFormat, EventRoute, ID_HEADER, KEY_HEADER};
+    use crate::test_support::free_port;
+
+    #[actix_web::test]
+    async fn appends_events_to_the_routed_stream() {
+        let port = free_port();
+        let _container = GenericImage::new("redis", "7-alpine")
+            .with_wait_for(WaitFor::message_on_stdout("Ready to accept connections"))
+            .with_mapped_port(port, ContainerPort::Tcp(6379))
+            .start()
+            .await
+            .expect("Failed to start Redis container");
+        let url = format!("redis://127.0.0.1:{}", port);
+        let publisher = RedisStreamPublisher::new(
+            &url,
+            MessageMapper::new(
+                EventRoute::new("outbox.${routedByValue}"),
+                EventFormat::EventRouter,
+            ),
+        )
+        .expect("valid URL");
+
+        let order = event("Order");
+        publisher.publish(&order).await.expect("publish");
+        publisher.publish(&event("Payment")).await.expect("publish");
+
+        let mut connection = publisher.connection().await.expect("connection");
+        let entries: StreamRangeReply = redis::cmd("XRANGE")
+            .arg("outbox.Order")
+            .arg("-")
+            .arg("+")
+            .query_async(&mut connection)
+            .await
+            .expect("read stream");
+        assert_eq!(entries.ids.len(), 1);
+        let entry = &entries.ids[0];
+        assert_eq!(entry.get::<String>(ID_HEADER), Some(order.id.to_string()));
+        assert_eq!(entry.get::<String>(KEY_HEADER).as_deref(), Some("order-1"));
+        let body: Value =
+            serde_json::from_str(&entry.get::<String>(VALUE_FIELD).expect("value field"))
+                .expect("JSON body");
+        assert_eq!(body, envelope(&order));
+    }
+}
@@ -0,0 +1,701 @@
+//! Token-bucket rate limiting per client and per route.
+//!
+//! Every client gets one bucket per route, sized by the read budget for
+//! `GET`/`HEAD`/`OPTIONS` requests and by the write budget otherwise. Clients
+//! are identified, in order of preference, by the subject of their verified
+//! bearer token, a configured API key, or their peer IP address. Unknown API
+//! keys are ignored, so changing the header on every request does not escape
+//! the limit.
+
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::error::InternalError;
+use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue};
+use actix_web::http::Method;
+use actix_web::{Error, HttpMessage, ResponseError};
+use opentelemetry::metrics::Counter;
+use opentelemetry::{global, KeyValue};
+
+use crate::auth::Principal;
+use crate::errors::AppError;
+use crate::telemetry::INSTRUMENTATION_SCOPE;
+
+pub const RATE_LIMIT_LIMIT: HeaderName = HeaderName::from_static("ratelimit-limit");
+pub const RATE_LIMIT_REMAINING: HeaderName = HeaderName::from_static("ratelimit-remaining");
+pub const RATE_LIMIT_RESET: HeaderName = HeaderName::from_static("ratelimit-reset");
+
+/// Buckets kept at most; beyond that the least recently used one is dropped.
+const MAX_BUCKETS: usize = 10_000;
+
+/// Route of the requests that match no route, so that probing random paths
+/// does not create a bucket per path.
+const UNMATCHED_ROUTE: &str = "<unmatched>";
+
+/// Size and refill rate of a token bucket.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Budget {
+    /// Maximum burst size.
+    pub capacity: u32,
+    /// Tokens added back per second.
+    pub refill_per_second: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct RateLimitConfig {
+    pub read: Budget,
+    pub write: Budget,
+    /// Header carrying the caller's API key, e.g. `X-API-Key`.
+    pub api_key_header: String,
+    /// API keys that get a bucket of their own. Any other value of the header
+    /// is ignored.
+    pub api_keys: HashSet<String>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            read: Budget {
+                capacity: 100,
+                refill_per_second: 50.0,
+            },
+            write: Budget {
+                capacity: 20,
+                refill_per_second: 5.0,
+            },
+            api_key_header: "X-API-Key".to_string(),
+            api_keys: HashSet::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RequestClass {
+    Read,
+    Write,
+}
+
+impl RequestClass {
+    pub fn of(method: &Method) -> Self {
+        if matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS) {
+            RequestClass::Read
+        } else {
+            RequestClass::Write
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            RequestClass::Read => "read",
+            RequestClass::Write => "write",
+        }
+    }
+}
+
+/// Outcome of taking one token from a bucket.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Decision {
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    /// Time until the bucket is full again.
+    pub reset_after: Duration,
+    /// Time until the next token is available (zero when allowed).
+    pub retry_after: Duration,
+}
+
+impl Decision {
+    fn write_headers(&self, headers: &mut HeaderMap) {
+        headers.insert(RATE_LIMIT_LIMIT, HeaderValue::from(self.limit));
+        headers.insert(RATE_LIMIT_REMAINING, HeaderValue::from(self.remaining));
+        headers.insert(
+            RATE_LIMIT_RESET,
+            HeaderValue::from(ceil_secs(self.reset_after)),
+        );
+    }
+}
+
+fn ceil_secs(d: Duration) -> u64 {
+    d.as_secs() + u64::from(d.subsec_nanos() > 0)
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl TokenBucket {
+    fn full(budget: Budget, now: Instant) -> Self {
+        Self {
+            tokens: f64::from(budget.capacity),
+            updated_at: now,
+        }
+    }
+
+    fn refill(&mut self, budget: Budget, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated_at).as_secs_f64();
+        self.tokens =
+            (self.tokens + elapsed * budget.refill_per_second).min(f64::from(budget.capacity));
+        self.updated_at = now;
+    }
+
+    fn try_take(&mut self, budget: Budget, now: Instant) -> Decision {
+        self.refill(budget, now);
+        let allowed = self.tokens >= 1.0;
+        if allowed {
+            self.tokens -= 1.0;
+        }
+        let seconds_until = |tokens: f64| {
+            if tokens <= 0.0 || budget.refill_per_second <= 0.0 {
+                Duration::ZERO
+            } else {
+                Duration::from_secs_f64(tokens / budget.refill_per_second)
+            }
+        };
+        Decision {
+            allowed,
+            limit: budget.capacity,
+            remaining: self.tokens.floor() as u32,
+            reset_after: seconds_until(f64::from(budget.capacity) - self.tokens),
+            retry_after: if allowed {
+                Duration::ZERO
+            } else {
+                seconds_until(1.0 - self.tokens)
+            },
+        }
+    }
+}
+
+type BucketKey = (String, String, RequestClass);
+
+/// Token buckets by key, holding at most `capacity` of them: the least
+/// recently used bucket makes room for a new one. By then it has usually
+/// refilled, so dropping it costs its client nothing.
+struct BucketTable {
+    capacity: usize,
+    /// Bucket and last use of each key.
+    buckets: HashMap<BucketKey, (TokenBucket, u64)>,
+    /// Keys by last use, least recent first.
+    by_use: BTreeMap<u64, BucketKey>,
+    uses: u64,
+}
+
+impl BucketTable {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            buckets: HashMap::new(),
+            by_use: BTreeMap::new(),
+            uses: 0,
+        }
+    }
+
+    /// The bucket of `key`, a new one from `fresh` if there is none.
+    fn get(&mut self, key: BucketKey, fresh: impl FnOnce() -> TokenBucket) -> &mut TokenBucket {
+        self.uses += 1;
+        let used = self.uses;
+        if !self.buckets.contains_key(&key) && self.buckets.len() >= self.capacity {
+            if let Some((_, evicted)) = self.by_use.pop_first() {
+                self.buckets.remove(&evicted);
+            }
+        }
+        match self.buckets.entry(key) {
+            Entry::Occupied(entry) => {
+                let (bucket, last_used) = entry.into_mut();
+                if let Some(key) = self.by_use.remove(last_used) {
+                    self.by_use.insert(used, key);
+                }
+                *last_used = used;
+                bucket
+            }
+            Entry::Vacant(entry) => {
+                self.by_use.insert(used, entry.key().clone());
+                &mut entry.insert((fresh(), used)).0
+            }
+        }
+    }
+}
+
+/// Thread-safe table of token buckets, shared by all server workers.
+#[derive(Clone)]
+pub struct RateLimiter {
+    config: Arc<RateLimitConfig>,
+    buckets: Arc<Mutex<BucketTable>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+            buckets: Arc::new(Mutex::new(BucketTable::new(MAX_BUCKETS))),
+        }
+    }
+
+    fn budget(&self, class: RequestClass) -> Budget {
+        match class {
+            RequestClass::Read => self.config.read,
+            RequestClass::Write => self.config.write,
+        }
+    }
+
+    /// Take one token from the bucket of `client` on `route`.
+    pub fn check(&self, client: &str, route: &str, class: RequestClass, now: Instant) -> Decision {
+        let budget = self.budget(class);
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        buckets
+            .get((client.to_string(), route.to_string(), class), || {
+                TokenBucket::full(budget, now)
+            })
+            .try_take(budget, now)
+    }
+
+    /// Identify the client of `req`: token subject, then configured API key,
+    /// then peer IP.
+    fn client_key(&self, req: &ServiceRequest) -> String {
+        if let Some(principal) = req.extensions().get::<Principal>() {
+            if principal.is_authenticated() {
+                return format!("sub:{}", principal.subject);
+            }
+        }
+        if let Some(key) = req
+            .headers()
+            .get(self.config.api_key_header.as_str())
+            .and_then(|v| v.to_str().ok())
+            .filter(|key| self.config.api_keys.contains(*key))
+        {
+            return format!("key:{}", key);
+        }
+        match req.peer_addr() {
+            Some(addr) => format!("ip:{}", addr.ip()),
+            None => "ip:unknown".to_string(),
+        }
+    }
+}
+
+static DECISIONS: LazyLock<Counter<u64>> = LazyLock::new(|| {
+    global::meter(INSTRUMENTATION_SCOPE)
+        .u64_counter("http.server.rate_limit.decisions")
+        .with_description("Requests checked by the rate limiter")
+        .build()
+});
+
+fn record_decision(class: RequestClass, decision: &Decision) {
+    DECISIONS.add(
+        1,
+        &[
+            KeyValue::new("rate_limit.class", class.as_str()),
+            KeyValue::new(
+                "rate_limit.outcome",
+                if decision.allowed {
+                    "allowed"
+                } else {
+                    "rejected"
+                },
+            ),
+        ],
+    );
+}
+
+// ── Middleware ───────────────────────────────────────────────────────────────
+
+type LocalBoxFuture<T> = Pin<Box<dyn Future<Output = T>>>;
+
+/// Rejects requests over budget with 429 and `Retry-After`, and adds the
+/// `RateLimit-*` headers to every response.
+///
+/// Wrap it inside [`Authentication`](crate::auth::Authentication) so that
+/// clients can be told apart by token subject. With no limiter (rate limiting
+/// disabled) requests pass through untouched.
+#[derive(Clone)]
+pub struct RateLimit {
+    limiter: Option<RateLimiter>,
+}
+
+impl RateLimit {
+    pub fn new(limiter: Option<RateLimiter>) -> Self {
+        Self { limiter }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimit
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RateLimitMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimitMiddleware {
+            service: Rc::new(service),
+            limiter: self.limiter.clone(),
+        }))
+    }
+}
+
+pub struct RateLimitMiddleware<S> {
+    service: Rc<S>,
+    limiter: Option<RateLimiter>,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimitMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let Some(limiter) = &self.limiter else {
+            return Box::pin(self.service.call(req));
+        };
+        let class = RequestClass::of(req.method());
+        let client = limiter.client_key(&req);
+        let route = req
+            .match_pattern()
+            .unwrap_or_else(|| UNMATCHED_ROUTE.to_string());
+        let decision = limiter.check(&client, &route, class, Instant::now());
+        record_decision(class, &decision);
+
+        if !decision.allowed {
+            log::debug!("Rate limit exceeded for {} on {}", client, route);
+            let err = AppError::TooManyRequests {
+                retry_after_secs: ceil_secs(decision.retry_after).max(1),
+            };
+            let mut response = err.error_response();
+            decision.write_headers(response.headers_mut());
+            return Box::pin(ready(Err(
+                InternalError::from_response(err, response).into()
+            )));
+        }
+
+        let service = Rc::clone(&self.service);
+        Box::pin(async move {
+            let mut res = service.call(req).await?;
+            decision.write_headers(res.headers_mut());
+            Ok(res)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+    use actix_web::{test as actix_test, web, App, HttpResponse};
+
+    fn limiter(read: Budget, write: Budget) -> RateLimiter {
+        RateLimiter::new(RateLimitConfig {
+            read,
+            write,
+            api_keys: ["partner-1", "partner-2"]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            ..Default::default()
+        })
+    }
+
+    const TIGHT: Budget = Budget {
+        capacity: 2,
+        refill_per_second: 1.0,
+    };
+    const LOOSE: Budget = Budget {
+        capacity: 100,
+        refill_per_second: 100.0,
+    };
+
+    // ── Token bucket ──────────────────────────────────────────────────────────
+
+    #[test]
+    fn bucket_allows_burst_then_rejects() {
+        let limiter = limiter(TIGHT, TIGHT);
+        let now = Instant::now();
+
+        let first = limiter.check("c", "/orders", RequestClass::Read, now);
+        assert!(first.allowed);
+        assert_eq!(first.remaining, 1);
+        assert!(
+            limiter
+                .check("c", "/orders", RequestClass::Read, now)
+                .allowed
+        );
+
+        let third = limiter.check("c", "/orders", RequestClass::Read, now);
+        assert!(!third.allowed);
+        assert_eq!(third.remaining, 0);
+        assert_eq!(third.retry_after, Duration::from_secs(1));
+        assert_eq!(third.reset_after, Duration::from_secs(2));
+    }
+
+    #[test]
+    fn bucket_refills_over_time() {
+        let limiter = limiter(TIGHT, TIGHT);
+        let now = Instant::now();
+        for _ in 0..2 {
+            limiter.check("c", "/orders", RequestClass::Read, now);
+        }
+        assert!(
+            !limiter
+                .check("c", "/orders", RequestClass::Read, now)
+                .allowed
+        );
+
+        let later = now + Duration::from_millis(1_000);
+        assert!(
+            limiter
+                .check("c", "/orders", RequestClass::Read, later)
+                .allowed
+        );
+    }
+
+    #[test]
+    fn clients_routes_and_classes_have_separate_buckets() {
+        let limiter = limiter(TIGHT, TIGHT);
+        let now = Instant::now();
+        for _ in 0..2 {
+            limiter.check("a", "/orders", RequestClass::Write, now);
+        }
+        assert!(
+            !limiter
+                .check("a", "/orders", RequestClass::Write, now)
+                .allowed
+        );
+
+        assert!(
+            limiter
+                .check("b", "/orders", RequestClass::Write, now)
+                .allowed
+        );
+        assert!(
+            limiter
+                .check("a", "/orders/{id}", RequestClass::Write, now)
+                .allowed
+        );
+        assert!(
+            limiter
+                .check("a", "/orders", RequestClass::Read, now)
+                .allowed
+        );
+    }
+
+    #[test]
+    fn read_and_write_use_their_own_budget() {
+        let limiter = limiter(LOOSE, TIGHT);
+        let now = Instant::now();
+        assert_eq!(
+            limiter.check("c", "/orders", RequestClass::Read, now).limit,
+            100
+        );
+        assert_eq!(
+            limiter
+                .check("c", "/orders", RequestClass::Write, now)
+                .limit,
+            2
+        );
+    }
+
+    #[test]
+    fn table_drops_the_least_recently_used_bucket() {
+        let now = Instant::now();
+        let key = |client: &str| {
+            (
+                client.to_string(),
+                "/orders".to_string(),
+                RequestClass::Read,
+            )
+        };
+        let mut table = BucketTable::new(2);
+        table.get(key("a"), || TokenBucket::full(TIGHT, now));
+        table.get(key("b"), || TokenBucket::full(TIGHT, now));
+        table.get(key("a"), || TokenBucket::full(TIGHT, now));
+
+        table.get(key("c"), || TokenBucket::full(TIGHT, now));
+        assert_eq!(table.buckets.len(), 2);
+        assert_eq!(table.by_use.len(), 2);
+        assert!(table.buckets.contains_key(&key("a")));
+        assert!(!table.buckets.contains_key(&key("b")));
+    }
+
+    #[test]
+    fn request_class_follows_http_method() {
+        assert_eq!(RequestClass::of(&Method::GET), RequestClass::Read);
+        assert_eq!(RequestClass::of(&Method::HEAD), RequestClass::Read);
+        assert_eq!(RequestClass::of(&Method::POST), RequestClass::Write);
+        assert_eq!(RequestClass::of(&Method::DELETE), RequestClass::Write);
+    }
+
+    // ── Middleware ────────────────────────────────────────────────────────────
+
+    macro_rules! test_app {
+        ($limiter:expr) => {
+            actix_test::init_service(
+                App::new()
+                    .wrap(RateLimit::new(Some($limiter)))
+                    .route("/orders", web::get().to(HttpResponse::Ok))
+                    .route("/orders", web::post().to(HttpResponse::Created)),
+            )
+            .await
+        };
+    }
+
+    /// Call `app`, rendering middleware errors the way the server would.
+    macro_rules! send {
+        ($app:expr, $req:expr) => {
+            match actix_test::try_call_service($app, $req).await {
+                Ok(resp) => (resp.status(), resp.headers().clone()),
+                Err(err) => {
+                    let resp = err.error_response();
+                    (resp.status(), resp.headers().clone())
+                }
+            }
+        };
+    }
+
+    fn header<'a>(headers: &'a HeaderMap, name: &HeaderName) -> &'a str {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_else(|| panic!("missing header {}", name))
+    }
+
+    #[actix_web::test]
+    async fn responses_carry_rate_limit_headers() {
+        let app = test_app!(limiter(TIGHT, TIGHT));
+        let req = actix_test::TestRequest::get()
+            .uri("/orders")
+            .peer_addr("10.0.0.1:1234".parse().expect("addr"))
+            .to_request();
+        let (status, headers) = send!(&app, req);
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(header(&headers, &RATE_LIMIT_LIMIT), "2");
+        assert_eq!(header(&headers, &RATE_LIMIT_REMAINING), "1");
+    }
+
+    #[actix_web::test]
+    async fn exhausted_budget_yields_429_with_retry_after() {
+        let app = test_app!(limiter(LOOSE, TIGHT));
+        let request = |method: Method, api_key: &str| {
+            actix_test::TestRequest::default()
+                .method(method)
+                .uri("/orders")
+                .insert_header(("X-API-Key", api_key.to_string()))
+                .to_request()
+        };
+        for _ in 0..2 {
+            let (status, _) = send!(&app, request(Method::POST, "partner-1"));
+            assert_eq!(status, StatusCode::CREATED);
+        }
+
+        let (status, headers) = send!(&app, request(Method::POST, "partner-1"));
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(header(&headers, &actix_web::http::header::RETRY_AFTER), "1");
+        assert_eq!(header(&headers, &RATE_LIMIT_REMAINING), "0");
+
+        // Reads have their own budget and another API key its own bucket.
+        let (status, _) = send!(&app, request(Method::GET, "partner-1"));
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = send!(&app, request(Method::POST, "partner-2"));
+        assert_eq!(status, StatusCode::CREATED);
+    }
+
+    #[actix_web::test]
+    async fn unmatched_paths_share_one_bucket() {
+        let app = test_app!(limiter(TIGHT, TIGHT));
+        let get = |path: String| {
+            actix_test::TestRequest::get()
+                .uri(&path)
+                .peer_addr("10.0.0.1:1234".parse().expect("addr"))
+                .to_request()
+        };
+
+        for i in 0..2 {
+            let (status, _) = send!(&app, get(format!("/probe-{i}")));
+            assert_eq!(status, StatusCode::NOT_FOUND);
+        }
+        let (status, _) = send!(&app, get("/probe-2".to_string()));
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[actix_web::test]
+    async fn unknown_api_keys_fall_back_to_the_peer_ip() {
+        let app = test_app!(limiter(LOOSE, TIGHT));
+        let post = |api_key: String| {
+            actix_test::TestRequest::post()
+                .uri("/orders")
+                .insert_header(("X-API-Key", api_key))
+                .peer_addr("10.0.0.1:1234".parse().expect("addr"))
+                .to_request()
+        };
+
+        for i in 0..2 {
+            let (status, _) = send!(&app, post(format!("made-up-{i}")));
+            assert_eq!(status, StatusCode::CREATED);
+        }
+        let (status, _) = send!(&app, post("made-up-2".to_string()));
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[actix_web::test]
+    async fn authenticated_subject_keys_the_bucket() {
+        let app = actix_test::init_service(
+            App::new()
+                .wrap(RateLimit::new(Some(limiter(LOOSE, TIGHT))))
+                .wrap_fn(|req, srv| {
+                    let subject = req
+                        .headers()
+                        .get("x-test-subject")
+                        .and_then(|v| v.to_str().ok())
+                        .unwrap_or("nobody")
+                        .to_string();
+                    req.extensions_mut()
+                        .insert(Principal::new(subject, Vec::new()));
+                    srv.call(req)
+                })
+                .route("/orders", web::post().to(HttpResponse::Created)),
+        )
+        .await;
+        let post = |subject: &str| {
+            actix_test::TestRequest::post()
+                .uri("/orders")
+                .insert_header(("x-test-subject", subject.to_string()))
+                .peer_addr("10.0.0.1:1234".parse().expect("addr"))
+                .to_request()
+        };
+
+        for _ in 0..2 {
+            send!(&app, post("alice"));
+        }
+        assert_eq!(send!(&app, post("alice")).0, StatusCode::TOO_MANY_REQUESTS);
+        // Same IP, different subject: separate bucket.
+        assert_eq!(send!(&app, post("bob")).0, StatusCode::CREATED);
+    }
+
+    #[actix_web::test]
+    async fn disabled_limiter_passes_requests_through() {
+        let app = actix_test::init_service(
+            App::new()
+                .wrap(RateLimit::new(None))
+                .route("/orders", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+        let req = actix_test::TestRequest::get().uri("/orders").to_request();
+        let (status, headers) = send!(&app, req);
+        assert_eq!(status, StatusCode::OK);
+        assert!(headers.get(RATE_LIMIT_LIMIT).is_none());
+    }
+}
@@ -0,0 +1,472 @@
+//! Health of the replication slot feeding the outbox: Debezium's, or the
+//! replication relay's.
+//!
+//! While the connector is down, its `pgoutput` slot keeps every WAL segment
+//! from its `restart_lsn` on, until the disk fills up. The
+//! [`ReplicationMonitor`] samples the slot in `pg_replication_slots`:
+//! whether a consumer is attached, how much WAL it retains and how far its
+//! confirmed flush position lags behind the current WAL position. It records
+//! these as metrics and reports the `replication_slot` readiness check, which
+//! is unhealthy when the slot is missing, has been inactive for too long or
+//! exceeds a byte threshold. The check only fails readiness when configured
+//! to: otherwise a connector outage would take every replica out of the load
+//! balancer, although orders can still be written.
+//!
+//! With heartbeats on, each check also touches `replication_heartbeat`, so a
+//! connector capturing that table has a change to confirm even when the
+//! outbox is idle.
+
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Bool, Nullable, Text};
+use opentelemetry::metrics::Gauge;
+use opentelemetry::{global, KeyValue};
+use serde::Serialize;
+use serde_json::json;
+use thiserror::Error;
+
+use crate::debezium::DEFAULT_SLOT_NAME;
+use crate::schema::replication_heartbeat;
+use crate::shutdown::{Readiness, ReadinessCheck, ShutdownSignal};
+use crate::telemetry::{in_db_span, INSTRUMENTATION_SCOPE};
+use crate::DbPool;
+
+/// Name of the readiness check.
+pub const READINESS_CHECK: &str = "replication_slot";
+
+/// Replication monitor settings (`REPLICATION_*` variables).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplicationMonitorConfig {
+    /// The connector's slot (`DEBEZIUM_SLOT_NAME`).
+    pub slot_name: String,
+    /// Pause between checks.
+    pub interval: Duration,
+    /// WAL the slot may hold back before readiness fails.
+    pub max_retained_wal_bytes: i64,
+    /// Distance between the current WAL position and the slot's confirmed
+    /// flush position before readiness fails.
+    pub max_flush_lag_bytes: i64,
+    /// How long the slot may have no consumer before readiness fails.
+    pub max_inactive: Duration,
+    /// Touch `replication_heartbeat` on every check.
+    pub heartbeat: bool,
+    /// Fail readiness while the slot is unhealthy, instead of only showing it.
+    pub required_for_readiness: bool,
+}
+
+impl Default for ReplicationMonitorConfig {
+    fn default() -> Self {
+        Self {
+            slot_name: DEFAULT_SLOT_NAME.to_string(),
+            interval: Duration::from_secs(10),
+            max_retained_wal_bytes: 1 << 30,
+            max_flush_lag_bytes: 256 << 20,
+            max_inactive: Duration::from_secs(300),
+            heartbeat: false,
+            required_for_readiness: false,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum MonitorError {
+    #[error("Database error: {0}")]
+    Database(#[from] diesel::result::Error),
+
+    #[error("Connection pool error: {0}")]
+    Pool(#[from] r2d2::Error),
+
+    #[error("Worker error: {0}")]
+    Worker(#[from] tokio::task::JoinError),
+}
+
+/// A row of `pg_replication_slots`, with byte distances to the current WAL
+/// position.
+#[derive(Debug, Clone, PartialEq, Eq, QueryableByName)]
+pub struct SlotSample {
+    #[diesel(sql_type = Bool)]
+    pub active: bool,
+    /// `None` once the slot no longer reserves WAL (it was invalidated).
+    #[diesel(sql_type = Nullable<BigInt>)]
+    pub retained_wal_bytes: Option<i64>,
+    /// `None` for slots without a confirmed position, e.g. physical ones.
+    #[diesel(sql_type = Nullable<BigInt>)]
+    pub confirmed_flush_lag_bytes: Option<i64>,
+}
+
+/// `slot_name` as it stands, or `None` if there is no such slot.
+pub fn sample_slot(conn: &mut PgConnection, slot_name: &str) -> QueryResult<Option<SlotSample>> {
+    in_db_span("SELECT", "pg_replication_slots", || {
+        diesel::sql_query(
+            "SELECT active, \
+                    pg_wal_lsn_diff(pg_current_wal_lsn(), restart_lsn)::bigint \
+                        AS retained_wal_bytes, \
+                    pg_wal_lsn_diff(pg_current_wal_lsn(), confirmed_flush_lsn)::bigint \
+                        AS confirmed_flush_lag_bytes \
+             FROM pg_replication_slots WHERE slot_name = $1",
+        )
+        .bind::<Text, _>(slot_name)
+        .get_result(conn)
+        .optional()
+    })
+}
+
+/// Touch the heartbeat row.
+pub fn write_heartbeat(conn: &mut PgConnection) -> QueryResult<()> {
+    use replication_heartbeat::dsl;
+
+    in_db_span("INSERT", "replication_heartbeat", || {
+        diesel::insert_into(dsl::replication_heartbeat)
+            .values(dsl::id.eq(1))
+            .on_conflict(dsl::id)
+            .do_update()
+            .set(dsl::beat_at.eq(diesel::dsl::now))
+            .execute(conn)
+    })?;
+    Ok(())
+}
+
+/// What a check found, as shown in the readiness response.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SlotHealth {
+    pub slot_name: String,
+    pub exists: bool,
+    pub active: bool,
+    pub retained_wal_bytes: Option<i64>,
+    pub confirmed_flush_lag_bytes: Option<i64>,
+    /// How long the slot has had no consumer, as seen by this monitor.
+    pub inactive_secs: Option<u64>,
+    /// Thresholds exceeded; empty when healthy.
+    pub problems: Vec<String>,
+}
+
+impl SlotHealth {
+    pub fn is_healthy(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Judge `sample` against the thresholds of `config`.
+pub fn assess(
+    config: &ReplicationMonitorConfig,
+    sample: Option<&SlotSample>,
+    inactive_for: Option<Duration>,
+) -> SlotHealth {
+    let mut health = SlotHealth {
+        slot_name: config.slot_name.clone(),
+        exists: sample.is_some(),
+        active: sample.is_some_and(|s| s.active),
+        retained_wal_bytes: sample.and_then(|s| s.retained_wal_bytes),
+        confirmed_flush_lag_bytes: sample.and_then(|s| s.confirmed_flush_lag_bytes),
+        inactive_secs: inactive_for.map(|d| d.as_secs()),
+        problems: Vec::new(),
+    };
+    let Some(sample) = sample else {
+        health.problems.push("the slot does not exist".to_string());
+        return health;
+    };
+    if let Some(inactive_for) = inactive_for.filter(|d| *d > config.max_inactive) {
+        health.problems.push(format!(
+            "no consumer for {}s (at most {}s)",
+            inactive_for.as_secs(),
+            config.max_inactive.as_secs()
+        ));
+    }
+    match sample.retained_wal_bytes {
+        None => health
+            .problems
+            .push("the slot no longer reserves WAL".to_string()),
+        Some(bytes) if bytes > config.max_retained_wal_bytes => health.problems.push(format!(
+            "retains {} bytes of WAL (at most {})",
+            bytes, config.max_retained_wal_bytes
+        )),
+        Some(_) => {}
+    }
+    if let Some(lag) = sample
+        .confirmed_flush_lag_bytes
+        .filter(|lag| *lag > config.max_flush_lag_bytes)
+    {
+        health.problems.push(format!(
+            "confirmed flush lags {} bytes behind (at most {})",
+            lag, config.max_flush_lag_bytes
+        ));
+    }
+    health
+}
+
+pub struct ReplicationMonitor {
+    pool: DbPool,
+    config: ReplicationMonitorConfig,
+    readiness: Readiness,
+    /// When the slot was first seen without a consumer.
+    inactive_since: Mutex<Option<Instant>>,
+}
+
+impl ReplicationMonitor {
+    pub fn new(pool: DbPool, config: ReplicationMonitorConfig, readiness: Readiness) -> Self {
+        Self {
+            pool,
+            config,
+            readiness,
+            inactive_since: Mutex::new(None),
+        }
+    }
+
+    /// Sample the slot (and write the heartbeat if enabled), record metrics
+    /// and report the readiness check.
+    pub async fn check(self: &Arc<Self>) -> Result<SlotHealth, MonitorError> {
+        let this = Arc::clone(self);
+        let sample = tokio::task::spawn_blocking(move || {
+            let mut conn = this.pool.get()?;
+            let sample = sample_slot(&mut conn, &this.config.slot_name)?;
+            if this.config.heartbeat {
+                write_heartbeat(&mut conn)?;
+            }
+            Ok::<_, MonitorError>(sample)
+        })
+        .await??;
+
+        let inactive_for = {
+            let mut since = self
+                .inactive_since
+                .lock()
+                .unwrap_or_else(|e| e.into_inner());
+            match &sample {
+                Some(s) if !s.active => Some(since.get_or_insert_with(Instant::now).elapsed()),
+                _ => {
+                    *since = None;
+                    None
+                }
+            }
+        };
+        let health = assess(&self.config, sample.as_ref(), inactive_for);
+        record_metrics(&health);
+        self.readiness.report_check(
+            READINESS_CHECK,
+            ReadinessCheck {
+                healthy: health.is_healthy(),
+                required: self.config.required_for_readiness,
+                details: serde_json::to_value(&health).unwrap_or_default(),
+            },
+        );
+        Ok(health)
+    }
+}
+
+struct SlotGauges {
+    active: Gauge<i64>,
+    retained_wal: Gauge<i64>,
+    confirmed_flush_lag: Gauge<i64>,
+}
+
+static SLOT_GAUGES: LazyLock<SlotGauges> = LazyLock::new(|| {
+    let meter = global::meter(INSTRUMENTATION_SCOPE);
+    SlotGauges {
+        active: meter
+            .i64_gauge("replication.slot.active")
+            .with_description(
+                "Whether a consumer is attached to the replication slot (1) or not (0)",
+            )
+            .build(),
+        retained_wal: meter
+            .i64_gauge("replication.slot.retained_wal")
+            .with_description("WAL retained by the replication slot")
+            .with_unit("By")
+            .build(),
+        confirmed_flush_lag: meter
+            .i64_gauge("replication.slot.confirmed_flush_lag")
+            .with_description("WAL between the current position and the slot's confirmed flush")
+            .with_unit("By")
+            .build(),
+    }
+});
+
+fn record_metrics(health: &SlotHealth) {
+    let gauges = &*SLOT_GAUGES;
+    let attributes = [KeyValue::new(
+        "replication.slot.name",
+        health.slot_name.clone(),
+    )];
+    gauges.active.record(i64::from(health.active), &attributes);
+    if let Some(bytes) = health.retained_wal_bytes {
+        gauges.retained_wal.record(bytes, &attributes);
+    }
+    if let Some(bytes) = health.confirmed_flush_lag_bytes {
+        gauges.confirmed_flush_lag.record(bytes, &attributes);
+    }
+}
+
+/// Check the slot every `interval` until shutdown is signalled. Meant to run
+/// as a [`ShutdownCoordinator`](crate::shutdown::ShutdownCoordinator) worker.
+///
+/// A check that cannot reach the database reports the check unhealthy too.
+pub async fn run(monitor: Arc<ReplicationMonitor>, mut shutdown: ShutdownSignal) {
+    loop {
+        match monitor.check().await {
+            Ok(health) if !health.is_healthy() => log::warn!(
+                "Replication slot {} is unhealthy: {}",
+                health.slot_name,
+                health.problems.join("; ")
+            ),
+            Ok(_) => {}
+            Err(e) => {
+                log::error!("Replication slot check failed: {}", e);
+                monitor.readiness.report_check(
+                    READINESS_CHECK,
+                    ReadinessCheck {
+                        healthy: false,
+                        required: monitor.config.required_for_readiness,
+                        details: json!({
+                            "slot_name": monitor.config.slot_name,
+                            "problems": [format!("check failed: {}", e)],
+                        }),
+                    },
+                );
+            }
+        }
+        tokio::select! {
+            _ = shutdown.triggered() => return,
+            _ = tokio::time::sleep(monitor.config.interval) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Utc};
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::test_support::setup_db;
+
+    fn sample(active: bool, retained: i64, lag: Option<i64>) -> SlotSample {
+        SlotSample {
+            active,
+            retained_wal_bytes: Some(retained),
+            confirmed_flush_lag_bytes: lag,
+        }
+    }
+
+    #[test]
+    fn a_consumed_slot_within_thresholds_is_healthy() {
+        let config = ReplicationMonitorConfig::default();
+        let health = assess(&config, Some(&sample(true, 4096, Some(128))), None);
+        assert!(health.is_healthy());
+        assert!(health.exists && health.active);
+        assert_eq!(health.retained_wal_bytes, Some(4096));
+        assert_eq!(health.confirmed_flush_lag_bytes, Some(128));
+    }
+
+    #[test]
+    fn thresholds_fail_the_slot() {
+        let config = ReplicationMonitorConfig {
+            max_retained_wal_bytes: 1000,
+            max_flush_lag_bytes: 100,
+            max_inactive: Duration::from_secs(60),
+            ..ReplicationMonitorConfig::default()
+        };
+
+        let missing = assess(&config, None, None);
+        assert_eq!(missing.problems, ["the slot does not exist"]);
+
+        let behind = assess(&config, Some(&sample(true, 1001, Some(101))), None);
+        assert_eq!(behind.problems.len(), 2);
+
+        let briefly_idle = Some(Duration::from_secs(30));
+        assert!(assess(&config, Some(&sample(false, 10, None)), briefly_idle).is_healthy());
+        let abandoned = assess(
+            &config,
+            Some(&sample(false, 10, None)),
+            Some(Duration::from_secs(61)),
+        );
+        assert_eq!(abandoned.problems, ["no consumer for 61s (at most 60s)"]);
+        assert_eq!(abandoned.inactive_secs, Some(61));
+
+        let invalidated = SlotSample {
+            active: false,
+            retained_wal_bytes: None,
+            confirmed_flush_lag_bytes: None,
+        };
+        assert_eq!(
+            assess(&config, Some(&invalidated), None).problems,
+            ["the slot no longer reserves WAL"]
+        );
+    }
+
+    #[tokio::test]
+    async fn reports_the_slot_to_readiness() {
+        let (_container, pool) = setup_db().await;
+        // Slots belong to the cluster; a physical one needs no logical WAL.
+        let slot_name = format!("monitor_{}", Uuid::new_v4().simple());
+        let mut conn = pool.get().expect("connection");
+        diesel::sql_query("SELECT pg_create_physical_replication_slot($1, true)")
+            .bind::<Text, _>(&slot_name)
+            .execute(&mut conn)
+            .expect("create slot");
+
+        let readiness = Readiness::new();
+        let config = ReplicationMonitorConfig {
+            slot_name: slot_name.clone(),
+            max_inactive: Duration::ZERO,
+            ..ReplicationMonitorConfig::default()
+        };
+        let monitor = Arc::new(ReplicationMonitor::new(
+            pool.clone(),
+            config,
+            readiness.clone(),
+        ));
+
+        let health = monitor.check().await.expect("check");
+        assert!(health.exists);
+        assert!(!health.active);
+        assert!(health.retained_wal_bytes.is_some_and(|bytes| bytes >= 0));
+        assert_eq!(health.confirmed_flush_lag_bytes, None);
+        // The slot had no consumer for longer than zero seconds at the second look.
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let health = monitor.check().await.expect("check");
+        assert!(!health.is_healthy());
+        let check = &readiness.checks()[READINESS_CHECK];
+        assert!(!check.healthy);
+        assert!(!check.required, "shown without failing readiness");
+        assert_eq!(check.details["slot_name"], slot_name.as_str());
+
+        diesel::sql_query("SELECT pg_drop_replication_slot($1)")
+            .bind::<Text, _>(&slot_name)
+            .execute(&mut conn)
+            .expect("drop slot");
+        let health = monitor.check().await.expect("check");
+        assert!(!health.exists);
+    }
+
+    #[tokio::test]
+    async fn writes_the_heartbeat_when_enabled() {
+        let (_container, pool) = setup_db().await;
+        let config = ReplicationMonitorConfig {
+            slot_name: format!("absent_{}", Uuid::new_v4().simple()),
+            heartbeat: true,
+            ..ReplicationMonitorConfig::default()
+        };
+        let monitor = Arc::new(ReplicationMonitor::new(
+            pool.clone(),
+            config,
+            Readiness::new(),
+        ));
+
+        let beat = || -> Vec<DateTime<Utc>> {
+            let mut conn = pool.get().expect("connection");
+            replication_heartbeat::table
+                .select(replication_heartbeat::beat_at)
+                .load(&mut conn)
+                .expect("heartbeat")
+        };
+        monitor.check().await.expect("check");
+        let first = beat();
+        assert_eq!(first.len(), 1);
+        monitor.check().await.expect("check");
+        let second = beat();
+        assert_eq!(second.len(), 1);
+        assert!(second[0] >= first[0]);
+    }
+}
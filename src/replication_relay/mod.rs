@@ -0,0 +1,616 @@
+//! Outbox relay fed by logical replication, as an alternative to both
+//! Debezium and the polling [`outbox_relay`](crate::outbox_relay).
+//!
+//! The [`ReplicationRelay`] streams the inserts into `commerce_order_outbox`
+//! published by [`PUBLICATION`] through its own replication slot, decodes
+//! the `pgoutput` messages and hands the events of each committed
+//! transaction, in commit order, to an [`EventPublisher`]. It acknowledges a
+//! transaction's end LSN only once all of its events are published: after a
+//! failed publish it drops the connection and streams again from the last
+//! acknowledged transaction. Delivery is at least once and in commit order;
+//! nothing is dead-lettered, so an event that cannot be published holds the
+//! stream back until the publisher accepts it.
+
+pub mod pgoutput;
+pub mod stream;
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use thiserror::Error;
+use tokio::time::MissedTickBehavior;
+use uuid::Uuid;
+
+use crate::infrastructure::models::OutboxEventRow;
+use crate::infrastructure::outbox::ORDER_OUTBOX;
+use crate::outbox_relay::{record_outcome, EventPublisher, PublishError};
+use crate::poll_loop::Outcome;
+use crate::shutdown::ShutdownSignal;
+use pgoutput::{Column, DecodeError, LogicalMessage, Relation};
+use stream::{ReplicationMessage, ReplicationStream, StreamError};
+
+/// Publication of the outbox inserts, created by the migrations.
+pub const PUBLICATION: &str = "commerce_order_outbox_relay";
+
+/// Slot of the relay unless `OUTBOX_REPLICATION_SLOT` names another.
+pub const DEFAULT_SLOT_NAME: &str = "order_outbox_relay";
+
+/// First reconnection delay; doubled after each failed session up to
+/// `max_reconnect_delay`.
+const RECONNECT_DELAY: Duration = Duration::from_millis(500);
+
+/// Replication relay settings (`OUTBOX_REPLICATION_*` variables).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplicationRelayConfig {
+    /// Logical replication slot of the relay, created if missing. It retains
+    /// WAL until dropped, also after switching back to polling
+    /// (`order-admin outbox drop-slot`).
+    pub slot_name: String,
+    /// Longest pause between status updates to the server.
+    pub status_interval: Duration,
+    /// Bound for a single publish attempt.
+    pub publish_timeout: Duration,
+    pub max_reconnect_delay: Duration,
+}
+
+impl Default for ReplicationRelayConfig {
+    fn default() -> Self {
+        Self {
+            slot_name: DEFAULT_SLOT_NAME.to_string(),
+            status_interval: Duration::from_secs(10),
+            publish_timeout: Duration::from_secs(10),
+            max_reconnect_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ReplicationRelayError {
+    #[error("replication stream: {0}")]
+    Stream(#[from] StreamError),
+
+    #[error("cannot decode pgoutput message: {0}")]
+    Decode(#[from] DecodeError),
+
+    #[error("invalid outbox row: {0}")]
+    Row(String),
+
+    #[error("cannot find the outbox table {0}")]
+    UnknownTable(&'static str),
+
+    #[error("publishing outbox event {id} failed: {error}")]
+    Publish { id: Uuid, error: PublishError },
+}
+
+pub struct ReplicationRelay<P> {
+    database_url: String,
+    publisher: P,
+    config: ReplicationRelayConfig,
+    /// End LSN of the last transaction whose events were all published.
+    acknowledged: AtomicU64,
+}
+
+impl<P: EventPublisher> ReplicationRelay<P> {
+    pub fn new(
+        database_url: impl Into<String>,
+        publisher: P,
+        config: ReplicationRelayConfig,
+    ) -> Self {
+        Self {
+            database_url: database_url.into(),
+            publisher,
+            config,
+            acknowledged: AtomicU64::new(0),
+        }
+    }
+
+    /// The position up to which events have been published, `0` before the
+    /// first acknowledgement.
+    pub fn acknowledged(&self) -> u64 {
+        self.acknowledged.load(Ordering::Relaxed)
+    }
+
+    /// Stream and publish until shutdown (`Ok`) or until the session fails.
+    async fn session(&self, shutdown: &mut ShutdownSignal) -> Result<(), ReplicationRelayError> {
+        let mut stream = ReplicationStream::connect(&self.database_url).await?;
+        self.ensure_slot(&mut stream).await?;
+        let outbox = outbox_table(&mut stream).await?;
+        stream.start(&self.config.slot_name, PUBLICATION).await?;
+        log::info!(
+            "Streaming outbox inserts through replication slot {}",
+            self.config.slot_name
+        );
+
+        let mut relations: HashMap<u32, Relation> = HashMap::new();
+        // Events of the transaction being received.
+        let mut transaction: Option<Vec<OutboxEventRow>> = None;
+        let mut status = tokio::time::interval(self.config.status_interval);
+        status.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            let message = tokio::select! {
+                _ = shutdown.triggered() => {
+                    stream.close().await?;
+                    return Ok(());
+                }
+                _ = status.tick() => {
+                    stream.acknowledge(self.acknowledged()).await?;
+                    continue;
+                }
+                message = stream.next() => message?,
+            };
+            let data = match message {
+                ReplicationMessage::Keepalive { wal_end, reply } => {
+                    // Everything before it was received and, outside of a
+                    // transaction, published.
+                    if transaction.is_none() {
+                        self.acknowledged.fetch_max(wal_end, Ordering::Relaxed);
+                    }
+                    if reply {
+                        stream.acknowledge(self.acknowledged()).await?;
+                    }
+                    continue;
+                }
+                ReplicationMessage::XLogData { data, .. } => data,
+            };
+            match pgoutput::decode(&data)? {
+                LogicalMessage::Begin { .. } => transaction = Some(Vec::new()),
+                LogicalMessage::Relation(relation) => {
+                    relations.insert(relation.id, relation);
+                }
+                LogicalMessage::Insert { relation_id, row } if relation_id == outbox => {
+                    let relation = relations.get(&relation_id).ok_or_else(|| {
+                        ReplicationRelayError::Row(format!("unknown relation {}", relation_id))
+                    })?;
+                    transaction
+                        .get_or_insert_with(Vec::new)
+                        .push(outbox_event(relation, &row)?);
+                }
+                LogicalMessage::Insert { .. } => {}
+                LogicalMessage::Commit { end_lsn, .. } => {
+                    for event in transaction.take().unwrap_or_default() {
+                        self.publish(&event).await?;
+                    }
+                    self.acknowledged.fetch_max(end_lsn, Ordering::Relaxed);
+                    stream.acknowledge(self.acknowledged()).await?;
+                }
+                LogicalMessage::Other(_) => {}
+            }
+        }
+    }
+
+    /// Create the slot if it does not exist yet; inserts are streamed from
+    /// its creation on.
+    async fn ensure_slot(&self, stream: &mut ReplicationStream) -> Result<(), StreamError> {
+        let slot = &self.config.slot_name;
+        let existing = stream
+            .query(&format!(
+                "SELECT 1 FROM pg_replication_slots WHERE slot_name = '{}'",
+                slot
+            ))
+            .await?;
+        if existing.is_empty() {
+            stream
+                .query(&format!(
+                    "CREATE_REPLICATION_SLOT \"{}\" LOGICAL pgoutput NOEXPORT_SNAPSHOT",
+                    slot
+                ))
+                .await?;
+            log::info!("Created replication slot {}", slot);
+        }
+        Ok(())
+    }
+
+    async fn publish(&self, event: &OutboxEventRow) -> Result<(), ReplicationRelayError> {
+        let timeout = self.config.publish_timeout;
+        let result = match tokio::time::timeout(timeout, self.publisher.publish(event)).await {
+            Ok(result) => result,
+            Err(_) => Err(PublishError::Unavailable(format!(
+                "no answer within {:?}",
+                timeout
+            ))),
+        };
+        record_outcome(match result {
+            Ok(()) => Outcome::Succeeded,
+            Err(_) => Outcome::Retrying,
+        });
+        result.map_err(|error| ReplicationRelayError::Publish {
+            id: event.id,
+            error,
+        })
+    }
+}
+
+/// OID of the outbox table, looked up through the `search_path` like the
+/// service's writes do, so that a table of the same name in another schema
+/// is not taken for it.
+async fn outbox_table(stream: &mut ReplicationStream) -> Result<u32, ReplicationRelayError> {
+    let table = ORDER_OUTBOX.table();
+    let rows = stream
+        .query(&format!("SELECT to_regclass('{}')::oid", table))
+        .await?;
+    rows.first()
+        .and_then(|row| row.first().cloned().flatten())
+        .and_then(|oid| oid.parse().ok())
+        .ok_or(ReplicationRelayError::UnknownTable(table))
+}
+
+/// The outbox event inserted as `row` of `relation`.
+fn outbox_event(
+    relation: &Relation,
+    row: &[Column],
+) -> Result<OutboxEventRow, ReplicationRelayError> {
+    let text = |name: &str| match relation.value(row, name) {
+        Some(Column::Text(text)) => Ok(text.as_str()),
+        _ => Err(ReplicationRelayError::Row(format!("no {} value", name))),
+    };
+    let invalid =
+        |name: &str, e: &dyn Display| ReplicationRelayError::Row(format!("{}: {}", name, e));
+    Ok(OutboxEventRow {
+        id: text("id")?.parse().map_err(|e| invalid("id", &e))?,
+        aggregate_type: text("aggregate_type")?.to_string(),
+        aggregate_id: text("aggregate_id")?.to_string(),
+        event_type: text("event_type")?.to_string(),
+        payload: serde_json::from_str(text("payload")?).map_err(|e| invalid("payload", &e))?,
+        // ISO `DateStyle` in UTC, e.g. `2026-10-18 12:00:00.123456+00`.
+        created_at: DateTime::parse_from_str(text("created_at")?, "%Y-%m-%d %H:%M:%S%.f%#z")
+            .map_err(|e| invalid("created_at", &e))?
+            .with_timezone(&Utc),
+        sequence: text("sequence")?
+            .parse()
+            .map_err(|e| invalid("sequence", &e))?,
+        headers: serde_json::from_str(text("headers")?).map_err(|e| invalid("headers", &e))?,
+    })
+}
+
+/// Relay outbox inserts until shutdown is signalled, reconnecting after
+/// failures. Meant to run as a
+/// [`ShutdownCoordinator`](crate::shutdown::ShutdownCoordinator) worker.
+pub async fn run<P: EventPublisher>(relay: Arc<ReplicationRelay<P>>, mut shutdown: ShutdownSignal) {
+    let mut delay = RECONNECT_DELAY.min(relay.config.max_reconnect_delay);
+    loop {
+        let acknowledged = relay.acknowledged();
+        match relay.session(&mut shutdown).await {
+            Ok(()) => return,
+            Err(e) => {
+                // Back off from the start once the session got somewhere.
+                if relay.acknowledged() > acknowledged {
+                    delay = RECONNECT_DELAY.min(relay.config.max_reconnect_delay);
+                }
+                log::warn!(
+                    "Outbox replication relay stopped: {} (reconnecting in {:?})",
+                    e,
+                    delay
+                );
+            }
+        }
+        tokio::select! {
+            _ = shutdown.triggered() => return,
+            _ = tokio::time::sleep(delay) => {}
+        }
+        delay = (delay * 2).min(relay.config.max_reconnect_delay);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicU32;
+    use std::sync::Mutex;
+
+    use diesel::connection::SimpleConnection;
+    use diesel::{Connection, OptionalExtension, QueryableByName, RunQueryDsl};
+    use serde_json::json;
+
+    use super::*;
+    use crate::domain::outbox::OutboxEvent;
+    use crate::infrastructure::outbox::{OutboxWriteError, OutboxWriter};
+    use crate::test_support::setup_db_with_url;
+    use crate::DbPool;
+
+    /// Records what it publishes; fails the first `failures` attempts.
+    #[derive(Default)]
+    struct RecordingPublisher {
+        published: Mutex<Vec<OutboxEventRow>>,
+        failures: AtomicU32,
+    }
+
+    impl RecordingPublisher {
+        fn published(&self) -> Vec<OutboxEventRow> {
+            self.published.lock().expect("lock").clone()
+        }
+    }
+
+    impl EventPublisher for RecordingPublisher {
+        async fn publish(&self, event: &OutboxEventRow) -> Result<(), PublishError> {
+            let failing = self
+                .failures
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |left| {
+                    left.checked_sub(1)
+                })
+                .is_ok();
+            if failing {
+                return Err(PublishError::Unavailable("broker down".to_string()));
+            }
+            self.published.lock().expect("lock").push(event.clone());
+            Ok(())
+        }
+    }
+
+    fn relation() -> Relation {
+        Relation {
+            id: 1,
+            namespace: "public".to_string(),
+            name: "commerce_order_outbox".to_string(),
+            columns: [
+                "id",
+                "aggregate_type",
+                "aggregate_id",
+                "event_type",
+                "payload",
+                "created_at",
+                "sequence",
+                "headers",
+            ]
+            .map(str::to_string)
+            .to_vec(),
+        }
+    }
+
+    #[test]
+    fn maps_inserted_rows_to_outbox_events() {
+        let id = Uuid::new_v4();
+        let text = |value: &str| Column::Text(value.to_string());
+        let mut row = vec![
+            text(&id.to_string()),
+            text("Order"),
+            text("order-1"),
+            text("OrderPaid"),
+            text(r#"{"status": "PAID"}"#),
+            text("2026-10-18 12:00:00.25+00"),
+            text("2"),
+            text(r#"{"tenant": "acme"}"#),
+        ];
+
+        let event = outbox_event(&relation(), &row).expect("event");
+        assert_eq!(event.id, id);
+        assert_eq!(event.aggregate_type, "Order");
+        assert_eq!(event.payload, json!({ "status": "PAID" }));
+        assert_eq!(
+            event.created_at.to_rfc3339(),
+            "2026-10-18T12:00:00.250+00:00"
+        );
+        assert_eq!(event.sequence, 2);
+        assert_eq!(event.headers, json!({ "tenant": "acme" }));
+
+        row[6] = Column::Null;
+        assert!(matches!(
+            outbox_event(&relation(), &row),
+            Err(ReplicationRelayError::Row(reason)) if reason == "no sequence value"
+        ));
+    }
+
+    fn event(aggregate_id: &str, event_type: &str) -> OutboxEvent {
+        OutboxEvent::new(
+            "Order",
+            aggregate_id,
+            event_type,
+            json!({ "id": aggregate_id }),
+        )
+    }
+
+    fn config(slot_name: &str) -> ReplicationRelayConfig {
+        ReplicationRelayConfig {
+            slot_name: slot_name.to_string(),
+            status_interval: Duration::from_millis(100),
+            publish_timeout: Duration::from_secs(5),
+            max_reconnect_delay: Duration::from_millis(50),
+        }
+    }
+
+    /// Confirmed flush position of `slot`, once it exists.
+    fn confirmed_flush(pool: &DbPool, slot: &str) -> Option<i64> {
+        #[derive(QueryableByName)]
+        struct Position {
+            #[diesel(sql_type = diesel::sql_types::BigInt)]
+            lsn: i64,
+        }
+        diesel::sql_query(
+            "SELECT pg_wal_lsn_diff(confirmed_flush_lsn, '0/0')::bigint AS lsn \
+             FROM pg_replication_slots WHERE slot_name = $1",
+        )
+        .bind::<diesel::sql_types::Text, _>(slot)
+        .get_result::<Position>(&mut pool.get().expect("connection"))
+        .optional()
+        .expect("slot query")
+        .map(|position| position.lsn)
+    }
+
+    async fn eventually(what: &str, condition: impl Fn() -> bool) {
+        for _ in 0..200 {
+            if condition() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(25)).await;
+        }
+        panic!("timed out waiting for {}", what);
+    }
+
+    fn drop_slot(pool: &DbPool, slot: &str) {
+        diesel::sql_query("SELECT pg_drop_replication_slot($1)")
+            .bind::<diesel::sql_types::Text, _>(slot)
+            .execute(&mut pool.get().expect("connection"))
+            .expect("drop slot");
+    }
+
+    /// Stop the relay, wait for its slot to be released and drop it.
+    async fn stop_relay(
+        stop: tokio::sync::watch::Sender<bool>,
+        worker: actix_web::rt::task::JoinHandle<()>,
+        pool: &DbPool,
+        slot: &str,
+    ) {
+        stop.send(true).expect("relay running");
+        tokio::time::timeout(Duration::from_secs(5), worker)
+            .await
+            .expect("relay stopped")
+            .expect("relay did not panic");
+        #[derive(QueryableByName)]
+        struct Active {
+            #[diesel(sql_type = diesel::sql_types::Bool)]
+            active: bool,
+        }
+        eventually("the slot to be released", || {
+            diesel::sql_query("SELECT active FROM pg_replication_slots WHERE slot_name = $1")
+                .bind::<diesel::sql_types::Text, _>(slot)
+                .get_result::<Active>(&mut pool.get().expect("connection"))
+                .map(|slot| !slot.active)
+                .unwrap_or(true)
+        })
+        .await;
+        drop_slot(pool, slot);
+    }
+
+    #[actix_web::test]
+    async fn stream_negotiates_tls_as_sslmode_asks() {
+        // The test server does not offer TLS.
+        let (_container, _pool, database_url) = setup_db_with_url().await;
+
+        let mut stream = ReplicationStream::connect(&format!("{}?sslmode=prefer", database_url))
+            .await
+            .expect("falls back to a plain connection");
+        assert_eq!(
+            stream.query("SELECT 1").await.expect("query"),
+            [[Some("1".to_string())]]
+        );
+        for mode in ["require", "verify-ca", "verify-full"] {
+            let required =
+                ReplicationStream::connect(&format!("{}?sslmode={}", database_url, mode)).await;
+            assert!(matches!(required, Err(StreamError::Tls(_))), "{}", mode);
+        }
+    }
+
+    #[actix_web::test]
+    async fn publishes_committed_events_in_order_then_acknowledges_them() {
+        let (_container, pool, url) = setup_db_with_url().await;
+        let slot = format!("relay_{}", Uuid::new_v4().simple());
+        let relay = Arc::new(ReplicationRelay::new(
+            url,
+            RecordingPublisher::default(),
+            config(&slot),
+        ));
+        let (stop, signal) = ShutdownSignal::for_test();
+        let worker = actix_web::rt::spawn(run(Arc::clone(&relay), signal));
+        eventually("the slot", || confirmed_flush(&pool, &slot).is_some()).await;
+
+        let mut conn = pool.get().expect("connection");
+        let mut appended = conn
+            .transaction(|conn| {
+                Ok::<_, OutboxWriteError>(vec![
+                    ORDER_OUTBOX.append(conn, &event("a", "OrderCreated"))?,
+                    ORDER_OUTBOX.append(conn, &event("a", "OrderPaid"))?,
+                ])
+            })
+            .expect("first transaction");
+        appended.push(
+            ORDER_OUTBOX
+                .append(
+                    &mut conn,
+                    &event("b", "OrderCreated").with_header("tenant", "acme"),
+                )
+                .expect("second transaction"),
+        );
+
+        eventually("three events", || relay.publisher.published().len() == 3).await;
+        let published = relay.publisher.published();
+        for (published, appended) in published.iter().zip(&appended) {
+            assert_eq!(published.id, appended.id);
+            assert_eq!(published.event_type, appended.event_type);
+            assert_eq!(published.payload, appended.payload);
+            assert_eq!(published.created_at, appended.created_at);
+            assert_eq!(published.sequence, appended.sequence);
+            assert_eq!(published.headers, appended.headers);
+        }
+        eventually("the acknowledgement", || {
+            confirmed_flush(&pool, &slot).unwrap_or_default() as u64 >= relay.acknowledged()
+                && relay.acknowledged() > 0
+        })
+        .await;
+
+        stop_relay(stop, worker, &pool, &slot).await;
+    }
+
+    #[actix_web::test]
+    async fn ignores_tables_of_the_same_name_in_other_schemas() {
+        let (_container, pool, url) = setup_db_with_url().await;
+        let mut conn = pool.get().expect("connection");
+        conn.batch_execute(
+            "CREATE SCHEMA archive; \
+             CREATE TABLE archive.commerce_order_outbox (LIKE commerce_order_outbox INCLUDING ALL); \
+             ALTER PUBLICATION commerce_order_outbox_relay ADD TABLE archive.commerce_order_outbox;",
+        )
+        .expect("create the other table");
+        let slot = format!("relay_{}", Uuid::new_v4().simple());
+        let relay = Arc::new(ReplicationRelay::new(
+            url,
+            RecordingPublisher::default(),
+            config(&slot),
+        ));
+        let (stop, signal) = ShutdownSignal::for_test();
+        let worker = actix_web::rt::spawn(run(Arc::clone(&relay), signal));
+        eventually("the slot", || confirmed_flush(&pool, &slot).is_some()).await;
+
+        OutboxWriter::new("archive.commerce_order_outbox")
+            .with_headers_column("headers")
+            .append(&mut conn, &event("a", "OrderCreated"))
+            .expect("append elsewhere");
+        let created = ORDER_OUTBOX
+            .append(&mut conn, &event("b", "OrderCreated"))
+            .expect("append");
+
+        eventually("the outbox event", || {
+            !relay.publisher.published().is_empty()
+        })
+        .await;
+        let ids: Vec<Uuid> = relay.publisher.published().iter().map(|e| e.id).collect();
+        assert_eq!(ids, [created.id]);
+
+        stop_relay(stop, worker, &pool, &slot).await;
+    }
+
+    #[actix_web::test]
+    async fn redelivers_a_transaction_until_its_events_are_published() {
+        let (_container, pool, url) = setup_db_with_url().await;
+        let slot = format!("relay_{}", Uuid::new_v4().simple());
+        let publisher = RecordingPublisher {
+            failures: AtomicU32::new(2),
+            ..RecordingPublisher::default()
+        };
+        let relay = Arc::new(ReplicationRelay::new(url, publisher, config(&slot)));
+        let (stop, signal) = ShutdownSignal::for_test();
+        let worker = actix_web::rt::spawn(run(Arc::clone(&relay), signal));
+        eventually("the slot", || confirmed_flush(&pool, &slot).is_some()).await;
+        let before = confirmed_flush(&pool, &slot).expect("slot");
+
+        let mut conn = pool.get().expect("connection");
+        let created = ORDER_OUTBOX
+            .append(&mut conn, &event("a", "OrderCreated"))
+            .expect("append");
+        let paid = ORDER_OUTBOX
+            .append(&mut conn, &event("a", "OrderPaid"))
+            .expect("append");
+
+        eventually("both events", || relay.publisher.published().len() == 2).await;
+        let ids: Vec<Uuid> = relay.publisher.published().iter().map(|e| e.id).collect();
+        assert_eq!(ids, [created.id, paid.id]);
+        assert_eq!(relay.publisher.failures.load(Ordering::Relaxed), 0);
+        eventually("the acknowledgement", || {
+            confirmed_flush(&pool, &slot).expect("slot") > before
+        })
+        .await;
+
+        stop_relay(stop, worker, &pool, &slot).await;
+    }
+}
@@ -0,0 +1,335 @@
+//! Decoding of the `pgoutput` logical decoding plugin's messages (protocol
+//! version 1), as carried by the `XLogData` of a replication stream.
+//!
+//! Only what the relay needs is decoded in full: transaction boundaries,
+//! relations and inserted rows. Other messages are reported by their tag.
+
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum DecodeError {
+    #[error("message ends early")]
+    Truncated,
+
+    #[error("unexpected {0:?} in {1}")]
+    Unexpected(char, &'static str),
+
+    #[error("text is not UTF-8")]
+    NotUtf8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LogicalMessage {
+    Begin {
+        /// LSN of the transaction's commit record.
+        final_lsn: u64,
+        xid: u32,
+    },
+    Commit {
+        commit_lsn: u64,
+        /// LSN just past the commit; the position to acknowledge.
+        end_lsn: u64,
+    },
+    Relation(Relation),
+    Insert {
+        relation_id: u32,
+        row: Vec<Column>,
+    },
+    /// `Update`, `Delete`, `Truncate`, `Type`, `Origin`, ...
+    Other(char),
+}
+
+/// A table as described ahead of its first change in a session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Relation {
+    pub id: u32,
+    pub namespace: String,
+    pub name: String,
+    pub columns: Vec<String>,
+}
+
+impl Relation {
+    /// Value of column `name` in `row`, a row of this relation.
+    pub fn value<'a>(&self, row: &'a [Column], name: &str) -> Option<&'a Column> {
+        let index = self.columns.iter().position(|column| column == name)?;
+        row.get(index)
+    }
+}
+
+/// A column value, in the text format of its type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Column {
+    Null,
+    /// An unchanged TOASTed value, which is not sent.
+    Unchanged,
+    Text(String),
+}
+
+/// Decode one `pgoutput` message.
+pub fn decode(message: &[u8]) -> Result<LogicalMessage, DecodeError> {
+    let mut reader = Reader(message);
+    let tag = reader.u8()? as char;
+    Ok(match tag {
+        'B' => {
+            let final_lsn = reader.u64()?;
+            let _commit_time = reader.u64()?;
+            let xid = reader.u32()?;
+            LogicalMessage::Begin { final_lsn, xid }
+        }
+        'C' => {
+            let _flags = reader.u8()?;
+            let commit_lsn = reader.u64()?;
+            let end_lsn = reader.u64()?;
+            LogicalMessage::Commit {
+                commit_lsn,
+                end_lsn,
+            }
+        }
+        'R' => {
+            let id = reader.u32()?;
+            let namespace = reader.string()?;
+            let name = reader.string()?;
+            let _replica_identity = reader.u8()?;
+            let count = reader.u16()?;
+            let mut columns = Vec::with_capacity(count.into());
+            for _ in 0..count {
+                let _flags = reader.u8()?;
+                columns.push(reader.string()?);
+                let _type_id = reader.u32()?;
+                let _type_modifier = reader.u32()?;
+            }
+            LogicalMessage::Relation(Relation {
+                id,
+                namespace,
+                name,
+                columns,
+            })
+        }
+        'I' => {
+            let relation_id = reader.u32()?;
+            match reader.u8()? as char {
+                'N' => {}
+                other => return Err(DecodeError::Unexpected(other, "insert")),
+            }
+            LogicalMessage::Insert {
+                relation_id,
+                row: reader.row()?,
+            }
+        }
+        other => LogicalMessage::Other(other),
+    })
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+        if self.0.len() < len {
+            return Err(DecodeError::Truncated);
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, DecodeError> {
+        Ok(u16::from_be_bytes(
+            self.take(2)?.try_into().expect("2 bytes"),
+        ))
+    }
+
+    fn u32(&mut self) -> Result<u32, DecodeError> {
+        Ok(u32::from_be_bytes(
+            self.take(4)?.try_into().expect("4 bytes"),
+        ))
+    }
+
+    fn u64(&mut self) -> Result<u64, DecodeError> {
+        Ok(u64::from_be_bytes(
+            self.take(8)?.try_into().expect("8 bytes"),
+        ))
+    }
+
+    /// A NUL-terminated string.
+    fn string(&mut self) -> Result<String, DecodeError> {
+        let len = self
+            .0
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or(DecodeError::Truncated)?;
+        let text = text(self.take(len)?)?;
+        self.take(1)?;
+        Ok(text)
+    }
+
+    /// `TupleData`.
+    fn row(&mut self) -> Result<Vec<Column>, DecodeError> {
+        let count = self.u16()?;
+        let mut row = Vec::with_capacity(count.into());
+        for _ in 0..count {
+            row.push(match self.u8()? as char {
+                'n' => Column::Null,
+                'u' => Column::Unchanged,
+                't' => {
+                    let len = self.u32()? as usize;
+                    Column::Text(text(self.take(len)?)?)
+                }
+                other => return Err(DecodeError::Unexpected(other, "column")),
+            });
+        }
+        Ok(row)
+    }
+}
+
+fn text(bytes: &[u8]) -> Result<String, DecodeError> {
+    String::from_utf8(bytes.to_vec()).map_err(|_| DecodeError::NotUtf8)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Encodes messages the way `pgoutput` does.
+    #[derive(Default)]
+    pub(crate) struct Encoder(pub(crate) Vec<u8>);
+
+    impl Encoder {
+        pub(crate) fn new(tag: char) -> Self {
+            Self(vec![tag as u8])
+        }
+
+        pub(crate) fn u8(mut self, value: u8) -> Self {
+            self.0.push(value);
+            self
+        }
+
+        pub(crate) fn u16(mut self, value: u16) -> Self {
+            self.0.extend(value.to_be_bytes());
+            self
+        }
+
+        pub(crate) fn u32(mut self, value: u32) -> Self {
+            self.0.extend(value.to_be_bytes());
+            self
+        }
+
+        pub(crate) fn u64(mut self, value: u64) -> Self {
+            self.0.extend(value.to_be_bytes());
+            self
+        }
+
+        pub(crate) fn string(mut self, value: &str) -> Self {
+            self.0.extend(value.as_bytes());
+            self.0.push(0);
+            self
+        }
+
+        pub(crate) fn relation(id: u32, namespace: &str, name: &str, columns: &[&str]) -> Self {
+            let mut encoder = Self::new('R')
+                .u32(id)
+                .string(namespace)
+                .string(name)
+                .u8(b'd')
+                .u16(columns.len() as u16);
+            for column in columns {
+                encoder = encoder.u8(0).string(column).u32(25).u32(u32::MAX);
+            }
+            encoder
+        }
+
+        pub(crate) fn insert(relation_id: u32, values: &[Option<&str>]) -> Self {
+            let mut encoder = Self::new('I')
+                .u32(relation_id)
+                .u8(b'N')
+                .u16(values.len() as u16);
+            for value in values {
+                encoder = match value {
+                    None => encoder.u8(b'n'),
+                    Some(text) => {
+                        let mut encoder = encoder.u8(b't').u32(text.len() as u32);
+                        encoder.0.extend(text.as_bytes());
+                        encoder
+                    }
+                };
+            }
+            encoder
+        }
+    }
+
+    #[test]
+    fn decodes_transaction_boundaries() {
+        let begin = Encoder::new('B').u64(0x1_0000_0028).u64(0).u32(742);
+        assert_eq!(
+            decode(&begin.0),
+            Ok(LogicalMessage::Begin {
+                final_lsn: 0x1_0000_0028,
+                xid: 742
+            })
+        );
+        let commit = Encoder::new('C')
+            .u8(0)
+            .u64(0x1_0000_0028)
+            .u64(0x1_0000_0058)
+            .u64(0);
+        assert_eq!(
+            decode(&commit.0),
+            Ok(LogicalMessage::Commit {
+                commit_lsn: 0x1_0000_0028,
+                end_lsn: 0x1_0000_0058
+            })
+        );
+    }
+
+    #[test]
+    fn decodes_relations_and_inserted_rows() {
+        let relation = Encoder::relation(16401, "public", "outbox", &["id", "note", "extra"]);
+        let LogicalMessage::Relation(relation) = decode(&relation.0).expect("relation") else {
+            panic!("not a relation");
+        };
+        assert_eq!(relation.namespace, "public");
+        assert_eq!(relation.name, "outbox");
+        assert_eq!(relation.columns, ["id", "note", "extra"]);
+
+        let insert = Encoder::insert(16401, &[Some("1"), None, Some("é")]);
+        let LogicalMessage::Insert { relation_id, row } = decode(&insert.0).expect("insert") else {
+            panic!("not an insert");
+        };
+        assert_eq!(relation_id, 16401);
+        assert_eq!(
+            row,
+            [
+                Column::Text("1".to_string()),
+                Column::Null,
+                Column::Text("é".to_string()),
+            ]
+        );
+        assert_eq!(relation.value(&row, "extra"), Some(&row[2]));
+        assert_eq!(relation.value(&row, "missing"), None);
+    }
+
+    #[test]
+    fn decodes_unchanged_toasted_values() {
+        let insert = Encoder::new('I').u32(1).u8(b'N').u16(1).u8(b'u');
+        assert_eq!(
+            decode(&insert.0),
+            Ok(LogicalMessage::Insert {
+                relation_id: 1,
+                row: vec![Column::Unchanged],
+            })
+        );
+    }
+
+    #[test]
+    fn reports_other_and_malformed_messages() {
+        assert_eq!(decode(b"T\0\0\0\x01"), Ok(LogicalMessage::Other('T')));
+        assert_eq!(decode(b"B\0\0"), Err(DecodeError::Truncated));
+        assert_eq!(
+            decode(&Encoder::new('I').u32(1).u8(b'O').0),
+            Err(DecodeError::Unexpected('O', "insert"))
+        );
+    }
+}
@@ -0,0 +1,348 @@
+//! A logical replication connection: the frontend side of Postgres'
+//! streaming replication protocol over a plain TCP connection.
+//!
+//! `tokio-postgres` cannot enter replication mode, so the connection speaks
+//! the protocol itself, with `postgres-protocol` for message framing and
+//! authentication (cleartext, MD5 or SCRAM-SHA-256). TLS follows `sslmode`
+//! as described in [`pg_tls`](crate::pg_tls).
+
+use std::time::SystemTime;
+
+use bytes::{Buf, BufMut, BytesMut};
+use fallible_iterator::FallibleIterator;
+use postgres_protocol::authentication::md5_hash;
+use postgres_protocol::authentication::sasl::{ChannelBinding, ScramSha256, SCRAM_SHA_256};
+use postgres_protocol::message::backend::{ErrorResponseBody, Message};
+use postgres_protocol::message::frontend;
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio_postgres::config::{Host, SslMode};
+
+use crate::pg_tls::{self, MakeTlsConnector, PgTls, PgTlsError};
+
+/// Seconds from the Unix epoch to the Postgres epoch (2000-01-01).
+const POSTGRES_EPOCH: u64 = 946_684_800;
+
+#[derive(Debug, Error)]
+pub enum StreamError {
+    #[error("invalid database URL: {0}")]
+    Url(String),
+
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("TLS error: {0}")]
+    Tls(String),
+
+    #[error("{severity} {code}: {message}")]
+    Server {
+        severity: String,
+        code: String,
+        message: String,
+    },
+
+    #[error("protocol error: {0}")]
+    Protocol(String),
+}
+
+/// What the server sends while streaming.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplicationMessage {
+    /// WAL data: one `pgoutput` message starting at `start_lsn`.
+    XLogData { start_lsn: u64, data: bytes::Bytes },
+    /// The server's position; `reply` asks for a status update right away.
+    Keepalive { wal_end: u64, reply: bool },
+}
+
+/// A plain or TLS connection.
+trait Socket: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<S: AsyncRead + AsyncWrite + Unpin + Send> Socket for S {}
+
+pub struct ReplicationStream {
+    socket: Box<dyn Socket>,
+    read: BytesMut,
+    write: BytesMut,
+}
+
+enum Received {
+    CopyBothResponse,
+    Message(Message),
+}
+
+/// Ask the server for TLS unless `mode` disables it, and fall back to a
+/// plain connection if it declines, unless `mode` requires TLS. The server
+/// certificate is verified as `tls` asks.
+async fn negotiate_tls(
+    mut socket: TcpStream,
+    host: &str,
+    mode: SslMode,
+    tls: &PgTls,
+) -> Result<Box<dyn Socket>, StreamError> {
+    if mode == SslMode::Disable {
+        return Ok(Box::new(socket));
+    }
+    let mut request = BytesMut::new();
+    frontend::ssl_request(&mut request);
+    socket.write_all(&request).await?;
+    match socket.read_u8().await? {
+        b'S' => {
+            let tls = MakeTlsConnector::new(tls).map_err(|e| StreamError::Tls(e.to_string()))?;
+            let socket = tls
+                .connect(host, socket)
+                .await
+                .map_err(|e| StreamError::Tls(e.to_string()))?;
+            Ok(Box::new(socket))
+        }
+        b'N' if mode == SslMode::Require => Err(StreamError::Tls(
+            "sslmode requires TLS but the server does not support it".to_string(),
+        )),
+        b'N' => Ok(Box::new(socket)),
+        _ => Err(unexpected("answer to the TLS request")),
+    }
+}
+
+impl ReplicationStream {
+    /// Open a logical replication connection (`replication=database`) to the
+    /// database of `database_url`.
+    pub async fn connect(database_url: &str) -> Result<Self, StreamError> {
+        let (config, tls) = pg_tls::parse(database_url).map_err(|e| match e {
+            PgTlsError::Url(e) => StreamError::Url(e),
+            e => StreamError::Tls(e.to_string()),
+        })?;
+        let host = match config.get_hosts().first() {
+            Some(Host::Tcp(host)) => host.clone(),
+            Some(_) => return Err(StreamError::Url("only TCP hosts are supported".to_string())),
+            None => "localhost".to_string(),
+        };
+        let port = config.get_ports().first().copied().unwrap_or(5432);
+        let user = config
+            .get_user()
+            .ok_or_else(|| StreamError::Url("no user".to_string()))?;
+        let dbname = config.get_dbname().unwrap_or(user);
+        let password = config.get_password().unwrap_or_default();
+
+        let socket = TcpStream::connect((host.as_str(), port)).await?;
+        let mut stream = Self {
+            socket: negotiate_tls(socket, &host, config.get_ssl_mode(), &tls).await?,
+            read: BytesMut::with_capacity(8192),
+            write: BytesMut::new(),
+        };
+        frontend::startup_message(
+            [
+                ("user", user),
+                ("database", dbname),
+                ("replication", "database"),
+                ("application_name", "order-service-outbox-relay"),
+                ("client_encoding", "UTF8"),
+                // Column values arrive in their text format; these make it
+                // the one `OutboxEventRow` parses.
+                ("DateStyle", "ISO"),
+                ("TimeZone", "UTC"),
+            ],
+            &mut stream.write,
+        )?;
+        stream.flush().await?;
+        stream.authenticate(user, password).await?;
+        Ok(stream)
+    }
+
+    async fn authenticate(&mut self, user: &str, password: &[u8]) -> Result<(), StreamError> {
+        let mut scram = None;
+        loop {
+            match self.message().await? {
+                Message::AuthenticationOk => break,
+                Message::AuthenticationCleartextPassword => {
+                    frontend::password_message(password, &mut self.write)?;
+                }
+                Message::AuthenticationMd5Password(body) => {
+                    let hash = md5_hash(user.as_bytes(), password, body.salt());
+                    frontend::password_message(hash.as_bytes(), &mut self.write)?;
+                }
+                Message::AuthenticationSasl(body) => {
+                    let mechanisms: Vec<&str> = body.mechanisms().collect()?;
+                    if !mechanisms.contains(&SCRAM_SHA_256) {
+                        return Err(StreamError::Protocol(format!(
+                            "unsupported SASL mechanisms {:?}",
+                            mechanisms
+                        )));
+                    }
+                    let client = ScramSha256::new(password, ChannelBinding::unsupported());
+                    frontend::sasl_initial_response(
+                        SCRAM_SHA_256,
+                        client.message(),
+                        &mut self.write,
+                    )?;
+                    scram = Some(client);
+                }
+                Message::AuthenticationSaslContinue(body) => {
+                    let client = scram.as_mut().ok_or_else(|| unexpected("SASL continue"))?;
+                    client.update(body.data())?;
+                    frontend::sasl_response(client.message(), &mut self.write)?;
+                }
+                Message::AuthenticationSaslFinal(body) => {
+                    let client = scram.as_mut().ok_or_else(|| unexpected("SASL final"))?;
+                    client.finish(body.data())?;
+                    continue;
+                }
+                _ => return Err(unexpected("authentication message")),
+            }
+            self.flush().await?;
+        }
+        // Parameter statuses and the backend key, up to the first query.
+        loop {
+            if let Message::ReadyForQuery(_) = self.message().await? {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Run `sql` (one statement) and return its rows as text.
+    pub async fn query(&mut self, sql: &str) -> Result<Vec<Vec<Option<String>>>, StreamError> {
+        frontend::query(sql, &mut self.write)?;
+        self.flush().await?;
+        let mut rows = Vec::new();
+        loop {
+            match self.message().await? {
+                Message::DataRow(row) => {
+                    let buffer = row.buffer();
+                    let values = row
+                        .ranges()
+                        .map(|range| {
+                            Ok(range
+                                .map(|range| String::from_utf8_lossy(&buffer[range]).into_owned()))
+                        })
+                        .collect()?;
+                    rows.push(values);
+                }
+                Message::ReadyForQuery(_) => return Ok(rows),
+                _ => {}
+            }
+        }
+    }
+
+    /// Stream the changes of `publication` from the slot's confirmed position.
+    pub async fn start(&mut self, slot: &str, publication: &str) -> Result<(), StreamError> {
+        let command = format!(
+            "START_REPLICATION SLOT \"{}\" LOGICAL 0/0 \
+             (proto_version '1', publication_names '\"{}\"')",
+            slot, publication
+        );
+        frontend::query(&command, &mut self.write)?;
+        self.flush().await?;
+        match self.receive().await? {
+            Received::CopyBothResponse => Ok(()),
+            Received::Message(_) => Err(unexpected("answer to START_REPLICATION")),
+        }
+    }
+
+    /// The next message of a started stream. Cancel safe.
+    pub async fn next(&mut self) -> Result<ReplicationMessage, StreamError> {
+        let Message::CopyData(body) = self.message().await? else {
+            return Err(unexpected("message while streaming"));
+        };
+        let mut data = body.into_bytes();
+        match data.first() {
+            Some(b'w') if data.len() >= 25 => {
+                data.advance(1);
+                let start_lsn = data.get_u64();
+                let _wal_end = data.get_u64();
+                let _send_time = data.get_i64();
+                Ok(ReplicationMessage::XLogData { start_lsn, data })
+            }
+            Some(b'k') if data.len() >= 18 => {
+                data.advance(1);
+                let wal_end = data.get_u64();
+                let _send_time = data.get_i64();
+                let reply = data.get_u8() == 1;
+                Ok(ReplicationMessage::Keepalive { wal_end, reply })
+            }
+            _ => Err(unexpected("copy data")),
+        }
+    }
+
+    /// Tell the server that everything before `lsn` has been processed, so
+    /// the slot may advance to it.
+    pub async fn acknowledge(&mut self, lsn: u64) -> Result<(), StreamError> {
+        let micros = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|since| since.as_micros() as i64 - (POSTGRES_EPOCH * 1_000_000) as i64)
+            .unwrap_or_default();
+        let mut update = BytesMut::with_capacity(34);
+        update.put_u8(b'r');
+        // Written, flushed and applied.
+        update.put_u64(lsn);
+        update.put_u64(lsn);
+        update.put_u64(lsn);
+        update.put_i64(micros);
+        update.put_u8(0);
+        frontend::CopyData::new(update)?.write(&mut self.write);
+        self.flush().await
+    }
+
+    /// End the session.
+    pub async fn close(mut self) -> Result<(), StreamError> {
+        frontend::terminate(&mut self.write);
+        self.flush().await
+    }
+
+    async fn flush(&mut self) -> Result<(), StreamError> {
+        self.socket.write_all_buf(&mut self.write).await?;
+        Ok(())
+    }
+
+    /// The next message, server errors turned into [`StreamError::Server`].
+    async fn message(&mut self) -> Result<Message, StreamError> {
+        match self.receive().await? {
+            Received::Message(message) => Ok(message),
+            Received::CopyBothResponse => Err(unexpected("CopyBothResponse")),
+        }
+    }
+
+    async fn receive(&mut self) -> Result<Received, StreamError> {
+        loop {
+            // `postgres-protocol` does not know CopyBothResponse.
+            if self.read.len() >= 5 && self.read[0] == b'W' {
+                let len = u32::from_be_bytes(self.read[1..5].try_into().expect("4 bytes"));
+                if self.read.len() > len as usize {
+                    self.read.advance(len as usize + 1);
+                    return Ok(Received::CopyBothResponse);
+                }
+            } else {
+                match Message::parse(&mut self.read)? {
+                    Some(Message::ErrorResponse(body)) => return Err(server_error(&body)),
+                    Some(Message::NoticeResponse(_)) => continue,
+                    Some(message) => return Ok(Received::Message(message)),
+                    None => {}
+                }
+            }
+            if self.socket.read_buf(&mut self.read).await? == 0 {
+                return Err(StreamError::Io(std::io::ErrorKind::UnexpectedEof.into()));
+            }
+        }
+    }
+}
+
+fn unexpected(what: &str) -> StreamError {
+    StreamError::Protocol(format!("unexpected {}", what))
+}
+
+fn server_error(body: &ErrorResponseBody) -> StreamError {
+    let (mut severity, mut code, mut message) = (String::new(), String::new(), String::new());
+    let mut fields = body.fields();
+    while let Ok(Some(field)) = fields.next() {
+        let value = String::from_utf8_lossy(field.value_bytes()).into_owned();
+        match field.type_() {
+            b'S' => severity = value,
+            b'C' => code = value,
+            b'M' => message = value,
+            _ => {}
+        }
+    }
+    StreamError::Server {
+        severity,
+        code,
+        message,
+    }
+}
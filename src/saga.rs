@@ -0,0 +1,469 @@
+//! Order placement saga workers.
+//!
+//! Creating an order starts a saga (see [`crate::domain::saga`]) that sends
+//! commands to the inventory and payments services through the outbox.
+//! [`SagaReplyHandler`] applies their replies through
+//! [`SagaService::handle_reply`], driven by
+//! [`inbox::consume`](crate::inbox::consume) like the
+//! [`PaymentHandler`](crate::payments::PaymentHandler), and
+//! [`expire_timeouts`] periodically times out steps whose deadline has
+//! passed.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::Deserialize;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::application::saga_service::{SagaApplication, SagaService};
+use crate::domain::errors::DomainError;
+use crate::domain::order::SourceMessage;
+use crate::domain::ports::{SagaRepository, UnitOfWork};
+use crate::domain::saga::{
+    SagaReply, SagaTimeouts, INVENTORY_RELEASED, INVENTORY_RESERVATION_FAILED, INVENTORY_RESERVED,
+    PAYMENT_AUTHORIZATION_FAILED, PAYMENT_AUTHORIZED,
+};
+use crate::inbox::{InboundMessage, InboxError, InboxOutcome, MessageHandler};
+use crate::shutdown::ShutdownSignal;
+
+/// Saga settings (`SAGA_*` variables).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SagaConfig {
+    pub bootstrap_servers: String,
+    /// Topic the inventory and payments services reply on.
+    pub reply_topic: String,
+    pub group_id: String,
+    pub timeouts: SagaTimeouts,
+    /// How often overdue steps are looked for.
+    pub timeout_check_interval: Duration,
+}
+
+impl SagaConfig {
+    pub fn new(bootstrap_servers: impl Into<String>) -> Self {
+        Self {
+            bootstrap_servers: bootstrap_servers.into(),
+            reply_topic: "public.commerce.order-saga-reply.c2.v1".to_string(),
+            group_id: "order-service-saga".to_string(),
+            timeouts: SagaTimeouts::default(),
+            timeout_check_interval: Duration::from_secs(5),
+        }
+    }
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SagaReplyError {
+    #[error("unsupported message type {0}")]
+    UnsupportedType(String),
+
+    #[error("invalid payload: {0}")]
+    InvalidPayload(String),
+}
+
+/// A reply addressed to the saga of `order_id`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SagaReplyMessage {
+    pub order_id: Uuid,
+    pub reply: SagaReply,
+}
+
+#[derive(Deserialize)]
+struct ReplyPayload {
+    order_id: Uuid,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+impl SagaReplyMessage {
+    pub fn from_message(message: &InboundMessage) -> Result<Self, SagaReplyError> {
+        let payload: ReplyPayload = serde_json::from_value(message.payload.clone())
+            .map_err(|e| SagaReplyError::InvalidPayload(e.to_string()))?;
+        let reply = SagaReply::from_message_type(&message.message_type, payload.reason)
+            .ok_or_else(|| SagaReplyError::UnsupportedType(message.message_type.clone()))?;
+        Ok(Self {
+            order_id: payload.order_id,
+            reply,
+        })
+    }
+}
+
+/// Applies replies to the sagas they are addressed to.
+///
+/// Messages that can never be applied (undecodable, unknown saga, a reply the
+/// saga is not waiting for) are logged and reported unhandled, so they are
+/// acknowledged and do not block the partition.
+pub struct SagaReplyHandler<S, U> {
+    service: Arc<SagaService<S, U>>,
+}
+
+impl<S, U> SagaReplyHandler<S, U> {
+    pub fn new(service: Arc<SagaService<S, U>>) -> Self {
+        Self { service }
+    }
+}
+
+impl<S: SagaRepository, U: UnitOfWork> MessageHandler for SagaReplyHandler<S, U> {
+    fn message_types(&self) -> &[&'static str] {
+        &[
+            INVENTORY_RESERVED,
+            INVENTORY_RESERVATION_FAILED,
+            INVENTORY_RELEASED,
+            PAYMENT_AUTHORIZED,
+            PAYMENT_AUTHORIZATION_FAILED,
+        ]
+    }
+
+    fn handle(&self, message: &InboundMessage) -> Result<InboxOutcome, InboxError> {
+        let reply = match SagaReplyMessage::from_message(message) {
+            Ok(reply) => reply,
+            Err(e) => {
+                log::warn!("Skipping saga reply {}: {}", message.id, e);
+                return Ok(InboxOutcome::Unhandled);
+            }
+        };
+        let source = SourceMessage {
+            id: message.id.clone(),
+            message_type: message.message_type.clone(),
+        };
+        match self
+            .service
+            .handle_reply(reply.order_id, &reply.reply, &source)
+        {
+            Ok(applied) => {
+                log_application(message, &reply, &applied);
+                Ok(match applied {
+                    SagaApplication::Advanced(_) => InboxOutcome::Processed,
+                    SagaApplication::AlreadyApplied => InboxOutcome::Duplicate,
+                    SagaApplication::Ignored { .. } => InboxOutcome::Unhandled,
+                })
+            }
+            Err(DomainError::Internal(e)) => Err(InboxError::Handler(e)),
+            Err(e) => {
+                log::warn!("Skipping saga reply {}: {}", message.id, e);
+                Ok(InboxOutcome::Unhandled)
+            }
+        }
+    }
+}
+
+/// Time out overdue saga steps every `interval` until shutdown is signalled.
+pub async fn expire_timeouts<S: SagaRepository, U: UnitOfWork>(
+    service: Arc<SagaService<S, U>>,
+    interval: Duration,
+    mut shutdown: ShutdownSignal,
+) {
+    loop {
+        let service = Arc::clone(&service);
+        match tokio::task::spawn_blocking(move || service.expire_overdue()).await {
+            Ok(Ok(0)) => {}
+            Ok(Ok(expired)) => log::info!("Timed out {} saga step(s)", expired),
+            Ok(Err(e)) => log::error!("Failed to time out saga steps: {}", e),
+            Err(e) => log::error!("Saga timeout check panicked: {}", e),
+        }
+        tokio::select! {
+            _ = shutdown.triggered() => return,
+            _ = tokio::time::sleep(interval) => {}
+        }
+    }
+}
+
+fn log_application(message: &InboundMessage, reply: &SagaReplyMessage, applied: &SagaApplication) {
+    match applied {
+        SagaApplication::Advanced(state) => log::info!(
+            "Saga of order {} is now {} after {} {}",
+            reply.order_id,
+            state.as_str(),
+            message.message_type,
+            message.id
+        ),
+        SagaApplication::AlreadyApplied => log::debug!(
+            "{} {} was already applied to the saga of order {}",
+            message.message_type,
+            message.id,
+            reply.order_id
+        ),
+        SagaApplication::Ignored { state } => log::warn!(
+            "Ignoring {} {}: saga of order {} is {}",
+            message.message_type,
+            message.id,
+            reply.order_id,
+            state.as_str()
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bigdecimal::BigDecimal;
+    use diesel::prelude::*;
+    use serde_json::json;
+
+    use super::*;
+    use crate::application::order_service::{OrderService, PaymentApplication};
+    use crate::domain::caller::Caller;
+    use crate::domain::order::{OrderLineInput, PaymentOutcome};
+    use crate::domain::ports::OrderRepository;
+    use crate::domain::saga::{
+        SagaState, AUTHORIZE_PAYMENT, INVENTORY_RELEASED, INVENTORY_RESERVED,
+        PAYMENT_AUTHORIZATION_FAILED, PAYMENT_AUTHORIZED, RELEASE_INVENTORY, RESERVE_INVENTORY,
+        SAGA_AGGREGATE,
+    };
+    use crate::inbox::{self, InMemoryTransport, Inbox};
+    use crate::infrastructure::order_repo::DieselOrderRepository;
+    use crate::infrastructure::saga_repo::DieselSagaRepository;
+    use crate::infrastructure::unit_of_work::DieselUnitOfWork;
+    use crate::payments::PAYMENT_CAPTURED;
+    use crate::schema::commerce_order_outbox;
+    use crate::test_support::setup_db;
+    use crate::DbPool;
+
+    type DieselSagaService = SagaService<DieselSagaRepository, DieselUnitOfWork>;
+
+    fn place_order(pool: &DbPool, timeouts: SagaTimeouts) -> Uuid {
+        OrderService::new(
+            DieselOrderRepository::new(pool.clone()),
+            DieselUnitOfWork::new(pool.clone()),
+        )
+        .with_saga(timeouts)
+        .create_order(
+            &Caller::Staff,
+            Uuid::new_v4(),
+            vec![OrderLineInput {
+                product_id: Uuid::new_v4(),
+                quantity: 2,
+                unit_price: BigDecimal::from_str("4.99").expect("decimal"),
+            }],
+        )
+        .expect("create order")
+    }
+
+    fn service(pool: &DbPool, timeouts: SagaTimeouts) -> Arc<DieselSagaService> {
+        Arc::new(SagaService::new(
+            DieselSagaRepository::new(pool.clone()),
+            DieselUnitOfWork::new(pool.clone()),
+            timeouts,
+        ))
+    }
+
+    fn reply(id: &str, message_type: &str, order_id: Uuid) -> InboundMessage {
+        InboundMessage {
+            id: id.to_string(),
+            message_type: message_type.to_string(),
+            payload: json!({ "order_id": order_id, "reason": "card declined" }),
+        }
+    }
+
+    async fn run(service: &Arc<DieselSagaService>, replies: Vec<InboundMessage>) {
+        let (sender, transport) = InMemoryTransport::new();
+        let (_stop, signal) = ShutdownSignal::for_test();
+        for reply in replies {
+            sender.send(reply);
+        }
+        drop(sender);
+        consume(Arc::clone(service), transport, signal).await;
+    }
+
+    /// Feed `transport` through a saga reply inbox until it closes or
+    /// `signal` fires.
+    async fn consume(
+        service: Arc<DieselSagaService>,
+        transport: InMemoryTransport,
+        signal: ShutdownSignal,
+    ) {
+        let inbox = Inbox::new().with_handler(SagaReplyHandler::new(service));
+        inbox::consume(Arc::new(inbox), transport, signal).await;
+    }
+
+    fn commands(pool: &DbPool, order_id: Uuid) -> Vec<String> {
+        let mut conn = pool.get().expect("connection");
+        commerce_order_outbox::table
+            .filter(commerce_order_outbox::aggregate_type.eq(SAGA_AGGREGATE))
+            .filter(commerce_order_outbox::aggregate_id.eq(order_id.to_string()))
+            .order(commerce_order_outbox::sequence.asc())
+            .select(commerce_order_outbox::event_type)
+            .load(&mut conn)
+            .expect("query")
+    }
+
+    fn order_status(pool: &DbPool, order_id: Uuid) -> String {
+        DieselOrderRepository::new(pool.clone())
+            .find_by_id(order_id)
+            .expect("find")
+            .expect("order")
+            .status
+    }
+
+    fn saga_state(service: &DieselSagaService, order_id: Uuid) -> SagaState {
+        service
+            .saga(&Caller::Staff, order_id)
+            .expect("saga")
+            .expect("saga exists")
+            .saga
+            .state
+    }
+
+    #[test]
+    fn replies_are_decoded_from_messages() {
+        let order_id = Uuid::new_v4();
+        assert_eq!(
+            SagaReplyMessage::from_message(&reply("m", PAYMENT_AUTHORIZATION_FAILED, order_id)),
+            Ok(SagaReplyMessage {
+                order_id,
+                reply: SagaReply::PaymentAuthorizationFailed {
+                    reason: Some("card declined".to_string())
+                }
+            })
+        );
+        assert!(matches!(
+            SagaReplyMessage::from_message(&reply("m", "InventoryCounted", order_id)),
+            Err(SagaReplyError::UnsupportedType(_))
+        ));
+        let mut invalid = reply("m", INVENTORY_RESERVED, order_id);
+        invalid.payload = json!({});
+        assert!(matches!(
+            SagaReplyMessage::from_message(&invalid),
+            Err(SagaReplyError::InvalidPayload(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn successful_replies_confirm_the_order() {
+        let (_container, pool) = setup_db().await;
+        let order_id = place_order(&pool, SagaTimeouts::default());
+        let service = service(&pool, SagaTimeouts::default());
+
+        run(
+            &service,
+            vec![
+                reply("r-1", INVENTORY_RESERVED, order_id),
+                reply("r-1", INVENTORY_RESERVED, order_id),
+                reply("r-2", PAYMENT_AUTHORIZED, order_id),
+            ],
+        )
+        .await;
+
+        assert_eq!(saga_state(&service, order_id), SagaState::Completed);
+        assert_eq!(order_status(&pool, order_id), "CONFIRMED");
+        assert_eq!(
+            commands(&pool, order_id),
+            [RESERVE_INVENTORY, AUTHORIZE_PAYMENT]
+        );
+    }
+
+    #[tokio::test]
+    async fn payments_do_not_overtake_a_running_saga() {
+        let (_container, pool) = setup_db().await;
+        let order_id = place_order(&pool, SagaTimeouts::default());
+        let service = service(&pool, SagaTimeouts::default());
+        run(&service, vec![reply("r-1", INVENTORY_RESERVED, order_id)]).await;
+
+        // The payment lands while the saga awaits its authorization.
+        let payment = OrderService::new(
+            DieselOrderRepository::new(pool.clone()),
+            DieselUnitOfWork::new(pool.clone()),
+        )
+        .apply_payment(
+            order_id,
+            &PaymentOutcome::Captured,
+            &SourceMessage {
+                id: "p-1".to_string(),
+                message_type: PAYMENT_CAPTURED.to_string(),
+            },
+        )
+        .expect("apply payment");
+        assert_eq!(
+            payment,
+            PaymentApplication::InSaga {
+                state: SagaState::AuthorizingPayment
+            }
+        );
+        assert_eq!(order_status(&pool, order_id), "PENDING");
+
+        run(&service, vec![reply("r-2", PAYMENT_AUTHORIZED, order_id)]).await;
+        assert_eq!(saga_state(&service, order_id), SagaState::Completed);
+        assert_eq!(order_status(&pool, order_id), "CONFIRMED");
+    }
+
+    #[tokio::test]
+    async fn failed_authorization_releases_the_inventory_and_cancels() {
+        let (_container, pool) = setup_db().await;
+        let order_id = place_order(&pool, SagaTimeouts::default());
+        let service = service(&pool, SagaTimeouts::default());
+
+        run(
+            &service,
+            vec![
+                reply("r-1", INVENTORY_RESERVED, order_id),
+                reply("r-2", PAYMENT_AUTHORIZATION_FAILED, order_id),
+            ],
+        )
+        .await;
+        assert_eq!(saga_state(&service, order_id), SagaState::Compensating);
+        assert_eq!(order_status(&pool, order_id), "PENDING");
+
+        run(&service, vec![reply("r-3", INVENTORY_RELEASED, order_id)]).await;
+        let view = service
+            .saga(&Caller::Staff, order_id)
+            .expect("saga")
+            .expect("saga exists");
+        assert_eq!(view.saga.state, SagaState::Failed);
+        assert_eq!(
+            view.saga.failure_reason.as_deref(),
+            Some("payment authorization failed: card declined")
+        );
+        assert_eq!(order_status(&pool, order_id), "CANCELLED");
+        assert_eq!(
+            commands(&pool, order_id),
+            [RESERVE_INVENTORY, AUTHORIZE_PAYMENT, RELEASE_INVENTORY]
+        );
+    }
+
+    #[tokio::test]
+    async fn replies_for_unknown_sagas_are_acknowledged_and_skipped() {
+        let (_container, pool) = setup_db().await;
+        let service = service(&pool, SagaTimeouts::default());
+        let (sender, transport) = InMemoryTransport::new();
+        let acknowledged = transport.acknowledgements();
+        let (_stop, signal) = ShutdownSignal::for_test();
+
+        sender.send(reply("r-1", INVENTORY_RESERVED, Uuid::new_v4()));
+        sender.send(reply("r-2", "InventoryCounted", Uuid::new_v4()));
+        drop(sender);
+        consume(service, transport, signal).await;
+
+        assert_eq!(acknowledged.ids(), ["r-1", "r-2"]);
+    }
+
+    #[tokio::test]
+    async fn overdue_steps_are_timed_out_until_shutdown() {
+        let (_container, pool) = setup_db().await;
+        let timeouts = SagaTimeouts {
+            reserve_inventory: Duration::ZERO,
+            ..SagaTimeouts::default()
+        };
+        let order_id = place_order(&pool, timeouts);
+        let service = service(&pool, timeouts);
+        let (stop, signal) = ShutdownSignal::for_test();
+
+        let worker = tokio::spawn(expire_timeouts(
+            Arc::clone(&service),
+            Duration::from_millis(10),
+            signal,
+        ));
+        while saga_state(&service, order_id) != SagaState::Compensating {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        stop.send(true).expect("worker listening");
+        tokio::time::timeout(Duration::from_secs(5), worker)
+            .await
+            .expect("worker stopped")
+            .expect("worker did not panic");
+
+        assert_eq!(
+            commands(&pool, order_id),
+            [RESERVE_INVENTORY, RELEASE_INVENTORY]
+        );
+    }
+}
@@ -33,9 +33,171 @@ diesel::table! {
         event_type -> Varchar,
         payload -> Jsonb,
         created_at -> Timestamptz,
+        sequence -> Int8,
+        headers -> Jsonb,
+    }
+}
+
+diesel::table! {
+    outbox_aggregate_sequences (aggregate_type, aggregate_id) {
+        #[max_length = 255]
+        aggregate_type -> Varchar,
+        #[max_length = 255]
+        aggregate_id -> Varchar,
+        last_sequence -> Int8,
+    }
+}
+
+diesel::table! {
+    processed_messages (message_id) {
+        #[max_length = 255]
+        message_id -> Varchar,
+        #[max_length = 255]
+        message_type -> Varchar,
+        processed_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    order_sagas (order_id) {
+        order_id -> Uuid,
+        customer_id -> Uuid,
+        amount -> Numeric,
+        #[max_length = 50]
+        state -> Varchar,
+        version -> Int8,
+        step_deadline -> Nullable<Timestamptz>,
+        failure_reason -> Nullable<Text>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    order_saga_steps (id) {
+        id -> Int8,
+        order_id -> Uuid,
+        #[max_length = 50]
+        step -> Varchar,
+        #[max_length = 50]
+        outcome -> Varchar,
+        detail -> Nullable<Text>,
+        recorded_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    webhook_subscriptions (id) {
+        id -> Uuid,
+        url -> Text,
+        event_types -> Array<Nullable<Text>>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    webhook_dispatched_events (event_id) {
+        event_id -> Uuid,
+        dispatched_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    webhook_deliveries (id) {
+        id -> Int8,
+        subscription_id -> Uuid,
+        event_id -> Uuid,
+        #[max_length = 255]
+        event_type -> Varchar,
+        body -> Jsonb,
+        #[max_length = 20]
+        status -> Varchar,
+        attempts -> Int4,
+        next_attempt_at -> Timestamptz,
+        last_error -> Nullable<Text>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    commerce_order_outbox_publications (event_id) {
+        event_id -> Uuid,
+        #[max_length = 20]
+        status -> Varchar,
+        attempts -> Int4,
+        next_attempt_at -> Timestamptz,
+        last_error -> Nullable<Text>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    outbox_relay_baseline (id) {
+        id -> Int2,
+        started_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    replication_heartbeat (id) {
+        id -> Int2,
+        beat_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    debezium_signal (id) {
+        #[max_length = 42]
+        id -> Varchar,
+        #[max_length = 32]
+        #[sql_name = "type"]
+        signal_type -> Varchar,
+        #[max_length = 2048]
+        data -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    outbox_snapshot_requests (signal_id) {
+        #[max_length = 42]
+        signal_id -> Varchar,
+        #[max_length = 255]
+        data_collection -> Varchar,
+        condition -> Nullable<Text>,
+        matching_events -> Int8,
+        signal_lsn -> Nullable<Int8>,
+        #[max_length = 20]
+        status -> Varchar,
+        requested_at -> Timestamptz,
+        updated_at -> Timestamptz,
     }
 }
 
 diesel::joinable!(order_lines -> orders (order_id));
+diesel::joinable!(order_sagas -> orders (order_id));
+diesel::joinable!(order_saga_steps -> order_sagas (order_id));
+diesel::joinable!(webhook_deliveries -> webhook_subscriptions (subscription_id));
+diesel::joinable!(webhook_dispatched_events -> commerce_order_outbox (event_id));
+diesel::joinable!(commerce_order_outbox_publications -> commerce_order_outbox (event_id));
+diesel::joinable!(outbox_snapshot_requests -> debezium_signal (signal_id));
 
-diesel::allow_tables_to_appear_in_same_query!(order_lines, orders, commerce_order_outbox,);
+diesel::allow_tables_to_appear_in_same_query!(
+    order_lines,
+    orders,
+    commerce_order_outbox,
+    outbox_aggregate_sequences,
+    processed_messages,
+    order_sagas,
+    order_saga_steps,
+    webhook_subscriptions,
+    webhook_dispatched_events,
+    webhook_deliveries,
+    commerce_order_outbox_publications,
+    outbox_relay_baseline,
+    replication_heartbeat,
+    debezium_signal,
+    outbox_snapshot_requests,
+);
@@ -0,0 +1,494 @@
+//! Coordinated, graceful shutdown.
+//!
+//! On SIGTERM/SIGINT (or [`ShutdownTrigger::trigger`]) the
+//! [`ShutdownCoordinator`]:
+//!
+//! 1. fails readiness so load balancers stop routing new traffic, then waits
+//!    `readiness_delay` for them to notice;
+//! 2. stops accepting connections and drains in-flight HTTP requests for up
+//!    to `drain_timeout`;
+//! 3. signals background workers, which finish their current unit of work and
+//!    return, and waits for them (again bounded by `drain_timeout`);
+//! 4. closes the database pool handed to
+//!    [`close_pool`](ShutdownCoordinator::close_pool).
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{Server, ServiceRequest, ServiceResponse};
+use actix_web::http::ConnectionType;
+use actix_web::middleware::Next;
+use actix_web::{web, Error};
+use serde::Serialize;
+use serde_json::Value;
+use tokio::sync::{watch, Notify};
+use tokio::task::JoinHandle;
+
+use crate::db::DbPool;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShutdownConfig {
+    /// How long readiness fails before the server stops accepting connections.
+    pub readiness_delay: Duration,
+    /// Upper bound for draining HTTP requests, and again for stopping workers.
+    pub drain_timeout: Duration,
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        Self {
+            readiness_delay: Duration::ZERO,
+            drain_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Whether the service should receive traffic; served by `GET /readiness`.
+///
+/// Besides shutdown, background monitors report named
+/// [checks](Self::report_check); only the required ones hold readiness back.
+#[derive(Debug, Clone)]
+pub struct Readiness {
+    ready: Arc<AtomicBool>,
+    checks: Arc<RwLock<BTreeMap<&'static str, ReadinessCheck>>>,
+}
+
+/// The latest outcome of a named readiness check.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReadinessCheck {
+    pub healthy: bool,
+    /// Whether an unhealthy outcome fails readiness; otherwise the check is
+    /// only shown.
+    pub required: bool,
+    /// What the check saw, shown in the readiness response.
+    pub details: Value,
+}
+
+impl Readiness {
+    /// A readiness flag that starts out ready.
+    pub fn new() -> Self {
+        Self {
+            ready: Arc::new(AtomicBool::new(true)),
+            checks: Arc::default(),
+        }
+    }
+
+    /// Whether shutdown has not started yet; checks are not considered.
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::SeqCst)
+    }
+
+    pub fn set_not_ready(&self) {
+        self.ready.store(false, Ordering::SeqCst);
+    }
+
+    /// Record the outcome of check `name`, replacing the previous one.
+    pub fn report_check(&self, name: &'static str, check: ReadinessCheck) {
+        self.checks
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(name, check);
+    }
+
+    /// The latest outcome of every check, by name.
+    pub fn checks(&self) -> BTreeMap<&'static str, ReadinessCheck> {
+        self.checks
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
+
+impl Default for Readiness {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Middleware that answers with `Connection: close` once readiness has
+/// failed, so keep-alive clients reconnect elsewhere instead of holding the
+/// drain open until `drain_timeout`. Use with
+/// [`from_fn`](actix_web::middleware::from_fn).
+pub async fn close_connections_when_draining(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let draining = req
+        .app_data::<web::Data<Readiness>>()
+        .is_some_and(|readiness| !readiness.is_ready());
+    let mut res = next.call(req).await?;
+    if draining {
+        res.response_mut()
+            .head_mut()
+            .set_connection_type(ConnectionType::Close);
+    }
+    Ok(res)
+}
+
+/// Handed to background workers so they can stop at a safe checkpoint.
+///
+/// Workers should check [`is_triggered`](Self::is_triggered) between units of
+/// work (never in the middle of a transaction), or await
+/// [`triggered`](Self::triggered) while idle.
+#[derive(Debug, Clone)]
+pub struct ShutdownSignal(watch::Receiver<bool>);
+
+impl ShutdownSignal {
+    pub fn is_triggered(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    /// Resolves once shutdown has reached the worker phase.
+    pub async fn triggered(&mut self) {
+        // An error means the coordinator is gone, which also means stop.
+        let _ = self.0.wait_for(|stop| *stop).await;
+    }
+
+    /// A signal driven directly by the test: send `true` to stop the worker.
+    #[cfg(test)]
+    pub(crate) fn for_test() -> (watch::Sender<bool>, Self) {
+        let (tx, rx) = watch::channel(false);
+        (tx, Self(rx))
+    }
+}
+
+/// Requests shutdown programmatically, as if the process had received SIGTERM.
+#[derive(Debug, Clone)]
+pub struct ShutdownTrigger(Arc<Notify>);
+
+impl ShutdownTrigger {
+    pub fn trigger(&self) {
+        self.0.notify_one();
+    }
+}
+
+pub struct ShutdownCoordinator {
+    config: ShutdownConfig,
+    readiness: Readiness,
+    trigger: Arc<Notify>,
+    stop_workers: watch::Sender<bool>,
+    workers: Vec<(&'static str, JoinHandle<()>)>,
+    pool: Option<DbPool>,
+}
+
+impl ShutdownCoordinator {
+    pub fn new(config: ShutdownConfig) -> Self {
+        Self {
+            config,
+            readiness: Readiness::new(),
+            trigger: Arc::new(Notify::new()),
+            stop_workers: watch::channel(false).0,
+            workers: Vec::new(),
+            pool: None,
+        }
+    }
+
+    pub fn readiness(&self) -> Readiness {
+        self.readiness.clone()
+    }
+
+    pub fn trigger(&self) -> ShutdownTrigger {
+        ShutdownTrigger(Arc::clone(&self.trigger))
+    }
+
+    /// Spawn a background worker that is stopped after HTTP has drained.
+    pub fn spawn_worker<F, Fut>(&mut self, name: &'static str, worker: F)
+    where
+        F: FnOnce(ShutdownSignal) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let signal = ShutdownSignal(self.stop_workers.subscribe());
+        self.workers.push((name, tokio::spawn(worker(signal))));
+    }
+
+    /// Close `pool` once the workers have stopped.
+    ///
+    /// r2d2 closes its connections when the last clone of the pool is
+    /// dropped, so the coordinator should hold that last clone: hand the
+    /// others only to the server and to workers.
+    pub fn close_pool(&mut self, pool: DbPool) {
+        self.pool = Some(pool);
+    }
+
+    /// Run `server` until shutdown is requested, then shut everything down in
+    /// order. `server` should be built with signal handling disabled (as
+    /// [`build_server`](crate::build_server) does).
+    pub async fn run(mut self, server: Server) -> std::io::Result<()> {
+        let handle = server.handle();
+        // Boxed rather than pinned on the stack so it can be dropped, with the
+        // pool clones held by its app factories, before the pool is closed.
+        let mut server = Box::pin(server);
+
+        let result = tokio::select! {
+            result = &mut server => {
+                log::warn!("HTTP server stopped before shutdown was requested");
+                Some(result)
+            }
+            reason = wait_for_shutdown(&self.trigger) => {
+                log::info!("Shutdown requested ({}): failing readiness", reason);
+                None
+            }
+        };
+        let result = match result {
+            Some(result) => result,
+            None => {
+                self.readiness.set_not_ready();
+                tokio::time::sleep(self.config.readiness_delay).await;
+
+                log::info!(
+                    "Draining in-flight HTTP requests (up to {:?})",
+                    self.config.drain_timeout
+                );
+                let (_, result) = tokio::join!(handle.stop(true), &mut server);
+                result
+            }
+        };
+        drop(server);
+
+        let pool = self.pool.take();
+        self.stop_workers().await;
+        if let Some(pool) = pool {
+            let state = pool.state();
+            log::info!(
+                "Closing the database pool ({} connection(s), {} idle)",
+                state.connections,
+                state.idle_connections
+            );
+            drop(pool);
+        }
+        result
+    }
+
+    async fn stop_workers(self) {
+        if self.workers.is_empty() {
+            return;
+        }
+        log::info!("Stopping {} background worker(s)", self.workers.len());
+        let _ = self.stop_workers.send(true);
+
+        let deadline = tokio::time::Instant::now() + self.config.drain_timeout;
+        for (name, mut worker) in self.workers {
+            match tokio::time::timeout_at(deadline, &mut worker).await {
+                Ok(Ok(())) => log::debug!("Worker {} stopped", name),
+                Ok(Err(e)) => log::error!("Worker {} failed: {}", name, e),
+                Err(_) => {
+                    log::warn!("Worker {} did not stop in time; aborting it", name);
+                    worker.abort();
+                }
+            }
+        }
+    }
+}
+
+async fn wait_for_shutdown(trigger: &Notify) -> &'static str {
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(e) => {
+                log::error!("Cannot listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = terminate => "SIGTERM",
+        _ = tokio::signal::ctrl_c() => "SIGINT",
+        _ = trigger.notified() => "requested",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+
+    use diesel::prelude::*;
+    use reqwest::StatusCode;
+    use serde_json::json;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::order_stream::OrderEventBus;
+    use crate::schema::{commerce_order_outbox, orders};
+    use crate::test_support::{free_port, setup_db_with_url};
+    use crate::{build_server, Config};
+
+    #[test]
+    fn readiness_starts_ready_and_can_be_failed() {
+        let readiness = Readiness::new();
+        let observer = readiness.clone();
+        assert!(observer.is_ready());
+        readiness.set_not_ready();
+        assert!(!observer.is_ready());
+    }
+
+    #[tokio::test]
+    async fn workers_finish_their_unit_of_work_before_stopping() {
+        let mut coordinator = ShutdownCoordinator::new(ShutdownConfig::default());
+        let completed = Arc::new(AtomicUsize::new(0));
+        let started = Arc::new(Notify::new());
+
+        let (done, notify) = (Arc::clone(&completed), Arc::clone(&started));
+        coordinator.spawn_worker("batcher", move |signal| async move {
+            while !signal.is_triggered() {
+                notify.notify_one();
+                // A unit of work that must not be interrupted half-way.
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                done.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+        started.notified().await;
+
+        coordinator.stop_workers().await;
+        assert!(completed.load(Ordering::SeqCst) >= 1);
+    }
+
+    #[tokio::test]
+    async fn stuck_workers_are_aborted_after_the_drain_timeout() {
+        let mut coordinator = ShutdownCoordinator::new(ShutdownConfig {
+            drain_timeout: Duration::from_millis(50),
+            ..Default::default()
+        });
+        coordinator.spawn_worker("stuck", |_signal| std::future::pending());
+
+        tokio::time::timeout(Duration::from_secs(5), coordinator.stop_workers())
+            .await
+            .expect("shutdown is bounded by the drain timeout");
+    }
+
+    #[actix_web::test]
+    async fn no_committed_order_is_lost_during_shutdown() {
+        let (_container, pool, url) = setup_db_with_url().await;
+        let port = free_port();
+        let config = Config {
+            host: "127.0.0.1".into(),
+            port,
+            rate_limit: None,
+            shutdown: ShutdownConfig {
+                readiness_delay: Duration::from_millis(300),
+                drain_timeout: Duration::from_secs(10),
+            },
+            ..Config::new("unused")
+        };
+        let mut coordinator = ShutdownCoordinator::new(config.shutdown);
+        let trigger = coordinator.trigger();
+        let server = build_server(
+            pool.clone(),
+            &config,
+            coordinator.readiness(),
+            OrderEventBus::new(),
+        )
+        .expect("server binds");
+        coordinator.close_pool(pool);
+        let shutdown = actix_web::rt::spawn(coordinator.run(server));
+
+        let base = format!("http://127.0.0.1:{}", port);
+        let http = reqwest::Client::new();
+
+        // Keep several clients placing orders until the server goes away.
+        let clients: Vec<_> = (0..8)
+            .map(|_| {
+                let (http, url) = (http.clone(), format!("{}/orders", base));
+                actix_web::rt::spawn(async move {
+                    let mut accepted: Vec<Uuid> = Vec::new();
+                    loop {
+                        let body = json!({
+                            "customer_id": Uuid::new_v4(),
+                            "lines": [{
+                                "product_id": Uuid::new_v4(),
+                                "quantity": 1,
+                                "unit_price": "1.00"
+                            }]
+                        });
+                        let Ok(resp) = http.post(&url).json(&body).send().await else {
+                            return accepted;
+                        };
+                        if resp.status() != StatusCode::CREATED {
+                            continue;
+                        }
+                        let Ok(created) = resp.json::<serde_json::Value>().await else {
+                            return accepted;
+                        };
+                        let id = created["id"].as_str().expect("id").parse().expect("uuid");
+                        accepted.push(id);
+                    }
+                })
+            })
+            .collect();
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        trigger.trigger();
+
+        // Readiness fails first, while requests are still being served.
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let readiness = http
+            .get(format!("{}/readiness", base))
+            .send()
+            .await
+            .expect("server still up during the readiness delay");
+        assert_eq!(readiness.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(
+            readiness
+                .headers()
+                .get(reqwest::header::CONNECTION)
+                .and_then(|v| v.to_str().ok()),
+            Some("close")
+        );
+
+        shutdown
+            .await
+            .expect("coordinator task")
+            .expect("clean shutdown");
+
+        let mut accepted: Vec<Uuid> = Vec::new();
+        for client in clients {
+            accepted.extend(client.await.expect("client task"));
+        }
+        assert!(!accepted.is_empty(), "some orders were placed");
+
+        let mut conn = PgConnection::establish(&url).expect("connection");
+        let stored_orders: i64 = orders::table
+            .filter(orders::id.eq_any(&accepted))
+            .count()
+            .get_result(&mut conn)
+            .expect("count orders");
+        let aggregate_ids: Vec<String> = accepted.iter().map(Uuid::to_string).collect();
+        let stored_events: i64 = commerce_order_outbox::table
+            .filter(commerce_order_outbox::aggregate_id.eq_any(&aggregate_ids))
+            .count()
+            .get_result(&mut conn)
+            .expect("count outbox events");
+        assert_eq!(stored_orders, accepted.len() as i64);
+        assert_eq!(stored_events, accepted.len() as i64);
+
+        // The pool's connections are gone once the coordinator returns; the
+        // server only notices a moment later.
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+        loop {
+            let others: i64 = diesel::dsl::sql::<diesel::sql_types::BigInt>(
+                "SELECT count(*) FROM pg_stat_activity \
+                 WHERE datname = current_database() AND pid <> pg_backend_pid()",
+            )
+            .get_result(&mut conn)
+            .expect("count connections");
+            if others == 0 {
+                break;
+            }
+            assert!(
+                tokio::time::Instant::now() < deadline,
+                "{} pool connection(s) still open after shutdown",
+                others
+            );
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+}
@@ -0,0 +1,334 @@
+//! OpenTelemetry instrumentation.
+//!
+//! Spans and metrics are always recorded against the global OpenTelemetry
+//! providers. Those are no-ops until [`init`] installs real ones, which only
+//! happens when the crate is built with the `otel` feature. Exporters are then
+//! configured through the standard `OTEL_*` environment variables
+//! (`OTEL_EXPORTER_OTLP_ENDPOINT`, `OTEL_SERVICE_NAME`, …).
+//!
+//! Metric instruments are built once, on first use, so [`init`] has to run
+//! before anything is recorded.
+
+use std::sync::LazyLock;
+use std::time::Instant;
+
+use opentelemetry::global::{self, BoxedTracer};
+use opentelemetry::metrics::Histogram;
+use opentelemetry::trace::{SpanKind, Status, TraceContextExt, Tracer};
+use opentelemetry::{Context, KeyValue};
+
+/// Instrumentation scope name used for every tracer and meter of this crate.
+pub const INSTRUMENTATION_SCOPE: &str = env!("CARGO_PKG_NAME");
+
+// ── Database semantic conventions ────────────────────────────────────────────
+// https://opentelemetry.io/docs/specs/semconv/database/database-spans/
+
+pub const DB_SYSTEM_NAME: &str = "db.system.name";
+pub const DB_OPERATION_NAME: &str = "db.operation.name";
+pub const DB_COLLECTION_NAME: &str = "db.collection.name";
+pub const ERROR_TYPE: &str = "error.type";
+
+const DB_SYSTEM_POSTGRESQL: &str = "postgresql";
+const DB_CLIENT_OPERATION_DURATION: &str = "db.client.operation.duration";
+
+static DB_OPERATION_DURATION: LazyLock<Histogram<f64>> = LazyLock::new(|| {
+    global::meter(INSTRUMENTATION_SCOPE)
+        .f64_histogram(DB_CLIENT_OPERATION_DURATION)
+        .with_unit("s")
+        .with_description("Duration of database client operations")
+        .build()
+});
+
+pub fn tracer() -> BoxedTracer {
+    global::tracer(INSTRUMENTATION_SCOPE)
+}
+
+/// Run `f` inside an internal span named `name`.
+///
+/// Used to group the individual database spans of one repository call.
+pub fn in_span<T>(name: &'static str, f: impl FnOnce() -> T) -> T {
+    tracer().in_span(name, |_cx| f())
+}
+
+/// Run a single Diesel query inside a client span following the OpenTelemetry
+/// database semantic conventions, and record its duration in the
+/// `db.client.operation.duration` histogram.
+///
+/// The span is named `"{operation} {collection}"`, e.g. `"INSERT orders"`.
+pub fn in_db_span<T>(
+    operation: &'static str,
+    collection: &'static str,
+    f: impl FnOnce() -> diesel::QueryResult<T>,
+) -> diesel::QueryResult<T> {
+    let mut attributes = vec![
+        KeyValue::new(DB_SYSTEM_NAME, DB_SYSTEM_POSTGRESQL),
+        KeyValue::new(DB_OPERATION_NAME, operation),
+        KeyValue::new(DB_COLLECTION_NAME, collection),
+    ];
+
+    let tracer = tracer();
+    let span = tracer
+        .span_builder(format!("{operation} {collection}"))
+        .with_kind(SpanKind::Client)
+        .with_attributes(attributes.clone())
+        .start(&tracer);
+    let cx = Context::current_with_span(span);
+
+    let started = Instant::now();
+    let result = {
+        let _guard = cx.clone().attach();
+        f()
+    };
+    let elapsed = started.elapsed().as_secs_f64();
+
+    let span = cx.span();
+    if let Err(e) = &result {
+        let error_type = KeyValue::new(ERROR_TYPE, error_type(e));
+        span.set_attribute(error_type.clone());
+        span.set_status(Status::error(e.to_string()));
+        attributes.push(error_type);
+    }
+    span.end();
+
+    DB_OPERATION_DURATION.record(elapsed, &attributes);
+
+    result
+}
+
+/// Low-cardinality classification of a Diesel error for the `error.type` attribute.
+fn error_type(e: &diesel::result::Error) -> String {
+    use diesel::result::Error;
+    match e {
+        Error::NotFound => "NotFound".to_string(),
+        Error::DatabaseError(kind, _) => format!("{:?}", kind),
+        Error::RollbackTransaction => "RollbackTransaction".to_string(),
+        _ => "_OTHER".to_string(),
+    }
+}
+
+// ── Exporter setup ───────────────────────────────────────────────────────────
+
+/// Keeps the installed providers alive; flushes and shuts them down on drop.
+#[derive(Default)]
+pub struct TelemetryGuard {
+    #[cfg(feature = "otel")]
+    tracer_provider: Option<opentelemetry_sdk::trace::SdkTracerProvider>,
+    #[cfg(feature = "otel")]
+    meter_provider: Option<opentelemetry_sdk::metrics::SdkMeterProvider>,
+}
+
+/// Without the `otel` feature there is nothing to export: the global providers
+/// stay no-ops.
+#[cfg(not(feature = "otel"))]
+pub fn init() -> TelemetryGuard {
+    TelemetryGuard::default()
+}
+
+/// Install OTLP (HTTP/protobuf) trace and metric exporters as the global
+/// providers.
+///
+/// Honoured variables, besides the exporter's own `OTEL_EXPORTER_OTLP_*`:
+/// - `OTEL_SDK_DISABLED=true` – install nothing.
+/// - `OTEL_TRACES_EXPORTER` / `OTEL_METRICS_EXPORTER` – `otlp` (default) or `none`.
+/// - `OTEL_SERVICE_NAME` / `OTEL_RESOURCE_ATTRIBUTES` – resource attributes.
+#[cfg(feature = "otel")]
+pub fn init() -> TelemetryGuard {
+    use opentelemetry_sdk::metrics::SdkMeterProvider;
+    use opentelemetry_sdk::trace::SdkTracerProvider;
+    use opentelemetry_sdk::Resource;
+
+    let env = |name: &str| std::env::var(name).ok();
+    if env("OTEL_SDK_DISABLED").is_some_and(|v| v.eq_ignore_ascii_case("true")) {
+        log::info!("OpenTelemetry SDK disabled via OTEL_SDK_DISABLED");
+        return TelemetryGuard::default();
+    }
+
+    let resource = if env("OTEL_SERVICE_NAME").is_some() {
+        Resource::builder().build()
+    } else {
+        Resource::builder()
+            .with_service_name(INSTRUMENTATION_SCOPE)
+            .build()
+    };
+
+    let mut guard = TelemetryGuard::default();
+
+    if otlp_selected(env("OTEL_TRACES_EXPORTER").as_deref()) {
+        match opentelemetry_otlp::SpanExporter::builder()
+            .with_http()
+            .build()
+        {
+            Ok(exporter) => {
+                let provider = SdkTracerProvider::builder()
+                    .with_resource(resource.clone())
+                    .with_batch_exporter(exporter)
+                    .build();
+                global::set_tracer_provider(provider.clone());
+                guard.tracer_provider = Some(provider);
+            }
+            Err(e) => log::error!("Failed to build OTLP span exporter: {}", e),
+        }
+    }
+
+    if otlp_selected(env("OTEL_METRICS_EXPORTER").as_deref()) {
+        match opentelemetry_otlp::MetricExporter::builder()
+            .with_http()
+            .build()
+        {
+            Ok(exporter) => {
+                let provider = SdkMeterProvider::builder()
+                    .with_resource(resource)
+                    .with_periodic_exporter(exporter)
+                    .build();
+                global::set_meter_provider(provider.clone());
+                guard.meter_provider = Some(provider);
+            }
+            Err(e) => log::error!("Failed to build OTLP metric exporter: {}", e),
+        }
+    }
+
+    guard
+}
+
+/// `OTEL_{TRACES,METRICS}_EXPORTER` selects OTLP when unset or set to `otlp`.
+#[cfg(feature = "otel")]
+fn otlp_selected(value: Option<&str>) -> bool {
+    match value.map(str::trim) {
+        None | Some("") | Some("otlp") => true,
+        Some("none") => false,
+        Some(other) => {
+            log::warn!("Unsupported OpenTelemetry exporter '{}', ignoring", other);
+            false
+        }
+    }
+}
+
+#[cfg(feature = "otel")]
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        if let Some(provider) = self.tracer_provider.take() {
+            if let Err(e) = provider.shutdown() {
+                log::warn!("Failed to shut down tracer provider: {}", e);
+            }
+        }
+        if let Some(provider) = self.meter_provider.take() {
+            if let Err(e) = provider.shutdown() {
+                log::warn!("Failed to shut down meter provider: {}", e);
+            }
+        }
+    }
+}
+
+/// In-memory span exporter installed as the global tracer provider for tests.
+///
+/// The provider is global, so tests running in parallel share it: filter the
+/// exported spans by something unique to the test.
+#[cfg(test)]
+pub(crate) fn test_span_exporter() -> opentelemetry_sdk::trace::InMemorySpanExporter {
+    use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider};
+    use std::sync::OnceLock;
+
+    static EXPORTER: OnceLock<InMemorySpanExporter> = OnceLock::new();
+    EXPORTER
+        .get_or_init(|| {
+            let exporter = InMemorySpanExporter::default();
+            let provider = SdkTracerProvider::builder()
+                .with_simple_exporter(exporter.clone())
+                .build();
+            global::set_tracer_provider(provider);
+            exporter
+        })
+        .clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::Value;
+    use opentelemetry_sdk::trace::SpanData;
+
+    fn attribute<'a>(span: &'a SpanData, key: &str) -> Option<&'a Value> {
+        span.attributes
+            .iter()
+            .find(|kv| kv.key.as_str() == key)
+            .map(|kv| &kv.value)
+    }
+
+    fn finished_span(name: &str) -> SpanData {
+        test_span_exporter()
+            .get_finished_spans()
+            .expect("read finished spans")
+            .into_iter()
+            .find(|s| s.name == name)
+            .unwrap_or_else(|| panic!("span '{}' was not exported", name))
+    }
+
+    #[test]
+    fn db_span_carries_semantic_convention_attributes() {
+        test_span_exporter();
+
+        let result = in_db_span("SELECT", "telemetry_ok", || Ok(42));
+        assert_eq!(result.expect("closure result"), 42);
+
+        let span = finished_span("SELECT telemetry_ok");
+        assert_eq!(span.span_kind, SpanKind::Client);
+        assert_eq!(
+            attribute(&span, DB_SYSTEM_NAME),
+            Some(&Value::from("postgresql"))
+        );
+        assert_eq!(
+            attribute(&span, DB_OPERATION_NAME),
+            Some(&Value::from("SELECT"))
+        );
+        assert_eq!(
+            attribute(&span, DB_COLLECTION_NAME),
+            Some(&Value::from("telemetry_ok"))
+        );
+        assert_eq!(attribute(&span, ERROR_TYPE), None);
+        assert_eq!(span.status, Status::Unset);
+    }
+
+    #[test]
+    fn db_span_records_errors() {
+        test_span_exporter();
+
+        let result: diesel::QueryResult<()> =
+            in_db_span("DELETE", "telemetry_err", || Err(diesel::NotFound));
+        assert!(result.is_err());
+
+        let span = finished_span("DELETE telemetry_err");
+        assert_eq!(attribute(&span, ERROR_TYPE), Some(&Value::from("NotFound")));
+        assert!(matches!(span.status, Status::Error { .. }));
+    }
+
+    #[test]
+    fn db_spans_are_children_of_the_enclosing_span() {
+        test_span_exporter();
+
+        in_span("telemetry.parent", || {
+            in_db_span("INSERT", "telemetry_child", || Ok(()))
+        })
+        .expect("closure result");
+
+        let parent = finished_span("telemetry.parent");
+        let child = finished_span("INSERT telemetry_child");
+        assert_eq!(child.parent_span_id, parent.span_context.span_id());
+        assert_eq!(
+            child.span_context.trace_id(),
+            parent.span_context.trace_id()
+        );
+    }
+
+    #[test]
+    fn error_type_uses_database_error_kind() {
+        let err = diesel::result::Error::DatabaseError(
+            diesel::result::DatabaseErrorKind::UniqueViolation,
+            Box::new("duplicate key".to_string()),
+        );
+        assert_eq!(error_type(&err), "UniqueViolation");
+        assert_eq!(
+            error_type(&diesel::result::Error::BrokenTransactionManager),
+            "_OTHER"
+        );
+    }
+}
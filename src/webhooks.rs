@@ -0,0 +1,629 @@
+//! Delivery of order events to webhook subscribers.
+//!
+//! The [`WebhookDispatcher`] polls the outbox for order events not yet
+//! dispatched and queues one delivery per interested subscription (see
+//! [`webhook_repo::fan_out`]). Due deliveries are then POSTed to their
+//! endpoint with an HMAC-SHA256 signature. Failed attempts are retried with
+//! exponential backoff; after `max_attempts` the delivery is dead-lettered and
+//! only sent again when an operator asks for it.
+//!
+//! Deliveries only go to public addresses unless private destinations are
+//! allowed: names resolving to internal addresses, and redirects, are
+//! refused when sending too, not just when subscribing.
+
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use diesel::pg::PgConnection;
+use diesel::QueryResult;
+use hmac::{Hmac, Mac};
+use opentelemetry::metrics::Counter;
+use opentelemetry::{global, KeyValue};
+use sha2::Sha256;
+use thiserror::Error;
+
+use crate::domain::retry::RetryPolicy;
+use crate::domain::webhook::{destination, is_public_address, WebhookSecretKey};
+use crate::infrastructure::webhook_repo::{self, DueDelivery};
+use crate::outbox_listener::WakeupReceiver;
+use crate::poll_loop::{self, LeasedWork, Outcome, PollSettings, PollSummary};
+use crate::shutdown::ShutdownSignal;
+use crate::telemetry::INSTRUMENTATION_SCOPE;
+use crate::DbPool;
+
+/// `sha256=<hex HMAC-SHA256 of "{timestamp}.{body}">`, keyed with the
+/// subscription secret (see [`WebhookSecretKey::secret_for`]).
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+/// Unix time (seconds) of the attempt, part of the signed content.
+pub const TIMESTAMP_HEADER: &str = "X-Webhook-Timestamp";
+/// Outbox event id; identical across retries, for deduplication.
+pub const EVENT_ID_HEADER: &str = "X-Webhook-Id";
+pub const EVENT_TYPE_HEADER: &str = "X-Webhook-Event";
+
+/// Webhook delivery settings (`WEBHOOK_*` variables).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebhookConfig {
+    pub retry: RetryPolicy,
+    /// Pause between polls when there is nothing to do.
+    pub poll_interval: Duration,
+    /// Bound for a single HTTP attempt.
+    pub request_timeout: Duration,
+    /// Events fanned out, and deliveries attempted, per poll.
+    pub batch_size: i64,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            retry: RetryPolicy::default(),
+            poll_interval: Duration::from_secs(1),
+            request_timeout: Duration::from_secs(10),
+            batch_size: 100,
+        }
+    }
+}
+
+/// Secrets and destinations of webhooks (`WEBHOOK_SECRET_KEY`,
+/// `WEBHOOK_ALLOW_PRIVATE_DESTINATIONS`), for subscribing and delivering
+/// alike.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WebhookSecurity {
+    /// `None` refuses new subscriptions and leaves deliveries unsent.
+    pub secret_key: Option<WebhookSecretKey>,
+    /// Accept loopback, private and link-local destinations, for local
+    /// setups.
+    pub allow_private_destinations: bool,
+}
+
+#[derive(Debug, Error)]
+pub enum WebhookError {
+    #[error("WEBHOOK_SECRET_KEY is not set, deliveries cannot be signed")]
+    MissingSecretKey,
+
+    #[error("Database error: {0}")]
+    Database(#[from] diesel::result::Error),
+
+    #[error("Connection pool error: {0}")]
+    Pool(#[from] r2d2::Error),
+
+    #[error("HTTP client error: {0}")]
+    Client(#[from] reqwest::Error),
+
+    #[error("Worker error: {0}")]
+    Worker(#[from] tokio::task::JoinError),
+}
+
+/// Signature header value for `body` sent at `timestamp`.
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let digest = signing_mac(secret, timestamp, body).finalize().into_bytes();
+    format!("sha256={}", hex::encode(digest))
+}
+
+/// Whether `signature` is the signature of `body` sent at `timestamp`, as a
+/// subscriber would check it. Compares in constant time.
+pub fn verify(secret: &str, timestamp: i64, body: &[u8], signature: &str) -> bool {
+    let Some(digest) = signature
+        .strip_prefix("sha256=")
+        .and_then(|hex_digest| hex::decode(hex_digest).ok())
+    else {
+        return false;
+    };
+    signing_mac(secret, timestamp, body)
+        .verify_slice(&digest)
+        .is_ok()
+}
+
+fn signing_mac(secret: &str, timestamp: i64, body: &[u8]) -> Hmac<Sha256> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    mac
+}
+
+pub struct WebhookDispatcher {
+    pool: DbPool,
+    client: reqwest::Client,
+    config: WebhookConfig,
+    secret_key: WebhookSecretKey,
+    allow_private_destinations: bool,
+}
+
+impl WebhookDispatcher {
+    pub fn new(
+        pool: DbPool,
+        config: WebhookConfig,
+        security: WebhookSecurity,
+    ) -> Result<Self, WebhookError> {
+        let secret_key = security.secret_key.ok_or(WebhookError::MissingSecretKey)?;
+        let mut client = reqwest::Client::builder()
+            .timeout(config.request_timeout)
+            // A redirect could lead anywhere, internal addresses included.
+            .redirect(reqwest::redirect::Policy::none());
+        if !security.allow_private_destinations {
+            client = client.dns_resolver(Arc::new(PublicResolver));
+        }
+        Ok(Self {
+            pool,
+            client: client.build()?,
+            config,
+            secret_key,
+            allow_private_destinations: security.allow_private_destinations,
+        })
+    }
+
+    /// Fan out new outbox events, then attempt the deliveries that are due.
+    pub async fn run_once(self: &Arc<Self>) -> Result<PollSummary, WebhookError> {
+        poll_loop::run_once(self).await
+    }
+}
+
+static DELIVERY_ATTEMPTS: LazyLock<Counter<u64>> = LazyLock::new(|| {
+    global::meter(INSTRUMENTATION_SCOPE)
+        .u64_counter("webhook.delivery.attempts")
+        .with_description("Webhook delivery attempts")
+        .build()
+});
+
+impl LeasedWork for WebhookDispatcher {
+    const NAME: &'static str = "Webhook delivery";
+
+    type Item = DueDelivery;
+    type Failure = String;
+    type Error = WebhookError;
+
+    fn pool(&self) -> &DbPool {
+        &self.pool
+    }
+
+    fn settings(&self) -> PollSettings {
+        PollSettings {
+            retry: self.config.retry,
+            poll_interval: self.config.poll_interval,
+            attempt_timeout: self.config.request_timeout,
+            batch_size: self.config.batch_size,
+        }
+    }
+
+    fn take_up(&self, conn: &mut PgConnection, limit: i64) -> QueryResult<usize> {
+        webhook_repo::fan_out(conn, limit)
+    }
+
+    fn claim_due(
+        &self,
+        conn: &mut PgConnection,
+        now: DateTime<Utc>,
+        lease_until: DateTime<Utc>,
+        limit: i64,
+    ) -> QueryResult<Vec<DueDelivery>> {
+        webhook_repo::claim_due(conn, now, lease_until, limit)
+    }
+
+    async fn attempt(&self, delivery: &DueDelivery) -> Result<(), String> {
+        if !self.allow_private_destinations {
+            check_ip_destination(&delivery.url)?;
+        }
+        let body = serde_json::to_vec(&delivery.body).map_err(|e| e.to_string())?;
+        let timestamp = Utc::now().timestamp();
+        let secret = self.secret_key.secret_for(delivery.subscription_id);
+        let response = self
+            .client
+            .post(&delivery.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, sign(&secret, timestamp, &body))
+            .header(TIMESTAMP_HEADER, timestamp)
+            .header(EVENT_ID_HEADER, delivery.event_id.to_string())
+            .header(EVENT_TYPE_HEADER, &delivery.event_type)
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        let status = response.status();
+        if status.is_success() {
+            Ok(())
+        } else {
+            Err(format!("HTTP {}", status))
+        }
+    }
+
+    fn attempts(delivery: &DueDelivery) -> i32 {
+        delivery.attempts
+    }
+
+    fn mark_succeeded(&self, conn: &mut PgConnection, delivery: &DueDelivery) -> QueryResult<()> {
+        webhook_repo::mark_delivered(conn, delivery.id)
+    }
+
+    fn mark_failed(
+        &self,
+        conn: &mut PgConnection,
+        delivery: &DueDelivery,
+        error: &str,
+        retry_at: Option<DateTime<Utc>>,
+    ) -> QueryResult<()> {
+        webhook_repo::mark_failed(conn, delivery.id, error, retry_at)
+    }
+
+    fn describe(delivery: &DueDelivery) -> String {
+        format!("Webhook delivery {} to {}", delivery.id, delivery.url)
+    }
+
+    fn record_outcome(outcome: Outcome) {
+        let outcome = match outcome {
+            Outcome::Succeeded => "delivered",
+            Outcome::Retrying => "retrying",
+            Outcome::DeadLettered => "dead_lettered",
+        };
+        DELIVERY_ATTEMPTS.add(1, &[KeyValue::new("webhook.outcome", outcome)]);
+    }
+}
+
+/// URLs with an IP address as host are not resolved, so [`PublicResolver`]
+/// does not see them.
+fn check_ip_destination(url: &str) -> Result<(), String> {
+    let (host, _) = destination(url).map_err(|e| e.to_string())?;
+    match host.parse::<IpAddr>() {
+        Ok(ip) if !is_public_address(ip) => Err(format!("{} is an internal address", ip)),
+        _ => Ok(()),
+    }
+}
+
+/// Resolves through the operating system but only hands out public
+/// addresses, so a name cannot be pointed at an internal address after it was
+/// subscribed.
+struct PublicResolver;
+
+impl reqwest::dns::Resolve for PublicResolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        Box::pin(async move {
+            let host = name.as_str();
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, 0))
+                .await?
+                .filter(|addr| is_public_address(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!("{} does not resolve to a public address", host).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as reqwest::dns::Addrs)
+        })
+    }
+}
+
+/// Deliver webhooks until shutdown is signalled; see [`poll_loop::run`].
+pub async fn run(
+    dispatcher: Arc<WebhookDispatcher>,
+    wakeups: WakeupReceiver,
+    shutdown: ShutdownSignal,
+) {
+    poll_loop::run(dispatcher, wakeups, shutdown).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+    use serde_json::{json, Value};
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::domain::order::ORDER_AGGREGATE;
+    use crate::domain::ports::WebhookRepository;
+    use crate::domain::webhook::WebhookSubscriptionInput;
+    use crate::infrastructure::webhook_repo::DieselWebhookRepository;
+    use crate::outbox_listener::{self, OutboxListenerConfig, OutboxWakeups};
+    use crate::test_support::{append_event, setup_db, setup_db_with_url};
+
+    #[derive(Debug, Clone)]
+    struct Received {
+        headers: Vec<(String, String)>,
+        body: Vec<u8>,
+    }
+
+    impl Received {
+        fn header(&self, name: &str) -> &str {
+            self.headers
+                .iter()
+                .find(|(n, _)| n.eq_ignore_ascii_case(name))
+                .map(|(_, v)| v.as_str())
+                .unwrap_or_default()
+        }
+    }
+
+    /// Local HTTP endpoint answering with the queued statuses, then 200.
+    #[derive(Clone, Default)]
+    struct StubEndpoint {
+        statuses: Arc<Mutex<VecDeque<u16>>>,
+        received: Arc<Mutex<Vec<Received>>>,
+    }
+
+    impl StubEndpoint {
+        fn failing(times: usize) -> Self {
+            let endpoint = Self::default();
+            endpoint
+                .statuses
+                .lock()
+                .expect("lock")
+                .extend(std::iter::repeat_n(500, times));
+            endpoint
+        }
+
+        fn received(&self) -> Vec<Received> {
+            self.received.lock().expect("lock").clone()
+        }
+
+        /// Serve on a free local port; returns the hook URL.
+        fn start(&self) -> String {
+            let endpoint = self.clone();
+            let server = HttpServer::new(move || {
+                App::new()
+                    .app_data(web::Data::new(endpoint.clone()))
+                    .route("/hook", web::post().to(receive))
+            })
+            .workers(1)
+            .bind(("127.0.0.1", 0))
+            .expect("bind");
+            let address = server.addrs()[0];
+            actix_web::rt::spawn(server.run());
+            format!("http://{}/hook", address)
+        }
+    }
+
+    async fn receive(
+        endpoint: web::Data<StubEndpoint>,
+        request: HttpRequest,
+        body: web::Bytes,
+    ) -> HttpResponse {
+        endpoint.received.lock().expect("lock").push(Received {
+            headers: request
+                .headers()
+                .iter()
+                .map(|(n, v)| (n.to_string(), v.to_str().unwrap_or_default().to_string()))
+                .collect(),
+            body: body.to_vec(),
+        });
+        let status = endpoint
+            .statuses
+            .lock()
+            .expect("lock")
+            .pop_front()
+            .unwrap_or(200);
+        HttpResponse::build(actix_web::http::StatusCode::from_u16(status).expect("status")).finish()
+    }
+
+    fn config(max_attempts: u32) -> WebhookConfig {
+        WebhookConfig {
+            retry: RetryPolicy {
+                max_attempts,
+                initial_backoff: Duration::ZERO,
+                max_backoff: Duration::ZERO,
+            },
+            poll_interval: Duration::from_millis(10),
+            ..WebhookConfig::default()
+        }
+    }
+
+    fn key() -> WebhookSecretKey {
+        WebhookSecretKey::new(*b"0123456789abcdef0123456789abcdef")
+    }
+
+    /// The stub endpoints listen on the loopback interface.
+    fn security() -> WebhookSecurity {
+        WebhookSecurity {
+            secret_key: Some(key()),
+            allow_private_destinations: true,
+        }
+    }
+
+    fn subscribe(pool: &DbPool, url: &str, event_types: &[&str]) -> (Uuid, String) {
+        let subscription = DieselWebhookRepository::new(pool.clone())
+            .create(&WebhookSubscriptionInput {
+                url: url.to_string(),
+                event_types: event_types.iter().map(|t| t.to_string()).collect(),
+            })
+            .expect("subscribe");
+        (subscription.id, key().secret_for(subscription.id))
+    }
+
+    fn publish(pool: &DbPool, event_type: &str) -> Uuid {
+        let mut conn = pool.get().expect("connection");
+        append_event(
+            &mut conn,
+            ORDER_AGGREGATE,
+            "order-1",
+            event_type,
+            json!({ "status": "PAID" }),
+        )
+        .expect("append")
+        .id
+    }
+
+    #[test]
+    fn signatures_cover_timestamp_and_body() {
+        let signature = sign("s3cret", 1_700_000_000, br#"{"a":1}"#);
+        assert!(signature.starts_with("sha256="));
+        assert!(verify("s3cret", 1_700_000_000, br#"{"a":1}"#, &signature));
+        assert!(!verify("other", 1_700_000_000, br#"{"a":1}"#, &signature));
+        assert!(!verify("s3cret", 1_700_000_001, br#"{"a":1}"#, &signature));
+        assert!(!verify("s3cret", 1_700_000_000, br#"{"a":2}"#, &signature));
+        assert!(!verify("s3cret", 1_700_000_000, br#"{"a":1}"#, "sha256=zz"));
+    }
+
+    #[actix_web::test]
+    async fn events_are_delivered_signed_to_subscribed_endpoints() {
+        let (_container, pool) = setup_db().await;
+        let endpoint = StubEndpoint::default();
+        let url = endpoint.start();
+        let (_, secret) = subscribe(&pool, &url, &["OrderPaid"]);
+        publish(&pool, "OrderCreated");
+        let event_id = publish(&pool, "OrderPaid");
+        let dispatcher =
+            Arc::new(WebhookDispatcher::new(pool, config(3), security()).expect("client"));
+
+        let summary = dispatcher.run_once().await.expect("poll");
+        assert_eq!(summary.taken_up, 2);
+        assert_eq!(summary.succeeded, 1);
+
+        let received = endpoint.received();
+        assert_eq!(received.len(), 1);
+        let request = &received[0];
+        assert_eq!(request.header(EVENT_ID_HEADER), event_id.to_string());
+        assert_eq!(request.header(EVENT_TYPE_HEADER), "OrderPaid");
+        let timestamp: i64 = request.header(TIMESTAMP_HEADER).parse().expect("timestamp");
+        assert!(verify(
+            &secret,
+            timestamp,
+            &request.body,
+            request.header(SIGNATURE_HEADER)
+        ));
+        let body: Value = serde_json::from_slice(&request.body).expect("json");
+        assert_eq!(body["event_type"], "OrderPaid");
+        assert_eq!(body["aggregate_id"], "order-1");
+        assert_eq!(body["payload"]["status"], "PAID");
+
+        assert!(dispatcher.run_once().await.expect("poll").is_idle());
+    }
+
+    #[actix_web::test]
+    async fn failed_deliveries_are_retried_until_they_succeed() {
+        let (_container, pool) = setup_db().await;
+        let endpoint = StubEndpoint::failing(2);
+        let url = endpoint.start();
+        subscribe(&pool, &url, &[]);
+        publish(&pool, "OrderCreated");
+        let dispatcher =
+            Arc::new(WebhookDispatcher::new(pool, config(3), security()).expect("client"));
+
+        let first = dispatcher.run_once().await.expect("poll");
+        let second = dispatcher.run_once().await.expect("poll");
+        let third = dispatcher.run_once().await.expect("poll");
+        assert_eq!(
+            (first.retrying, second.retrying, third.succeeded),
+            (1, 1, 1)
+        );
+        assert_eq!(endpoint.received().len(), 3);
+        // Retries carry the same event id.
+        let ids: Vec<String> = endpoint
+            .received()
+            .iter()
+            .map(|r| r.header(EVENT_ID_HEADER).to_string())
+            .collect();
+        assert!(ids.iter().all(|id| *id == ids[0]));
+    }
+
+    #[actix_web::test]
+    async fn deliveries_are_dead_lettered_after_max_attempts() {
+        let (_container, pool) = setup_db().await;
+        let endpoint = StubEndpoint::failing(2);
+        let url = endpoint.start();
+        let (subscription_id, _) = subscribe(&pool, &url, &[]);
+        publish(&pool, "OrderCreated");
+        let dispatcher =
+            Arc::new(WebhookDispatcher::new(pool.clone(), config(2), security()).expect("client"));
+
+        assert_eq!(dispatcher.run_once().await.expect("poll").retrying, 1);
+        assert_eq!(dispatcher.run_once().await.expect("poll").dead_lettered, 1);
+        assert!(dispatcher.run_once().await.expect("poll").is_idle());
+
+        let dead = DieselWebhookRepository::new(pool)
+            .dead_letters(subscription_id, 10)
+            .expect("dead letters");
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].attempts, 2);
+        assert_eq!(
+            dead[0].last_error.as_deref(),
+            Some("HTTP 500 Internal Server Error")
+        );
+    }
+
+    #[actix_web::test]
+    async fn internal_destinations_are_refused_unless_allowed() {
+        let (_container, pool) = setup_db().await;
+        let endpoint = StubEndpoint::default();
+        let url = endpoint.start();
+        let (subscription_id, _) = subscribe(&pool, &url, &[]);
+        let named = url.replace("127.0.0.1", "localhost");
+        subscribe(&pool, &named, &[]);
+        publish(&pool, "OrderCreated");
+        let security = WebhookSecurity {
+            allow_private_destinations: false,
+            ..security()
+        };
+        let dispatcher =
+            Arc::new(WebhookDispatcher::new(pool.clone(), config(1), security).expect("client"));
+
+        assert_eq!(dispatcher.run_once().await.expect("poll").dead_lettered, 2);
+        assert!(endpoint.received().is_empty());
+        let dead = DieselWebhookRepository::new(pool)
+            .dead_letters(subscription_id, 10)
+            .expect("dead letters");
+        assert_eq!(
+            dead[0].last_error.as_deref(),
+            Some("127.0.0.1 is an internal address")
+        );
+    }
+
+    #[test]
+    fn delivery_needs_a_secret_key() {
+        let pool = r2d2::Pool::builder()
+            .build_unchecked(diesel::r2d2::ConnectionManager::new("postgres://unused"));
+        let security = WebhookSecurity {
+            secret_key: None,
+            ..security()
+        };
+
+        assert!(matches!(
+            WebhookDispatcher::new(pool, config(3), security),
+            Err(WebhookError::MissingSecretKey)
+        ));
+    }
+
+    #[actix_web::test]
+    async fn worker_delivers_on_outbox_wakeups_until_shutdown() {
+        let (_container, pool, database_url) = setup_db_with_url().await;
+        let endpoint = StubEndpoint::default();
+        let url = endpoint.start();
+        subscribe(&pool, &url, &[]);
+        // Only a wakeup can make the worker poll again in time.
+        let config = WebhookConfig {
+            poll_interval: Duration::from_secs(3600),
+            ..config(3)
+        };
+        let dispatcher =
+            Arc::new(WebhookDispatcher::new(pool.clone(), config, security()).expect("client"));
+        let wakeups = OutboxWakeups::new();
+        let mut listening = wakeups.subscribe();
+        let received = wakeups.subscribe();
+        let (stop, signal) = ShutdownSignal::for_test();
+
+        let listener = actix_web::rt::spawn(outbox_listener::listen(
+            database_url,
+            OutboxListenerConfig::default(),
+            wakeups,
+            signal.clone(),
+        ));
+        let worker = actix_web::rt::spawn(run(dispatcher, received, signal));
+        tokio::time::timeout(Duration::from_secs(5), listening.next())
+            .await
+            .expect("listening in time");
+        publish(&pool, "OrderCreated");
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while endpoint.received().is_empty() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("delivered in time");
+        stop.send(true).expect("workers listening");
+        for worker in [listener, worker] {
+            tokio::time::timeout(Duration::from_secs(5), worker)
+                .await
+                .expect("worker stopped")
+                .expect("worker did not panic");
+        }
+    }
+}
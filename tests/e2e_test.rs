@@ -15,9 +15,15 @@
 
 use apache_avro::types::Value as AvroValue;
 use futures::StreamExt;
-use order_service::{build_server, create_pool, run_migrations};
+use order_service::debezium::{self, ConnectClient, DebeziumConfig, Reconciliation};
+use order_service::domain::outbox::OutboxEvent;
+use order_service::infrastructure::outbox::ORDER_OUTBOX;
+use order_service::order_stream::OrderEventBus;
+use order_service::publishers::EventRoute;
+use order_service::shutdown::Readiness;
+use order_service::{build_server, create_pool, run_migrations, Config};
 use rdkafka::consumer::{Consumer, StreamConsumer};
-use rdkafka::message::Message;
+use rdkafka::message::{BorrowedMessage, Headers, Message};
 use rdkafka::ClientConfig;
 use reqwest::Client;
 use serde_json::{json, Value};
@@ -28,8 +34,10 @@ const DEBEZIUM_URL: &str = "http://localhost:8083";
 const SCHEMA_REGISTRY_URL: &str = "http://localhost:8081";
 const KAFKA_BROKERS: &str = "localhost:9092";
 const KAFKA_TOPIC: &str = "public.commerce.order.c2.v1";
+const CONNECTOR_NAME: &str = "order-outbox-connector";
 const APP_PORT: u16 = 18080;
 const KAFKA_WAIT_SECS: u64 = 60;
+const TRACEPARENT: &str = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01";
 
 /// Wait until `url` returns an HTTP 2xx, retrying every `interval` for up to
 /// `timeout` total. Panics if the service never becomes healthy.
@@ -51,113 +59,64 @@ async fn wait_for_http(label: &str, url: &str, timeout: Duration, interval: Dura
     }
 }
 
-/// Register (or replace) the Debezium outbox connector.
-///
-/// The connector is configured to read `public.commerce_order_outbox` from the
-/// Postgres container (reachable inside Docker as "postgres") and publish
-/// events to the fixed topic `public.commerce.order.c2.v1`.
-async fn register_debezium_connector(http: &Client) {
-    // Remove any stale connector so registration is idempotent.
-    let _ = http
-        .delete(format!(
-            "{}/connectors/order-outbox-connector",
-            DEBEZIUM_URL
-        ))
-        .send()
-        .await;
-    tokio::time::sleep(Duration::from_secs(2)).await;
-
-    let connector_config = json!({
-        "name": "order-outbox-connector",
-        "config": {
-            "connector.class": "io.debezium.connector.postgresql.PostgresConnector",
-            // "postgres" is the service name inside the Docker Compose network.
-            "database.hostname": "postgres",
-            "database.port": "5432",
-            "database.user": "order_user",
-            "database.password": "order_pass",
-            "database.dbname": "order_db",
-            "topic.prefix": "order_db_e2e",
-            "plugin.name": "pgoutput",
-            "slot.name": "e2e_slot",
-            "publication.name": "e2e_pub",
-            "table.include.list": "public.commerce_order_outbox",
-            "tombstones.on.delete": "false",
-            "transforms": "outbox",
-            "transforms.outbox.type": "io.debezium.transforms.outbox.EventRouter",
-            "transforms.outbox.table.field.event.id": "id",
-            "transforms.outbox.table.field.event.key": "aggregate_id",
-            "transforms.outbox.table.field.event.type": "event_type",
-            "transforms.outbox.table.field.event.payload": "payload",
-            "transforms.outbox.route.by.field": "aggregate_type",
-            "transforms.outbox.route.topic.replacement": "public.commerce.order.c2.v1",
-            "transforms.outbox.table.fields.additional.placement": "id:envelope:event_id,event_type:envelope,created_at:envelope:event_date",
-            "transforms.outbox.table.expand.json.payload": "true",
-            "key.converter": "org.apache.kafka.connect.storage.StringConverter",
-            "value.converter": "io.confluent.connect.avro.AvroConverter",
-            "value.converter.schema.registry.url": "http://schema-registry:8081"
-        }
-    });
-
-    let resp = http
-        .post(format!("{}/connectors", DEBEZIUM_URL))
-        .json(&connector_config)
-        .send()
+/// Register the Debezium outbox connector built from `config`, replacing any
+/// stale one, with its own slot and publication, routing all events to the
+/// fixed topic `public.commerce.order.c2.v1`.
+async fn register_debezium_connector(config: &Config) -> ConnectClient {
+    let mut config = config.clone();
+    config.debezium = DebeziumConfig {
+        connect_url: DEBEZIUM_URL.to_string(),
+        connector_name: CONNECTOR_NAME.to_string(),
+        // "postgres" is the service name inside the Docker Compose network.
+        database_hostname: Some("postgres".to_string()),
+        topic_prefix: "order_db_e2e".to_string(),
+        slot_name: Some("e2e_slot".to_string()),
+        publication_name: Some("e2e_pub".to_string()),
+        route: EventRoute::new(KAFKA_TOPIC),
+        ..DebeziumConfig::default()
+    };
+    let connector = debezium::connector(&config).expect("Failed to build the connector");
+    let client = ConnectClient::new(&config.debezium.connect_url);
+
+    // Remove any stale connector so registration starts from scratch.
+    if client
+        .delete(&connector.name)
         .await
-        .expect("Failed to POST connector to Debezium");
-
-    assert!(
-        resp.status().is_success(),
-        "Debezium connector registration failed ({}): {}",
-        resp.status(),
-        resp.text().await.unwrap_or_default()
-    );
+        .expect("Failed to delete the connector")
+    {
+        tokio::time::sleep(Duration::from_secs(2)).await;
+    }
+    let outcome = debezium::reconcile(&client, &connector)
+        .await
+        .expect("Debezium connector registration failed");
+    assert_eq!(outcome, Reconciliation::Created);
+    client
 }
 
 /// Poll the Debezium connector status until both the connector and its task report RUNNING.
-async fn wait_for_connector_running(http: &Client) {
+async fn wait_for_connector_running(client: &ConnectClient) {
     let deadline = tokio::time::Instant::now() + Duration::from_secs(60);
     loop {
         if tokio::time::Instant::now() > deadline {
             panic!("Debezium connector did not reach RUNNING state within 60 s");
         }
-        let resp = http
-            .get(format!(
-                "{}/connectors/order-outbox-connector/status",
-                DEBEZIUM_URL
-            ))
-            .send()
-            .await;
-
-        if let Ok(r) = resp {
-            if let Ok(v) = r.json::<Value>().await {
-                let connector_running = v["connector"]["state"].as_str() == Some("RUNNING");
-                let task_running = v["tasks"]
-                    .as_array()
-                    .and_then(|tasks| tasks.first())
-                    .and_then(|t| t["state"].as_str())
-                    == Some("RUNNING");
-
-                if connector_running && task_running {
-                    return;
-                }
+        if let Ok(status) = client.status(CONNECTOR_NAME).await {
+            if status.is_running() {
+                return;
+            }
 
-                // Fail fast if the task is in a terminal FAILED state.
-                let task_failed = v["tasks"]
-                    .as_array()
-                    .and_then(|tasks| tasks.first())
-                    .and_then(|t| t["state"].as_str())
-                    == Some("FAILED");
-                if task_failed {
-                    let trace = v["tasks"][0]["trace"]
-                        .as_str()
-                        .unwrap_or("<no trace>")
-                        .lines()
-                        .take(5)
-                        .collect::<Vec<_>>()
-                        .join("\n");
-                    panic!("Debezium connector task entered FAILED state:\n{}", trace);
-                }
+            // Fail fast if the task is in a terminal FAILED state.
+            if status.has_failed() {
+                let trace = status
+                    .tasks
+                    .iter()
+                    .find_map(|t| t.trace.as_deref())
+                    .unwrap_or("<no trace>")
+                    .lines()
+                    .take(5)
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                panic!("Debezium connector task entered FAILED state:\n{}", trace);
             }
         }
         tokio::time::sleep(Duration::from_secs(2)).await;
@@ -174,7 +133,7 @@ async fn wait_for_connector_running(http: &Client) {
 ///     `OrderCreated` event matching the new order's ID is received (up to 60 s).
 ///
 /// Messages are Avro records with envelope fields (`event_id`, `event_type`,
-/// `event_date`) plus a `payload` string containing the order JSON.
+/// `event_date`, `sequence`) plus a `payload` string containing the order JSON.
 #[tokio::test]
 #[ignore = "requires docker-compose infrastructure – run via scripts/run_e2e_tests.sh"]
 async fn test_create_order_event_reaches_kafka() {
@@ -185,8 +144,18 @@ async fn test_create_order_event_reaches_kafka() {
     let pool = create_pool(&database_url);
     run_migrations(&pool);
 
-    let server =
-        build_server(pool, "127.0.0.1", APP_PORT).expect("Failed to bind the order service");
+    let config = Config {
+        host: "127.0.0.1".to_string(),
+        port: APP_PORT,
+        ..Config::new(&database_url)
+    };
+    let server = build_server(
+        pool.clone(),
+        &config,
+        Readiness::new(),
+        OrderEventBus::new(),
+    )
+    .expect("Failed to bind the order service");
     tokio::spawn(server);
 
     let app_url = format!("http://127.0.0.1:{}", APP_PORT);
@@ -219,8 +188,8 @@ async fn test_create_order_event_reaches_kafka() {
     )
     .await;
 
-    register_debezium_connector(&http).await;
-    wait_for_connector_running(&http).await;
+    let connect = register_debezium_connector(&config).await;
+    wait_for_connector_running(&connect).await;
 
     // ── 3. Create a Kafka consumer before posting the order ──────────────────
     // A unique group-id per test run ensures offset tracking starts fresh and
@@ -275,10 +244,27 @@ async fn test_create_order_event_reaches_kafka() {
 
     println!("Created order id={}", order_id);
 
-    // ── 5. Poll Kafka until the matching OrderCreated event appears ──────────
+    // An event with headers, written as the service's own writers do.
+    {
+        let mut conn = pool.get().expect("Failed to get a database connection");
+        let tagged = OutboxEvent::new(
+            "Order",
+            order_id.clone(),
+            "OrderTagged",
+            json!({ "order_id": order_id }),
+        )
+        .with_header("tenant", "e2e")
+        .with_header("traceparent", TRACEPARENT);
+        ORDER_OUTBOX
+            .append(&mut conn, &tagged)
+            .expect("Failed to append an event with headers");
+    }
+
+    // ── 5. Poll Kafka until both events of the order appear ─────────────────
     let deadline = tokio::time::Instant::now() + Duration::from_secs(KAFKA_WAIT_SECS);
     let mut kafka_stream = consumer.stream();
     let mut found = false;
+    let mut found_tagged = false;
 
     loop {
         if tokio::time::Instant::now() > deadline {
@@ -351,6 +337,40 @@ async fn test_create_order_event_reaches_kafka() {
             continue;
         }
 
+        // Each entry of the outbox row's headers is a Kafka header of its
+        // own, as the outbox relay publishes them; the JSON is not passed on.
+        assert_eq!(
+            kafka_header(&msg, "headers"),
+            None,
+            "the headers column must be expanded"
+        );
+        let tenant = kafka_header(&msg, "tenant");
+        let traceparent = kafka_header(&msg, "traceparent");
+
+        if record.get("event_type") == Some(&AvroValue::String("OrderTagged".to_string())) {
+            assert_eq!(tenant.as_deref(), Some("e2e"), "OrderTagged tenant header");
+            assert_eq!(
+                traceparent.as_deref(),
+                Some(TRACEPARENT),
+                "OrderTagged traceparent header"
+            );
+            assert_eq!(
+                record.get("sequence"),
+                Some(&AvroValue::Long(2)),
+                "OrderTagged must follow OrderCreated"
+            );
+            found_tagged = true;
+            if found {
+                break;
+            }
+            continue;
+        }
+        assert_eq!(
+            (tenant, traceparent),
+            (None, None),
+            "OrderCreated carries no headers"
+        );
+
         // ── Payload assertions ────────────────────────────────────────────────
         assert_eq!(
             event["status"].as_str(),
@@ -408,8 +428,16 @@ async fn test_create_order_event_reaches_kafka() {
             "Avro envelope event_date should be non-empty"
         );
 
+        assert_eq!(
+            record.get("sequence"),
+            Some(&AvroValue::Long(1)),
+            "OrderCreated must be the first event of the order"
+        );
+
         found = true;
-        break;
+        if found_tagged {
+            break;
+        }
     }
 
     assert!(
@@ -417,6 +445,20 @@ async fn test_create_order_event_reaches_kafka() {
         "OrderCreated event for order '{}' was not received on Kafka topic '{}' within {} seconds",
         order_id, KAFKA_TOPIC, KAFKA_WAIT_SECS
     );
+    assert!(
+        found_tagged,
+        "OrderTagged event for order '{}' was not received on Kafka topic '{}' within {} seconds",
+        order_id, KAFKA_TOPIC, KAFKA_WAIT_SECS
+    );
+}
+
+/// The value of the Kafka header `name`, as text.
+fn kafka_header(msg: &BorrowedMessage<'_>, name: &str) -> Option<String> {
+    msg.headers()?
+        .iter()
+        .find(|header| header.key == name)?
+        .value
+        .map(|value| String::from_utf8_lossy(value).into_owned())
 }
 
 // ── Avro wire format helpers ──────────────────────────────────────────────────